@@ -1,7 +1,10 @@
+pub mod visit;
+
 use std::fmt;
 
 use crate::span::Span;
 
+#[cfg(test)]
 macro_rules! writeind {
     ($f:expr, $indent:expr, $($arg:tt)*) => {{
         writeln!($f, "{}{}", " ".repeat($indent), format!($($arg)*))
@@ -14,6 +17,7 @@ pub struct SourceFile<'input> {
     pub span: Span,
 }
 
+#[cfg(test)]
 impl<'input> SourceFile<'input> {
     pub fn dump<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
         self.dump_internal(w, 0)
@@ -34,6 +38,7 @@ pub struct Item<'input> {
     pub span: Span,
 }
 
+#[cfg(test)]
 impl<'input> Item<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         match &self.kind {
@@ -41,11 +46,33 @@ impl<'input> Item<'input> {
                 writeind!(w, indent, "Entry@{}", self.span)?;
                 entry.dump(w, indent + 1)
             }
-            ItemKind::Const(name, expr) => {
-                writeind!(w, indent, "Const@{}", self.span)?;
+            ItemKind::Const(name, ty, expr, secret) => {
+                if *secret {
+                    writeind!(w, indent, "Const@{} secret", self.span)?;
+                } else {
+                    writeind!(w, indent, "Const@{}", self.span)?;
+                }
                 name.dump(w, indent + 1)?;
+                if let Some(ty) = ty {
+                    writeind!(w, indent + 1, "Type@{} {}", ty.span, ty)?;
+                }
                 expr.dump(w, indent + 1)
             }
+            ItemKind::Env(name, items) => {
+                writeind!(w, indent, "Env@{}", self.span)?;
+                name.dump(w, indent + 1)?;
+                for item in items {
+                    item.dump(w, indent + 1)?;
+                }
+                Ok(())
+            }
+            ItemKind::Defaults(items) => {
+                writeind!(w, indent, "Defaults@{}", self.span)?;
+                for item in items {
+                    item.dump(w, indent + 1)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -53,18 +80,59 @@ impl<'input> Item<'input> {
 #[derive(Debug, Clone)]
 pub enum ItemKind<'input> {
     Entry(Entry<'input>),
-    Const(Name<'input>, Expr<'input>),
+    /// E.g. `const port: int = 8080`, the type is optional. Preceded by
+    /// `secret`, the value is redacted in all output.
+    Const(Name<'input>, Option<TypeExpr<'input>>, Expr<'input>, bool),
+    /// E.g. `env staging { const base = "..." }`, only contains consts
+    Env(Name<'input>, Vec<Item<'input>>),
+    /// E.g. `defaults { [BaseUrl] "https://example.com" }`, sections that
+    /// every entry inherits
+    Defaults(Vec<EntryItem<'input>>),
 }
 
 #[derive(Debug, Clone)]
 pub struct Entry<'input> {
     pub name: Name<'input>,
+    /// E.g. `smoke` in `@smoke entry health`
+    pub tags: Vec<Name<'input>>,
+    /// E.g. `id` in `entry getUser(id)`
+    pub params: Vec<Name<'input>>,
+    /// The entry this entry inherits from, e.g. `entry foo extends base`
+    pub extends: Option<Name<'input>>,
+    /// Entries that have to succeed before this one runs, e.g.
+    /// `entry me needs login`
+    pub needs: Vec<Need<'input>>,
     pub body: Vec<EntryItem<'input>>,
 }
 
+/// An entry named after `needs`, e.g. `login` or `getUser("42")`
+#[derive(Debug, Clone)]
+pub struct Need<'input> {
+    pub name: Name<'input>,
+    /// The arguments of an entry that is called with them, e.g. `"42"` in
+    /// `needs getUser("42")`
+    pub args: Option<Vec<Expr<'input>>>,
+}
+
+#[cfg(test)]
 impl<'input> Entry<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
+        for tag in &self.tags {
+            writeind!(w, indent, "Tag@{} {}", tag.span, tag.text)?;
+        }
         self.name.dump(w, indent)?;
+        for param in &self.params {
+            writeind!(w, indent, "Param@{} {}", param.span, param.text)?;
+        }
+        if let Some(base) = &self.extends {
+            writeind!(w, indent, "Extends@{} {}", base.span, base.text)?;
+        }
+        for need in &self.needs {
+            writeind!(w, indent, "Needs@{} {}", need.name.span, need.name.text)?;
+            for arg in need.args.iter().flatten() {
+                arg.dump(w, indent + 1)?;
+            }
+        }
         for item in &self.body {
             item.dump(w, indent)?;
         }
@@ -78,6 +146,7 @@ pub struct EntryItem<'input> {
     pub span: Span,
 }
 
+#[cfg(test)]
 impl<'input> EntryItem<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         match &self.kind {
@@ -90,6 +159,22 @@ impl<'input> EntryItem<'input> {
                 name.dump(w, indent + 1)?;
                 body.dump(w, indent + 1)
             }
+            EntryItemKind::Assert(assertions) => {
+                writeind!(w, indent, "Assert@{}", self.span)?;
+                for assertion in assertions {
+                    assertion.dump(w, indent + 1)?;
+                }
+                Ok(())
+            }
+            EntryItemKind::For(name, items, body) => {
+                writeind!(w, indent, "For@{}", self.span)?;
+                name.dump(w, indent + 1)?;
+                items.dump(w, indent + 1)?;
+                for item in body {
+                    item.dump(w, indent + 1)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -98,6 +183,86 @@ impl<'input> EntryItem<'input> {
 pub enum EntryItemKind<'input> {
     Request(Request<'input>),
     Section(Name<'input>, Expr<'input>),
+    /// E.g. `[Assert] { status == 200 }`
+    Assert(Vec<Assertion<'input>>),
+    /// E.g. `for user in users { POST "..." }`
+    For(Name<'input>, Expr<'input>, Vec<EntryItem<'input>>),
+}
+
+/// E.g. `header "Content-Type" contains "json"`
+#[derive(Debug, Clone)]
+pub struct Assertion<'input> {
+    /// `soft` in front of the subject, a failure is only a warning
+    pub soft: bool,
+    /// `not` in front of the subject, the comparison must not hold
+    pub negated: bool,
+    /// What is checked, e.g. `status`
+    pub subject: Name<'input>,
+    /// Selects a part of the subject, e.g. the name of a header
+    pub argument: Option<Expr<'input>>,
+    pub op: CompareOp,
+    pub expected: Expr<'input>,
+    /// Written right after the expected value, e.g. `ms` in `500ms`
+    pub unit: Option<Name<'input>>,
+    pub span: Span,
+}
+
+#[cfg(test)]
+impl<'input> Assertion<'input> {
+    fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
+        writeind!(w, indent, "Assertion@{} {}", self.span, self.op)?;
+        if self.soft {
+            writeind!(w, indent + 1, "Soft")?;
+        }
+        if self.negated {
+            writeind!(w, indent + 1, "Not")?;
+        }
+        self.subject.dump(w, indent + 1)?;
+        if let Some(argument) = &self.argument {
+            argument.dump(w, indent + 1)?;
+        }
+        self.expected.dump(w, indent + 1)?;
+        if let Some(unit) = &self.unit {
+            writeind!(w, indent + 1, "Unit@{} {}", unit.span, unit.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    /// `==`
+    Eq,
+    /// `!=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// `contains`
+    Contains,
+    /// `matches`
+    Matches,
+}
+
+impl fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Contains => "contains",
+            CompareOp::Matches => "matches",
+        };
+        write!(f, "{s}")
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -106,18 +271,47 @@ pub struct Name<'input> {
     pub span: Span,
 }
 
+#[cfg(test)]
 impl<'input> Name<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         writeind!(w, indent, "Name@{} {}", self.span, self.text)
     }
 }
 
+/// A type written in source code, e.g. `[string]`
+#[derive(Debug, Clone)]
+pub struct TypeExpr<'input> {
+    pub kind: TypeExprKind<'input>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeExprKind<'input> {
+    /// E.g. `int`
+    Named(Name<'input>),
+    /// E.g. `[int]`
+    Array(Box<TypeExpr<'input>>),
+    /// E.g. `{string}`, a dictionary whose values all have this type
+    Dictionary(Box<TypeExpr<'input>>),
+}
+
+impl fmt::Display for TypeExpr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            TypeExprKind::Named(name) => write!(f, "{}", name.text),
+            TypeExprKind::Array(elem) => write!(f, "[{elem}]"),
+            TypeExprKind::Dictionary(value) => write!(f, "{{{value}}}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Request<'input> {
     pub method: HttpMethod,
     pub url: Expr<'input>,
 }
 
+#[cfg(test)]
 impl<'input> Request<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         writeind!(w, indent, "{}", self.method)?;
@@ -132,6 +326,8 @@ pub enum HttpMethod {
     Put,
     Patch,
     Delete,
+    Head,
+    Options,
 }
 
 impl fmt::Display for HttpMethod {
@@ -142,6 +338,8 @@ impl fmt::Display for HttpMethod {
             HttpMethod::Put => "PUT",
             HttpMethod::Patch => "PATCH",
             HttpMethod::Delete => "DELETE",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Options => "OPTIONS",
         };
         write!(f, "{s}")
     }
@@ -153,6 +351,7 @@ pub struct Expr<'input> {
     pub span: Span,
 }
 
+#[cfg(test)]
 impl<'input> Expr<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         match &self.kind {
@@ -175,6 +374,9 @@ impl<'input> Expr<'input> {
             ExprKind::NullLiteral => {
                 writeind!(w, indent, "NullLiteral@{}", self.span)
             }
+            ExprKind::BoolLiteral(lit) => {
+                writeind!(w, indent, "BoolLiteral@{} {}", self.span, lit)
+            }
             ExprKind::Dictionary(fields) => {
                 writeind!(w, indent, "Dictionary@{}", self.span)?;
                 for field in fields {
@@ -189,6 +391,34 @@ impl<'input> Expr<'input> {
                 }
                 Ok(())
             }
+            ExprKind::Binary(op, lhs, rhs) => {
+                writeind!(w, indent, "Binary@{} {}", self.span, op)?;
+                lhs.dump(w, indent + 1)?;
+                rhs.dump(w, indent + 1)
+            }
+            ExprKind::Field(base, name) => {
+                writeind!(w, indent, "Field@{}", self.span)?;
+                base.dump(w, indent + 1)?;
+                name.dump(w, indent + 1)
+            }
+            ExprKind::If(cond, then, else_) => {
+                writeind!(w, indent, "If@{}", self.span)?;
+                cond.dump(w, indent + 1)?;
+                then.dump(w, indent + 1)?;
+                else_.dump(w, indent + 1)
+            }
+            ExprKind::Paren(inner) => {
+                writeind!(w, indent, "Paren@{}", self.span)?;
+                inner.dump(w, indent + 1)
+            }
+            ExprKind::Call(name, args) => {
+                writeind!(w, indent, "Call@{}", self.span)?;
+                name.dump(w, indent + 1)?;
+                for arg in args {
+                    arg.dump(w, indent + 1)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -200,8 +430,57 @@ pub enum ExprKind<'input> {
     IntegerLiteral(&'input str),
     FloatLiteral(&'input str),
     NullLiteral,
+    BoolLiteral(bool),
     Dictionary(Vec<DictionaryField<'input>>),
     Array(Vec<Expr<'input>>),
+    /// E.g., `raw(path)`
+    Call(Name<'input>, Vec<Expr<'input>>),
+    /// E.g., `base + "/users"`
+    Binary(BinOp, Box<Expr<'input>>, Box<Expr<'input>>),
+    /// E.g., `user.name`
+    Field(Box<Expr<'input>>, Name<'input>),
+    /// E.g., `if useStaging then stagingUrl else prodUrl`
+    If(Box<Expr<'input>>, Box<Expr<'input>>, Box<Expr<'input>>),
+    /// E.g., `(port + 1)`
+    Paren(Box<Expr<'input>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `??`
+    Coalesce,
+}
+
+impl BinOp {
+    /// Operators with a higher precedence bind tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinOp::Coalesce => 1,
+            BinOp::Add | BinOp::Sub => 2,
+            BinOp::Mul | BinOp::Div => 3,
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Coalesce => "??",
+        };
+        write!(f, "{s}")
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -210,6 +489,7 @@ pub enum TemplatePart<'input> {
     Expr(Expr<'input>),
 }
 
+#[cfg(test)]
 impl<'input> TemplatePart<'input> {
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
         match self {
@@ -222,14 +502,35 @@ impl<'input> TemplatePart<'input> {
 }
 
 #[derive(Debug, Clone)]
-pub struct DictionaryField<'input> {
-    pub key: Expr<'input>,
-    pub value: Expr<'input>,
+pub enum DictionaryField<'input> {
+    /// E.g., `"Accept": "application/json"`
+    Pair {
+        key: Expr<'input>,
+        value: Expr<'input>,
+    },
+    /// E.g., `...defaultHeaders`, the span includes the `...`
+    Spread(Expr<'input>, Span),
 }
 
 impl<'input> DictionaryField<'input> {
+    pub fn span(&self) -> Span {
+        match self {
+            DictionaryField::Pair { key, value } => key.span.to(value.span),
+            DictionaryField::Spread(_, span) => *span,
+        }
+    }
+
+    #[cfg(test)]
     fn dump<W: fmt::Write>(&self, w: &mut W, indent: usize) -> fmt::Result {
-        self.key.dump(w, indent)?;
-        self.value.dump(w, indent)
+        match self {
+            DictionaryField::Pair { key, value } => {
+                key.dump(w, indent)?;
+                value.dump(w, indent)
+            }
+            DictionaryField::Spread(expr, span) => {
+                writeind!(w, indent, "Spread@{}", span)?;
+                expr.dump(w, indent + 1)
+            }
+        }
     }
 }
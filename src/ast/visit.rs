@@ -0,0 +1,239 @@
+//! Read-only traversal over the syntax tree.
+//!
+//! Implement [`Visitor`] and override the `visit_*` methods for the nodes you
+//! are interested in. The default implementations call the matching `walk_*`
+//! function, which visits all children of the node. An overriding method can
+//! call `walk_*` itself to keep descending.
+
+use crate::ast::{
+    DictionaryField, Entry, EntryItem, EntryItemKind, Expr, ExprKind, Item, ItemKind, Name,
+    Request, SourceFile, TemplatePart, TypeExpr,
+};
+
+pub trait Visitor<'input> {
+    fn visit_source_file(&mut self, file: &SourceFile<'input>) {
+        walk_source_file(self, file);
+    }
+
+    fn visit_item(&mut self, item: &Item<'input>) {
+        walk_item(self, item);
+    }
+
+    fn visit_entry(&mut self, entry: &Entry<'input>) {
+        walk_entry(self, entry);
+    }
+
+    fn visit_entry_item(&mut self, item: &EntryItem<'input>) {
+        walk_entry_item(self, item);
+    }
+
+    fn visit_request(&mut self, request: &Request<'input>) {
+        walk_request(self, request);
+    }
+
+    fn visit_name(&mut self, _name: &Name<'input>) {}
+
+    fn visit_type_expr(&mut self, _ty: &TypeExpr<'input>) {}
+
+    fn visit_expr(&mut self, expr: &Expr<'input>) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_template_part(&mut self, part: &TemplatePart<'input>) {
+        walk_template_part(self, part);
+    }
+
+    fn visit_dictionary_field(&mut self, field: &DictionaryField<'input>) {
+        walk_dictionary_field(self, field);
+    }
+}
+
+pub fn walk_source_file<'input, V: Visitor<'input> + ?Sized>(v: &mut V, file: &SourceFile<'input>) {
+    for item in &file.items {
+        v.visit_item(item);
+    }
+}
+
+pub fn walk_item<'input, V: Visitor<'input> + ?Sized>(v: &mut V, item: &Item<'input>) {
+    match &item.kind {
+        ItemKind::Entry(entry) => v.visit_entry(entry),
+        ItemKind::Const(name, ty, expr, _) => {
+            v.visit_name(name);
+            if let Some(ty) = ty {
+                v.visit_type_expr(ty);
+            }
+            v.visit_expr(expr);
+        }
+        ItemKind::Env(name, items) => {
+            v.visit_name(name);
+            for item in items {
+                v.visit_item(item);
+            }
+        }
+        ItemKind::Defaults(items) => {
+            for item in items {
+                v.visit_entry_item(item);
+            }
+        }
+    }
+}
+
+pub fn walk_entry<'input, V: Visitor<'input> + ?Sized>(v: &mut V, entry: &Entry<'input>) {
+    for tag in &entry.tags {
+        v.visit_name(tag);
+    }
+    v.visit_name(&entry.name);
+    for param in &entry.params {
+        v.visit_name(param);
+    }
+    if let Some(base) = &entry.extends {
+        v.visit_name(base);
+    }
+    for need in &entry.needs {
+        v.visit_name(&need.name);
+        for arg in need.args.iter().flatten() {
+            v.visit_expr(arg);
+        }
+    }
+    for item in &entry.body {
+        v.visit_entry_item(item);
+    }
+}
+
+pub fn walk_entry_item<'input, V: Visitor<'input> + ?Sized>(v: &mut V, item: &EntryItem<'input>) {
+    match &item.kind {
+        EntryItemKind::Request(request) => v.visit_request(request),
+        EntryItemKind::Section(name, body) => {
+            v.visit_name(name);
+            v.visit_expr(body);
+        }
+        EntryItemKind::Assert(assertions) => {
+            for assertion in assertions {
+                v.visit_name(&assertion.subject);
+                if let Some(argument) = &assertion.argument {
+                    v.visit_expr(argument);
+                }
+                v.visit_expr(&assertion.expected);
+                if let Some(unit) = &assertion.unit {
+                    v.visit_name(unit);
+                }
+            }
+        }
+        EntryItemKind::For(name, items, body) => {
+            v.visit_name(name);
+            v.visit_expr(items);
+            for item in body {
+                v.visit_entry_item(item);
+            }
+        }
+    }
+}
+
+pub fn walk_request<'input, V: Visitor<'input> + ?Sized>(v: &mut V, request: &Request<'input>) {
+    v.visit_expr(&request.url);
+}
+
+pub fn walk_expr<'input, V: Visitor<'input> + ?Sized>(v: &mut V, expr: &Expr<'input>) {
+    match &expr.kind {
+        ExprKind::NameRef(_)
+        | ExprKind::IntegerLiteral(_)
+        | ExprKind::FloatLiteral(_)
+        | ExprKind::NullLiteral
+        | ExprKind::BoolLiteral(_) => {}
+        ExprKind::StringLiteral(parts) => {
+            for part in parts {
+                v.visit_template_part(part);
+            }
+        }
+        ExprKind::Dictionary(fields) => {
+            for field in fields {
+                v.visit_dictionary_field(field);
+            }
+        }
+        ExprKind::Array(elems) => {
+            for elem in elems {
+                v.visit_expr(elem);
+            }
+        }
+        ExprKind::Call(name, args) => {
+            v.visit_name(name);
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::Binary(_, lhs, rhs) => {
+            v.visit_expr(lhs);
+            v.visit_expr(rhs);
+        }
+        ExprKind::Field(base, name) => {
+            v.visit_expr(base);
+            v.visit_name(name);
+        }
+        ExprKind::If(cond, then, else_) => {
+            v.visit_expr(cond);
+            v.visit_expr(then);
+            v.visit_expr(else_);
+        }
+        ExprKind::Paren(inner) => v.visit_expr(inner),
+    }
+}
+
+pub fn walk_template_part<'input, V: Visitor<'input> + ?Sized>(
+    v: &mut V,
+    part: &TemplatePart<'input>,
+) {
+    match part {
+        TemplatePart::Literal(_, _) => {}
+        TemplatePart::Expr(expr) => v.visit_expr(expr),
+    }
+}
+
+pub fn walk_dictionary_field<'input, V: Visitor<'input> + ?Sized>(
+    v: &mut V,
+    field: &DictionaryField<'input>,
+) {
+    match field {
+        DictionaryField::Pair { key, value } => {
+            v.visit_expr(key);
+            v.visit_expr(value);
+        }
+        DictionaryField::Spread(expr, _) => v.visit_expr(expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast::ExprKind, parser};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct NameRefCollector<'input> {
+        names: Vec<&'input str>,
+    }
+
+    impl<'input> Visitor<'input> for NameRefCollector<'input> {
+        fn visit_expr(&mut self, expr: &Expr<'input>) {
+            if let ExprKind::NameRef(name) = expr.kind {
+                self.names.push(name);
+            }
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_nested_name_refs() {
+        let input = r#"
+const base = "example.com"
+const token = "secret"
+entry foo {
+    GET "{{base}}/users"
+    [Headers] { "Authorization": token, "X": [base] }
+}
+"#;
+        let file = parser::parse(input).unwrap();
+        let mut collector = NameRefCollector::default();
+        collector.visit_source_file(&file);
+        assert_eq!(collector.names, vec!["base", "token", "base"]);
+    }
+}
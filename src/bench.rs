@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::client::{HttpError, Response};
+
+/// How often `aurora bench` sends the request, and how many at a time.
+#[derive(Debug, Clone, Copy)]
+pub struct BenchSettings {
+    pub requests: usize,
+    pub concurrency: usize,
+}
+
+/// What the requests of a benchmark resulted in. Every worker collects its
+/// own samples, they are merged when all are done.
+#[derive(Debug, Default)]
+pub struct Samples {
+    latencies: Vec<Duration>,
+    statuses: BTreeMap<u16, usize>,
+    /// How often each transport error occurred
+    errors: BTreeMap<String, usize>,
+}
+
+impl Samples {
+    pub fn record(&mut self, latency: Duration, result: &Result<Response, HttpError>) {
+        self.latencies.push(latency);
+        match result {
+            Ok(response) => *self.statuses.entry(response.status.as_u16()).or_default() += 1,
+            Err(e) => *self.errors.entry(e.to_string()).or_default() += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        for (status, count) in other.statuses {
+            *self.statuses.entry(status).or_default() += count;
+        }
+        for (error, count) in other.errors {
+            *self.errors.entry(error).or_default() += count;
+        }
+    }
+}
+
+/// The result of `aurora bench`.
+#[derive(Debug)]
+pub struct BenchReport {
+    samples: Samples,
+    /// Time from sending the first request until the last response
+    elapsed: Duration,
+}
+
+impl BenchReport {
+    pub fn new(mut samples: Samples, elapsed: Duration) -> Self {
+        samples.latencies.sort();
+        Self { samples, elapsed }
+    }
+
+    /// Returns the latency that `percent` percent of the requests didn't
+    /// exceed, by the nearest-rank method.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        let latencies = &self.samples.latencies;
+        let rank = (percent / 100.0 * latencies.len() as f64).ceil() as usize;
+        latencies.get(rank.max(1) - 1).copied()
+    }
+
+    /// Requests without a response or with a non-2xx status.
+    pub fn failures(&self) -> usize {
+        let errors = self.samples.errors.values().sum::<usize>();
+        let statuses = self
+            .samples
+            .statuses
+            .iter()
+            .filter(|(status, _)| !(200..300).contains(*status))
+            .map(|(_, count)| count)
+            .sum::<usize>();
+        errors + statuses
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let millis = |it: Duration| format!("{:.2}ms", it.as_secs_f64() * 1000.0);
+        let requests = self.samples.latencies.len();
+        let throughput = if self.elapsed.is_zero() {
+            0.0
+        } else {
+            requests as f64 / self.elapsed.as_secs_f64()
+        };
+        writeln!(
+            f,
+            "Requests:   {requests} in {:.2}s, {throughput:.1} requests/s",
+            self.elapsed.as_secs_f64()
+        )?;
+        if let (Some(min), Some(max)) = (
+            self.samples.latencies.first(),
+            self.samples.latencies.last(),
+        ) {
+            let percentiles = [50.0, 90.0, 95.0, 99.0]
+                .into_iter()
+                .filter_map(|p| Some(format!("p{p} {}", millis(self.percentile(p)?))))
+                .collect::<Vec<_>>();
+            writeln!(
+                f,
+                "Latency:    min {}, {}, max {}",
+                millis(*min),
+                percentiles.join(", "),
+                millis(*max)
+            )?;
+        }
+        if !self.samples.statuses.is_empty() {
+            let statuses = self
+                .samples
+                .statuses
+                .iter()
+                .map(|(status, count)| format!("{status} x{count}"))
+                .collect::<Vec<_>>();
+            writeln!(f, "Status:     {}", statuses.join(", "))?;
+        }
+        for (error, count) in &self.samples.errors {
+            writeln!(f, "Error:      {error} x{count}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::client::{CacheStatus, StatusCode};
+
+    fn response(status: u16) -> Result<Response, HttpError> {
+        Ok(Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(status),
+            headers: vec![],
+            body: vec![],
+            elapsed: Duration::ZERO,
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        })
+    }
+
+    #[test]
+    fn merge_and_summarize() {
+        let mut samples = Samples::default();
+        for millis in 1..=95 {
+            samples.record(Duration::from_millis(millis), &response(200));
+        }
+        let mut worker = Samples::default();
+        for millis in 96..=98 {
+            worker.record(Duration::from_millis(millis), &response(503));
+        }
+        worker.record(Duration::from_millis(99), &Err(HttpError::Timeout));
+        worker.record(Duration::from_millis(100), &Err(HttpError::Timeout));
+        samples.merge(worker);
+
+        let report = BenchReport::new(samples, Duration::from_secs(2));
+        assert_eq!(report.percentile(50.0), Some(Duration::from_millis(50)));
+        assert_eq!(report.percentile(99.9), Some(Duration::from_millis(100)));
+        assert_eq!(report.failures(), 5);
+        expect![[r#"
+            Requests:   100 in 2.00s, 50.0 requests/s
+            Latency:    min 1.00ms, p50 50.00ms, p90 90.00ms, p95 95.00ms, p99 99.00ms, max 100.00ms
+            Status:     200 x95, 503 x3
+            Error:      request timed out x2
+        "#]]
+        .assert_eq(&report.to_string());
+
+        let empty = BenchReport::new(Samples::default(), Duration::ZERO);
+        assert_eq!(empty.percentile(50.0), None);
+        assert_eq!(empty.failures(), 0);
+    }
+}
@@ -0,0 +1,455 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    client::{CacheStatus, HttpClient, HttpError, Request, Response, StatusCode},
+    timing::Timings,
+    validated::HttpMethod,
+};
+
+/// An [`HttpClient`] that answers `GET` requests from an on-disk cache while
+/// the cached response is still fresh according to `Cache-Control` and
+/// `Expires`.
+///
+/// Stale responses with an `ETag` or `Last-Modified` header are revalidated
+/// with a conditional request, and their body is reused if the server
+/// responds with `304 Not Modified`.
+///
+/// Without a cache directory, every request is passed through unchanged.
+pub struct CachingHttpClient<C> {
+    inner: C,
+    cache: Option<HttpCache>,
+}
+
+impl<C: HttpClient> CachingHttpClient<C> {
+    pub fn new(inner: C, dir: Option<PathBuf>) -> Self {
+        Self {
+            inner,
+            cache: dir.map(|dir| HttpCache { dir }),
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for CachingHttpClient<C> {
+    fn send(&self, mut request: Request) -> Result<Response, HttpError> {
+        let Some(cache) = &self.cache else {
+            return self.inner.send(request);
+        };
+
+        let directives = CacheControl::parse(&request.headers);
+        if request.method != HttpMethod::Get || directives.no_store {
+            return self.inner.send(request);
+        }
+
+        let cached = match cache.lookup(&request.url, &request.headers) {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("I couldn't read the HTTP cache: {e}");
+                None
+            }
+        };
+
+        let cached = match cached {
+            Some(cached) if cached.expires > unix_now() && !directives.no_cache => {
+                return Ok(cached.response(CacheStatus::Hit, Duration::ZERO, Timings::default()));
+            }
+            Some(cached) => {
+                add_conditional_headers(&mut request.headers, &cached.headers);
+                Some(cached)
+            }
+            None => None,
+        };
+
+        let url = request.url.clone();
+        let headers = request.headers.clone();
+        let response = self.inner.send(request)?;
+
+        let response = match cached {
+            Some(mut cached) if response.status.as_u16() == 304 => {
+                // A 304 carries updated freshness information for the cached
+                // response, but no body.
+                for (name, value) in &response.headers {
+                    cached
+                        .headers
+                        .retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+                    cached.headers.push((name.clone(), value.clone()));
+                }
+                cached.response(CacheStatus::Revalidated, response.elapsed, response.timings)
+            }
+            _ => response,
+        };
+
+        if let Err(e) = cache.store(&url, &headers, &response) {
+            tracing::warn!("I couldn't write to the HTTP cache: {e}");
+        }
+        Ok(response)
+    }
+}
+
+fn add_conditional_headers(
+    request_headers: &mut Vec<(String, String)>,
+    cached_headers: &[(String, String)],
+) {
+    for (validator, condition) in [
+        ("ETag", "If-None-Match"),
+        ("Last-Modified", "If-Modified-Since"),
+    ] {
+        if let Some(value) = header(cached_headers, validator)
+            && header(request_headers, condition).is_none()
+        {
+            request_headers.push((condition.to_string(), value.to_string()));
+        }
+    }
+}
+
+struct HttpCache {
+    dir: PathBuf,
+}
+
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    /// Unix time until which the response can be used without revalidation
+    expires: u64,
+}
+
+impl CachedResponse {
+    fn response(self, cache: CacheStatus, elapsed: Duration, timings: Timings) -> Response {
+        // The version isn't stored, it doesn't affect what was cached.
+        Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(self.status),
+            headers: self.headers,
+            body: self.body,
+            elapsed,
+            timings,
+            cache,
+            redirects: vec![],
+            streamed: false,
+        }
+    }
+}
+
+impl HttpCache {
+    fn lookup(
+        &self,
+        url: &str,
+        request_headers: &[(String, String)],
+    ) -> io::Result<Option<CachedResponse>> {
+        let (meta_path, body_path) = self.paths(url);
+        let meta = match fs::read(&meta_path) {
+            Ok(meta) => meta,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let Ok(meta) = serde_json::from_slice::<serde_json::Value>(&meta) else {
+            return Ok(None);
+        };
+
+        if meta["url"].as_str() != Some(url) {
+            return Ok(None);
+        }
+
+        // The response is only valid for requests that agree on all headers
+        // listed in `Vary`.
+        let vary_matches = meta["vary"].as_array().into_iter().flatten().all(|pair| {
+            let name = pair[0].as_str().unwrap_or_default();
+            header(request_headers, name).unwrap_or_default()
+                == pair[1].as_str().unwrap_or_default()
+        });
+        if !vary_matches {
+            return Ok(None);
+        }
+
+        let headers = meta["headers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|pair| {
+                (
+                    pair[0].as_str().unwrap_or_default().to_string(),
+                    pair[1].as_str().unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+
+        Ok(Some(CachedResponse {
+            status: meta["status"].as_u64().unwrap_or(200) as u16,
+            headers,
+            body: fs::read(body_path)?,
+            expires: meta["expires"].as_u64().unwrap_or_default(),
+        }))
+    }
+
+    fn store(
+        &self,
+        url: &str,
+        request_headers: &[(String, String)],
+        response: &Response,
+    ) -> io::Result<()> {
+        // Event streams are live, replaying one from the cache would be wrong.
+        if response.status.as_u16() != 200 || response.streamed {
+            return Ok(());
+        }
+
+        let directives = CacheControl::parse(&response.headers);
+        if directives.no_store {
+            return Ok(());
+        }
+
+        // Stale responses are still worth keeping if they can be revalidated.
+        let expires = freshness_deadline(&directives, &response.headers);
+        let has_validator = header(&response.headers, "ETag").is_some()
+            || header(&response.headers, "Last-Modified").is_some();
+        if expires.is_none() && !has_validator {
+            return Ok(());
+        }
+
+        let mut vary = vec![];
+        if let Some(names) = header(&response.headers, "Vary") {
+            for name in names.split(',').map(str::trim).filter(|it| !it.is_empty()) {
+                if name == "*" {
+                    return Ok(());
+                }
+                let value = header(request_headers, name).unwrap_or_default();
+                vary.push((name.to_string(), value.to_string()));
+            }
+        }
+
+        let meta = json!({
+            "url": url,
+            "status": response.status.as_u16(),
+            "headers": response.headers,
+            "vary": vary,
+            "expires": expires.unwrap_or_default(),
+        });
+
+        fs::create_dir_all(&self.dir)?;
+        let (meta_path, body_path) = self.paths(url);
+        fs::write(body_path, &response.body)?;
+        fs::write(meta_path, meta.to_string())
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = format!("{:x}", Sha256::digest(url.as_bytes()));
+        (
+            self.dir.join(format!("{key}.json")),
+            self.dir.join(format!("{key}.body")),
+        )
+    }
+}
+
+/// The `Cache-Control` directives aurora cares about. As a private cache,
+/// `private` and `s-maxage` are irrelevant.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(headers: &[(String, String)]) -> Self {
+        let mut directives = CacheControl::default();
+        for (_, value) in headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Cache-Control"))
+        {
+            for directive in value.split(',').map(str::trim) {
+                let (name, arg) = directive.split_once('=').unwrap_or((directive, ""));
+                match name.to_ascii_lowercase().as_str() {
+                    "no-store" => directives.no_store = true,
+                    "no-cache" => directives.no_cache = true,
+                    "max-age" => directives.max_age = arg.trim_matches('"').parse().ok(),
+                    _ => {}
+                }
+            }
+        }
+        directives
+    }
+}
+
+/// Returns the unix time until which a response may be served from the
+/// cache without revalidation, or `None` if it is already stale.
+fn freshness_deadline(directives: &CacheControl, headers: &[(String, String)]) -> Option<u64> {
+    if directives.no_cache {
+        return None;
+    }
+
+    let now = unix_now();
+    let deadline = if let Some(max_age) = directives.max_age {
+        let age = header(headers, "Age")
+            .and_then(|it| it.trim().parse::<u64>().ok())
+            .unwrap_or_default();
+        now + max_age.saturating_sub(age)
+    } else {
+        let expires = header(headers, "Expires")?;
+        let expires = httpdate::parse_http_date(expires).ok()?;
+        expires.duration_since(UNIX_EPOCH).ok()?.as_secs()
+    };
+
+    (deadline > now).then_some(deadline)
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::client::RequestOptions;
+
+    /// Responds with `304 Not Modified` to conditional requests.
+    struct CountingClient {
+        sent: Cell<usize>,
+        headers: Vec<(String, String)>,
+        last_request_headers: RefCell<Vec<(String, String)>>,
+    }
+
+    impl HttpClient for CountingClient {
+        fn send(&self, request: Request) -> Result<Response, HttpError> {
+            self.sent.set(self.sent.get() + 1);
+            let not_modified = header(&request.headers, "If-None-Match").is_some()
+                || header(&request.headers, "If-Modified-Since").is_some();
+            *self.last_request_headers.borrow_mut() = request.headers;
+            Ok(Response {
+                version: "HTTP/1.1",
+                status: StatusCode::from(if not_modified { 304 } else { 200 }),
+                headers: self.headers.clone(),
+                body: if not_modified { vec![] } else { b"{}".to_vec() },
+                elapsed: Duration::from_millis(5),
+                timings: Default::default(),
+                redirects: vec![],
+                streamed: false,
+                cache: CacheStatus::Network,
+            })
+        }
+    }
+
+    fn client(name: &str, headers: &[(&str, &str)]) -> CachingHttpClient<CountingClient> {
+        let dir = std::env::temp_dir().join(format!("aurora-cache-{name}-{}", std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        let inner = CountingClient {
+            sent: Cell::new(0),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            last_request_headers: RefCell::new(vec![]),
+        };
+        CachingHttpClient::new(inner, Some(dir))
+    }
+
+    fn get(url: &str) -> Request {
+        Request {
+            method: HttpMethod::Get,
+            url: url.to_string(),
+            headers: vec![],
+            body: None,
+            options: RequestOptions::default(),
+            events: None,
+        }
+    }
+
+    #[test]
+    fn fresh_response_is_served_from_cache() {
+        let client = client("fresh", &[("Cache-Control", "public, max-age=60")]);
+        let first = client.send(get("http://localhost/a")).unwrap();
+        let second = client.send(get("http://localhost/a")).unwrap();
+
+        assert_eq!(first.cache, CacheStatus::Network);
+        assert_eq!(second.cache, CacheStatus::Hit);
+        assert_eq!(second.body, b"{}");
+        assert_eq!(client.inner.sent.get(), 1);
+    }
+
+    #[test]
+    fn no_store_is_not_cached() {
+        let client = client("no-store", &[("Cache-Control", "no-store, max-age=60")]);
+        client.send(get("http://localhost/a")).unwrap();
+        let second = client.send(get("http://localhost/a")).unwrap();
+
+        assert_eq!(second.cache, CacheStatus::Network);
+        assert_eq!(client.inner.sent.get(), 2);
+    }
+
+    #[test]
+    fn expired_response_is_not_served() {
+        let client = client("expired", &[("Expires", "Thu, 01 Jan 2015 00:00:00 GMT")]);
+        client.send(get("http://localhost/a")).unwrap();
+        client.send(get("http://localhost/a")).unwrap();
+
+        assert_eq!(client.inner.sent.get(), 2);
+    }
+
+    #[test]
+    fn stale_response_is_revalidated_with_etag() {
+        let client = client("etag", &[("ETag", "\"v1\""), ("Cache-Control", "no-cache")]);
+        client.send(get("http://localhost/a")).unwrap();
+        let second = client.send(get("http://localhost/a")).unwrap();
+
+        assert_eq!(second.cache, CacheStatus::Revalidated);
+        assert_eq!(second.status.as_u16(), 200);
+        assert_eq!(second.body, b"{}");
+        assert_eq!(
+            header(&client.inner.last_request_headers.borrow(), "If-None-Match"),
+            Some("\"v1\"")
+        );
+    }
+
+    #[test]
+    fn stale_response_is_revalidated_with_last_modified() {
+        let date = "Thu, 01 Jan 2015 00:00:00 GMT";
+        let client = client("last-modified", &[("Last-Modified", date)]);
+        client.send(get("http://localhost/a")).unwrap();
+        let second = client.send(get("http://localhost/a")).unwrap();
+
+        assert_eq!(second.cache, CacheStatus::Revalidated);
+        assert_eq!(
+            header(
+                &client.inner.last_request_headers.borrow(),
+                "If-Modified-Since"
+            ),
+            Some(date)
+        );
+    }
+
+    #[test]
+    fn parse_cache_control() {
+        let headers = vec![(
+            "cache-control".to_string(),
+            "No-Cache, max-age=\"30\"".to_string(),
+        )];
+        assert_eq!(
+            CacheControl::parse(&headers),
+            CacheControl {
+                no_store: false,
+                no_cache: true,
+                max_age: Some(30),
+            }
+        );
+    }
+}
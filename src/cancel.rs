@@ -0,0 +1,66 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// How often [`CancellationToken::sleep`] checks whether the run was
+/// cancelled.
+const SLEEP_STEP: Duration = Duration::from_millis(50);
+
+/// A cheaply clonable flag used to stop a run from another thread.
+///
+/// The machine checks the token before every request, so a cancelled run
+/// stops cleanly after the request that is currently in flight.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Sleeps for `duration`, but wakes up early when the token is
+    /// cancelled. Returns `false` if it was.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return true;
+            }
+            std::thread::sleep(left.min(SLEEP_STEP));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleep_wakes_up_when_cancelled() {
+        let token = CancellationToken::new();
+        assert!(token.sleep(Duration::from_millis(1)));
+
+        token.cancel();
+        let start = Instant::now();
+        assert!(!token.sleep(Duration::from_secs(60)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
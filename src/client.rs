@@ -1,23 +1,217 @@
-use std::str::FromStr;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-use crate::validated::HttpMethod;
+use flate2::{Compression, read::GzEncoder};
 
-#[derive(Debug)]
+use crate::{
+    sse::{EventStreamParser, ServerEvent},
+    timing::{TimingRecorder, Timings},
+    validated::HttpMethod,
+};
+
+#[derive(Debug, Clone)]
 pub struct Request {
     pub method: HttpMethod,
     pub url: String,
     pub headers: Vec<(String, String)>,
-    pub body: Option<String>,
+    pub body: Option<Body>,
+    pub options: RequestOptions,
+    /// Receives the events of a `text/event-stream` response while they
+    /// arrive. Without it, they are only in the body of the response.
+    pub events: Option<EventSink>,
+}
+
+/// A callback for the events of a `text/event-stream` response.
+#[derive(Clone)]
+pub struct EventSink(Arc<dyn Fn(ServerEvent) + Send + Sync>);
+
+impl EventSink {
+    pub fn new(f: impl Fn(ServerEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub fn send(&self, event: ServerEvent) {
+        (self.0)(event);
+    }
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventSink")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    Text(String),
+    /// Streamed from the file while the request is sent, so it never has to
+    /// fit into memory
+    File(PathBuf),
+    /// Streamed from standard input
+    Stdin,
+}
+
+impl Body {
+    /// `false` if sending the body consumes it, so the request can't be sent
+    /// again.
+    pub fn is_replayable(&self) -> bool {
+        !matches!(self, Body::Stdin)
+    }
+}
+
+/// Writes the request like an HTTP/1.1 message, with the full URL in the
+/// request line. Streamed bodies are only named, not read.
+impl std::fmt::Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} {}", self.method.as_str(), self.url)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{name}: {value}")?;
+        }
+        match &self.body {
+            None => Ok(()),
+            Some(Body::Text(text)) => writeln!(f, "\n{text}"),
+            Some(Body::File(path)) => writeln!(f, "\n<contents of {}>", path.display()),
+            Some(Body::Stdin) => writeln!(f, "\n<standard input>"),
+        }
+    }
+}
+
+/// Settings of an `[Options]` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Limit for the whole request, from connecting until the body was read
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub follow_redirects: bool,
+    /// Redirects that are followed before the request fails
+    pub max_redirects: usize,
+    /// How often a request is sent again after it failed with a connection
+    /// error, a timeout or a `5xx` status
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further retry
+    pub retry_backoff: Duration,
+    /// `None` lets the client and the server negotiate the version
+    pub http_version: Option<HttpVersion>,
+    /// Pause before sending the request, unless it is the first one of the
+    /// run. The machine waits, not the client.
+    pub delay: Duration,
+    /// Events of a `text/event-stream` response after which the connection
+    /// is closed
+    pub max_events: Option<usize>,
+    /// Time after which the connection of a `text/event-stream` response is
+    /// closed. Any other response that takes longer fails with a timeout.
+    pub max_duration: Option<Duration>,
+    /// Compress the body with gzip and set `Content-Encoding` accordingly
+    pub gzip: bool,
+    /// Ask for compressed responses and decode gzip, brotli and deflate
+    /// bodies before they are printed
+    pub decompress: bool,
+    /// Sends the request again until its assertions hold. The machine
+    /// repeats it, not the client.
+    pub poll: Option<Poll>,
+}
+
+/// E.g. `"poll": {"interval": 2000, "attempts": 30}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poll {
+    /// Pause between two attempts
+    pub interval: Duration,
+    /// Attempts after which the last failed assertions are reported, at
+    /// least one
+    pub attempts: u32,
+}
+
+impl Default for Poll {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            attempts: 10,
+        }
+    }
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            connect_timeout: None,
+            follow_redirects: true,
+            max_redirects: 10,
+            retries: 0,
+            retry_backoff: Duration::from_millis(500),
+            http_version: None,
+            delay: Duration::ZERO,
+            max_events: None,
+            max_duration: None,
+            gzip: false,
+            decompress: true,
+            poll: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    Http1,
+    /// Sent without upgrading from HTTP/1.1 first, so the server must
+    /// support it
+    Http2,
+}
+
+impl HttpVersion {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1.1" => Some(HttpVersion::Http1),
+            "2" => Some(HttpVersion::Http2),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Response {
+    /// The protocol of the status line, e.g. `HTTP/1.1`
+    pub version: &'static str,
     pub status: StatusCode,
     pub headers: Vec<(String, String)>,
     pub body: Vec<u8>,
+    /// Time from sending the request until the whole body was received
+    pub elapsed: Duration,
+    /// Durations of the phases within `elapsed`
+    pub timings: Timings,
+    pub cache: CacheStatus,
+    /// Redirects that were followed to get the response, in order
+    pub redirects: Vec<Redirect>,
+    /// `true` if the body is an event stream whose events were printed while
+    /// they arrived
+    pub streamed: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub status: StatusCode,
+    /// URL the redirect pointed to
+    pub location: String,
+}
+
+/// Where the response came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Network,
+    /// Served from the HTTP cache without contacting the server
+    Hit,
+    /// Served from the HTTP cache after the server confirmed it is unchanged
+    Revalidated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct StatusCode(u16);
 
 impl From<u16> for StatusCode {
@@ -30,10 +224,47 @@ impl StatusCode {
     pub fn is_success(self) -> bool {
         (200..300).contains(&self.0)
     }
+
+    pub fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// E.g. `Not Found` for 404, `None` for codes without a standard reason
+    pub fn canonical_reason(self) -> Option<&'static str> {
+        reqwest::StatusCode::from_u16(self.0)
+            .ok()
+            .and_then(|it| it.canonical_reason())
+    }
 }
 
+/// JSON bodies larger than this are printed as they are, unless pretty
+/// printing is forced.
+pub const PRETTY_PRINT_LIMIT: usize = 1024 * 1024;
+
 impl Response {
-    pub fn pretty_body(&self) -> String {
+    /// E.g. `HTTP/1.1 200 OK`
+    pub fn status_line(&self) -> String {
+        let line = format!(
+            "{} {} {}",
+            self.version,
+            self.status.as_u16(),
+            self.status.canonical_reason().unwrap_or_default()
+        );
+        line.trim_end().to_string()
+    }
+
+    /// Returns the status line and the headers, one per line, as `curl -i`
+    /// prints them before the body.
+    pub fn head(&self) -> String {
+        let mut out = self.status_line();
+        out.push('\n');
+        for (name, value) in &self.headers {
+            out.push_str(&format!("{name}: {value}\n"));
+        }
+        out
+    }
+
+    pub fn pretty_body(&self, force_pretty: bool) -> String {
         let content_type = self
             .headers
             .iter()
@@ -41,17 +272,27 @@ impl Response {
             .map(|(_, v)| v.as_str())
             .unwrap_or_default();
 
-        let body_str = String::from_utf8_lossy(&self.body);
-        if content_type.contains("application/json") {
-            return serde_json::from_str::<serde_json::Value>(&body_str)
-                .map(|v| serde_json::to_string_pretty(&v).unwrap_or_else(|_| body_str.to_string()))
-                .unwrap_or_else(|_| body_str.to_string());
+        if content_type.contains("application/json")
+            && (force_pretty || self.body.len() <= PRETTY_PRINT_LIMIT)
+            && let Some(pretty) = pretty_json(&self.body)
+        {
+            return pretty;
         }
 
-        body_str.to_string()
+        String::from_utf8_lossy(&self.body).into_owned()
     }
 }
 
+/// Re-indents a JSON document without building an intermediate tree.
+fn pretty_json(body: &[u8]) -> Option<String> {
+    let mut out = Vec::with_capacity(body.len() + body.len() / 2);
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let mut serializer = serde_json::Serializer::pretty(&mut out);
+    serde_transcode::transcode(&mut deserializer, &mut serializer).ok()?;
+    deserializer.end().ok()?;
+    String::from_utf8(out).ok()
+}
+
 #[derive(Debug)]
 pub enum HttpError {
     InvalidUrl(String),
@@ -59,8 +300,10 @@ pub enum HttpError {
     InvalidHeaderValue(String),
     Connection(String),
     Timeout,
+    TooManyRedirects(usize),
     Transport(String),
     BodyRead(String),
+    BodyFile(PathBuf, String),
 }
 
 impl std::fmt::Display for HttpError {
@@ -71,8 +314,12 @@ impl std::fmt::Display for HttpError {
             HttpError::InvalidHeaderValue(value) => write!(f, "invalid header value: `{value}`"),
             HttpError::Connection(msg) => write!(f, "connection error: {msg}"),
             HttpError::Timeout => write!(f, "request timed out"),
+            HttpError::TooManyRedirects(max) => write!(f, "stopped after {max} redirects"),
             HttpError::Transport(msg) => write!(f, "transport error: {msg}"),
             HttpError::BodyRead(msg) => write!(f, "failed to read response body: {msg}"),
+            HttpError::BodyFile(path, msg) => {
+                write!(f, "failed to read request body `{}`: {msg}", path.display())
+            }
         }
     }
 }
@@ -81,27 +328,161 @@ pub trait HttpClient {
     fn send(&self, request: Request) -> Result<Response, HttpError>;
 }
 
+/// Connection settings that apply to every request of a run.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionOptions {
+    /// Accept any certificate, e.g. the self-signed ones of staging servers
+    pub insecure: bool,
+    /// Trusted in addition to the root certificates of the system
+    pub root_certificates: Vec<reqwest::Certificate>,
+    /// Addresses used for hosts instead of looking them up. reqwest uses
+    /// them for any port of the host.
+    pub resolve: Vec<(String, SocketAddr)>,
+}
+
+impl ConnectionOptions {
+    /// Reads all certificates of a PEM file, e.g. a CA bundle.
+    pub fn load_certificates(path: &Path) -> Result<Vec<reqwest::Certificate>, String> {
+        let pem = std::fs::read(path).map_err(|e| e.to_string())?;
+        let certificates =
+            reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| e.to_string())?;
+        if certificates.is_empty() {
+            return Err("the file contains no PEM certificates".to_string());
+        }
+        Ok(certificates)
+    }
+}
+
+/// Options that reqwest only lets us set per client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClientConfig {
+    connect_timeout: Option<Duration>,
+    follow_redirects: bool,
+    max_redirects: usize,
+    http_version: Option<HttpVersion>,
+    decompress: bool,
+}
+
+impl From<&RequestOptions> for ClientConfig {
+    fn from(options: &RequestOptions) -> Self {
+        Self {
+            connect_timeout: options.connect_timeout,
+            follow_redirects: options.follow_redirects,
+            max_redirects: options.max_redirects,
+            http_version: options.http_version,
+            decompress: options.decompress,
+        }
+    }
+}
+
+/// Sends requests with reqwest, reusing connections across requests.
+///
+/// The client can be shared between threads, but the timings and redirects
+/// of requests sent at the same time then get mixed up. Only `aurora bench`
+/// does that, it just measures how long requests take.
 pub struct ReqwestHttpClient {
-    client: reqwest::blocking::Client,
+    connection: ConnectionOptions,
+    /// One client for every combination of options used so far
+    clients: Mutex<Vec<(ClientConfig, reqwest::blocking::Client)>>,
+    /// Redirects followed by the request that is being sent. The redirect
+    /// policy of every client writes to it, since reqwest doesn't tell us.
+    redirects: Arc<Mutex<Vec<Redirect>>>,
+    /// Timings of the request that is being sent, shared by every client
+    timings: TimingRecorder,
 }
 
 impl ReqwestHttpClient {
-    pub fn new() -> Self {
-        Self {
-            client: reqwest::blocking::Client::new(),
+    pub fn new(connection: ConnectionOptions) -> Result<Self, HttpError> {
+        let client = Self {
+            connection,
+            clients: Mutex::new(vec![]),
+            redirects: Arc::new(Mutex::new(vec![])),
+            timings: TimingRecorder::default(),
+        };
+        // Fails early when the TLS settings can't be used.
+        client.client_for(&RequestOptions::default())?;
+        Ok(client)
+    }
+
+    fn client_for(&self, options: &RequestOptions) -> Result<reqwest::blocking::Client, HttpError> {
+        let config = ClientConfig::from(options);
+        let mut clients = self.clients.lock().unwrap();
+        if let Some((_, client)) = clients.iter().find(|(it, _)| *it == config) {
+            return Ok(client.clone());
+        }
+
+        let mut builder = reqwest::blocking::Client::builder()
+            .danger_accept_invalid_certs(self.connection.insecure);
+        for certificate in &self.connection.root_certificates {
+            builder = builder.add_root_certificate(certificate.clone());
+        }
+        for (host, addr) in &self.connection.resolve {
+            builder = builder.resolve(host, *addr);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder = builder
+            .dns_resolver(self.timings.resolver())
+            .connector_layer(self.timings.connector_layer())
+            .redirect(self.redirect_policy(config))
+            .gzip(config.decompress)
+            .brotli(config.decompress)
+            .deflate(config.decompress);
+        builder = match config.http_version {
+            Some(HttpVersion::Http1) => builder.http1_only(),
+            Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+            None => builder,
+        };
+        let client = builder
+            .build()
+            .map_err(|e| HttpError::Transport(e.to_string()))?;
+        clients.push((config, client.clone()));
+        Ok(client)
+    }
+
+    fn redirect_policy(&self, config: ClientConfig) -> reqwest::redirect::Policy {
+        if !config.follow_redirects {
+            return reqwest::redirect::Policy::none();
         }
+
+        let redirects = Arc::clone(&self.redirects);
+        let max = config.max_redirects;
+        reqwest::redirect::Policy::custom(move |attempt| {
+            // `previous` starts with the URL of the request itself.
+            if attempt.previous().len() > max {
+                return attempt.error(HttpError::TooManyRedirects(max).to_string());
+            }
+            redirects.lock().unwrap().push(Redirect {
+                status: StatusCode::from(attempt.status().as_u16()),
+                location: attempt.url().to_string(),
+            });
+            attempt.follow()
+        })
     }
 }
 
 impl HttpClient for ReqwestHttpClient {
     fn send(&self, request: Request) -> Result<Response, HttpError> {
+        let client = self.client_for(&request.options)?;
         let mut builder = match request.method {
-            HttpMethod::Get => self.client.get(&request.url),
-            HttpMethod::Post => self.client.post(&request.url),
-            HttpMethod::Put => self.client.put(&request.url),
-            HttpMethod::Patch => self.client.patch(&request.url),
-            HttpMethod::Delete => self.client.delete(&request.url),
+            HttpMethod::Get => client.get(&request.url),
+            HttpMethod::Post => client.post(&request.url),
+            HttpMethod::Put => client.put(&request.url),
+            HttpMethod::Patch => client.patch(&request.url),
+            HttpMethod::Delete => client.delete(&request.url),
+            HttpMethod::Head => client.head(&request.url),
+            HttpMethod::Options => client.request(reqwest::Method::OPTIONS, &request.url),
+        };
+        // The client can't close a connection while it waits for the next
+        // event, so the timeout does it.
+        let timeout = match (request.options.timeout, request.options.max_duration) {
+            (Some(timeout), Some(max)) => Some(timeout.min(max)),
+            (timeout, max) => timeout.or(max),
         };
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
 
         let mut headers = reqwest::header::HeaderMap::with_capacity(request.headers.len());
         for (k, v) in &request.headers {
@@ -112,14 +493,26 @@ impl HttpClient for ReqwestHttpClient {
             headers.insert(name, value);
         }
 
-        builder = builder.headers(headers);
         if let Some(body) = request.body {
-            builder = builder.body(body);
+            if request.options.gzip && !headers.contains_key(reqwest::header::CONTENT_ENCODING) {
+                headers.insert(
+                    reqwest::header::CONTENT_ENCODING,
+                    reqwest::header::HeaderValue::from_static("gzip"),
+                );
+            }
+            builder = builder.body(encode_body(body, request.options.gzip)?);
         }
+        builder = builder.headers(headers);
 
+        let start = Instant::now();
+        self.redirects.lock().unwrap().clear();
+        self.timings.reset();
+        let max_redirects = request.options.max_redirects;
         let response = builder.send().map_err(|e| {
             if e.is_timeout() {
                 HttpError::Timeout
+            } else if e.is_redirect() {
+                HttpError::TooManyRedirects(max_redirects)
             } else if e.is_connect() {
                 HttpError::Connection(e.to_string())
             } else {
@@ -131,23 +524,234 @@ impl HttpClient for ReqwestHttpClient {
             .headers()
             .iter()
             .map(|(k, v)| {
-                let value = v.to_str().map_err(|_| {
-                    HttpError::InvalidHeaderValue(format!("{}: invalid UTF-8", k.to_string()))
-                })?;
+                let value = v
+                    .to_str()
+                    .map_err(|_| HttpError::InvalidHeaderValue(format!("{k}: invalid UTF-8")))?;
                 Ok((k.to_string(), value.to_string()))
             })
             .collect::<Result<Vec<_>, HttpError>>()?;
 
+        let ttfb = start.elapsed();
+        tracing::debug!("received an {:?} response", response.version());
+        let version = match response.version() {
+            reqwest::Version::HTTP_09 => "HTTP/0.9",
+            reqwest::Version::HTTP_10 => "HTTP/1.0",
+            reqwest::Version::HTTP_2 => "HTTP/2",
+            reqwest::Version::HTTP_3 => "HTTP/3",
+            _ => "HTTP/1.1",
+        };
         let status = StatusCode::from(response.status().as_u16());
-        let body = response
-            .bytes()
-            .map_err(|e| HttpError::BodyRead(e.to_string()))?
-            .to_vec();
+        let streamed = headers.iter().any(|(k, v)| {
+            k.eq_ignore_ascii_case("content-type") && v.starts_with("text/event-stream")
+        });
+        let body = if streamed {
+            read_event_stream(response, &request.options, request.events.as_ref(), start)?
+        } else {
+            response
+                .bytes()
+                .map_err(|e| HttpError::BodyRead(e.to_string()))?
+                .to_vec()
+        };
 
         Ok(Response {
+            version,
             status,
             headers,
             body,
+            elapsed: start.elapsed(),
+            timings: Timings {
+                ttfb,
+                ..self.timings.get()
+            },
+            cache: CacheStatus::Network,
+            redirects: std::mem::take(&mut *self.redirects.lock().unwrap()),
+            streamed,
         })
     }
 }
+
+/// Wraps `reader` into the encoder used for the `gzip` option. Its output only
+/// depends on the input, so a signature can be computed over it in advance.
+pub fn gzip<R: Read>(reader: R) -> GzEncoder<R> {
+    GzEncoder::new(reader, Compression::default())
+}
+
+fn encode_body(body: Body, gzip: bool) -> Result<reqwest::blocking::Body, HttpError> {
+    let reader: Box<dyn Read + Send> = match body {
+        Body::Text(text) if gzip => {
+            // Compressed up front, so the length is known.
+            let mut compressed = vec![];
+            self::gzip(text.as_bytes())
+                .read_to_end(&mut compressed)
+                .expect("reading from memory can't fail");
+            return Ok(compressed.into());
+        }
+        Body::Text(text) => return Ok(text.into()),
+        Body::File(path) => {
+            let file =
+                File::open(&path).map_err(|e| HttpError::BodyFile(path.clone(), e.to_string()))?;
+            if !gzip {
+                return Ok(file.into());
+            }
+            Box::new(file)
+        }
+        Body::Stdin => Box::new(std::io::stdin()),
+    };
+    Ok(if gzip {
+        reqwest::blocking::Body::new(self::gzip(reader))
+    } else {
+        reqwest::blocking::Body::new(reader)
+    })
+}
+
+/// Hands the events of `response` to `events` as they arrive, until the
+/// server closes the stream or a cutoff of `options` is reached. Returns the
+/// raw stream.
+fn read_event_stream(
+    response: reqwest::blocking::Response,
+    options: &RequestOptions,
+    events: Option<&EventSink>,
+    start: Instant,
+) -> Result<Vec<u8>, HttpError> {
+    let mut reader = BufReader::new(response);
+    let mut parser = EventStreamParser::new();
+    let mut body = vec![];
+    let mut line = String::new();
+    let mut count = 0;
+    while options.max_events.is_none_or(|max| count < max) {
+        line.clear();
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(_)
+                if options
+                    .max_duration
+                    .is_some_and(|max| start.elapsed() >= max) =>
+            {
+                break;
+            }
+            Err(e) => return Err(HttpError::BodyRead(e.to_string())),
+        }
+        body.extend_from_slice(line.as_bytes());
+
+        if let Some(event) = parser.feed_line(line.trim_end_matches(['\r', '\n'])) {
+            if let Some(events) = events {
+                events.send(event);
+            }
+            count += 1;
+        }
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json_response(body: &str) -> Response {
+        Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(200),
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: body.as_bytes().to_vec(),
+            elapsed: Duration::ZERO,
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        }
+    }
+
+    #[test]
+    fn gzip_is_deterministic() {
+        let compress = || {
+            let mut out = vec![];
+            gzip(&b"{\"name\": \"Ada\"}"[..])
+                .read_to_end(&mut out)
+                .unwrap();
+            out
+        };
+        let compressed = compress();
+        assert_eq!(compressed, compress());
+
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "{\"name\": \"Ada\"}");
+    }
+
+    #[test]
+    fn load_certificates_without_pem() {
+        let path = std::env::temp_dir().join(format!("aurora-ca-{}.pem", std::process::id()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let result = ConnectionOptions::load_certificates(&path);
+        _ = std::fs::remove_file(&path);
+        assert_eq!(
+            result.unwrap_err(),
+            "the file contains no PEM certificates".to_string()
+        );
+        assert!(ConnectionOptions::load_certificates(Path::new("/does/not/exist.pem")).is_err());
+    }
+
+    #[test]
+    fn pretty_body_json() {
+        let response = json_response(r#"{"b":1,"a":[true,null]}"#);
+        assert_eq!(
+            response.pretty_body(false),
+            "{\n  \"b\": 1,\n  \"a\": [\n    true,\n    null\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn pretty_body_invalid_json_is_unchanged() {
+        let response = json_response(r#"{"a":1} trailing"#);
+        assert_eq!(response.pretty_body(false), r#"{"a":1} trailing"#);
+    }
+
+    #[test]
+    fn pretty_body_skips_large_json_unless_forced() {
+        let body = format!("[{}1]", "1,".repeat(PRETTY_PRINT_LIMIT / 2));
+        let response = json_response(&body);
+        assert_eq!(response.pretty_body(false), body);
+        assert!(response.pretty_body(true).starts_with("[\n  1,\n"));
+    }
+
+    #[test]
+    fn response_head() {
+        let mut response = json_response("");
+        assert_eq!(response.status_line(), "HTTP/1.1 200 OK");
+        assert_eq!(
+            response.head(),
+            "HTTP/1.1 200 OK\ncontent-type: application/json\n"
+        );
+
+        response.version = "HTTP/2";
+        response.status = StatusCode::from(599);
+        response.headers.clear();
+        assert_eq!(response.head(), "HTTP/2 599\n");
+    }
+
+    #[test]
+    fn display_request() {
+        let mut request = Request {
+            method: HttpMethod::Post,
+            url: "https://example.com/users?page=2".to_string(),
+            headers: vec![("Authorization".to_string(), "Bearer abc".to_string())],
+            body: Some(Body::Text(r#"{"name":"Ada"}"#.to_string())),
+            options: RequestOptions::default(),
+            events: None,
+        };
+        assert_eq!(
+            request.to_string(),
+            "POST https://example.com/users?page=2\nAuthorization: Bearer abc\n\n{\"name\":\"Ada\"}\n"
+        );
+
+        request.body = Some(Body::Stdin);
+        request.headers.clear();
+        assert_eq!(
+            request.to_string(),
+            "POST https://example.com/users?page=2\n\n<standard input>\n"
+        );
+    }
+}
@@ -0,0 +1,483 @@
+//! Stable codes of the kinds of diagnostics, like `AU0004` for unknown
+//! identifiers, so errors can be searched for and categorized by tools.
+//! `aurora explain` prints their explanations.
+//!
+//! Codes are never reused or renumbered. A kind of diagnostic that goes away
+//! keeps its code.
+
+/// The code of a kind of diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    pub id: &'static str,
+    /// What the diagnostics of this kind are about, in a few words
+    pub title: &'static str,
+    /// What causes them, with an example and how to fix it
+    pub explanation: &'static str,
+}
+
+pub const SYNTAX: Code = Code {
+    id: "AU0001",
+    title: "Syntax error",
+    explanation: r#"The file doesn't follow the syntax of `.au` files, e.g. a token is
+missing or in the wrong place.
+
+    const port 8080
+
+A `const` needs a `=` between its name and value:
+
+    const port = 8080
+"#,
+};
+
+pub const UNTERMINATED: Code = Code {
+    id: "AU0002",
+    title: "Unterminated literal",
+    explanation: r#"A string, a template inside a string or an identifier in backticks
+isn't closed before the end of its line or the file.
+
+    const url = "https://example.com
+
+Close it with the same character it was opened with:
+
+    const url = "https://example.com"
+"#,
+};
+
+pub const INVALID_LITERAL: Code = Code {
+    id: "AU0003",
+    title: "Invalid literal",
+    explanation: r#"A number doesn't fit its type, or a string contains an escape sequence
+that doesn't exist.
+
+    const path = "C:\data"
+
+The escapes are `\"`, `\\` and `\n`. Escape the backslash itself to
+write one:
+
+    const path = "C:\\data"
+"#,
+};
+
+pub const UNKNOWN_IDENTIFIER: Code = Code {
+    id: "AU0004",
+    title: "Unknown identifier",
+    explanation: r#"A name refers to nothing. Names can refer to consts, variables given
+on the command line, parameters and loop variables of the entry, and
+values captured by any entry.
+
+    entry getUser {
+        GET "{{baseUrl}}/users/1"
+    }
+
+Define the name, pass it with `--var baseUrl=...`, or fix its spelling:
+
+    const baseUrl = "https://example.com"
+"#,
+};
+
+pub const MISMATCHED_TYPES: Code = Code {
+    id: "AU0005",
+    title: "Mismatched types",
+    explanation: r#"A value has a type that can't be used where it is, e.g. a number as a
+header value or a string as a condition.
+
+    entry a {
+        GET "https://example.com"
+        [Headers] {"X-Retries": 3}
+    }
+
+Convert the value, e.g. by writing it into a string:
+
+        [Headers] {"X-Retries": "3"}
+"#,
+};
+
+pub const UNKNOWN_TYPE: Code = Code {
+    id: "AU0006",
+    title: "Unknown type",
+    explanation: r#"A type annotation names a type that doesn't exist.
+
+    const port: integer = 8080
+
+The types are `string`, `int`, `float`, `bool` and `null`, arrays like
+`[int]` and dictionaries like `{string}`:
+
+    const port: int = 8080
+"#,
+};
+
+pub const DEFINED_MULTIPLE_TIMES: Code = Code {
+    id: "AU0007",
+    title: "Defined multiple times",
+    explanation: r#"Two consts, entries, environments, parameters or captures have the
+same name, or there is more than one `defaults` block. A const may also
+not have the name of a variable given on the command line.
+
+    entry login {}
+    entry login {}
+
+Rename or remove one of them.
+"#,
+};
+
+pub const UNKNOWN_SECTION: Code = Code {
+    id: "AU0008",
+    title: "Unknown section",
+    explanation: r#"An entry has a section that doesn't exist. Names of sections are case
+sensitive.
+
+    entry a {
+        GET "https://example.com"
+        [headers] {"Accept": "application/json"}
+    }
+
+The sections are `[Assert]`, `[Auth]`, `[BaseUrl]`, `[Body]`,
+`[Capture]`, `[Cookies]`, `[Headers]`, `[Options]` and `[Query]`.
+"#,
+};
+
+pub const REPEATED_SECTION: Code = Code {
+    id: "AU0009",
+    title: "Repeated section",
+    explanation: r#"An entry has more than one request, or more than one section of the
+same name.
+
+    entry a {
+        GET "https://example.com"
+        [Headers] {"Accept": "application/json"}
+        [Headers] {"X-Trace": "1"}
+    }
+
+Merge the sections, or move the second request into an entry of its own:
+
+        [Headers] {"Accept": "application/json", "X-Trace": "1"}
+"#,
+};
+
+pub const MISPLACED_ITEM: Code = Code {
+    id: "AU0010",
+    title: "Misplaced request or loop",
+    explanation: r#"A request is in the `defaults` block, which only holds sections, or a
+`for` loop shares its entry with other items. A loop repeats the whole
+entry, so it has to be its only item.
+
+    entry seed {
+        [Headers] {"Accept": "application/json"}
+        for user in users {
+            POST "https://example.com/users"
+        }
+    }
+
+Move the other items into the loop.
+"#,
+};
+
+pub const UNKNOWN_ENTRY: Code = Code {
+    id: "AU0011",
+    title: "Unknown entry",
+    explanation: r#"`extends` or `needs` names an entry that isn't in the file.
+
+    entry me needs logn {
+        GET "https://example.com/me"
+    }
+
+Fix the name, or add the entry:
+
+    entry me needs login {
+"#,
+};
+
+pub const CYCLIC_ENTRIES: Code = Code {
+    id: "AU0012",
+    title: "Cyclic entries",
+    explanation: r#"Entries extend or need each other in a cycle, so there is no entry to
+start with.
+
+    entry a needs b {}
+    entry b needs a {}
+
+Break the cycle, e.g. by moving what both need into a third entry.
+"#,
+};
+
+pub const INVALID_CALL: Code = Code {
+    id: "AU0013",
+    title: "Invalid function call",
+    explanation: r#"A function doesn't exist, or is called with the wrong number of
+arguments.
+
+    const id = uuid(4)
+
+Check the name and the arguments of the function:
+
+    const id = uuid()
+
+The same goes for an entry called after `needs`, which takes one argument
+per parameter:
+
+    entry getUser(id) {
+        GET "https://example.com/users/{{id}}"
+    }
+
+    entry me needs getUser("42") {}
+"#,
+};
+
+pub const UNKNOWN_FIELD: Code = Code {
+    id: "AU0014",
+    title: "Unknown field",
+    explanation: r#"A field is accessed that the dictionary doesn't have.
+
+    const user = {"name": "Ada"}
+    const email = user.email
+
+Add the field to the dictionary, or access one it has.
+"#,
+};
+
+pub const INVALID_OPTION: Code = Code {
+    id: "AU0015",
+    title: "Invalid option",
+    explanation: r#"An `[Options]` section has an option that doesn't exist, or a value
+it can't use.
+
+    entry a {
+        GET "https://example.com"
+        [Options] {"timeout": "5s"}
+    }
+
+Durations are given in milliseconds:
+
+        [Options] {"timeout": 5000}
+"#,
+};
+
+pub const INVALID_AUTH: Code = Code {
+    id: "AU0016",
+    title: "Invalid authentication",
+    explanation: r#"An `[Auth]` section doesn't name exactly one known scheme, or misses
+settings of its scheme.
+
+    entry a {
+        GET "https://example.com"
+        [Auth] {"token": token}
+    }
+
+Name the scheme, e.g. `bearer` or `aws-sigv4`:
+
+        [Auth] {"bearer": token}
+"#,
+};
+
+pub const INVALID_JSONPATH: Code = Code {
+    id: "AU0017",
+    title: "Invalid JSONPath",
+    explanation: r#"A path into a JSON body, in a `[Capture]` section or an assertion,
+isn't a valid JSONPath.
+
+    [Capture] {"token": "access_token"}
+
+Paths start at the root `$`:
+
+    [Capture] {"token": "$.access_token"}
+"#,
+};
+
+pub const INVALID_CAPTURE: Code = Code {
+    id: "AU0018",
+    title: "Invalid capture",
+    explanation: r#"A `[Capture]` section names a value with a template or a spread, or a
+value couldn't be captured from the response, e.g. because the body
+doesn't contain the path.
+
+    [Capture] {"{{prefix}}Token": "$.access_token"}
+
+Captures need plain names, so other entries can refer to them:
+
+    [Capture] {"token": "$.access_token"}
+"#,
+};
+
+pub const INVALID_ASSERTION: Code = Code {
+    id: "AU0019",
+    title: "Invalid assertion",
+    explanation: r#"An assertion checks something that doesn't exist, compares in a way
+its subject doesn't support, or uses an invalid regex or unit.
+
+    [Assert] {
+        status == 200ms
+    }
+
+Units are only allowed for `duration` and `ttfb`:
+
+    [Assert] {
+        status == 200
+    }
+"#,
+};
+
+pub const GET_WITH_BODY: Code = Code {
+    id: "AU0020",
+    title: "Request without body semantics has a body",
+    explanation: r#"A `GET` or `HEAD` request has a `[Body]`. Servers usually ignore the
+body of these requests, or reject them. This is a warning, the body is
+sent anyway.
+
+    entry search {
+        GET "https://example.com/search"
+        [Body] {"query": "aurora"}
+    }
+
+Send the values in the query, or use a method that has a body:
+
+        [Query] {"query": "aurora"}
+"#,
+};
+
+pub const FAILED_ASSERTION: Code = Code {
+    id: "AU0021",
+    title: "Failed assertion",
+    explanation: r#"An assertion of an `[Assert]` section didn't hold for the response.
+The diagnostic shows the actual value.
+
+    [Assert] {
+        status == 200
+    }
+
+Either the server behaves unexpectedly, or the assertion has to change.
+An assertion with `soft` in front only warns when it fails, e.g. while
+an API still changes:
+
+    [Assert] {
+        soft not body contains "deprecated"
+    }
+"#,
+};
+
+pub const UNSET_ENV_VAR: Code = Code {
+    id: "AU0022",
+    title: "Unset environment variable",
+    explanation: r#"`env(...)` reads an environment variable that isn't set, and has no
+default.
+
+    const token = env("API_TOKEN")
+
+Set the variable, or give a default as the second argument:
+
+    const token = env("API_TOKEN", "")
+"#,
+};
+
+pub const UNREADABLE_FILE: Code = Code {
+    id: "AU0023",
+    title: "Unreadable file",
+    explanation: r#"A file that a request sends couldn't be read, e.g. because it doesn't
+exist. Relative paths start at the directory of the `.au` file.
+
+Check the path and the permissions of the file.
+"#,
+};
+
+pub const INVALID_TIME_FORMAT: Code = Code {
+    id: "AU0024",
+    title: "Invalid time format",
+    explanation: r#"A time function got a `strftime` format it doesn't understand.
+
+    const today = now("%Q")
+
+See the `strftime` documentation for the known specifiers:
+
+    const today = now("%Y-%m-%d")
+"#,
+};
+
+pub const UNUSED_CONST: Code = Code {
+    id: "AU0025",
+    title: "Unused const",
+    explanation: r#"No entry and no other const refers to a const, so it has no effect.
+This is a warning.
+
+    const timeout = 5000
+
+    entry a {
+        GET "https://example.com"
+    }
+
+Remove the const, or use it. Start its name with `_` to keep it without
+a warning:
+
+    const _timeout = 5000
+"#,
+};
+
+pub const SHADOWED_NAME: Code = Code {
+    id: "AU0026",
+    title: "Shadowed name",
+    explanation: r#"A parameter or loop variable of an entry has the name of a const or a
+variable given on the command line. Inside the entry, the name refers
+to the parameter, and the const can't be used. This is a warning.
+
+    const id = "me"
+
+    entry getUser(id) {
+        GET "https://example.com/users/{{id}}"
+    }
+
+Rename the parameter or the const:
+
+    entry getUser(userId) {
+        GET "https://example.com/users/{{userId}}"
+    }
+"#,
+};
+
+/// All codes, in the order of their ids.
+pub const ALL: &[Code] = &[
+    SYNTAX,
+    UNTERMINATED,
+    INVALID_LITERAL,
+    UNKNOWN_IDENTIFIER,
+    MISMATCHED_TYPES,
+    UNKNOWN_TYPE,
+    DEFINED_MULTIPLE_TIMES,
+    UNKNOWN_SECTION,
+    REPEATED_SECTION,
+    MISPLACED_ITEM,
+    UNKNOWN_ENTRY,
+    CYCLIC_ENTRIES,
+    INVALID_CALL,
+    UNKNOWN_FIELD,
+    INVALID_OPTION,
+    INVALID_AUTH,
+    INVALID_JSONPATH,
+    INVALID_CAPTURE,
+    INVALID_ASSERTION,
+    GET_WITH_BODY,
+    FAILED_ASSERTION,
+    UNSET_ENV_VAR,
+    UNREADABLE_FILE,
+    INVALID_TIME_FORMAT,
+    UNUSED_CONST,
+    SHADOWED_NAME,
+];
+
+/// Returns the code with the id `id`, ignoring case, e.g. `au0004`.
+pub fn find(id: &str) -> Option<Code> {
+    ALL.iter()
+        .copied()
+        .find(|it| it.id.eq_ignore_ascii_case(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_sequential() {
+        for (i, code) in ALL.iter().enumerate() {
+            assert_eq!(code.id, format!("AU{:04}", i + 1));
+        }
+        assert_eq!(find("au0004"), Some(UNKNOWN_IDENTIFIER));
+        assert_eq!(find("AU9999"), None);
+    }
+}
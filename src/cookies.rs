@@ -0,0 +1,539 @@
+use std::{
+    fmt::Write,
+    io,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::client::{HttpClient, HttpError, Request, Response};
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Host name without a leading dot
+    pub domain: String,
+    pub include_subdomains: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Unix time at which the cookie expires, `0` for session cookies
+    pub expires: u64,
+}
+
+impl Cookie {
+    fn matches(&self, url: &reqwest::Url, now: u64) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        let domain_matches = host.eq_ignore_ascii_case(&self.domain)
+            || (self.include_subdomains
+                && host.len() > self.domain.len()
+                && host.ends_with(&self.domain)
+                && host[..host.len() - self.domain.len()].ends_with('.'));
+
+        domain_matches
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && (self.expires == 0 || self.expires > now)
+    }
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Cookies in the Netscape cookie file format used by curl and browsers, or
+/// in JSON when the file name ends in `.json`.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let input = std::fs::read_to_string(path)?;
+        if is_json(path) {
+            Self::parse_json(&input).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(Self::parse(&input))
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let contents = if is_json(path) {
+            self.to_json()
+        } else {
+            self.to_netscape()
+        };
+        std::fs::write(path, contents)
+    }
+
+    /// Parses a Netscape cookie file, skipping lines it doesn't understand
+    /// the way curl does.
+    pub fn parse(input: &str) -> Self {
+        let mut jar = CookieJar::new();
+        for line in input.lines() {
+            let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+                Some(line) => (line, true),
+                None => (line, false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let [
+                domain,
+                include_subdomains,
+                path,
+                secure,
+                expires,
+                name,
+                value,
+            ] = fields[..]
+            else {
+                tracing::warn!("Skipping malformed cookie line `{line}`");
+                continue;
+            };
+
+            jar.insert(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_string(),
+                include_subdomains: include_subdomains.eq_ignore_ascii_case("TRUE"),
+                path: path.to_string(),
+                secure: secure.eq_ignore_ascii_case("TRUE"),
+                http_only,
+                expires: expires.parse().unwrap_or_default(),
+            });
+        }
+        jar
+    }
+
+    pub fn to_netscape(&self) -> String {
+        let mut out = String::from("# Netscape HTTP Cookie File\n");
+        for cookie in &self.cookies {
+            let bool_field = |b: bool| if b { "TRUE" } else { "FALSE" };
+            let prefix = if cookie.http_only {
+                HTTP_ONLY_PREFIX
+            } else {
+                ""
+            };
+            _ = writeln!(
+                out,
+                "{prefix}{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                if cookie.include_subdomains { "." } else { "" },
+                cookie.domain,
+                bool_field(cookie.include_subdomains),
+                cookie.path,
+                bool_field(cookie.secure),
+                cookie.expires,
+                cookie.name,
+                cookie.value,
+            );
+        }
+        out
+    }
+
+    /// Parses an array of cookie objects as written by [`CookieJar::to_json`].
+    pub fn parse_json(input: &str) -> Result<Self, String> {
+        let value = serde_json::from_str::<serde_json::Value>(input).map_err(|e| e.to_string())?;
+        let Some(cookies) = value.as_array() else {
+            return Err("expected an array of cookies".to_string());
+        };
+
+        let mut jar = CookieJar::new();
+        for cookie in cookies {
+            let string = |key: &str| cookie.get(key).and_then(|it| it.as_str());
+            let bool = |key: &str| cookie.get(key).and_then(|it| it.as_bool());
+            let (Some(name), Some(value), Some(domain)) =
+                (string("name"), string("value"), string("domain"))
+            else {
+                return Err(format!("expected `name`, `value` and `domain` in {cookie}"));
+            };
+            jar.insert(Cookie {
+                name: name.to_string(),
+                value: value.to_string(),
+                domain: domain.trim_start_matches('.').to_string(),
+                include_subdomains: bool("includeSubdomains").unwrap_or_default(),
+                path: string("path").unwrap_or("/").to_string(),
+                secure: bool("secure").unwrap_or_default(),
+                http_only: bool("httpOnly").unwrap_or_default(),
+                expires: cookie
+                    .get("expires")
+                    .and_then(|it| it.as_u64())
+                    .unwrap_or_default(),
+            });
+        }
+        Ok(jar)
+    }
+
+    pub fn to_json(&self) -> String {
+        let cookies = self
+            .cookies
+            .iter()
+            .map(|cookie| {
+                serde_json::json!({
+                    "name": cookie.name,
+                    "value": cookie.value,
+                    "domain": cookie.domain,
+                    "includeSubdomains": cookie.include_subdomains,
+                    "path": cookie.path,
+                    "secure": cookie.secure,
+                    "httpOnly": cookie.http_only,
+                    "expires": cookie.expires,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut out = serde_json::to_string_pretty(&cookies).unwrap_or_default();
+        out.push('\n');
+        out
+    }
+
+    /// Adds `cookie`, replacing a cookie with the same name, domain and path.
+    pub fn insert(&mut self, cookie: Cookie) {
+        self.cookies.retain(|it| {
+            !(it.name == cookie.name && it.domain == cookie.domain && it.path == cookie.path)
+        });
+        self.cookies.push(cookie);
+    }
+
+    /// Returns the value of the `Cookie` header for a request to `url`,
+    /// leaving out the cookies named in `except`.
+    pub fn header_for(&self, url: &reqwest::Url, except: &[&str]) -> Option<String> {
+        let now = unix_now();
+        let pairs = self
+            .cookies
+            .iter()
+            .filter(|it| it.matches(url, now) && !except.contains(&it.name.as_str()))
+            .map(|it| format!("{}={}", it.name, it.value))
+            .collect::<Vec<_>>();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    /// Stores the cookies of all `Set-Cookie` headers of a response to `url`.
+    pub fn store_response(&mut self, url: &reqwest::Url, headers: &[(String, String)]) {
+        let now = unix_now();
+        for (_, value) in headers
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("Set-Cookie"))
+        {
+            if let Some(cookie) = parse_set_cookie(value, url, now) {
+                self.insert(cookie);
+            }
+        }
+        self.cookies
+            .retain(|it| it.expires == 0 || it.expires > now);
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|it| it.eq_ignore_ascii_case("json"))
+}
+
+fn parse_set_cookie(header: &str, url: &reqwest::Url, now: u64) -> Option<Cookie> {
+    let host = url.host_str()?;
+    let mut attributes = header.split(';');
+    let (name, value) = attributes.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    // Without a `Path`, the cookie belongs to the "directory" of the request.
+    let default_path = match url.path().rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &url.path()[..i],
+    };
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: host.to_string(),
+        include_subdomains: false,
+        path: default_path.to_string(),
+        secure: false,
+        http_only: false,
+        expires: 0,
+    };
+
+    let mut max_age = None;
+    for attribute in attributes {
+        let (key, arg) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let arg = arg.trim();
+        match key.trim().to_ascii_lowercase().as_str() {
+            "domain" if !arg.is_empty() => {
+                let domain = arg.trim_start_matches('.').to_ascii_lowercase();
+                // Servers may only set cookies for their own domain.
+                let allowed = host.eq_ignore_ascii_case(&domain)
+                    || host.to_ascii_lowercase().ends_with(&format!(".{domain}"));
+                if !allowed {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.include_subdomains = true;
+            }
+            "path" if arg.starts_with('/') => cookie.path = arg.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "max-age" => max_age = arg.parse::<i64>().ok(),
+            "expires" => {
+                if let Ok(time) = httpdate::parse_http_date(arg)
+                    && let Ok(time) = time.duration_since(UNIX_EPOCH)
+                {
+                    // An expiry in the past deletes the cookie.
+                    cookie.expires = time.as_secs().max(1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // `Max-Age` takes precedence over `Expires`.
+    if let Some(max_age) = max_age {
+        cookie.expires = if max_age <= 0 {
+            1
+        } else {
+            now + max_age as u64
+        };
+    }
+
+    Some(cookie)
+}
+
+/// An [`HttpClient`] that sends the cookies of a [`CookieJar`] and keeps it
+/// up to date with the cookies set by responses. The jar may be shared with
+/// the clients of other threads.
+pub struct CookieHttpClient<C> {
+    inner: C,
+    pub jar: Arc<Mutex<CookieJar>>,
+}
+
+impl<C: HttpClient> CookieHttpClient<C> {
+    pub fn new(inner: C, jar: Arc<Mutex<CookieJar>>) -> Self {
+        Self { inner, jar }
+    }
+}
+
+impl<C: HttpClient> HttpClient for CookieHttpClient<C> {
+    fn send(&self, mut request: Request) -> Result<Response, HttpError> {
+        let Ok(url) = reqwest::Url::parse(&request.url) else {
+            return self.inner.send(request);
+        };
+
+        let header = request
+            .headers
+            .iter_mut()
+            .find(|(name, _)| name.eq_ignore_ascii_case("Cookie"));
+        // Cookies the request sets itself take precedence over the jar.
+        let own_names = match &header {
+            Some((_, value)) => value
+                .split(';')
+                .filter_map(|it| it.split_once('=').map(|(name, _)| name.trim().to_string()))
+                .collect(),
+            None => vec![],
+        };
+        let own_names = own_names.iter().map(String::as_str).collect::<Vec<_>>();
+        if let Some(cookies) = self.jar.lock().unwrap().header_for(&url, &own_names) {
+            match header {
+                Some((_, value)) => {
+                    value.push_str("; ");
+                    value.push_str(&cookies);
+                }
+                None => request.headers.push(("Cookie".to_string(), cookies)),
+            }
+        }
+
+        let response = self.inner.send(request)?;
+        self.jar
+            .lock()
+            .unwrap()
+            .store_response(&url, &response.headers);
+        Ok(response)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    fn url(s: &str) -> reqwest::Url {
+        reqwest::Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn netscape_round_trip() {
+        let input = "# Netscape HTTP Cookie File\n\
+                     .example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+                     #HttpOnly_api.example.com\tFALSE\t/v1\tTRUE\t4102444800\ttoken\txyz\n";
+        let jar = CookieJar::parse(input);
+        assert_eq!(jar.cookies.len(), 2);
+        assert!(jar.cookies[0].include_subdomains);
+        assert!(jar.cookies[1].http_only);
+        assert_eq!(jar.to_netscape(), input);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let jar = CookieJar::parse(
+            ".example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+             #HttpOnly_api.example.com\tFALSE\t/v1\tTRUE\t4102444800\ttoken\txyz\n",
+        );
+        let json = jar.to_json();
+        let parsed = CookieJar::parse_json(&json).unwrap();
+        assert_eq!(parsed.cookies, jar.cookies);
+
+        let minimal =
+            CookieJar::parse_json(r#"[{"name": "a", "value": "1", "domain": ".example.com"}]"#)
+                .unwrap();
+        assert_eq!(minimal.cookies[0].domain, "example.com");
+        assert_eq!(minimal.cookies[0].path, "/");
+
+        assert!(CookieJar::parse_json(r#"{"name": "a"}"#).is_err());
+        assert!(CookieJar::parse_json(r#"[{"name": "a"}]"#).is_err());
+    }
+
+    #[test]
+    fn header_for_matching_cookies() {
+        let jar = CookieJar::parse(
+            ".example.com\tTRUE\t/\tFALSE\t0\ta\t1\n\
+             api.example.com\tFALSE\t/v1\tTRUE\t0\tb\t2\n\
+             other.com\tFALSE\t/\tFALSE\t0\tc\t3\n\
+             example.com\tFALSE\t/\tFALSE\t1\texpired\t4\n",
+        );
+
+        assert_eq!(
+            jar.header_for(&url("https://api.example.com/v1/users"), &[]),
+            Some("a=1; b=2".to_string())
+        );
+        assert_eq!(
+            jar.header_for(&url("http://api.example.com/v1/users"), &[]),
+            Some("a=1".to_string())
+        );
+        assert_eq!(
+            jar.header_for(&url("https://api.example.com/v10"), &[]),
+            Some("a=1".to_string())
+        );
+        assert_eq!(jar.header_for(&url("https://example.org/"), &[]), None);
+        assert_eq!(
+            jar.header_for(&url("https://api.example.com/v1/users"), &["a"]),
+            Some("b=2".to_string())
+        );
+    }
+
+    /// Answers every request with a `Set-Cookie` header and remembers the
+    /// `Cookie` headers it received.
+    struct LoginClient {
+        sent: RefCell<Vec<Option<String>>>,
+    }
+
+    impl HttpClient for LoginClient {
+        fn send(&self, request: Request) -> Result<Response, HttpError> {
+            let cookie = request
+                .headers
+                .iter()
+                .find(|(name, _)| name == "Cookie")
+                .map(|(_, value)| value.clone());
+            self.sent.borrow_mut().push(cookie);
+            Ok(Response {
+                version: "HTTP/1.1",
+                status: 200.into(),
+                headers: vec![("Set-Cookie".to_string(), "session=abc".to_string())],
+                body: vec![],
+                elapsed: std::time::Duration::ZERO,
+                timings: Default::default(),
+                cache: crate::client::CacheStatus::Network,
+                redirects: vec![],
+                streamed: false,
+            })
+        }
+    }
+
+    #[test]
+    fn cookies_carry_over_to_later_requests() {
+        let client = CookieHttpClient::new(
+            LoginClient {
+                sent: RefCell::new(vec![]),
+            },
+            Arc::new(Mutex::new(CookieJar::new())),
+        );
+        let request = |cookie: Option<&str>| Request {
+            method: crate::validated::HttpMethod::Get,
+            url: "https://example.com/".to_string(),
+            headers: cookie
+                .map(|it| ("Cookie".to_string(), it.to_string()))
+                .into_iter()
+                .collect(),
+            body: None,
+            options: crate::client::RequestOptions::default(),
+            events: None,
+        };
+
+        client.send(request(None)).unwrap();
+        client.send(request(None)).unwrap();
+        client.send(request(Some("theme=dark"))).unwrap();
+        client.send(request(Some("session=mine"))).unwrap();
+
+        assert_eq!(
+            *client.inner.sent.borrow(),
+            [
+                None,
+                Some("session=abc".to_string()),
+                Some("theme=dark; session=abc".to_string()),
+                Some("session=mine".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn store_set_cookie() {
+        let mut jar = CookieJar::new();
+        let url = url("https://api.example.com/v1/login");
+        jar.store_response(
+            &url,
+            &[
+                (
+                    "set-cookie".to_string(),
+                    "session=abc; Path=/; Domain=.example.com; Secure; HttpOnly".to_string(),
+                ),
+                ("Set-Cookie".to_string(), "local=1".to_string()),
+                (
+                    "Set-Cookie".to_string(),
+                    "evil=1; Domain=attacker.com".to_string(),
+                ),
+            ],
+        );
+
+        assert_eq!(jar.cookies.len(), 2);
+        assert_eq!(jar.cookies[0].domain, "example.com");
+        assert!(jar.cookies[0].secure && jar.cookies[0].http_only);
+        assert_eq!(jar.cookies[1].path, "/v1");
+
+        jar.store_response(
+            &url,
+            &[("Set-Cookie".to_string(), "local=; Max-Age=0".to_string())],
+        );
+        assert_eq!(jar.cookies.len(), 1);
+    }
+}
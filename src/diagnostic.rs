@@ -1,12 +1,40 @@
 use std::{fmt, path::Path};
 
-use crate::span::Span;
+use serde_json::{Value as Json, json};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::{codes::Code, line_index::LineIndex, span::Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Level {
+    /// Extra information below the snippet, e.g. why something is a problem
+    Note,
+    /// A suggestion below the snippet, e.g. how to fix a problem
+    Help,
+    /// A problem that doesn't stop the file from running
+    Warning,
     Error,
 }
 
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Note => "note",
+            Level::Help => "help",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+
+    fn to_annotate(self) -> annotate_snippets::Level<'static> {
+        match self {
+            Level::Note => annotate_snippets::Level::NOTE,
+            Level::Help => annotate_snippets::Level::HELP,
+            Level::Warning => annotate_snippets::Level::WARNING,
+            Level::Error => annotate_snippets::Level::ERROR,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Label {
     pub message: String,
@@ -20,6 +48,8 @@ pub struct Diagnostic {
     pub span: Span,
     pub level: Level,
     pub labels: Vec<Label>,
+    /// The kind of the diagnostic, explained by `aurora explain`
+    pub code: Option<Code>,
 }
 
 impl Diagnostic {
@@ -29,6 +59,7 @@ impl Diagnostic {
             span,
             level,
             labels: vec![],
+            code: None,
         }
     }
 
@@ -36,6 +67,54 @@ impl Diagnostic {
         Self::new(message, span, Level::Error)
     }
 
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(message, span, Level::Warning)
+    }
+
+    pub fn code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.level == Level::Error
+    }
+
+    /// The diagnostic as an object like
+    ///
+    /// ```json
+    /// {"level": "error", "code": "AU0004", "message": "...", "start": {"offset": 4, "line": 0, "column": 4}, "end": ..., "labels": [...]}
+    /// ```
+    ///
+    /// Offsets and columns count bytes of the UTF-8 input, lines start at 0.
+    /// The code is `null` if the diagnostic has none.
+    pub fn to_json(&self, line_index: &LineIndex) -> Json {
+        let position = |offset| {
+            let (line, column) = line_index.line_col(offset);
+            json!({"offset": offset, "line": line, "column": column})
+        };
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| {
+                json!({
+                    "level": label.level.as_str(),
+                    "message": label.message,
+                    "start": position(label.span.start),
+                    "end": position(label.span.end),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "level": self.level.as_str(),
+            "code": self.code.map(|it| it.id),
+            "message": self.message,
+            "start": position(self.span.start),
+            "end": position(self.span.end),
+            "labels": labels,
+        })
+    }
+
     pub fn primary_label(self, message: impl Into<String>, level: Level) -> Self {
         let span = self.span;
         self.label(message, span, level)
@@ -50,6 +129,138 @@ impl Diagnostic {
         self.labels.push(label);
         self
     }
+
+    /// Adds a note below the snippet.
+    pub fn note(self, message: impl Into<String>) -> Self {
+        let span = self.span;
+        self.label(message, span, Level::Note)
+    }
+
+    /// Adds a suggestion below the snippet.
+    pub fn help(self, message: impl Into<String>) -> Self {
+        let span = self.span;
+        self.label(message, span, Level::Help)
+    }
+}
+
+impl Label {
+    /// Notes and help are printed below the snippet instead of pointing
+    /// into it.
+    fn is_footer(&self) -> bool {
+        matches!(self.level, Level::Note | Level::Help)
+    }
+}
+
+/// `diagnostics` of the file at `path` as a SARIF 2.1.0 log, the format
+/// code scanning services read.
+///
+/// Lines and columns start at 1, and columns count Unicode code points.
+pub fn to_sarif(
+    input: &str,
+    line_index: &LineIndex,
+    path: &Path,
+    diagnostics: &[Diagnostic],
+) -> Json {
+    let uri = path.to_string_lossy();
+    let region = |span: Span| {
+        let position = |offset| {
+            let line = line_index.line(offset);
+            let column = input[line_index.line_start(line)..offset].chars().count();
+            (line + 1, column + 1)
+        };
+        let (start_line, start_column) = position(span.start);
+        let (end_line, end_column) = position(span.end);
+        json!({
+            "startLine": start_line,
+            "startColumn": start_column,
+            "endLine": end_line,
+            "endColumn": end_column,
+        })
+    };
+    let location = |span, message: Option<&str>| {
+        let mut location = json!({
+            "physicalLocation": {
+                "artifactLocation": {"uri": uri},
+                "region": region(span),
+            }
+        });
+        if let Some(message) = message {
+            location["message"] = json!({"text": message});
+        }
+        location
+    };
+
+    let results = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            // The message has to stand on its own, so it takes the label of
+            // the primary span and the notes.
+            let mut text = diagnostic.message.clone();
+            let mut footers = String::new();
+            let mut primary_found = false;
+            let mut related = vec![];
+            for label in &diagnostic.labels {
+                if label.is_footer() {
+                    footers.push_str(&format!("\n{}: {}", label.level.as_str(), label.message));
+                } else if !primary_found && label.span == diagnostic.span {
+                    primary_found = true;
+                    text.push_str(&format!(": {}", label.message));
+                } else {
+                    related.push(location(label.span, Some(&label.message)));
+                }
+            }
+            text.push_str(&footers);
+            let mut result = json!({
+                "level": match diagnostic.level {
+                    Level::Error => "error",
+                    Level::Warning => "warning",
+                    Level::Note | Level::Help => "note",
+                },
+                "message": {"text": text},
+                "locations": [location(diagnostic.span, None)],
+                "relatedLocations": related,
+            });
+            if let Some(code) = diagnostic.code {
+                result["ruleId"] = json!(code.id);
+            }
+            result
+        })
+        .collect::<Vec<_>>();
+
+    // Only the rules the results refer to are described, in the order of
+    // their ids.
+    let mut codes = diagnostics
+        .iter()
+        .filter_map(|it| it.code)
+        .collect::<Vec<_>>();
+    codes.sort_by_key(|it| it.id);
+    codes.dedup();
+    let rules = codes
+        .into_iter()
+        .map(|code| {
+            json!({
+                "id": code.id,
+                "shortDescription": {"text": code.title},
+                "fullDescription": {"text": code.explanation},
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "version": "2.1.0",
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "aurora",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }]
+    })
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -58,16 +269,46 @@ pub enum RenderStyle {
     Plain,
 }
 
+/// Renders `diagnostics` one after another, separated by empty lines.
 pub fn dump<W: fmt::Write>(
     input: &str,
+    line_index: &LineIndex,
+    path: &Path,
+    diagnostics: &[Diagnostic],
+    style: RenderStyle,
+    w: &mut W,
+) -> fmt::Result {
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        if i > 0 {
+            write!(w, "\n\n")?;
+        }
+        dump_one(input, line_index, path, diagnostic, style, w)?;
+    }
+    Ok(())
+}
+
+fn dump_one<W: fmt::Write>(
+    input: &str,
+    line_index: &LineIndex,
     path: &Path,
     diagnostic: &Diagnostic,
     style: RenderStyle,
     w: &mut W,
 ) -> fmt::Result {
+    // Only the lines touched by the diagnostic are handed to the renderer, so
+    // the cost of rendering does not depend on the size of the input.
+    let (footers, labels): (Vec<_>, Vec<_>) =
+        diagnostic.labels.iter().partition(|it| it.is_footer());
+    let spans = std::iter::once(diagnostic.span).chain(labels.iter().map(|it| it.span));
+    let first_line = line_index.line(spans.clone().map(|it| it.start).min().unwrap_or(0));
+    let last_line = line_index.line(spans.map(|it| it.end).max().unwrap_or(0));
+    let offset = line_index.line_start(first_line);
+    let source = &input[offset..line_index.line_end(last_line)];
+    let relative = |span: Span| span.start - offset..span.end - offset;
+
     let mut annotations: Vec<annotate_snippets::Annotation> = vec![];
     let mut primary_found = false;
-    for label in &diagnostic.labels {
+    for label in labels {
         let annotation_kind = if !primary_found && label.span == diagnostic.span {
             primary_found = true;
             annotate_snippets::AnnotationKind::Primary
@@ -77,7 +318,7 @@ pub fn dump<W: fmt::Write>(
 
         annotations.push(
             annotation_kind
-                .span(label.span.start..label.span.end)
+                .span(relative(label.span))
                 .label(&label.message),
         );
     }
@@ -86,19 +327,28 @@ pub fn dump<W: fmt::Write>(
         annotations.insert(
             0,
             annotate_snippets::AnnotationKind::Primary
-                .span(diagnostic.span.start..diagnostic.span.end)
+                .span(relative(diagnostic.span))
                 .label("here"),
         );
     }
 
-    let report = &[annotate_snippets::Level::ERROR
-        .primary_title(&diagnostic.message)
-        .element(
-            annotate_snippets::Snippet::source(input)
-                .line_start(1)
-                .path(path.to_string_lossy())
-                .annotations(annotations),
-        )];
+    let mut title = diagnostic
+        .level
+        .to_annotate()
+        .primary_title(&diagnostic.message);
+    if let Some(code) = diagnostic.code {
+        title = title.id(code.id);
+    }
+    let mut group = title.element(
+        annotate_snippets::Snippet::source(source)
+            .line_start(first_line + 1)
+            .path(path.to_string_lossy())
+            .annotations(annotations),
+    );
+    for footer in footers {
+        group = group.element(footer.level.to_annotate().message(&footer.message));
+    }
+    let report = &[group];
 
     let renderer = match style {
         RenderStyle::Styled => annotate_snippets::Renderer::styled()
@@ -108,3 +358,196 @@ pub fn dump<W: fmt::Write>(
 
     write!(w, "{}", renderer.render(report))
 }
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::codes;
+
+    #[test]
+    fn dump_warning_with_footers() {
+        let input = "entry a {\n    GET \"https://example.com\"\n    [Body] {}\n}\n";
+        let diagnostic = Diagnostic::warning("A warning", Span::new(51, 53))
+            .code(codes::GET_WITH_BODY)
+            .primary_label("Pointing here", Level::Warning)
+            .note("A note")
+            .help("A suggestion");
+        let mut out = String::new();
+        dump(
+            input,
+            &LineIndex::new(input),
+            Path::new("a.au"),
+            &[diagnostic],
+            RenderStyle::Plain,
+            &mut out,
+        )
+        .unwrap();
+        expect![[r#"
+            warning[AU0020]: A warning
+             --> a.au:3:12
+              |
+            3 |     [Body] {}
+              |            ^^ Pointing here
+              |
+              = note: A note
+              = help: A suggestion"#]]
+        .assert_eq(&out);
+    }
+
+    #[test]
+    fn diagnostics_as_json_and_sarif() {
+        let input = "const café = 1\nconst b = cafe\n";
+        let diagnostic = Diagnostic::error("Unknown identifier", Span::new(26, 30))
+            .code(codes::UNKNOWN_IDENTIFIER)
+            .primary_label("I don't know what this name is referring to", Level::Error)
+            .label(
+                "A similar name is defined here",
+                Span::new(6, 11),
+                Level::Error,
+            )
+            .help("Did you mean `café`?");
+        let line_index = LineIndex::new(input);
+
+        expect![[r#"
+            {
+              "level": "error",
+              "code": "AU0004",
+              "message": "Unknown identifier",
+              "start": {
+                "offset": 26,
+                "line": 1,
+                "column": 10
+              },
+              "end": {
+                "offset": 30,
+                "line": 1,
+                "column": 14
+              },
+              "labels": [
+                {
+                  "level": "error",
+                  "message": "I don't know what this name is referring to",
+                  "start": {
+                    "offset": 26,
+                    "line": 1,
+                    "column": 10
+                  },
+                  "end": {
+                    "offset": 30,
+                    "line": 1,
+                    "column": 14
+                  }
+                },
+                {
+                  "level": "error",
+                  "message": "A similar name is defined here",
+                  "start": {
+                    "offset": 6,
+                    "line": 0,
+                    "column": 6
+                  },
+                  "end": {
+                    "offset": 11,
+                    "line": 0,
+                    "column": 11
+                  }
+                },
+                {
+                  "level": "help",
+                  "message": "Did you mean `café`?",
+                  "start": {
+                    "offset": 26,
+                    "line": 1,
+                    "column": 10
+                  },
+                  "end": {
+                    "offset": 30,
+                    "line": 1,
+                    "column": 14
+                  }
+                }
+              ]
+            }"#]]
+        .assert_eq(&serde_json::to_string_pretty(&diagnostic.to_json(&line_index)).unwrap());
+        expect![[r#"
+            {
+              "version": "2.1.0",
+              "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+              "runs": [
+                {
+                  "tool": {
+                    "driver": {
+                      "name": "aurora",
+                      "version": "0.1.0",
+                      "rules": [
+                        {
+                          "id": "AU0004",
+                          "shortDescription": {
+                            "text": "Unknown identifier"
+                          },
+                          "fullDescription": {
+                            "text": "A name refers to nothing. Names can refer to consts, variables given\non the command line, parameters and loop variables of the entry, and\nvalues captured by any entry.\n\n    entry getUser {\n        GET \"{{baseUrl}}/users/1\"\n    }\n\nDefine the name, pass it with `--var baseUrl=...`, or fix its spelling:\n\n    const baseUrl = \"https://example.com\"\n"
+                          }
+                        }
+                      ]
+                    }
+                  },
+                  "columnKind": "unicodeCodePoints",
+                  "results": [
+                    {
+                      "level": "error",
+                      "message": {
+                        "text": "Unknown identifier: I don't know what this name is referring to\nhelp: Did you mean `café`?"
+                      },
+                      "locations": [
+                        {
+                          "physicalLocation": {
+                            "artifactLocation": {
+                              "uri": "api.au"
+                            },
+                            "region": {
+                              "startLine": 2,
+                              "startColumn": 11,
+                              "endLine": 2,
+                              "endColumn": 15
+                            }
+                          }
+                        }
+                      ],
+                      "relatedLocations": [
+                        {
+                          "physicalLocation": {
+                            "artifactLocation": {
+                              "uri": "api.au"
+                            },
+                            "region": {
+                              "startLine": 1,
+                              "startColumn": 7,
+                              "endLine": 1,
+                              "endColumn": 11
+                            }
+                          },
+                          "message": {
+                            "text": "A similar name is defined here"
+                          }
+                        }
+                      ],
+                      "ruleId": "AU0004"
+                    }
+                  ]
+                }
+              ]
+            }"#]]
+        .assert_eq(
+            &serde_json::to_string_pretty(&to_sarif(
+                input,
+                &line_index,
+                Path::new("api.au"),
+                &[diagnostic],
+            ))
+            .unwrap(),
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt;
+
+use serde_json::Value as Json;
+
+/// A difference between two JSON documents, at the JSONPath `path`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub kind: ChangeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    /// Only the second document has a value at the path
+    Added(Json),
+    /// Only the first document has a value at the path
+    Removed(Json),
+    Changed(Json, Json),
+}
+
+/// Compares `a` with `b` structurally: objects key by key and arrays index
+/// by index. Changes are listed in document order, keys of `a` first.
+pub fn diff(a: &Json, b: &Json) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_at("$".to_string(), a, b, &mut changes);
+    changes
+}
+
+fn diff_at(path: String, a: &Json, b: &Json, changes: &mut Vec<Change>) {
+    match (a, b) {
+        (Json::Object(a), Json::Object(b)) => {
+            for (key, a_value) in a {
+                let path = format!("{path}{}", key_selector(key));
+                match b.get(key) {
+                    Some(b_value) => diff_at(path, a_value, b_value, changes),
+                    None => changes.push(Change {
+                        path,
+                        kind: ChangeKind::Removed(a_value.clone()),
+                    }),
+                }
+            }
+            for (key, b_value) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                changes.push(Change {
+                    path: format!("{path}{}", key_selector(key)),
+                    kind: ChangeKind::Added(b_value.clone()),
+                });
+            }
+        }
+        (Json::Array(a), Json::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                let path = format!("{path}[{i}]");
+                match (a.get(i), b.get(i)) {
+                    (Some(a), Some(b)) => diff_at(path, a, b, changes),
+                    (Some(a), None) => changes.push(Change {
+                        path,
+                        kind: ChangeKind::Removed(a.clone()),
+                    }),
+                    (None, Some(b)) => changes.push(Change {
+                        path,
+                        kind: ChangeKind::Added(b.clone()),
+                    }),
+                    (None, None) => unreachable!(),
+                }
+            }
+        }
+        (a, b) if a != b => changes.push(Change {
+            path,
+            kind: ChangeKind::Changed(a.clone(), b.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Returns `.key`, or `['key']` if the key isn't a plain name.
+fn key_selector(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!(".{key}")
+    } else {
+        format!("['{}']", key.replace('\\', "\\\\").replace('\'', "\\'"))
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ChangeKind::Added(value) => write!(f, "+ {}: {value}", self.path),
+            ChangeKind::Removed(value) => write!(f, "- {}: {value}", self.path),
+            ChangeKind::Changed(a, b) => write!(f, "~ {}: {a} -> {b}", self.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn diff_documents() {
+        let a = json!({
+            "id": 1,
+            "name": "Ada",
+            "roles": ["admin", "dev"],
+            "address": {"city": "London", "zip": "N1"},
+            "legacy": true,
+        });
+        let b = json!({
+            "id": 1,
+            "name": "Grace",
+            "roles": ["admin"],
+            "address": {"city": "London", "zip": null},
+            "content-type": "json",
+        });
+        let changes = diff(&a, &b)
+            .iter()
+            .map(|it| format!("{it}\n"))
+            .collect::<String>();
+        expect![[r#"
+            ~ $.name: "Ada" -> "Grace"
+            - $.roles[1]: "dev"
+            ~ $.address.zip: "N1" -> null
+            - $.legacy: true
+            + $['content-type']: "json"
+        "#]]
+        .assert_eq(&changes);
+
+        assert!(diff(&a, &a).is_empty());
+        assert_eq!(
+            diff(&json!([1]), &json!({"a": 1})),
+            [Change {
+                path: "$".to_string(),
+                kind: ChangeKind::Changed(json!([1]), json!({"a": 1})),
+            }]
+        );
+    }
+}
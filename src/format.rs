@@ -0,0 +1,516 @@
+use crate::{
+    ast::{
+        Assertion, DictionaryField, Entry, EntryItem, EntryItemKind, Expr, ExprKind, Item,
+        ItemKind, SourceFile, TemplatePart,
+    },
+    diagnostic::Diagnostic,
+    lexer::{self, escape_identifier},
+    parser,
+    token::Comment,
+};
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 80;
+
+/// The canonical order of the sections of an entry. Unknown sections go last.
+const SECTION_ORDER: &[&str] = &[
+    "BaseUrl", "Query", "Headers", "Cookies", "Auth", "Body", "Options", "Capture", "Assert",
+];
+
+/// Formats `.au` source code into its canonical layout.
+///
+/// Comments are preserved. Dictionaries and arrays stay on a single line if
+/// they were written on a single line and still fit, and are broken up with
+/// one element per line otherwise. The items of an entry are sorted into a
+/// canonical order, the request first, and take the comments in
+/// front of them along.
+pub fn format_source(input: &str) -> Result<String, Vec<Diagnostic>> {
+    let (tokens, comments) = lexer::lex_with_comments(input).map_err(|d| vec![d])?;
+    let file = parser::parse_tokens(tokens)?;
+    let mut formatter = Formatter::new(input, comments);
+    formatter.source_file(&file);
+    Ok(formatter.out)
+}
+
+struct Formatter<'input> {
+    input: &'input str,
+    comments: Vec<Comment<'input>>,
+    next_comment: usize,
+    out: String,
+    indent: usize,
+    /// End of the last source element that was written
+    last_end: usize,
+    /// Whether nothing has been written in the current block yet
+    block_start: bool,
+}
+
+impl<'input> Formatter<'input> {
+    fn new(input: &'input str, comments: Vec<Comment<'input>>) -> Self {
+        Self {
+            input,
+            comments,
+            next_comment: 0,
+            out: String::new(),
+            indent: 0,
+            last_end: 0,
+            block_start: true,
+        }
+    }
+
+    fn source_file(&mut self, file: &SourceFile<'input>) {
+        let mut prev_was_block = false;
+        for item in &file.items {
+            let is_block = matches!(
+                item.kind,
+                ItemKind::Entry(_) | ItemKind::Env(..) | ItemKind::Defaults(_)
+            );
+            self.leading_trivia(item.span.start, is_block || prev_was_block);
+            self.item(item);
+            prev_was_block = is_block;
+        }
+
+        self.leading_trivia(self.input.len(), false);
+    }
+
+    fn item(&mut self, item: &Item<'input>) {
+        self.write_indent();
+        match &item.kind {
+            ItemKind::Entry(entry) => self.entry(entry, item.span.end),
+            ItemKind::Const(name, ty, expr, secret) => {
+                if *secret {
+                    self.out.push_str("secret ");
+                }
+                self.out.push_str("const ");
+                self.out.push_str(&escape_identifier(name.text));
+                if let Some(ty) = ty {
+                    self.out.push_str(": ");
+                    self.out.push_str(&ty.to_string());
+                }
+                self.out.push_str(" = ");
+                self.expr(expr);
+            }
+            ItemKind::Env(name, items) => {
+                self.out.push_str("env ");
+                self.out.push_str(&escape_identifier(name.text));
+                if items.is_empty() && !self.has_comment_before(item.span.end) {
+                    self.out.push_str(" {}");
+                } else {
+                    self.out.push_str(" {");
+                    self.open_block();
+                    for item in items {
+                        self.leading_trivia(item.span.start, false);
+                        self.item(item);
+                    }
+                    self.close_block(item.span.end - 1);
+                    self.out.push('}');
+                }
+            }
+            ItemKind::Defaults(items) => {
+                self.out.push_str("defaults");
+                self.entry_items(items, item.span.end);
+            }
+        }
+        self.end_line(item.span.end);
+    }
+
+    fn entry(&mut self, entry: &Entry<'input>, end: usize) {
+        if !entry.tags.is_empty() {
+            let tags = entry
+                .tags
+                .iter()
+                .map(|it| format!("@{}", escape_identifier(it.text)))
+                .collect::<Vec<_>>();
+            self.out.push_str(&tags.join(" "));
+            self.out.push('\n');
+            self.write_indent();
+        }
+        self.out.push_str("entry ");
+        self.out.push_str(&escape_identifier(entry.name.text));
+        if !entry.params.is_empty() {
+            let params = entry
+                .params
+                .iter()
+                .map(|it| escape_identifier(it.text))
+                .collect::<Vec<_>>();
+            self.out.push('(');
+            self.out.push_str(&params.join(", "));
+            self.out.push(')');
+        }
+        if let Some(base) = &entry.extends {
+            self.out.push_str(" extends ");
+            self.out.push_str(&escape_identifier(base.text));
+        }
+        for (i, need) in entry.needs.iter().enumerate() {
+            self.out.push_str(if i == 0 { " needs " } else { ", " });
+            self.out.push_str(&escape_identifier(need.name.text));
+            if let Some(args) = &need.args {
+                let args = args.iter().map(flat_expr).collect::<Vec<_>>();
+                self.out.push('(');
+                self.out.push_str(&args.join(", "));
+                self.out.push(')');
+            }
+        }
+        self.entry_items(&entry.body, end);
+    }
+
+    /// Writes a block of entry items whose closing brace ends at `end`.
+    fn entry_items(&mut self, items: &[EntryItem<'input>], end: usize) {
+        if items.is_empty() && !self.has_comment_before(end) {
+            self.out.push_str(" {}");
+            return;
+        }
+
+        // Where the comments in front of each item start, and where the
+        // element before it ends, so both move along with the item.
+        let mut leading = Vec::with_capacity(items.len());
+        let mut next_comment = self.next_comment;
+        let mut prev_end = items.first().map_or(self.last_end, |it| it.span.start);
+        for item in items {
+            leading.push((next_comment, prev_end));
+            while self
+                .comments
+                .get(next_comment)
+                .is_some_and(|it| it.span.start < item.span.end)
+            {
+                next_comment += 1;
+            }
+            prev_end = item.span.end;
+            if let Some(comment) = self.comments.get(next_comment)
+                && !self.input[item.span.end..comment.span.start].contains('\n')
+            {
+                next_comment += 1;
+                prev_end = comment.span.end;
+            }
+        }
+
+        let mut order = (0..items.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| item_rank(&items[i]));
+
+        self.out.push_str(" {");
+        self.open_block();
+        for i in order {
+            let item = &items[i];
+            (self.next_comment, self.last_end) = leading[i];
+            self.leading_trivia(item.span.start, false);
+            self.write_indent();
+            self.entry_item(item);
+            self.end_line(item.span.end);
+        }
+        (self.next_comment, self.last_end) = (next_comment, prev_end);
+        self.close_block(end - 1);
+        self.out.push('}');
+    }
+
+    /// Writes the block of an `[Assert]` section whose closing brace ends at
+    /// `end`, one assertion per line.
+    fn assertions(&mut self, assertions: &[Assertion<'input>], end: usize) {
+        if assertions.is_empty() && !self.has_comment_before(end) {
+            self.out.push_str(" {}");
+            return;
+        }
+
+        self.out.push_str(" {");
+        self.open_block();
+        for assertion in assertions {
+            self.leading_trivia(assertion.span.start, false);
+            self.write_indent();
+            if assertion.soft {
+                self.out.push_str("soft ");
+            }
+            if assertion.negated {
+                self.out.push_str("not ");
+            }
+            self.out
+                .push_str(&escape_identifier(assertion.subject.text));
+            if let Some(argument) = &assertion.argument {
+                self.out.push(' ');
+                self.expr(argument);
+            }
+            self.out.push(' ');
+            self.out.push_str(&assertion.op.to_string());
+            self.out.push(' ');
+            self.expr(&assertion.expected);
+            if let Some(unit) = &assertion.unit {
+                self.out.push_str(unit.text);
+            }
+            self.end_line(assertion.span.end);
+        }
+        self.close_block(end - 1);
+        self.out.push('}');
+    }
+
+    fn entry_item(&mut self, item: &EntryItem<'input>) {
+        match &item.kind {
+            EntryItemKind::Request(request) => {
+                self.out.push_str(&request.method.to_string());
+                self.out.push(' ');
+                self.expr(&request.url);
+            }
+            EntryItemKind::Section(name, body) => {
+                self.out.push('[');
+                self.out.push_str(&escape_identifier(name.text));
+                self.out.push_str("] ");
+                self.expr(body);
+            }
+            EntryItemKind::Assert(assertions) => {
+                self.out.push_str("[Assert]");
+                self.assertions(assertions, item.span.end);
+            }
+            EntryItemKind::For(name, items, body) => {
+                self.out.push_str("for ");
+                self.out.push_str(&escape_identifier(name.text));
+                self.out.push_str(" in ");
+                self.expr(items);
+                self.entry_items(body, item.span.end);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr<'input>) {
+        match &expr.kind {
+            ExprKind::Dictionary(fields) if !fields.is_empty() && self.break_up(expr) => {
+                self.out.push('{');
+                self.open_block();
+                for field in fields {
+                    self.leading_trivia(field.span().start, false);
+                    self.write_indent();
+                    match field {
+                        DictionaryField::Pair { key, value } => {
+                            self.expr(key);
+                            self.out.push_str(": ");
+                            self.expr(value);
+                        }
+                        DictionaryField::Spread(expr, _) => {
+                            self.out.push_str("...");
+                            self.expr(expr);
+                        }
+                    }
+                    self.out.push(',');
+                    self.end_line(field.span().end);
+                }
+                self.close_block(expr.span.end - 1);
+                self.out.push('}');
+            }
+            ExprKind::Array(elems) if !elems.is_empty() && self.break_up(expr) => {
+                self.out.push('[');
+                self.open_block();
+                for elem in elems {
+                    self.leading_trivia(elem.span.start, false);
+                    self.write_indent();
+                    self.expr(elem);
+                    self.out.push(',');
+                    self.end_line(elem.span.end);
+                }
+                self.close_block(expr.span.end - 1);
+                self.out.push(']');
+            }
+            _ => self.out.push_str(&flat_expr(expr)),
+        }
+    }
+
+    /// Decides whether a dictionary or array is written with one element per line.
+    fn break_up(&self, expr: &Expr<'input>) -> bool {
+        if self.has_comment_before(expr.span.end) {
+            return true;
+        }
+
+        let first_start = match &expr.kind {
+            ExprKind::Dictionary(fields) => fields.first().map(|it| it.span().start),
+            ExprKind::Array(elems) => elems.first().map(|it| it.span.start),
+            _ => None,
+        };
+        if let Some(first_start) = first_start
+            && self.input[expr.span.start..first_start].contains('\n')
+        {
+            return true;
+        }
+
+        let column = self.out.len() - self.out.rfind('\n').map_or(0, |it| it + 1);
+        column + flat_expr(expr).len() > MAX_WIDTH
+    }
+
+    fn open_block(&mut self) {
+        self.out.push('\n');
+        self.indent += 1;
+        self.block_start = true;
+    }
+
+    fn close_block(&mut self, close_pos: usize) {
+        self.leading_trivia(close_pos, false);
+        self.indent -= 1;
+        self.write_indent();
+        self.block_start = false;
+    }
+
+    /// Writes all comments in front of `pos`, each on its own line, and keeps
+    /// single blank lines from the source between elements of a block.
+    fn leading_trivia(&mut self, pos: usize, mut force_blank_line: bool) {
+        while let Some(comment) = self.comments.get(self.next_comment).copied()
+            && comment.span.start < pos
+        {
+            self.next_comment += 1;
+            self.blank_line(comment.span.start, force_blank_line);
+            force_blank_line = false;
+            self.write_indent();
+            self.out.push_str(comment.text);
+            self.out.push('\n');
+            self.last_end = comment.span.end;
+            self.block_start = false;
+        }
+
+        if pos < self.input.len() {
+            self.blank_line(pos, force_blank_line);
+        }
+    }
+
+    fn blank_line(&mut self, pos: usize, force: bool) {
+        if self.block_start {
+            return;
+        }
+
+        let gap = &self.input[self.last_end.min(pos)..pos];
+        if force || gap.matches('\n').count() >= 2 {
+            self.out.push('\n');
+        }
+    }
+
+    /// Finishes the current line, pulling in a comment that follows `end` on
+    /// the same source line.
+    fn end_line(&mut self, end: usize) {
+        self.last_end = end;
+        if let Some(comment) = self.comments.get(self.next_comment).copied()
+            && comment.span.start >= end
+            && !self.input[end..comment.span.start].contains('\n')
+        {
+            self.next_comment += 1;
+            self.out.push(' ');
+            self.out.push_str(comment.text);
+            self.last_end = comment.span.end;
+        }
+
+        self.out.push('\n');
+        self.block_start = false;
+    }
+
+    fn has_comment_before(&self, pos: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|it| it.span.start < pos)
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+}
+
+/// The position of an entry item in the canonical order.
+fn item_rank(item: &EntryItem<'_>) -> usize {
+    match &item.kind {
+        EntryItemKind::Request(_) | EntryItemKind::For(..) => 0,
+        EntryItemKind::Section(name, _) => SECTION_ORDER
+            .iter()
+            .position(|it| *it == name.text)
+            .map_or(SECTION_ORDER.len() + 1, |it| it + 1),
+        EntryItemKind::Assert(_) => SECTION_ORDER.len(),
+    }
+}
+
+fn flat_expr(expr: &Expr<'_>) -> String {
+    match &expr.kind {
+        ExprKind::NameRef(name) => escape_identifier(name).into_owned(),
+        ExprKind::StringLiteral(parts) => {
+            let mut out = String::from('"');
+            for part in parts {
+                match part {
+                    TemplatePart::Literal(raw, _) => out.push_str(raw),
+                    TemplatePart::Expr(expr) => {
+                        out.push_str("{{");
+                        out.push_str(&flat_expr(expr));
+                        out.push_str("}}");
+                    }
+                }
+            }
+            out.push('"');
+            out
+        }
+        ExprKind::IntegerLiteral(lit) | ExprKind::FloatLiteral(lit) => lit.to_string(),
+        ExprKind::NullLiteral => "null".to_string(),
+        ExprKind::BoolLiteral(lit) => lit.to_string(),
+        ExprKind::Dictionary(fields) => {
+            let inner = fields
+                .iter()
+                .map(flat_dictionary_field)
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{{{inner}}}")
+        }
+        ExprKind::Array(elems) => {
+            let inner = elems.iter().map(flat_expr).collect::<Vec<_>>().join(", ");
+            format!("[{inner}]")
+        }
+        ExprKind::Call(name, args) => {
+            let inner = args.iter().map(flat_expr).collect::<Vec<_>>().join(", ");
+            format!("{}({inner})", escape_identifier(name.text))
+        }
+        ExprKind::Binary(op, lhs, rhs) => {
+            format!("{} {op} {}", flat_expr(lhs), flat_expr(rhs))
+        }
+        ExprKind::Field(base, name) => {
+            format!("{}.{}", flat_expr(base), escape_identifier(name.text))
+        }
+        ExprKind::If(cond, then, else_) => format!(
+            "if {} then {} else {}",
+            flat_expr(cond),
+            flat_expr(then),
+            flat_expr(else_)
+        ),
+        ExprKind::Paren(inner) => format!("({})", flat_expr(inner)),
+    }
+}
+
+fn flat_dictionary_field(field: &DictionaryField<'_>) -> String {
+    match field {
+        DictionaryField::Pair { key, value } => format!("{}: {}", flat_expr(key), flat_expr(value)),
+        DictionaryField::Spread(expr, _) => format!("...{}", flat_expr(expr)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use expect_test::expect_file;
+
+    use super::format_source;
+
+    #[test]
+    fn format() {
+        let crate_root_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let dir = crate_root_dir.join("test_data/format");
+        let read_dir =
+            fs::read_dir(&dir).unwrap_or_else(|_| panic!("could not read dir `{}`", dir.display()));
+
+        for file in read_dir {
+            let au_path = file.unwrap().path();
+            if au_path.extension().unwrap_or_default() != "au" {
+                continue;
+            }
+
+            let input = fs::read_to_string(&au_path)
+                .unwrap_or_else(|_| panic!("could not read file `{}`", au_path.display()));
+            let formatted = format_source(&input)
+                .unwrap_or_else(|e| panic!("Could not format `{}`: {:?}", au_path.display(), e));
+            expect_file![au_path.with_extension("fmt")].assert_eq(&formatted);
+
+            let reformatted = format_source(&formatted).unwrap();
+            assert_eq!(
+                formatted,
+                reformatted,
+                "formatting `{}` is not idempotent",
+                au_path.display()
+            );
+        }
+    }
+}
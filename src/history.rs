@@ -0,0 +1,366 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Value as Json, json};
+
+use crate::{
+    client::{HttpError, Response},
+    machine::matches_pattern,
+    secrets::Secrets,
+    validated::HttpMethod,
+};
+
+/// The file every sent request is appended to, one JSON object per line,
+/// for `aurora history`.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    /// The `.au` file whose requests are recorded
+    file: Option<String>,
+    /// Redacted from the records, e.g. tokens in URLs
+    secrets: Secrets,
+}
+
+impl History {
+    pub fn new(path: PathBuf, secrets: Secrets) -> Self {
+        Self {
+            path,
+            file: None,
+            secrets,
+        }
+    }
+
+    /// Where the history is kept unless `--history-file` is given:
+    /// `$XDG_STATE_HOME/aurora/history.jsonl`, falling back to
+    /// `~/.local/state`, or `%LOCALAPPDATA%` on Windows.
+    pub fn default_path() -> Option<PathBuf> {
+        let non_empty = |name| std::env::var_os(name).filter(|it| !it.is_empty());
+        let dir = non_empty("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| non_empty("HOME").map(|it| Path::new(&it).join(".local/state")))
+            .or_else(|| non_empty("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(dir.join("aurora").join("history.jsonl"))
+    }
+
+    /// The same history, for the requests of the source file `file`.
+    pub fn for_file(&self, file: &Path) -> Self {
+        Self {
+            path: self.path.clone(),
+            file: Some(file.to_string_lossy().into_owned()),
+            secrets: self.secrets.clone(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends the request of `entry` that was sent at `time` and took
+    /// `duration`.
+    pub fn record(
+        &self,
+        entry: &str,
+        (method, url): (HttpMethod, &str),
+        result: &Result<Response, HttpError>,
+        time: DateTime<Utc>,
+        duration: Duration,
+    ) -> io::Result<()> {
+        let record = HistoryRecord {
+            time: time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            file: self.file.clone(),
+            entry: entry.to_string(),
+            method: method.as_str().to_string(),
+            url: url.to_string(),
+            status: result.as_ref().ok().map(|it| it.status.as_u16()),
+            duration_ms: duration.as_millis() as u64,
+            error: result.as_ref().err().map(|it| it.to_string()),
+        };
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // The line is written at once, so records of entries that run at
+        // the same time don't interleave.
+        let mut line = self
+            .secrets
+            .redact(&record.to_json().to_string())
+            .into_owned();
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+}
+
+/// One sent request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryRecord {
+    /// When the request was sent, in RFC 3339 format in UTC
+    pub time: String,
+    pub file: Option<String>,
+    pub entry: String,
+    pub method: String,
+    pub url: String,
+    /// `None` if no response was received
+    pub status: Option<u16>,
+    pub duration_ms: u64,
+    /// Why no response was received
+    pub error: Option<String>,
+}
+
+impl HistoryRecord {
+    pub fn to_json(&self) -> Json {
+        let mut json = json!({
+            "time": self.time,
+            "entry": self.entry,
+            "method": self.method,
+            "url": self.url,
+            "status": self.status,
+            "duration_ms": self.duration_ms,
+        });
+        if let Some(file) = &self.file {
+            json["file"] = json!(file);
+        }
+        if let Some(error) = &self.error {
+            json["error"] = json!(error);
+        }
+        json
+    }
+
+    fn from_json(json: &Json) -> Option<Self> {
+        let string = |key| json.get(key)?.as_str().map(str::to_string);
+        Some(Self {
+            time: string("time")?,
+            file: string("file"),
+            entry: string("entry")?,
+            method: string("method")?,
+            url: string("url")?,
+            status: json
+                .get("status")
+                .and_then(Json::as_u64)
+                .and_then(|it| u16::try_from(it).ok()),
+            duration_ms: json.get("duration_ms").and_then(Json::as_u64)?,
+            error: string("error"),
+        })
+    }
+}
+
+/// Reads all records of the history file at `path`, oldest first. A missing
+/// file is an empty history, and lines that aren't records are skipped.
+pub fn read(path: &Path) -> io::Result<Vec<HistoryRecord>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .filter_map(|json| HistoryRecord::from_json(&json))
+        .collect())
+}
+
+/// Which records `aurora history` shows.
+#[derive(Debug, Default)]
+pub struct Query {
+    /// Entry name, may contain `*` and `?` wildcards
+    pub entry: Option<String>,
+    /// Status code, where `x` stands for any digit, like `404` or `5xx`
+    pub status: Option<String>,
+    /// Only records sent at this time or later
+    pub since: Option<DateTime<Utc>>,
+    /// Only the most recent records
+    pub limit: Option<usize>,
+}
+
+impl Query {
+    /// Returns the records that match, oldest first.
+    pub fn apply<'a>(&self, records: &'a [HistoryRecord]) -> Vec<&'a HistoryRecord> {
+        // The times have the same format, so they compare like strings.
+        let since = self
+            .since
+            .map(|it| it.to_rfc3339_opts(SecondsFormat::Millis, true));
+        let status = self.status.as_ref().map(|it| it.replace(['x', 'X'], "?"));
+        let mut matches = records
+            .iter()
+            .filter(|it| {
+                self.entry
+                    .as_ref()
+                    .is_none_or(|pattern| matches_pattern(pattern, &it.entry))
+            })
+            .filter(|it| {
+                status.as_ref().is_none_or(|pattern| {
+                    it.status
+                        .is_some_and(|status| matches_pattern(pattern, &status.to_string()))
+                })
+            })
+            .filter(|it| since.as_ref().is_none_or(|since| &it.time >= since))
+            .collect::<Vec<_>>();
+        if let Some(limit) = self.limit {
+            matches.drain(..matches.len().saturating_sub(limit));
+        }
+        matches
+    }
+}
+
+/// The records as a table, as printed by `aurora history`.
+pub struct Table<'a>(pub &'a [&'a HistoryRecord]);
+
+impl fmt::Display for Table<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .0
+            .iter()
+            .map(|record| {
+                let entry = match &record.file {
+                    Some(file) => format!("{file}::{}", record.entry),
+                    None => record.entry.clone(),
+                };
+                // Seconds are precise enough to find a request again.
+                let time = record.time.get(..19).unwrap_or(&record.time);
+                let status = match (record.status, &record.error) {
+                    (Some(status), _) => status.to_string(),
+                    (None, Some(error)) => format!("error: {error}"),
+                    (None, None) => "-".to_string(),
+                };
+                [
+                    time.replace('T', " "),
+                    entry,
+                    record.method.clone(),
+                    record.url.clone(),
+                    status,
+                    format!("{}ms", record.duration_ms),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = ["TIME", "ENTRY", "METHOD", "URL", "STATUS", "DURATION"].map(String::from);
+        let mut widths = header.clone().map(|it| it.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in std::iter::once(&header).chain(&rows) {
+            let mut line = String::new();
+            for (cell, width) in row.iter().zip(widths) {
+                line.push_str(&format!("{cell:width$}  "));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::client::{CacheStatus, StatusCode};
+
+    #[test]
+    fn record_and_query() {
+        let path = std::env::temp_dir().join(format!("aurora-history-{}", std::process::id()));
+        let secrets = Secrets::new();
+        secrets.add("s3cr3t");
+        let history =
+            History::new(path.join("history.jsonl"), secrets).for_file(Path::new("api.au"));
+        let response = |status| Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(status),
+            headers: vec![],
+            body: vec![],
+            elapsed: Duration::ZERO,
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        };
+        let time = |it: &str| DateTime::parse_from_rfc3339(it).unwrap().to_utc();
+
+        let requests = [
+            ("getUser", "https://example.com/users/1", Ok(response(200))),
+            (
+                "getUser",
+                "https://example.com/users/2?token=s3cr3t",
+                Ok(response(404)),
+            ),
+            (
+                "health",
+                "https://example.com/health",
+                Err(HttpError::Timeout),
+            ),
+        ];
+        for (i, (entry, url, result)) in requests.iter().enumerate() {
+            let sent = time(&format!("2026-10-1{i}T08:00:00Z"));
+            history
+                .record(
+                    entry,
+                    (HttpMethod::Get, url),
+                    result,
+                    sent,
+                    Duration::from_millis(42),
+                )
+                .unwrap();
+        }
+        std::fs::write(
+            history.path(),
+            std::fs::read_to_string(history.path()).unwrap() + "not a record\n",
+        )
+        .unwrap();
+
+        let records = read(history.path()).unwrap();
+        expect![[r#"
+            TIME                 ENTRY            METHOD  URL                                    STATUS                    DURATION
+            2026-10-10 08:00:00  api.au::getUser  GET     https://example.com/users/1            200                       42ms
+            2026-10-11 08:00:00  api.au::getUser  GET     https://example.com/users/2?token=***  404                       42ms
+            2026-10-12 08:00:00  api.au::health   GET     https://example.com/health             error: request timed out  42ms
+        "#]]
+        .assert_eq(&Table(&Query::default().apply(&records)).to_string());
+
+        let urls = |query: Query| {
+            query
+                .apply(&records)
+                .iter()
+                .map(|it| it.url.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            urls(Query {
+                status: Some("4xx".to_string()),
+                ..Query::default()
+            }),
+            ["https://example.com/users/2?token=***"]
+        );
+        assert_eq!(
+            urls(Query {
+                entry: Some("get*".to_string()),
+                limit: Some(1),
+                ..Query::default()
+            }),
+            ["https://example.com/users/2?token=***"]
+        );
+        assert_eq!(
+            urls(Query {
+                since: Some(time("2026-10-11T00:00:00Z")),
+                ..Query::default()
+            }),
+            [
+                "https://example.com/users/2?token=***",
+                "https://example.com/health"
+            ]
+        );
+        assert!(read(&path.join("missing.jsonl")).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
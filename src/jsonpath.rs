@@ -0,0 +1,259 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// A JSONPath like `$.data.items[0].id`, used to pick values out of JSON
+/// response bodies.
+///
+/// Supports child names (`.name`, `['name']`), indices (`[0]`, `[-1]`),
+/// slices (`[1:3]`), wildcards (`.*`, `[*]`) and recursive descent
+/// (`..name`). Filters and unions are not supported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Child(Selector),
+    /// Applies the selector to the value and all of its descendants
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selector {
+    Name(String),
+    /// Counts from the end when negative
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+}
+
+impl JsonPath {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let Some(mut rest) = raw.strip_prefix('$') else {
+            return Err("a path has to start with `$`".to_string());
+        };
+
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            let (segment, remaining) = if let Some(after) = rest.strip_prefix("..") {
+                let (selector, remaining) = match after.strip_prefix('[') {
+                    Some(after) => parse_bracket(after)?,
+                    None => parse_dotted(after)?,
+                };
+                (Segment::Descendant(selector), remaining)
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let (selector, remaining) = parse_dotted(after)?;
+                (Segment::Child(selector), remaining)
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let (selector, remaining) = parse_bracket(after)?;
+                (Segment::Child(selector), remaining)
+            } else {
+                return Err(format!("unexpected `{rest}`, expected `.` or `[`"));
+            };
+            segments.push(segment);
+            rest = remaining;
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            segments,
+        })
+    }
+
+    /// Returns all values the path selects in `root`, in document order.
+    pub fn query<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for value in current {
+                match segment {
+                    Segment::Child(selector) => select(selector, value, &mut next),
+                    Segment::Descendant(selector) => {
+                        for value in descendants(value) {
+                            select(selector, value, &mut next);
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+    }
+
+    /// Returns the first value the path selects in `root`.
+    pub fn first<'a>(&self, root: &'a Value) -> Option<&'a Value> {
+        self.query(root).into_iter().next()
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Parses the selector after a `.`, e.g. `name` or `*`.
+fn parse_dotted(input: &str) -> Result<(Selector, &str), String> {
+    if let Some(rest) = input.strip_prefix('*') {
+        return Ok((Selector::Wildcard, rest));
+    }
+    let end = input.find(['.', '[']).unwrap_or(input.len());
+    if end == 0 {
+        return Err("expected a name after `.`".to_string());
+    }
+    Ok((Selector::Name(input[..end].to_string()), &input[end..]))
+}
+
+/// Parses the selector after a `[`, including the closing `]`.
+fn parse_bracket(input: &str) -> Result<(Selector, &str), String> {
+    if let Some(quote) = input.chars().next().filter(|c| *c == '\'' || *c == '"') {
+        let body = &input[1..];
+        let end = body
+            .find(quote)
+            .ok_or_else(|| format!("expected a closing `{quote}`"))?;
+        let rest = body[end + 1..]
+            .strip_prefix(']')
+            .ok_or_else(|| "expected `]` after the name".to_string())?;
+        return Ok((Selector::Name(body[..end].to_string()), rest));
+    }
+
+    let end = input
+        .find(']')
+        .ok_or_else(|| "expected a closing `]`".to_string())?;
+    let (inner, rest) = (input[..end].trim(), &input[end + 1..]);
+    let number = |s: &str| {
+        s.trim()
+            .parse::<i64>()
+            .map_err(|_| format!("expected an index, but found `{s}`"))
+    };
+    let selector = if inner == "*" {
+        Selector::Wildcard
+    } else if let Some((start, end)) = inner.split_once(':') {
+        let bound = |s: &str| match s.trim() {
+            "" => Ok(None),
+            s => number(s).map(Some),
+        };
+        Selector::Slice(bound(start)?, bound(end)?)
+    } else {
+        Selector::Index(number(inner)?)
+    };
+    Ok((selector, rest))
+}
+
+fn select<'a>(selector: &Selector, value: &'a Value, out: &mut Vec<&'a Value>) {
+    match (selector, value) {
+        (Selector::Name(name), Value::Object(map)) => out.extend(map.get(name)),
+        (Selector::Index(index), Value::Array(items)) => {
+            out.extend(resolve_index(*index, items.len()).and_then(|i| items.get(i)))
+        }
+        (Selector::Slice(start, end), Value::Array(items)) => {
+            let len = items.len();
+            let clamp = |bound: i64| {
+                if bound < 0 {
+                    len.saturating_sub(bound.unsigned_abs() as usize)
+                } else {
+                    (bound as usize).min(len)
+                }
+            };
+            let start = start.map_or(0, clamp);
+            let end = end.map_or(len, clamp);
+            if start < end {
+                out.extend(&items[start..end]);
+            }
+        }
+        (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+        (Selector::Wildcard, Value::Array(items)) => out.extend(items),
+        _ => {}
+    }
+}
+
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    }
+}
+
+/// Returns `value` and everything nested in it, parents before children.
+fn descendants(value: &Value) -> Vec<&Value> {
+    let mut out = vec![value];
+    let mut i = 0;
+    while i < out.len() {
+        match out[i] {
+            Value::Object(map) => out.extend(map.values()),
+            Value::Array(items) => out.extend(items),
+            _ => {}
+        }
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn query(path: &str, root: &Value) -> Vec<Value> {
+        JsonPath::parse(path)
+            .unwrap()
+            .query(root)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn query_children_and_indices() {
+        let root = json!({"data": {"items": [{"id": 1}, {"id": 2}, {"id": 3}], "a.b": true}});
+
+        assert_eq!(query("$", &root), std::slice::from_ref(&root));
+        assert_eq!(query("$.data.items[0].id", &root), [json!(1)]);
+        assert_eq!(query("$.data.items[-1].id", &root), [json!(3)]);
+        assert_eq!(query("$['data'][\"a.b\"]", &root), [json!(true)]);
+        assert_eq!(
+            query("$.data.items[*].id", &root),
+            [json!(1), json!(2), json!(3)]
+        );
+        assert_eq!(query("$.data.items[1:].id", &root), [json!(2), json!(3)]);
+        assert_eq!(query("$.data.items[:-2].id", &root), [json!(1)]);
+        assert!(query("$.data.items[5]", &root).is_empty());
+        assert!(query("$.data.missing.id", &root).is_empty());
+    }
+
+    #[test]
+    fn query_descendants() {
+        let root = json!({"id": 0, "user": {"id": 1, "friends": [{"id": 2}]}});
+        assert_eq!(query("$..id", &root), [json!(0), json!(1), json!(2)]);
+        assert_eq!(query("$..friends[0].id", &root), [json!(2)]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            JsonPath::parse("data.id").unwrap_err(),
+            "a path has to start with `$`"
+        );
+        assert_eq!(
+            JsonPath::parse("$.items[one]").unwrap_err(),
+            "expected an index, but found `one`"
+        );
+        assert_eq!(
+            JsonPath::parse("$.items[0").unwrap_err(),
+            "expected a closing `]`"
+        );
+        assert_eq!(
+            JsonPath::parse("$.").unwrap_err(),
+            "expected a name after `.`"
+        );
+        assert_eq!(
+            JsonPath::parse("$id").unwrap_err(),
+            "unexpected `id`, expected `.` or `[`"
+        );
+    }
+}
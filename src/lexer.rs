@@ -1,30 +1,99 @@
+use std::borrow::Cow;
+
 use crate::{
+    codes,
     diagnostic::{Diagnostic, Level},
     span::Span,
-    token::{Delim, HttpMethod, Keyword, TemplatePart, Token, TokenKind},
+    token::{Comment, Delim, HttpMethod, Keyword, TemplatePart, Token, TokenKind},
 };
 
+/// Returns `name` as it has to be written in source code, in backticks if it
+/// would otherwise be lexed as something else than an identifier.
+pub fn escape_identifier(name: &str) -> Cow<'_, str> {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|it| it.is_alphabetic() || it == '_')
+        && name.chars().all(|it| it.is_alphanumeric() || it == '_')
+        && reserved_word(name).is_none();
+    if plain {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("`{name}`"))
+    }
+}
+
+fn reserved_word(text: &str) -> Option<TokenKind<'static>> {
+    let kind = match text {
+        "entry" => TokenKind::Keyword(Keyword::Entry),
+        "const" => TokenKind::Keyword(Keyword::Const),
+        "extends" => TokenKind::Keyword(Keyword::Extends),
+        "null" => TokenKind::Keyword(Keyword::Null),
+        "true" => TokenKind::Keyword(Keyword::True),
+        "false" => TokenKind::Keyword(Keyword::False),
+        "if" => TokenKind::Keyword(Keyword::If),
+        "then" => TokenKind::Keyword(Keyword::Then),
+        "else" => TokenKind::Keyword(Keyword::Else),
+        "for" => TokenKind::Keyword(Keyword::For),
+        "in" => TokenKind::Keyword(Keyword::In),
+        "GET" => TokenKind::HttpMethod(HttpMethod::Get),
+        "POST" => TokenKind::HttpMethod(HttpMethod::Post),
+        "PUT" => TokenKind::HttpMethod(HttpMethod::Put),
+        "PATCH" => TokenKind::HttpMethod(HttpMethod::Patch),
+        "DELETE" => TokenKind::HttpMethod(HttpMethod::Delete),
+        "HEAD" => TokenKind::HttpMethod(HttpMethod::Head),
+        "OPTIONS" => TokenKind::HttpMethod(HttpMethod::Options),
+        _ => return None,
+    };
+    Some(kind)
+}
+
+#[cfg(test)]
 pub fn lex<'input>(input: &'input str) -> Result<Vec<Token<'input>>, Diagnostic> {
+    Lexer::new(input).collect()
+}
+
+/// Splits `input` into tokens, and also returns the comments that were
+/// skipped.
+pub fn lex_with_comments<'input>(
+    input: &'input str,
+) -> Result<(Vec<Token<'input>>, Vec<Comment<'input>>), Diagnostic> {
     let mut lexer = Lexer::new(input);
-    lexer.lex()
+    let tokens = lexer.by_ref().collect::<Result<_, _>>()?;
+    Ok((tokens, lexer.comments))
 }
 
-struct Lexer<'input> {
+/// Produces tokens on demand, so the parser never needs the whole token
+/// stream in memory.
+///
+/// Lexing stops after the first error.
+pub struct Lexer<'input> {
     input: &'input str,
     pos: usize,
+    comments: Vec<Comment<'input>>,
 }
 
-impl<'input> Lexer<'input> {
-    fn new(input: &'input str) -> Self {
-        Self { input, pos: 0 }
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Result<Token<'input>, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_token() {
+            Ok(token) => token.map(Ok),
+            Err(diag) => {
+                self.pos = self.input.len();
+                Some(Err(diag))
+            }
+        }
     }
+}
 
-    fn lex(&mut self) -> Result<Vec<Token<'input>>, Diagnostic> {
-        let mut tokens = vec![];
-        while let Some(token) = self.next_token()? {
-            tokens.push(token);
+impl<'input> Lexer<'input> {
+    pub fn new(input: &'input str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            comments: vec![],
         }
-        Ok(tokens)
     }
 
     fn next_token(&mut self) -> Result<Option<Token<'input>>, Diagnostic> {
@@ -45,12 +114,48 @@ impl<'input> Lexer<'input> {
             let kind = match first {
                 ':' => TokenKind::Colon,
                 ',' => TokenKind::Comma,
+                '.' if self.first() == Some('.') && self.second() == Some('.') => {
+                    self.bump();
+                    self.bump();
+                    TokenKind::DotDotDot
+                }
+                '.' => TokenKind::Dot,
+                '=' if self.first() == Some('=') => {
+                    self.bump();
+                    TokenKind::EqEq
+                }
                 '=' => TokenKind::Eq,
+                '!' if self.first() == Some('=') => {
+                    self.bump();
+                    TokenKind::BangEq
+                }
+                '<' if self.first() == Some('=') => {
+                    self.bump();
+                    TokenKind::LtEq
+                }
+                '<' => TokenKind::Lt,
+                '>' if self.first() == Some('=') => {
+                    self.bump();
+                    TokenKind::GtEq
+                }
+                '>' => TokenKind::Gt,
+                '+' => TokenKind::Plus,
+                '-' => TokenKind::Minus,
+                '*' => TokenKind::Star,
+                '/' => TokenKind::Slash,
+                '?' if self.first() == Some('?') => {
+                    self.bump();
+                    TokenKind::QuestionQuestion
+                }
+                '@' => TokenKind::At,
                 '{' => TokenKind::Delim(Delim::OpenBrace),
                 '[' => TokenKind::Delim(Delim::OpenBrack),
+                '(' => TokenKind::Delim(Delim::OpenParen),
                 '}' => TokenKind::Delim(Delim::CloseBrace),
                 ']' => TokenKind::Delim(Delim::CloseBrack),
+                ')' => TokenKind::Delim(Delim::CloseParen),
                 '"' => self.string(start)?,
+                '`' => self.escaped_identifier(start)?,
                 _ if first.is_ascii_digit() => self.number(start),
                 _ if first.is_alphabetic() || first == '_' => self.identifier(start),
                 _ => {
@@ -58,6 +163,7 @@ impl<'input> Lexer<'input> {
                         "Unrecognized character",
                         Span::new(start, start),
                     )
+                    .code(codes::SYNTAX)
                     .primary_label("I don't know what to do with this character", Level::Error));
                 }
             };
@@ -75,7 +181,7 @@ impl<'input> Lexer<'input> {
         let mut parts = vec![];
         let mut chunk_start = self.pos;
 
-        while let Some(ch) = self.next() {
+        while let Some(ch) = self.next_char() {
             match ch {
                 '"' => {
                     if chunk_start < self.pos - 1 {
@@ -105,6 +211,7 @@ impl<'input> Lexer<'input> {
                                     "Unterminated template",
                                     Span::new(self.pos, self.pos),
                                 )
+                                .code(codes::UNTERMINATED)
                                 .primary_label("I was expecting `}}` here", Level::Error));
                             }
                             Some('}') if self.second() == Some('}') => {
@@ -118,6 +225,7 @@ impl<'input> Lexer<'input> {
                                         "Unterminated template",
                                         Span::new(self.pos, self.pos),
                                     )
+                                    .code(codes::UNTERMINATED)
                                     .primary_label("I was expecting `}}` here", Level::Error));
                                 };
                                 tokens.push(token);
@@ -137,6 +245,7 @@ impl<'input> Lexer<'input> {
 
         Err(
             Diagnostic::error("Unterminated string literal", Span::new(start, self.pos))
+                .code(codes::UNTERMINATED)
                 .primary_label(
                     "I never found the closing quote for this string",
                     Level::Error,
@@ -144,25 +253,43 @@ impl<'input> Lexer<'input> {
         )
     }
 
+    /// Lexes decimal and hexadecimal (`0xFF`) numbers. Digits can be
+    /// separated with underscores, floats can have an exponent (`1.5e9`).
     fn number(&mut self, start: usize) -> TokenKind<'input> {
-        fn eat_digits<'input>(l: &mut Lexer<'input>) {
+        fn eat_digits<'input>(l: &mut Lexer<'input>, radix: u32) {
             while let Some(ch) = l.first() {
-                if !ch.is_ascii_digit() {
+                if !ch.is_digit(radix) && ch != '_' {
                     break;
                 }
                 l.bump();
             }
         }
 
-        eat_digits(self);
+        if &self.input[start..self.pos] == "0" && matches!(self.first(), Some('x' | 'X')) {
+            self.bump();
+            eat_digits(self, 16);
+            return TokenKind::Integer(&self.input[start..self.pos]);
+        }
 
-        let is_float = if matches!(self.first(), Some('.')) {
+        eat_digits(self, 10);
+
+        let mut is_float = false;
+        if matches!(self.first(), Some('.')) {
             self.bump();
-            eat_digits(self);
-            true
-        } else {
-            false
-        };
+            eat_digits(self, 10);
+            is_float = true;
+        }
+
+        if matches!(self.first(), Some('e' | 'E'))
+            && matches!(self.second(), Some('0'..='9' | '+' | '-'))
+        {
+            self.bump();
+            if matches!(self.first(), Some('+' | '-')) {
+                self.bump();
+            }
+            eat_digits(self, 10);
+            is_float = true;
+        }
 
         let text = &self.input[start..self.pos];
         if is_float {
@@ -181,17 +308,43 @@ impl<'input> Lexer<'input> {
         }
 
         let text = &self.input[start..self.pos];
-        match text {
-            "entry" => TokenKind::Keyword(Keyword::Entry),
-            "const" => TokenKind::Keyword(Keyword::Const),
-            "null" => TokenKind::Keyword(Keyword::Null),
-            "GET" => TokenKind::HttpMethod(HttpMethod::Get),
-            "POST" => TokenKind::HttpMethod(HttpMethod::Post),
-            "PUT" => TokenKind::HttpMethod(HttpMethod::Put),
-            "PATCH" => TokenKind::HttpMethod(HttpMethod::Patch),
-            "DELETE" => TokenKind::HttpMethod(HttpMethod::Delete),
-            _ => TokenKind::Identifier(text),
+        reserved_word(text).unwrap_or(TokenKind::Identifier(text))
+    }
+
+    /// Lexes an identifier in backticks, e.g. `` `entry` ``, which is never a
+    /// keyword.
+    fn escaped_identifier(&mut self, start: usize) -> Result<TokenKind<'input>, Diagnostic> {
+        while let Some(ch) = self.first() {
+            match ch {
+                '`' => {
+                    self.bump();
+                    let text = &self.input[start + 1..self.pos - 1];
+                    if text.is_empty() {
+                        return Err(Diagnostic::error(
+                            "Empty identifier",
+                            Span::new(start, self.pos),
+                        )
+                        .code(codes::SYNTAX)
+                        .primary_label(
+                            "I was expecting a name between the backticks",
+                            Level::Error,
+                        ));
+                    }
+                    return Ok(TokenKind::Identifier(text));
+                }
+                '\n' => break,
+                _ => self.bump(),
+            }
         }
+
+        Err(
+            Diagnostic::error("Unterminated identifier", Span::new(start, self.pos))
+                .code(codes::UNTERMINATED)
+                .primary_label(
+                    "I never found the closing backtick for this name",
+                    Level::Error,
+                ),
+        )
     }
 
     fn skip_whitespace(&mut self) -> bool {
@@ -212,12 +365,18 @@ impl<'input> Lexer<'input> {
     }
 
     fn skip_comment(&mut self) {
+        let start = self.pos;
         while let Some(ch) = self.first() {
             if ch == '\n' {
                 break;
             }
             self.bump();
         }
+
+        self.comments.push(Comment {
+            text: self.input[start..self.pos].trim_end(),
+            span: Span::new(start, self.pos),
+        });
     }
 
     fn first(&mut self) -> Option<char> {
@@ -228,7 +387,7 @@ impl<'input> Lexer<'input> {
         self.input[self.pos..].chars().nth(1)
     }
 
-    fn next(&mut self) -> Option<char> {
+    fn next_char(&mut self) -> Option<char> {
         if let Some(ch) = self.first() {
             self.pos += ch.len_utf8();
             Some(ch)
@@ -238,7 +397,7 @@ impl<'input> Lexer<'input> {
     }
 
     fn bump(&mut self) {
-        _ = self.next();
+        _ = self.next_char();
     }
 }
 
@@ -416,6 +575,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_http_method_head() {
+        assert_token(
+            "HEAD",
+            Token {
+                kind: TokenKind::HttpMethod(HttpMethod::Head),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_http_method_options() {
+        assert_token(
+            "OPTIONS",
+            Token {
+                kind: TokenKind::HttpMethod(HttpMethod::Options),
+                span: Span::new(0, 7),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_keyword_entry() {
         assert_token(
@@ -452,6 +635,131 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_keyword_true() {
+        assert_token(
+            "true",
+            Token {
+                kind: TokenKind::Keyword(Keyword::True),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_false() {
+        assert_token(
+            "false",
+            Token {
+                kind: TokenKind::Keyword(Keyword::False),
+                span: Span::new(0, 5),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_if() {
+        assert_token(
+            "if",
+            Token {
+                kind: TokenKind::Keyword(Keyword::If),
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_then() {
+        assert_token(
+            "then",
+            Token {
+                kind: TokenKind::Keyword(Keyword::Then),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_else() {
+        assert_token(
+            "else",
+            Token {
+                kind: TokenKind::Keyword(Keyword::Else),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_for() {
+        assert_token(
+            "for",
+            Token {
+                kind: TokenKind::Keyword(Keyword::For),
+                span: Span::new(0, 3),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_in() {
+        assert_token(
+            "in",
+            Token {
+                kind: TokenKind::Keyword(Keyword::In),
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_keyword_extends() {
+        assert_token(
+            "extends",
+            Token {
+                kind: TokenKind::Keyword(Keyword::Extends),
+                span: Span::new(0, 7),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_escaped_identifier() {
+        assert_token(
+            "`entry`",
+            Token {
+                kind: TokenKind::Identifier("entry"),
+                span: Span::new(0, 7),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_escaped_identifier_errors() {
+        let diagnostic = lex("`entry").expect_err("unterminated identifier");
+        assert_eq!(diagnostic.message, "Unterminated identifier");
+        let diagnostic = lex("``").expect_err("empty identifier");
+        assert_eq!(diagnostic.message, "Empty identifier");
+    }
+
+    #[test]
+    fn escape_identifiers() {
+        assert_eq!(escape_identifier("user_id"), "user_id");
+        assert_eq!(escape_identifier("const"), "`const`");
+        assert_eq!(escape_identifier("GET"), "`GET`");
+        assert_eq!(escape_identifier("x-trace"), "`x-trace`");
+        assert_eq!(escape_identifier("1st"), "`1st`");
+    }
+
     #[test]
     fn lex_identifier_entry() {
         assert_token(
@@ -488,6 +796,18 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_identifier_true() {
+        assert_token(
+            "True",
+            Token {
+                kind: TokenKind::Identifier("True"),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_integer_single_digit() {
         assert_token(
@@ -548,6 +868,54 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_integer_hex() {
+        assert_token(
+            "0xFF",
+            Token {
+                kind: TokenKind::Integer("0xFF"),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_integer_with_underscores() {
+        assert_token(
+            "1_000_000",
+            Token {
+                kind: TokenKind::Integer("1_000_000"),
+                span: Span::new(0, 9),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_float_with_exponent() {
+        assert_token(
+            "1.5e9",
+            Token {
+                kind: TokenKind::Float("1.5e9"),
+                span: Span::new(0, 5),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_float_with_negative_exponent() {
+        assert_token(
+            "2E-3",
+            Token {
+                kind: TokenKind::Float("2E-3"),
+                span: Span::new(0, 4),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_string_empty() {
         assert_token(
@@ -758,6 +1126,102 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_plus() {
+        assert_token(
+            "+",
+            Token {
+                kind: TokenKind::Plus,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_minus() {
+        assert_token(
+            "-",
+            Token {
+                kind: TokenKind::Minus,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_star() {
+        assert_token(
+            "*",
+            Token {
+                kind: TokenKind::Star,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_slash() {
+        assert_token(
+            "/",
+            Token {
+                kind: TokenKind::Slash,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_dot() {
+        assert_token(
+            ".",
+            Token {
+                kind: TokenKind::Dot,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_question_question() {
+        assert_token(
+            "??",
+            Token {
+                kind: TokenKind::QuestionQuestion,
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_at() {
+        assert_token(
+            "@",
+            Token {
+                kind: TokenKind::At,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_dot_dot_dot() {
+        assert_token(
+            "...",
+            Token {
+                kind: TokenKind::DotDotDot,
+                span: Span::new(0, 3),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_eq() {
         assert_token(
@@ -770,6 +1234,78 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_eq_eq() {
+        assert_token(
+            "==",
+            Token {
+                kind: TokenKind::EqEq,
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_bang_eq() {
+        assert_token(
+            "!=",
+            Token {
+                kind: TokenKind::BangEq,
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_lt() {
+        assert_token(
+            "<",
+            Token {
+                kind: TokenKind::Lt,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_lt_eq() {
+        assert_token(
+            "<=",
+            Token {
+                kind: TokenKind::LtEq,
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_gt() {
+        assert_token(
+            ">",
+            Token {
+                kind: TokenKind::Gt,
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_gt_eq() {
+        assert_token(
+            ">=",
+            Token {
+                kind: TokenKind::GtEq,
+                span: Span::new(0, 2),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_open_brace() {
         assert_token(
@@ -818,6 +1354,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn lex_open_paren() {
+        assert_token(
+            "(",
+            Token {
+                kind: TokenKind::Delim(Delim::OpenParen),
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
+    #[test]
+    fn lex_close_paren() {
+        assert_token(
+            ")",
+            Token {
+                kind: TokenKind::Delim(Delim::CloseParen),
+                span: Span::new(0, 1),
+                skipped_newline: false,
+            },
+        );
+    }
+
     #[test]
     fn lex_multiple_tokens_ignores_whitespace() {
         assert_tokens(
@@ -0,0 +1,78 @@
+//! Aurora runs HTTP requests written in `.au` files.
+//!
+//! A file is [parsed](parse) into a syntax tree, [validated](validate) into
+//! typed entries and [executed](execute) by sending their requests. The
+//! `aurora` binary is a command line interface on top of this library, so
+//! other programs, e.g. test harnesses, can run `.au` files in the same way.
+//!
+//! Running files needs the `http` feature. Without it only the language is
+//! built, e.g. for the browser playground, which uses the JavaScript
+//! bindings of the `wasm` feature.
+
+pub mod ast;
+#[cfg(feature = "http")]
+pub mod bench;
+#[cfg(feature = "http")]
+mod cache;
+#[cfg(feature = "http")]
+pub mod cancel;
+#[cfg(feature = "http")]
+pub mod client;
+pub mod codes;
+#[cfg(feature = "http")]
+mod cookies;
+pub mod diagnostic;
+pub mod diff;
+pub mod format;
+#[cfg(feature = "http")]
+pub mod history;
+mod jsonpath;
+mod lexer;
+pub mod line_index;
+pub mod list;
+#[cfg(feature = "http")]
+pub mod machine;
+#[cfg(feature = "http")]
+mod metrics;
+#[cfg(feature = "http")]
+pub mod middleware;
+#[cfg(all(feature = "http", any(test, feature = "mock")))]
+pub mod mock;
+pub mod openapi;
+#[cfg(feature = "http")]
+pub mod output_dir;
+pub mod parser;
+#[cfg(feature = "http")]
+pub mod plan;
+#[cfg(feature = "http")]
+pub mod report;
+#[cfg(feature = "http")]
+mod retry;
+pub mod secrets;
+#[cfg(feature = "http")]
+mod sigv4;
+#[cfg(feature = "http")]
+pub mod sources;
+pub mod span;
+#[cfg(feature = "http")]
+mod sse;
+#[cfg(feature = "http")]
+mod telemetry;
+#[cfg(feature = "http")]
+mod timing;
+mod token;
+pub mod validated;
+pub mod validator;
+pub mod value;
+pub mod vars;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+#[cfg(feature = "http")]
+pub use client::HttpClient;
+pub use diagnostic::Diagnostic;
+#[cfg(feature = "http")]
+pub use machine::{ExecutionError, RunOptions, execute, execute_with_client};
+pub use parser::parse;
+pub use validator::{check_source, check_warnings, validate};
+pub use value::Value;
@@ -0,0 +1,66 @@
+/// Byte offsets of the start of every line in a source text.
+///
+/// Built once per source and shared by everything that needs to map byte
+/// offsets to lines, so the input is not rescanned for every diagnostic.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(input: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(input.match_indices('\n').map(|(i, _)| i + 1));
+        Self {
+            line_starts,
+            len: input.len(),
+        }
+    }
+
+    /// Returns the zero-based line containing `offset`.
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Returns the zero-based line and the byte column of `offset`.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        (line, offset - self.line_starts[line])
+    }
+
+    /// Returns the offset of the first byte of `line`.
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_starts[line]
+    }
+
+    /// Returns the offset just past `line`, including its line break.
+    pub fn line_end(&self, line: usize) -> usize {
+        self.line_starts.get(line + 1).copied().unwrap_or(self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_index_empty() {
+        let index = LineIndex::new("");
+        assert_eq!(index.line_col(0), (0, 0));
+        assert_eq!(index.line_end(0), 0);
+    }
+
+    #[test]
+    fn line_index_multiple_lines() {
+        let index = LineIndex::new("ab\ncd\n\nef");
+        assert_eq!(index.line_col(0), (0, 0));
+        assert_eq!(index.line_col(2), (0, 2));
+        assert_eq!(index.line_col(3), (1, 0));
+        assert_eq!(index.line_col(6), (2, 0));
+        assert_eq!(index.line_col(9), (3, 2));
+        assert_eq!(index.line_start(3), 7);
+        assert_eq!(index.line_end(1), 6);
+        assert_eq!(index.line_end(3), 9);
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use crate::{
+    ast::{Entry, EntryItem, EntryItemKind, HttpMethod, ItemKind},
+    diagnostic::Diagnostic,
+    lexer,
+    line_index::LineIndex,
+    parser,
+    token::Comment,
+};
+
+/// The entries of a source file, as printed by `aurora list`.
+#[derive(Debug)]
+pub struct Listing<'input> {
+    pub entries: Vec<EntryInfo<'input>>,
+}
+
+#[derive(Debug)]
+pub struct EntryInfo<'input> {
+    pub name: &'input str,
+    /// The method and the URL as written, inherited from the base entry if
+    /// the entry has no request of its own
+    pub request: Option<(HttpMethod, &'input str)>,
+    pub tags: Vec<&'input str>,
+    /// The comment lines right above the entry, joined into one line
+    pub doc: Option<String>,
+}
+
+/// Lists the entries of `input` in file order. The source is only parsed,
+/// so variables don't need to be given.
+pub fn list(input: &str) -> Result<Listing<'_>, Vec<Diagnostic>> {
+    let (tokens, comments) = lexer::lex_with_comments(input).map_err(|d| vec![d])?;
+    let file = parser::parse_tokens(tokens)?;
+    let line_index = LineIndex::new(input);
+
+    let entries = file
+        .items
+        .iter()
+        .filter_map(|item| match &item.kind {
+            ItemKind::Entry(entry) => Some((entry, item.span.start)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let entries = entries
+        .iter()
+        .map(|&(entry, start)| EntryInfo {
+            name: entry.name.text,
+            request: request(entry, &entries, input),
+            tags: entry.tags.iter().map(|it| it.text).collect(),
+            doc: doc(start, &comments, &line_index, input),
+        })
+        .collect();
+    Ok(Listing { entries })
+}
+
+/// Returns the first request of the entry or, if it has none, of the
+/// entries it extends.
+fn request<'input>(
+    entry: &Entry<'input>,
+    entries: &[(&Entry<'input>, usize)],
+    input: &'input str,
+) -> Option<(HttpMethod, &'input str)> {
+    fn find<'a, 'input>(items: &'a [EntryItem<'input>]) -> Option<&'a EntryItem<'input>> {
+        items.iter().find_map(|item| match &item.kind {
+            EntryItemKind::Request(_) => Some(item),
+            EntryItemKind::For(_, _, body) => find(body),
+            _ => None,
+        })
+    }
+
+    let mut entry = entry;
+    // A chain can't be longer than the number of entries, unless it is a
+    // cycle, which the validator reports.
+    for _ in 0..=entries.len() {
+        if let Some(EntryItem {
+            kind: EntryItemKind::Request(request),
+            ..
+        }) = find(&entry.body)
+        {
+            let url = &input[request.url.span.start..request.url.span.end];
+            return Some((request.method, url));
+        }
+        let base = entry.extends.as_ref()?;
+        entry = entries.iter().find(|(it, _)| it.name.text == base.text)?.0;
+    }
+    None
+}
+
+/// Returns the text of the comments that stand on their own lines directly
+/// above `start`, without a blank line in between.
+fn doc(
+    start: usize,
+    comments: &[Comment<'_>],
+    line_index: &LineIndex,
+    input: &str,
+) -> Option<String> {
+    let mut lines = vec![];
+    let mut line = line_index.line(start);
+    let mut next = comments.partition_point(|it| it.span.start < start);
+    while line > 0 && next > 0 {
+        let comment = comments[next - 1];
+        let line_start = line_index.line_start(line - 1);
+        if line_index.line(comment.span.start) != line - 1
+            || !input[line_start..comment.span.start].trim().is_empty()
+        {
+            break;
+        }
+        lines.push(comment.text.trim_start_matches('#').trim());
+        line -= 1;
+        next -= 1;
+    }
+
+    lines.reverse();
+    let doc = lines.join(" ");
+    (!doc.is_empty()).then_some(doc)
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let (method, url) = match entry.request {
+                    Some((method, url)) => (method.to_string(), url),
+                    None => ("-".to_string(), "-"),
+                };
+                let tags = entry
+                    .tags
+                    .iter()
+                    .map(|it| format!("@{it}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                [
+                    entry.name.to_string(),
+                    method,
+                    url.to_string(),
+                    tags,
+                    entry.doc.clone().unwrap_or_default(),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        let header = ["NAME", "METHOD", "URL", "TAGS", "DESCRIPTION"].map(String::from);
+        let mut widths = header.clone().map(|it| it.len());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in std::iter::once(&header).chain(&rows) {
+            let mut line = String::new();
+            for (cell, width) in row.iter().zip(widths) {
+                line.push_str(&format!("{cell:width$}  "));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::list;
+
+    #[test]
+    fn list_entries() {
+        let input = r#"const baseUrl = "https://example.com"
+
+# Fetches a single user.
+# Needs the `id` variable.
+@users @smoke
+entry getUser {
+    GET "{{baseUrl}}/users/{{id}}"
+}
+
+# Not a doc comment, there is a blank line below
+
+entry notes {
+}
+
+entry getAdmin extends getUser { # trailing comment
+    [Headers] {"X-Admin": "1"}
+}
+
+entry seed {
+    for user in users {
+        POST "{{baseUrl}}/users"
+    }
+}
+"#;
+        let listing = list(input).unwrap();
+
+        expect![[r#"
+            NAME      METHOD  URL                         TAGS           DESCRIPTION
+            getUser   GET     "{{baseUrl}}/users/{{id}}"  @users @smoke  Fetches a single user. Needs the `id` variable.
+            notes     -       -
+            getAdmin  GET     "{{baseUrl}}/users/{{id}}"
+            seed      POST    "{{baseUrl}}/users"
+        "#]]
+        .assert_eq(&listing.to_string());
+    }
+}
@@ -1,20 +1,104 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    io::{Read, Write as _},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{self, AtomicBool, AtomicUsize},
+    },
+    time::{Duration, Instant},
+};
 
+use base64::{Engine, prelude::BASE64_STANDARD};
+use chrono::{SecondsFormat, Utc};
 use indexmap::IndexMap;
+use md5::Md5;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
 use crate::{
-    client::{HttpClient, HttpError, Request, ReqwestHttpClient, Response},
-    diagnostic::Diagnostic,
-    validated::{Entry, Expr, ExprKind, SourceFile, TemplatePart},
-    validator,
-    value::Value,
+    bench::{BenchReport, BenchSettings, Samples},
+    cache::CachingHttpClient,
+    cancel::CancellationToken,
+    client::{
+        Body, CacheStatus, ConnectionOptions, EventSink, HttpClient, HttpError, HttpVersion, Poll,
+        Request, RequestOptions, ReqwestHttpClient, Response,
+    },
+    codes,
+    cookies::{CookieHttpClient, CookieJar},
+    diagnostic::{Diagnostic, Level},
+    history::History,
+    metrics::Metrics,
+    middleware::MiddlewareChain,
+    output_dir::OutputDir,
+    report::{EntryReport, ReportRecorder},
+    retry::RetryingHttpClient,
+    secrets::Secrets,
+    sigv4::{self, SigningParams},
+    span::Span,
+    telemetry::{SpanId, SpanKind, Tracer},
+    validated::{
+        self, Auth, BinOp, Builtin, CompareOp, Const, DictionaryField, Entry, Expr, ExprKind, Hook,
+        Subject, TemplatePart,
+    },
+    validator::{self, OverrideError},
+    value::{NonFiniteFloat, Value},
+    vars::VariableProvider,
 };
 
 #[derive(Debug)]
 pub enum ExecutionError {
     Diagnostic(Diagnostic),
+    /// Problems of the source file, found before anything was sent
+    InvalidSource(Vec<Diagnostic>),
     Runtime(RuntimeError),
     Transport(HttpError),
+    /// Assertions of an entry that didn't hold, in the order they are written
+    AssertionsFailed(Vec<Diagnostic>),
+    /// The errors of all entries that failed, when the run kept going after
+    /// the first one
+    EntriesFailed(Vec<ExecutionError>),
+    Cancelled,
+}
+
+impl std::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::Diagnostic(d) => write_diagnostic(f, d),
+            ExecutionError::InvalidSource(diagnostics) => match diagnostics.as_slice() {
+                [d] => write_diagnostic(f, d),
+                _ => write!(f, "I found {} problems in the file", diagnostics.len()),
+            },
+            ExecutionError::Runtime(e) => write!(f, "{e}"),
+            ExecutionError::Transport(e) => write!(f, "HTTP error: {e}"),
+            ExecutionError::AssertionsFailed(failures) => {
+                write!(f, "{} of the assertions failed", failures.len())
+            }
+            ExecutionError::EntriesFailed(errors) => {
+                write!(f, "{} of the entries failed", errors.len())
+            }
+            ExecutionError::Cancelled => write!(f, "Run cancelled"),
+        }
+    }
+}
+
+fn write_diagnostic(f: &mut std::fmt::Formatter<'_>, d: &Diagnostic) -> std::fmt::Result {
+    match d.labels.first() {
+        Some(label) => write!(f, "{}: {}", d.message, label.message),
+        None => write!(f, "{}", d.message),
+    }
+}
+
+impl From<Vec<Diagnostic>> for ExecutionError {
+    fn from(value: Vec<Diagnostic>) -> Self {
+        ExecutionError::InvalidSource(value)
+    }
 }
 
 impl From<Diagnostic> for ExecutionError {
@@ -23,6 +107,12 @@ impl From<Diagnostic> for ExecutionError {
     }
 }
 
+impl From<NonFiniteFloat> for ExecutionError {
+    fn from(value: NonFiniteFloat) -> Self {
+        ExecutionError::Runtime(RuntimeError::NonFiniteFloat(value))
+    }
+}
+
 impl From<HttpError> for ExecutionError {
     fn from(value: HttpError) -> Self {
         ExecutionError::Transport(value)
@@ -32,94 +122,906 @@ impl From<HttpError> for ExecutionError {
 #[derive(Debug)]
 pub enum RuntimeError {
     EntryNotFound(String),
+    EnvNotFound(String),
+    UnresolvedVariable(String),
+    CookieFile(PathBuf, std::io::Error),
+    CertificateFile(PathBuf, String),
+    InvalidOverride(OverrideError),
+    MissingArgument { entry: String, param: String },
+    UnknownArgument(String),
+    CallFailed { entry: String, called: String },
+    CannotBench { entry: String, reason: String },
+    Middleware { entry: String, message: String },
+    DivisionByZero,
+    IntegerOverflow,
+    NonFiniteFloat(NonFiniteFloat),
 }
 
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            RuntimeError::EntryNotFound(entry) if entry.contains(['*', '?']) => {
+                write!(f, "I couldn't find any entry matching `{entry}`")
+            }
             RuntimeError::EntryNotFound(entry) => {
                 write!(f, "I couldn't find any entry named `{entry}`")
             }
+            RuntimeError::EnvNotFound(env) => {
+                write!(f, "I couldn't find any environment named `{env}`")
+            }
+            RuntimeError::UnresolvedVariable(name) => {
+                write!(f, "I couldn't resolve the value of the variable `{name}`")
+            }
+            RuntimeError::CookieFile(path, e) => {
+                write!(
+                    f,
+                    "I couldn't read the cookie file `{}`: {e}",
+                    path.display()
+                )
+            }
+            RuntimeError::CertificateFile(path, e) => {
+                write!(
+                    f,
+                    "I couldn't read the certificates in `{}`: {e}",
+                    path.display()
+                )
+            }
+            RuntimeError::InvalidOverride(e) => write!(f, "{e}"),
+            RuntimeError::MissingArgument { entry, param } => write!(
+                f,
+                "I couldn't run the entry `{entry}` without a value for `{param}`, pass one with `--arg {param}=...`"
+            ),
+            RuntimeError::UnknownArgument(name) => {
+                write!(
+                    f,
+                    "I couldn't find any entry with a parameter named `{name}`"
+                )
+            }
+            RuntimeError::CallFailed { entry, called } => {
+                write!(
+                    f,
+                    "I couldn't run the entry `{entry}`, because the entry `{called}` it calls failed"
+                )
+            }
+            RuntimeError::CannotBench { entry, reason } => {
+                write!(f, "I couldn't benchmark `{entry}`, {reason}")
+            }
+            RuntimeError::Middleware { entry, message } => {
+                write!(f, "I couldn't send the request of `{entry}`: {message}")
+            }
+            RuntimeError::DivisionByZero => write!(f, "I couldn't divide an integer by zero"),
+            RuntimeError::IntegerOverflow => {
+                write!(
+                    f,
+                    "I couldn't compute a result that doesn't fit into an integer"
+                )
+            }
+            RuntimeError::NonFiniteFloat(e) => write!(f, "I couldn't compute a result, {e}"),
         }
     }
 }
 
+/// Settings that apply to a whole run rather than to a single entry.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub cancellation: CancellationToken,
+    pub idempotency_key: Option<IdempotencyKey>,
+    /// Base URL of an OTLP/HTTP collector that receives a trace of the run
+    pub otlp_endpoint: Option<String>,
+    /// File that is rewritten with Prometheus metrics after every request
+    pub metrics_file: Option<PathBuf>,
+    /// Receives a record of every request that was sent
+    pub history: Option<History>,
+    /// Hooks around every request that is sent. `bench` doesn't run them.
+    pub middleware: MiddlewareChain,
+    /// Receives the values of secret consts as they are evaluated, and
+    /// redacts what the machine prints
+    pub secrets: Secrets,
+    /// Directory of the HTTP cache, caching is disabled without one
+    pub cache_dir: Option<PathBuf>,
+    /// Cookie file to load cookies from before the run
+    pub cookies: Option<PathBuf>,
+    /// Cookie file to save all cookies to after the run. Unless `cookies` is
+    /// given, cookies are also loaded from it when it exists.
+    pub cookie_jar: Option<PathBuf>,
+    /// Values that replace the values of globals, as given with `--set`
+    pub overrides: Vec<(String, String)>,
+    /// Values of entry parameters, as given with `--arg`
+    pub args: Vec<(String, String)>,
+    /// Name of the `env` block whose consts replace top-level consts
+    pub env: Option<String>,
+    /// Directory that `read(...)` resolves relative paths against, usually
+    /// the directory of the source file
+    pub base_dir: Option<PathBuf>,
+    /// Only entries with at least one of these tags run, unless an entry is
+    /// selected by name
+    pub tags: Vec<String>,
+    /// HTTP version of requests whose `[Options]` don't choose one
+    pub http_version: Option<HttpVersion>,
+    /// Pause between requests, unless an entry's `[Options]` choose another
+    pub delay: Option<Duration>,
+    /// Skip verifying TLS certificates
+    pub insecure: bool,
+    /// PEM file with root certificates to trust, e.g. of an internal CA
+    pub ca_file: Option<PathBuf>,
+    /// Addresses to connect to instead of looking up the host, from
+    /// `--resolve`
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// Directory that the response bodies of the chosen entries are written
+    /// to, for `--output-dir`
+    pub output_dir: Option<OutputDir>,
+    /// Print the requests instead of sending them
+    pub dry_run: bool,
+    /// Print the request and response headers of every exchange to stderr
+    pub verbose: bool,
+    /// Receives what happened during the run, for `--report`
+    pub report: Option<ReportRecorder>,
+    /// Only run the entries with `[Assert]` sections, and the ones they need
+    pub tests_only: bool,
+    /// Continue with the next entry when one fails, instead of stopping
+    pub keep_going: bool,
+    /// Receives problems that don't fail the run, like failed soft
+    /// assertions
+    pub warnings: Warnings,
+    /// How many independent entries may run at the same time. Entries that
+    /// capture values, read captured values, need other entries or are
+    /// needed by them run one after another once the others are done.
+    pub jobs: usize,
+}
+
+/// How the `Idempotency-Key` header of mutating requests is generated.
+///
+/// The key is generated once per request, before it is sent, so retries of
+/// the same request carry the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdempotencyKey {
+    /// A random UUID for every request
+    Fresh,
+    /// A UUID derived from the entry name and URL, identical across runs
+    Stable,
+}
+
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Warnings of a run, in the order they happened. Clones share them, so
+/// entries that run at the same time add to the same list.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings(Arc<Mutex<Vec<Diagnostic>>>);
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, warning: Diagnostic) {
+        self.0.lock().unwrap().push(warning);
+    }
+
+    /// Removes and returns the warnings so far.
+    pub fn take(&self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+/// Runs the entries of `input` that match `entry_patterns`, or all of them
+/// if there are none, and returns their responses.
 pub fn execute(
     input: &str,
-    entry_name: Option<String>,
-    external_vars: &HashMap<String, String>,
+    entry_patterns: &[String],
+    external_vars: &(dyn VariableProvider + Sync),
+    options: &RunOptions,
 ) -> Result<Vec<Response>, ExecutionError> {
-    let file = validator::validate(input, external_vars)?;
-    let client = ReqwestHttpClient::new();
-    let mut machine = Machine::new(client);
-    machine.execute(file, entry_name, external_vars)
+    let file = validate(input, external_vars, options)?;
+    let jar_file = match (&options.cookies, &options.cookie_jar) {
+        (Some(path), _) => Some(path),
+        // Continue the session of an earlier run, like a browser profile.
+        (None, Some(path)) if path.exists() => Some(path),
+        _ => None,
+    };
+    let jar = Arc::new(Mutex::new(match jar_file {
+        Some(path) => CookieJar::load(path)
+            .map_err(|e| ExecutionError::Runtime(RuntimeError::CookieFile(path.clone(), e)))?,
+        None => CookieJar::new(),
+    }));
+    let connection = connection_options(options)?;
+    // Every thread gets its own client, but they all share the cookie jar.
+    let new_client = {
+        let jar = Arc::clone(&jar);
+        let cache_dir = options.cache_dir.clone();
+        let cancellation = options.cancellation.clone();
+        move || -> Result<_, ExecutionError> {
+            Ok(CookieHttpClient::new(
+                CachingHttpClient::new(
+                    RetryingHttpClient::new(
+                        ReqwestHttpClient::new(connection.clone())?,
+                        cancellation.clone(),
+                    ),
+                    cache_dir.clone(),
+                ),
+                Arc::clone(&jar),
+            ))
+        }
+    };
+    let result = run(
+        file,
+        entry_patterns,
+        external_vars,
+        options,
+        Arc::new(new_client),
+    );
+    if let Some(path) = &options.cookie_jar
+        && let Err(e) = jar.lock().unwrap().save(path)
+    {
+        tracing::warn!("I couldn't save cookies to `{}`: {e}", path.display());
+    }
+    result
 }
 
-struct Machine<C: HttpClient> {
-    names: HashMap<String, Value>,
-    client: C,
+/// Like [`execute`], but sends the requests with clients of the caller, e.g.
+/// one that answers them without a network. Every thread that runs entries
+/// creates its own client with `new_client`.
+///
+/// The clients are used as they are: cookies, the HTTP cache, retries and
+/// the connection settings of `options` don't apply.
+pub fn execute_with_client<C: HttpClient>(
+    input: &str,
+    entry_patterns: &[String],
+    external_vars: &(dyn VariableProvider + Sync),
+    options: &RunOptions,
+    new_client: impl Fn() -> C + Send + Sync + 'static,
+) -> Result<Vec<Response>, ExecutionError> {
+    let file = validate(input, external_vars, options)?;
+    run(
+        file,
+        entry_patterns,
+        external_vars,
+        options,
+        Arc::new(move || Ok(new_client())),
+    )
+}
+
+fn run<'input, C: HttpClient>(
+    file: validated::SourceFile<'input>,
+    entry_patterns: &[String],
+    external_vars: &(dyn VariableProvider + Sync),
+    options: &RunOptions,
+    new_client: NewClient<C>,
+) -> Result<Vec<Response>, ExecutionError> {
+    let machine = Machine::new(new_client, file.globals, external_vars, options.clone())?;
+    let result = machine.execute(&file.entries, entry_patterns);
+    if let Err(e) = machine.tracer.export() {
+        tracing::warn!("{e}");
+    }
+    result
 }
 
-impl<'input, C: HttpClient> Machine<C> {
-    fn new(client: C) -> Self {
-        Self {
-            names: HashMap::new(),
-            client,
+/// Repeats the request of the entry matching `entry_pattern` as often as
+/// `settings` say and measures how long the requests take. The entries it
+/// needs run once before.
+///
+/// The file is validated and the request is evaluated only once. A fixed
+/// number of threads send it, sharing one client and its connections.
+pub fn bench(
+    input: &str,
+    entry_pattern: &str,
+    external_vars: &(dyn VariableProvider + Sync),
+    options: &RunOptions,
+    settings: BenchSettings,
+) -> Result<BenchReport, ExecutionError> {
+    let file = validate(input, external_vars, options)?;
+    let target = match select_entries(&file.entries, &[entry_pattern.to_string()])
+        .map_err(ExecutionError::Runtime)?[..]
+    {
+        [entry] => entry,
+        ref entries => {
+            return Err(ExecutionError::Runtime(RuntimeError::CannotBench {
+                entry: entry_pattern.to_string(),
+                reason: format!("it matches {} entries instead of one", entries.len()),
+            }));
+        }
+    };
+    let connection = connection_options(options)?;
+    let machine = Machine::new(
+        Arc::new(move || Ok(ReqwestHttpClient::new(connection.clone())?)),
+        file.globals,
+        external_vars,
+        options.clone(),
+    )?;
+    let request = machine.bench_request(&file.entries, target)?;
+
+    let client = &machine.client;
+    let metrics = &machine.metrics;
+    let next = AtomicUsize::new(0);
+    let start = Instant::now();
+    let (samples, end) = std::thread::scope(|scope| {
+        let workers = (0..settings.concurrency.clamp(1, settings.requests.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut samples = Samples::default();
+                    while next.fetch_add(1, atomic::Ordering::Relaxed) < settings.requests
+                        && !options.cancellation.is_cancelled()
+                    {
+                        let start = Instant::now();
+                        let result = client.send(request.clone());
+                        let latency = start.elapsed();
+                        if options.metrics_file.is_some() {
+                            let mut metrics = metrics.lock().unwrap();
+                            match &result {
+                                Ok(response) => metrics.record_response(response.status, latency),
+                                Err(_) => metrics.record_error(),
+                            }
+                        }
+                        samples.record(latency, &result);
+                    }
+                    (samples, Instant::now())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Rewriting the metrics after every request would slow down the
+        // workers, so the file is rewritten a few times per second instead.
+        if let Some(path) = &options.metrics_file {
+            let write_metrics = || {
+                if let Err(e) = metrics.lock().unwrap().write_to(path) {
+                    tracing::warn!("I couldn't write metrics to `{}`: {e}", path.display());
+                }
+            };
+            while !workers.iter().all(|it| it.is_finished()) {
+                std::thread::sleep(Duration::from_millis(250));
+                write_metrics();
+            }
+            write_metrics();
+        }
+
+        let mut samples = Samples::default();
+        let mut end = start;
+        for worker in workers {
+            let (worker_samples, worker_end) = worker.join().unwrap();
+            samples.merge(worker_samples);
+            end = end.max(worker_end);
         }
+        (samples, end)
+    });
+    if options.cancellation.is_cancelled() {
+        return Err(ExecutionError::Cancelled);
+    }
+    Ok(BenchReport::new(samples, end - start))
+}
+
+/// Validates the file and applies the environment and the overrides of
+/// `options`.
+fn validate<'input>(
+    input: &'input str,
+    external_vars: &(dyn VariableProvider + Sync),
+    options: &RunOptions,
+) -> Result<validated::SourceFile<'input>, ExecutionError> {
+    let mut file = validator::validate_in_env(input, external_vars, options.env.as_deref())?;
+    if let Some(env) = &options.env
+        && !file.envs.iter().any(|it| it.text == env)
+    {
+        return Err(ExecutionError::Runtime(RuntimeError::EnvNotFound(
+            env.clone(),
+        )));
+    }
+    for (name, value) in &options.overrides {
+        validator::override_const(&mut file, name, value)
+            .map_err(|e| ExecutionError::Runtime(RuntimeError::InvalidOverride(e)))?;
+    }
+    Ok(file)
+}
+
+fn connection_options(options: &RunOptions) -> Result<ConnectionOptions, ExecutionError> {
+    let connection = ConnectionOptions {
+        insecure: options.insecure,
+        root_certificates: match &options.ca_file {
+            Some(path) => ConnectionOptions::load_certificates(path).map_err(|e| {
+                ExecutionError::Runtime(RuntimeError::CertificateFile(path.clone(), e))
+            })?,
+            None => vec![],
+        },
+        resolve: options.resolve.clone(),
+    };
+    if connection.insecure {
+        tracing::warn!("TLS certificates are not verified, only use `--insecure` for testing");
+    }
+    Ok(connection)
+}
+
+/// Creates the client of a machine, on the thread the machine runs on.
+type NewClient<C> = Arc<dyn Fn() -> Result<C, ExecutionError> + Send + Sync>;
+
+struct Machine<'vars, 'input, C: HttpClient> {
+    globals: IndexMap<&'input str, Const<'input>>,
+    /// Values of globals and external variables, evaluated on first use
+    names: RefCell<HashMap<String, Arc<Value>>>,
+    /// Values picked out of responses by `[Capture]` sections
+    captures: RefCell<HashMap<String, Arc<Value>>>,
+    /// Values of the parameters of the entry that is currently executed
+    locals: RefCell<Vec<(String, Arc<Value>)>>,
+    external_vars: &'vars (dyn VariableProvider + Sync),
+    client: C,
+    new_client: NewClient<C>,
+    options: RunOptions,
+    /// Shared with the machines of other threads, like the metrics
+    tracer: Arc<Tracer>,
+    metrics: Arc<Mutex<Metrics>>,
+    /// Whether a request was sent yet, the first one is never delayed
+    sent_any: Cell<bool>,
+    /// What the entry prints, if it runs at the same time as others.
+    /// Otherwise it is printed right away.
+    output: Option<Arc<Mutex<Vec<Output>>>>,
+}
+
+/// Text that is printed to stdout or stderr.
+enum Output {
+    Stdout(String),
+    Stderr(String),
+}
+
+/// What running an entry on another thread resulted in.
+struct EntryOutcome {
+    result: Result<Vec<Response>, ExecutionError>,
+    /// The part of the report about the entry
+    report: Vec<EntryReport>,
+}
+
+impl<'vars, 'input, C: HttpClient> Machine<'vars, 'input, C> {
+    fn new(
+        new_client: NewClient<C>,
+        globals: IndexMap<&'input str, Const<'input>>,
+        external_vars: &'vars (dyn VariableProvider + Sync),
+        options: RunOptions,
+    ) -> Result<Self, ExecutionError> {
+        Ok(Self {
+            globals,
+            names: RefCell::new(HashMap::new()),
+            captures: RefCell::new(HashMap::new()),
+            locals: RefCell::new(vec![]),
+            external_vars,
+            client: new_client()?,
+            new_client,
+            tracer: Arc::new(Tracer::new(options.otlp_endpoint.clone())),
+            metrics: Arc::new(Mutex::new(Metrics::new())),
+            sent_any: Cell::new(false),
+            output: None,
+            options,
+        })
     }
 
     fn execute(
-        &mut self,
-        source_file: SourceFile<'input>,
-        entry_name: Option<String>,
-        external_vars: &HashMap<String, String>,
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        entry_patterns: &[String],
     ) -> Result<Vec<Response>, ExecutionError> {
-        for (name, value) in external_vars {
-            self.names
-                .insert(name.clone(), Value::String(value.clone()));
+        let mut targets =
+            select_entries(entries, entry_patterns).map_err(ExecutionError::Runtime)?;
+        if entry_patterns.is_empty() {
+            // Hooks only run around the other entries.
+            targets.retain(|entry| {
+                entry.hook().is_none()
+                    && (self.options.tags.is_empty()
+                        || entry
+                            .tags
+                            .iter()
+                            .any(|tag| self.options.tags.iter().any(|it| it == tag.text)))
+            });
         }
+        if self.options.tests_only {
+            targets.retain(|entry| !entry.assertions.is_empty());
+        }
+        let order = run_order_with_hooks(entries, targets.clone());
 
-        for konst in source_file.globals.values() {
-            let value = self.eval_expr(&konst.expr)?;
-            self.names.insert(konst.name.text.to_string(), value);
+        for (name, _) in &self.options.args {
+            let known = order
+                .iter()
+                .any(|entry| entry.params.iter().any(|param| param.text == name));
+            if !known {
+                return Err(ExecutionError::Runtime(RuntimeError::UnknownArgument(
+                    name.clone(),
+                )));
+            }
         }
 
-        match entry_name {
-            Some(name) => {
-                let entry = source_file
-                    .entries
-                    .get(name.as_str())
-                    .ok_or(ExecutionError::Runtime(RuntimeError::EntryNotFound(name)))?;
+        let selected = targets
+            .iter()
+            .map(|it| it.name.text)
+            .collect::<HashSet<_>>();
+        // Independent entries run first, the order of the results stays the
+        // same. Hooks have to run around the other entries, so with hooks
+        // entries run one after another.
+        let hooked = order.iter().any(|it| it.hook().is_some());
+        let mut outcomes = if self.options.jobs > 1 && !hooked {
+            self.execute_parallel(entries, &independent_entries(&order), &selected)
+        } else {
+            HashMap::new()
+        };
 
-                if let Some(response) = self.execute_entry(entry)? {
-                    Ok(vec![response])
-                } else {
-                    Ok(vec![])
+        // Entries whose responses weren't all successful, and the entries
+        // skipped because of them
+        let mut failed = HashSet::new();
+        let mut errors = vec![];
+        let mut responses = vec![];
+        for entry in order {
+            let selected = selected.contains(entry.name.text);
+            if let Some(need) = entry.needs.iter().find(|it| failed.contains(it.text)) {
+                tracing::warn!(
+                    "Skipping `{}` because `{}` failed",
+                    entry.name.text,
+                    need.text
+                );
+                failed.insert(entry.name.text);
+                if let Some(report) = &self.options.report {
+                    report.skip_entry(entry.name.text, selected, need.text);
+                }
+                continue;
+            }
+
+            let result = match outcomes.remove(entry.name.text) {
+                Some(outcome) => {
+                    if let Some(report) = &self.options.report {
+                        report.append_entries(outcome.report);
+                    }
+                    outcome.result
                 }
+                None => self.execute_reported(entries, entry, selected),
+            };
+            let entry_responses = match result {
+                Ok(entry_responses) => entry_responses,
+                Err(e) => {
+                    if !self.options.keep_going || matches!(e, ExecutionError::Cancelled) {
+                        return Err(e);
+                    }
+                    errors.push(e);
+                    failed.insert(entry.name.text);
+                    continue;
+                }
+            };
+            if !entry_responses.iter().all(|it| it.status.is_success()) {
+                failed.insert(entry.name.text);
             }
-            None => {
-                let mut responses = vec![];
-                for entry in source_file.entries.values() {
-                    if let Some(response) = self.execute_entry(entry)? {
-                        responses.push(response);
+            // Only the responses of the chosen entries are shown, not the
+            // ones of the entries they need.
+            if selected {
+                if let Some(output_dir) = &self.options.output_dir {
+                    for response in &entry_responses {
+                        match output_dir.write(entry.name.text, response) {
+                            Ok(path) => tracing::info!("Wrote `{}`", path.display()),
+                            Err(e) => tracing::warn!(
+                                "I couldn't write the response of `{}`: {e}",
+                                entry.name.text
+                            ),
+                        }
                     }
                 }
+                responses.extend(entry_responses);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(responses)
+        } else {
+            Err(ExecutionError::EntriesFailed(errors))
+        }
+    }
+
+    /// Executes the entries on up to `jobs` threads, each with its own
+    /// machine. The output of an entry is printed at once when it is done.
+    fn execute_parallel(
+        &self,
+        all_entries: &IndexMap<&'input str, Entry<'input>>,
+        entries: &[&Entry<'input>],
+        selected: &HashSet<&str>,
+    ) -> HashMap<&'input str, EntryOutcome> {
+        // Globals are evaluated once for all threads, so e.g. `uuid()` has
+        // the same value in every entry.
+        for entry in entries {
+            for expr in entry_exprs(entry) {
+                visit_exprs(expr, &mut |expr| {
+                    if let ExprKind::NameRef(name) = &expr.kind {
+                        // Errors are reported when an entry needs the value.
+                        _ = self.lookup(name);
+                    }
+                });
+            }
+        }
+        // The values are shared with the threads, not copied.
+        let names = self.names.borrow().clone();
+
+        let next = AtomicUsize::new(0);
+        // Set when an entry failed and the run stops at the first failure
+        let stop = AtomicBool::new(false);
+        let outcomes = Mutex::new(HashMap::new());
+        let (globals, external_vars, options) = (&self.globals, self.external_vars, &self.options);
+        let (new_client, tracer, metrics) = (&self.new_client, &self.tracer, &self.metrics);
+        std::thread::scope(|scope| {
+            for _ in 0..self.options.jobs.min(entries.len()) {
+                scope.spawn(|| {
+                    let client = match new_client() {
+                        Ok(client) => client,
+                        Err(e) => {
+                            // Fails the first entry this thread would have run.
+                            stop.store(true, atomic::Ordering::Relaxed);
+                            if let Some(entry) =
+                                entries.get(next.fetch_add(1, atomic::Ordering::Relaxed))
+                            {
+                                let outcome = EntryOutcome {
+                                    result: Err(e),
+                                    report: vec![],
+                                };
+                                outcomes.lock().unwrap().insert(entry.name.text, outcome);
+                            }
+                            return;
+                        }
+                    };
+                    let machine = Machine {
+                        globals: globals.clone(),
+                        names: RefCell::new(names.clone()),
+                        captures: RefCell::new(HashMap::new()),
+                        locals: RefCell::new(vec![]),
+                        external_vars,
+                        client,
+                        new_client: Arc::clone(new_client),
+                        options: RunOptions {
+                            report: options.report.as_ref().map(|_| ReportRecorder::new()),
+                            ..options.clone()
+                        },
+                        tracer: Arc::clone(tracer),
+                        metrics: Arc::clone(metrics),
+                        sent_any: Cell::new(false),
+                        output: Some(Arc::new(Mutex::new(vec![]))),
+                    };
 
-                Ok(responses)
+                    while !stop.load(atomic::Ordering::Relaxed) {
+                        let Some(entry) = entries.get(next.fetch_add(1, atomic::Ordering::Relaxed))
+                        else {
+                            break;
+                        };
+                        let result = machine.execute_reported(
+                            all_entries,
+                            entry,
+                            selected.contains(entry.name.text),
+                        );
+                        if let Some(output) = &machine.output {
+                            print_outputs(std::mem::take(&mut *output.lock().unwrap()));
+                        }
+                        let report = match &machine.options.report {
+                            Some(report) => report.take_entries(),
+                            None => vec![],
+                        };
+                        if result.is_err()
+                            && (!options.keep_going
+                                || matches!(result, Err(ExecutionError::Cancelled)))
+                        {
+                            stop.store(true, atomic::Ordering::Relaxed);
+                        }
+                        outcomes
+                            .lock()
+                            .unwrap()
+                            .insert(entry.name.text, EntryOutcome { result, report });
+                    }
+                });
             }
+        });
+        outcomes.into_inner().unwrap()
+    }
+
+    /// Executes the entry and records it in the report.
+    fn execute_reported(
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        entry: &Entry<'input>,
+        selected: bool,
+    ) -> Result<Vec<Response>, ExecutionError> {
+        if let Some(report) = &self.options.report {
+            report.start_entry(entry.name.text, selected);
+        }
+        let result = self.execute_entry(entries, entry, None);
+        if let (Err(e), Some(report)) = (&result, &self.options.report) {
+            report.fail_entry(e.to_string());
+        }
+        result
+    }
+
+    /// Prints the text now, or when the entry is done if it runs at the
+    /// same time as others.
+    fn print(&self, output: Output) {
+        print_redacted(&self.options.secrets, self.output.as_deref(), output);
+    }
+
+    /// Prints the events of a streamed response while they arrive, like
+    /// other output.
+    fn event_sink(&self) -> EventSink {
+        let secrets = self.options.secrets.clone();
+        let buffer = self.output.clone();
+        EventSink::new(move |event| {
+            print_redacted(
+                &secrets,
+                buffer.as_deref(),
+                Output::Stdout(format!("{event}\n")),
+            );
+        })
+    }
+
+    /// Executes the entry with `args` as its parameters, or with the values
+    /// given with `--arg` without them.
+    fn execute_entry(
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        entry: &Entry<'input>,
+        args: Option<Vec<Arc<Value>>>,
+    ) -> Result<Vec<Response>, ExecutionError> {
+        let span = self.tracer.start_span(
+            format!("entry {}", entry.name.text),
+            SpanKind::Internal,
+            None,
+        );
+        self.tracer
+            .set_attribute(span, "aurora.entry.name", entry.name.text);
+        let result = self.execute_entry_in_span(entries, entry, args, span);
+        let failed = !matches!(
+            &result,
+            Ok(responses) if !responses.is_empty() && responses.iter().all(|it| it.status.is_success())
+        );
+        self.tracer.end_span(span, failed);
+        result
+    }
+
+    /// Binds the parameters of `entry` to `args`, or to the values given
+    /// with `--arg` without them.
+    fn bind_params(
+        &self,
+        entry: &Entry<'input>,
+        args: Option<Vec<Arc<Value>>>,
+    ) -> Result<(), ExecutionError> {
+        if let Some(args) = args {
+            // The validator checks that there is an argument per parameter.
+            *self.locals.borrow_mut() = entry
+                .params
+                .iter()
+                .map(|it| it.text.to_string())
+                .zip(args)
+                .collect();
+            return Ok(());
+        }
+        let mut locals = Vec::with_capacity(entry.params.len());
+        for param in &entry.params {
+            let (_, value) = self
+                .options
+                .args
+                .iter()
+                .find(|(name, _)| name == param.text)
+                .ok_or_else(|| {
+                    ExecutionError::Runtime(RuntimeError::MissingArgument {
+                        entry: entry.name.text.to_string(),
+                        param: param.text.to_string(),
+                    })
+                })?;
+            locals.push((
+                param.text.to_string(),
+                Arc::new(Value::String(value.clone())),
+            ));
         }
+        *self.locals.borrow_mut() = locals;
+        Ok(())
     }
 
-    fn execute_entry(&self, entry: &Entry<'input>) -> Result<Option<Response>, ExecutionError> {
+    fn execute_entry_in_span(
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        entry: &Entry<'input>,
+        args: Option<Vec<Arc<Value>>>,
+        entry_span: SpanId,
+    ) -> Result<Vec<Response>, ExecutionError> {
+        self.bind_params(entry, args)?;
+        self.execute_calls(entries, entry)?;
+
         let Some(request) = &entry.request else {
-            println!(
+            tracing::info!(
                 "I could not find any request in entry `{}`. Skipping...",
                 entry.name.text
             );
-            return Ok(None);
+            return Ok(vec![]);
+        };
+
+        let Some(for_each) = &entry.for_each else {
+            return Ok(self
+                .send_request(entry, request, entry_span)?
+                .into_iter()
+                .collect());
+        };
+
+        let items = self.eval_expr(&for_each.items)?;
+        let Value::Array(items) = &*items else {
+            unreachable!("the validator only accepts arrays in `for`");
+        };
+        let mut responses = Vec::with_capacity(items.len());
+        for item in items {
+            self.locals
+                .borrow_mut()
+                .push((for_each.name.text.to_string(), Arc::clone(item)));
+            let result = self.send_request(entry, request, entry_span);
+            self.locals.borrow_mut().pop();
+            responses.extend(result?);
+        }
+        Ok(responses)
+    }
+
+    /// Runs the entries that `entry` calls, each with its own locals. The
+    /// call fails unless all of its responses are successful.
+    fn execute_calls(
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        entry: &Entry<'input>,
+    ) -> Result<(), ExecutionError> {
+        for call in &entry.calls {
+            let args = call
+                .args
+                .iter()
+                .map(|it| self.eval_expr(it))
+                .collect::<Result<Vec<_>, _>>()?;
+            let locals = self.locals.take();
+            let result = self.execute_entry(entries, &entries[call.entry.text], Some(args));
+            *self.locals.borrow_mut() = locals;
+            if !result?.iter().all(|it| it.status.is_success()) {
+                return Err(ExecutionError::Runtime(RuntimeError::CallFailed {
+                    entry: entry.name.text.to_string(),
+                    called: call.entry.text.to_string(),
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the entries that `target` needs and evaluates its request, for
+    /// `bench`.
+    fn bench_request(
+        &self,
+        entries: &IndexMap<&'input str, Entry<'input>>,
+        target: &Entry<'input>,
+    ) -> Result<Request, ExecutionError> {
+        let cannot_bench = |reason: &str| {
+            ExecutionError::Runtime(RuntimeError::CannotBench {
+                entry: target.name.text.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        let order = run_order(entries, vec![target]);
+        for entry in &order[..order.len() - 1] {
+            self.execute_entry(entries, entry, None)?;
+        }
+
+        self.bind_params(target, None)?;
+        self.execute_calls(entries, target)?;
+        let Some(request) = &target.request else {
+            return Err(cannot_bench("it has no request"));
         };
+        if target.for_each.is_some() {
+            return Err(cannot_bench("it sends a request for every item of a list"));
+        }
+        let (mut request, signing) = self.build_request(target, request)?;
+        if request.body.as_ref().is_some_and(|it| !it.is_replayable()) {
+            return Err(cannot_bench("standard input can only be sent once"));
+        }
+        if let Some(params) = &signing {
+            sigv4::sign(&mut request, params, Utc::now())?;
+        }
+        Ok(request)
+    }
 
-        let url = self.eval_expr(&request.url)?;
+    /// Evaluates the request of `entry`. It still has to be signed with the
+    /// returned parameters before it is sent, if there are any.
+    fn build_request(
+        &self,
+        entry: &Entry<'input>,
+        request: &validated::Request,
+    ) -> Result<(Request, Option<SigningParams>), ExecutionError> {
+        let mut url = self.eval_url(&request.url)?;
+        if let Some(expr) = &entry.base_url {
+            url = with_base_url(self.eval_expr(expr)?.string(), &url);
+        }
+        if let Some(expr) = &entry.query {
+            self.append_query(&mut url, expr)?;
+        }
 
         let mut headers = vec![];
         if let Some(expr) = &entry.headers {
@@ -129,62 +1031,2238 @@ impl<'input, C: HttpClient> Machine<C> {
             }
         }
 
-        let body = if let Some(expr) = &entry.body {
-            Some(self.eval_expr(expr)?.to_json().to_string())
-        } else {
-            None
+        if let Some(expr) = &entry.cookies {
+            let value = self.eval_expr(expr)?;
+            let cookies = value
+                .dictionary()
+                .iter()
+                .map(|(k, v)| format!("{k}={}", v.string()))
+                .collect::<Vec<_>>()
+                .join("; ");
+            match headers
+                .iter_mut()
+                .find(|(name, _)| name.eq_ignore_ascii_case("Cookie"))
+            {
+                _ if cookies.is_empty() => {}
+                Some((_, value)) => {
+                    value.push_str("; ");
+                    value.push_str(&cookies);
+                }
+                None => headers.push(("Cookie".to_string(), cookies)),
+            }
+        }
+
+        let body = match &entry.body {
+            // Files are streamed while the request is sent instead of being
+            // read into memory.
+            Some(Expr {
+                kind: ExprKind::Call(Builtin::Read, args),
+                span,
+                ..
+            }) => Some(match self.eval_path(&args[0])? {
+                None => Body::Stdin,
+                Some(path) => match std::fs::metadata(&path) {
+                    Ok(metadata) if metadata.is_file() => Body::File(path),
+                    result => {
+                        let reason = match result {
+                            Ok(_) => "it is not a file".to_string(),
+                            Err(e) => e.to_string(),
+                        };
+                        return Err(unreadable_file(&path, &reason, *span));
+                    }
+                },
+            }),
+            Some(expr) => {
+                let value = self.eval_expr(expr)?;
+                Some(Body::Text(match &*value {
+                    Value::String(s) => s.clone(),
+                    _ => value.to_json()?.to_string(),
+                }))
+            }
+            None => None,
+        };
+
+        let has_authorization = headers
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case("Authorization"));
+        if let Some(Auth::Bearer(token)) = &entry.auth
+            && !has_authorization
+        {
+            let token = self.eval_expr(token)?;
+            headers.push(("Authorization".to_string(), format!("Bearer {token}")));
+        }
+        let signing = match &entry.auth {
+            Some(Auth::AwsSigV4(aws)) if !has_authorization => Some(SigningParams {
+                region: self.eval_expr(&aws.region)?.string().to_string(),
+                service: self.eval_expr(&aws.service)?.string().to_string(),
+                access_key: self.eval_expr(&aws.access_key)?.string().to_string(),
+                secret_key: self.eval_expr(&aws.secret_key)?.string().to_string(),
+                session_token: match &aws.session_token {
+                    Some(token) => Some(self.eval_expr(token)?.string().to_string()),
+                    None => None,
+                },
+            }),
+            _ => None,
         };
 
+        if let Some(mode) = self.options.idempotency_key
+            && request.method.is_mutating()
+            && !headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(IDEMPOTENCY_KEY_HEADER))
+        {
+            let key = match mode {
+                IdempotencyKey::Fresh => Uuid::new_v4(),
+                IdempotencyKey::Stable => {
+                    let seed = format!("{} {}", entry.name.text, url);
+                    Uuid::new_v5(&Uuid::NAMESPACE_URL, seed.as_bytes())
+                }
+            };
+            headers.push((IDEMPOTENCY_KEY_HEADER.to_string(), key.to_string()));
+        }
+
         let request = Request {
             method: request.method,
-            url: url.string().to_string(),
+            url,
             headers,
             body,
+            options: self.eval_request_options(entry.options.as_ref())?,
+            events: Some(self.event_sink()),
         };
+        Ok((request, signing))
+    }
+
+    fn send_request(
+        &self,
+        entry: &Entry<'input>,
+        request: &validated::Request,
+        entry_span: SpanId,
+    ) -> Result<Option<Response>, ExecutionError> {
+        let (mut request, signing) = self.build_request(entry, request)?;
+        // Standard input can only be sent once.
+        let poll = request
+            .options
+            .poll
+            .filter(|_| request.body.as_ref().is_none_or(|it| it.is_replayable()))
+            .unwrap_or(Poll {
+                interval: Duration::ZERO,
+                attempts: 1,
+            });
+
+        if self.options.dry_run {
+            if let Some(params) = &signing {
+                sigv4::sign(&mut request, params, Utc::now())?;
+            }
+            self.print(Output::Stdout(format!(
+                "# {}\n{request}\n",
+                entry.name.text
+            )));
+            return Ok(None);
+        }
+
+        let mut attempt = 1;
+        let response = loop {
+            let response = self.send(entry, request.clone(), signing.as_ref(), entry_span)?;
+            // Only the warnings of the last attempt are kept.
+            let mut warnings = vec![];
+            let result = self.check_assertions(entry, &response, &mut warnings);
+            if result.is_ok() || attempt == poll.attempts {
+                warnings
+                    .into_iter()
+                    .for_each(|it| self.options.warnings.push(it));
+            }
+            match result {
+                Ok(()) => break response,
+                Err(ExecutionError::AssertionsFailed(failures)) if attempt < poll.attempts => {
+                    tracing::info!(
+                        "Attempt {attempt}/{} of `{}`: {} of {} assertions failed, trying again in {}ms",
+                        poll.attempts,
+                        entry.name.text,
+                        failures.len(),
+                        entry.assertions.len(),
+                        poll.interval.as_millis()
+                    );
+                    if !self.options.cancellation.sleep(poll.interval) {
+                        return Err(ExecutionError::Cancelled);
+                    }
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        if attempt > 1 {
+            tracing::info!(
+                "Attempt {attempt}/{} of `{}`: all assertions hold",
+                poll.attempts,
+                entry.name.text
+            );
+        }
 
-        let response = self.client.send(request)?;
+        if response.status.is_success() && !entry.captures.is_empty() {
+            self.capture(entry, &response)?;
+        }
         Ok(Some(response))
     }
 
-    fn eval_expr(&self, expr: &Expr) -> Result<Value, ExecutionError> {
-        match &expr.kind {
-            ExprKind::StringLiteral(parts) => {
-                let mut out = String::new();
-                for part in parts {
-                    match part {
-                        TemplatePart::Literal(s) => {
-                            out.push_str(s);
-                        }
-                        TemplatePart::Expr(expr) => {
-                            let value = self.eval_expr(expr)?;
-                            out.push_str(&value.to_string());
-                        }
+    /// Sends one attempt of a request, after the delay between requests.
+    fn send(
+        &self,
+        entry: &Entry<'input>,
+        mut request: Request,
+        signing: Option<&SigningParams>,
+        entry_span: SpanId,
+    ) -> Result<Response, ExecutionError> {
+        let delay = request.options.delay;
+        if self.sent_any.get() && !delay.is_zero() {
+            tracing::debug!(
+                entry = entry.name.text,
+                delay_ms = delay.as_millis() as u64,
+                "waiting before the next request"
+            );
+            if !self.options.cancellation.sleep(delay) {
+                return Err(ExecutionError::Cancelled);
+            }
+        }
+        if self.options.cancellation.is_cancelled() {
+            return Err(ExecutionError::Cancelled);
+        }
+        self.sent_any.set(true);
+
+        let span =
+            self.tracer
+                .start_span(request.method.as_str(), SpanKind::Client, Some(entry_span));
+        self.tracer
+            .set_attribute(span, "http.request.method", request.method.as_str());
+        self.tracer
+            .set_attribute(span, "url.full", request.url.as_str());
+        if self.tracer.is_enabled()
+            && !request
+                .headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("traceparent"))
+        {
+            request
+                .headers
+                .push(("traceparent".to_string(), self.tracer.traceparent(span)));
+        }
+
+        self.options
+            .middleware
+            .before_request(entry.name.text, &mut request)
+            .map_err(|message| {
+                self.tracer.end_span(span, true);
+                ExecutionError::Runtime(RuntimeError::Middleware {
+                    entry: entry.name.text.to_string(),
+                    message,
+                })
+            })?;
+
+        // Signing comes last, so the signature covers every other header.
+        if let Some(params) = signing {
+            sigv4::sign(&mut request, params, Utc::now())?;
+        }
+
+        tracing::debug!(
+            entry = entry.name.text,
+            method = request.method.as_str(),
+            url = request.url,
+            "sending request"
+        );
+        for (name, value) in &request.headers {
+            tracing::trace!("{name}: {value}");
+        }
+        if self.options.verbose {
+            self.print(Output::Stderr(verbose_request(&request)));
+        }
+        if let Some(report) = &self.options.report {
+            report.request(&request);
+        }
+
+        let recorded = self
+            .options
+            .history
+            .as_ref()
+            .map(|_| (request.method, request.url.clone(), Utc::now()));
+        // The hooks get to see the request after it was sent.
+        let sent = (!self.options.middleware.is_empty()).then(|| request.clone());
+        let start = Instant::now();
+        let mut result = self.client.send(request);
+        if let Some(request) = &sent {
+            self.options
+                .middleware
+                .after_response(entry.name.text, request, &mut result);
+        }
+        self.record_metrics(&result);
+        if let (Some(history), Some((method, url, time))) = (&self.options.history, recorded)
+            && let Err(e) = history.record(
+                entry.name.text,
+                (method, &url),
+                &result,
+                time,
+                start.elapsed(),
+            )
+        {
+            tracing::warn!(
+                "I couldn't write to the history `{}`: {e}",
+                history.path().display()
+            );
+        }
+        if let Some(report) = &self.options.report {
+            report.response(&result);
+        }
+        match &result {
+            Ok(response) => {
+                for redirect in &response.redirects {
+                    tracing::debug!(
+                        entry = entry.name.text,
+                        status = redirect.status.as_u16(),
+                        location = redirect.location,
+                        "followed redirect"
+                    );
+                }
+                tracing::debug!(
+                    entry = entry.name.text,
+                    status = response.status.as_u16(),
+                    elapsed_ms = response.elapsed.as_millis() as u64,
+                    dns_ms = response.timings.dns.map(|it| it.as_millis() as u64),
+                    connect_ms = response.timings.connect.map(|it| it.as_millis() as u64),
+                    ttfb_ms = response.timings.ttfb.as_millis() as u64,
+                    bytes = response.body.len(),
+                    "received response"
+                );
+                for (name, value) in &response.headers {
+                    tracing::trace!("{name}: {value}");
+                }
+                if self.options.verbose {
+                    self.print(Output::Stderr(verbose_response(response)));
+                }
+                match response.cache {
+                    CacheStatus::Network => {}
+                    CacheStatus::Hit => {
+                        tracing::info!("`{}` was served from the cache", entry.name.text)
                     }
+                    CacheStatus::Revalidated => tracing::info!(
+                        "`{}` was served from the cache (revalidated)",
+                        entry.name.text
+                    ),
                 }
+                self.tracer.set_attribute(
+                    span,
+                    "http.response.status_code",
+                    i64::from(response.status.as_u16()),
+                );
+                self.tracer.end_span(span, !response.status.is_success());
+            }
+            Err(e) => {
+                self.tracer.set_attribute(span, "error.type", e.to_string());
+                self.tracer.end_span(span, true);
+            }
+        }
+
+        Ok(result?)
+    }
 
-                Ok(Value::String(out))
+    /// Stores the values that the `[Capture]` section of `entry` picks out
+    /// of its response.
+    fn capture(&self, entry: &Entry<'input>, response: &Response) -> Result<(), ExecutionError> {
+        let body = serde_json::from_slice::<serde_json::Value>(&response.body);
+        for capture in &entry.captures {
+            let value = match &body {
+                Ok(body) => capture.path.first(body),
+                Err(_) => None,
+            };
+            let Some(value) = value else {
+                let reason = match &body {
+                    Ok(_) => format!("the response contains nothing at `{}`", capture.path),
+                    Err(_) => "the response is not JSON".to_string(),
+                };
+                return Err(ExecutionError::Diagnostic(
+                    Diagnostic::error("Missing capture", capture.span)
+                        .code(codes::INVALID_CAPTURE)
+                        .primary_label(
+                            format!("I couldn't capture `{}`, {reason}", capture.name),
+                            Level::Error,
+                        ),
+                ));
+            };
+            // Captures are strings, other JSON values are kept as JSON text.
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            tracing::debug!(
+                entry = entry.name.text,
+                name = capture.name,
+                value,
+                "captured value"
+            );
+            if let Some(report) = &self.options.report {
+                report.capture(&capture.name, &value);
             }
-            ExprKind::IntegerLiteral(i) => Ok(Value::Integer(*i)),
-            ExprKind::FloatLiteral(f) => Ok(Value::Float(*f)),
-            ExprKind::NullLiteral => Ok(Value::Null),
-            ExprKind::Dictionary(fields) => {
-                let mut map = IndexMap::with_capacity(fields.len());
-                for field in fields {
-                    let key = self.eval_expr(&field.key)?.string().to_owned();
-                    let value = self.eval_expr(&field.value)?;
-                    map.insert(key, value);
+            self.captures
+                .borrow_mut()
+                .insert(capture.name.clone(), Arc::new(Value::String(value)));
+        }
+        Ok(())
+    }
+
+    /// Checks every assertion of `entry` against its response and reports
+    /// all that don't hold. Failed soft assertions are added to `warnings`.
+    fn check_assertions(
+        &self,
+        entry: &Entry<'input>,
+        response: &Response,
+        warnings: &mut Vec<Diagnostic>,
+    ) -> Result<(), ExecutionError> {
+        if entry.assertions.is_empty() {
+            return Ok(());
+        }
+        let body = serde_json::from_slice::<serde_json::Value>(&response.body);
+        let mut failures = vec![];
+        for assertion in &entry.assertions {
+            let (actual, description) = match &assertion.subject {
+                Subject::Status => {
+                    let status = response.status.as_u16();
+                    (
+                        Some(serde_json::Value::from(status)),
+                        format!("`status` is {status}"),
+                    )
+                }
+                Subject::Duration => {
+                    let millis = response.elapsed.as_millis() as u64;
+                    (
+                        Some(serde_json::Value::from(millis)),
+                        format!("`duration` is {millis}ms"),
+                    )
                 }
-                Ok(Value::Dictionary(map))
+                Subject::Ttfb => {
+                    let millis = response.timings.ttfb.as_millis() as u64;
+                    (
+                        Some(serde_json::Value::from(millis)),
+                        format!("`ttfb` is {millis}ms"),
+                    )
+                }
+                Subject::Size => {
+                    let size = response.body.len();
+                    (
+                        Some(serde_json::Value::from(size)),
+                        format!("`size` is {size} bytes"),
+                    )
+                }
+                Subject::Header(name) => {
+                    let name = self.eval_expr(name)?;
+                    let values = response
+                        .headers
+                        .iter()
+                        .filter(|(it, _)| it.eq_ignore_ascii_case(name.string()))
+                        .map(|(_, value)| value.as_str())
+                        .collect::<Vec<_>>();
+                    if values.is_empty() {
+                        (None, format!("the response has no `{name}` header"))
+                    } else {
+                        let value = values.join(", ");
+                        let description = format!("`{name}` is {value:?}");
+                        (Some(serde_json::Value::String(value)), description)
+                    }
+                }
+                Subject::Sha256 | Subject::Md5 => {
+                    let (name, digest) = match assertion.subject {
+                        Subject::Sha256 => {
+                            ("sha256", format!("{:x}", Sha256::digest(&response.body)))
+                        }
+                        _ => ("md5", format!("{:x}", Md5::digest(&response.body))),
+                    };
+                    let description = format!("`{name}` is {digest:?}");
+                    (Some(serde_json::Value::String(digest)), description)
+                }
+                Subject::Body(None) => {
+                    let text = String::from_utf8_lossy(&response.body).into_owned();
+                    let description = format!("the body is {:?}", truncate(&text, 80));
+                    (Some(serde_json::Value::String(text)), description)
+                }
+                Subject::Body(Some(path)) => match &body {
+                    Ok(body) => match path.first(body) {
+                        Some(value) => (Some(value.clone()), format!("`{path}` is {value}")),
+                        None => (None, format!("the response contains nothing at `{path}`")),
+                    },
+                    Err(_) => (None, "the response is not JSON".to_string()),
+                },
+            };
+
+            let expected = self.eval_expr(&assertion.expected)?;
+            let compared = match actual {
+                Some(actual) => match assertion.op {
+                    CompareOp::Matches => {
+                        let regex =
+                            validator::compile_regex(expected.string(), assertion.expected.span)?;
+                        match &actual {
+                            serde_json::Value::String(s) => regex.is_match(s),
+                            // Numbers are matched in the form they are written.
+                            actual => regex.is_match(&actual.to_string()),
+                        }
+                    }
+                    op => compare(op, &actual, &expected.to_json()?),
+                },
+                None => false,
+            };
+            let holds = compared != assertion.negated;
+            tracing::debug!(
+                entry = entry.name.text,
+                assertion = format!("{} {}", assertion.op, expected),
+                holds,
+                "checked assertion"
+            );
+            if let Some(report) = &self.options.report {
+                let subject = match &assertion.subject {
+                    Subject::Status => "status".to_string(),
+                    Subject::Duration => "duration".to_string(),
+                    Subject::Ttfb => "ttfb".to_string(),
+                    Subject::Size => "size".to_string(),
+                    Subject::Sha256 => "sha256".to_string(),
+                    Subject::Md5 => "md5".to_string(),
+                    Subject::Header(name) => format!("header {:?}", self.eval_expr(name)?.string()),
+                    Subject::Body(None) => "body".to_string(),
+                    Subject::Body(Some(path)) => format!("body {:?}", path.to_string()),
+                };
+                let modifiers = match (assertion.soft, assertion.negated) {
+                    (true, true) => "soft not ",
+                    (true, false) => "soft ",
+                    (false, true) => "not ",
+                    (false, false) => "",
+                };
+                // Soft assertions don't fail the tests.
+                report.assertion(
+                    format!(
+                        "{modifiers}{subject} {} {}",
+                        assertion.op,
+                        expected.to_json()?
+                    ),
+                    (!holds && !assertion.soft).then(|| description.clone()),
+                );
             }
-            ExprKind::Array(elems) => {
-                let mut values = Vec::with_capacity(elems.len());
-                for elem in elems {
-                    let value = self.eval_expr(elem)?;
-                    values.push(value);
+            if !holds {
+                let expectation = match assertion.negated {
+                    true => "I was expecting this not to hold",
+                    false => "I was expecting this to hold",
+                };
+                let label = format!("{expectation}, but {description}");
+                if assertion.soft {
+                    warnings.push(
+                        Diagnostic::warning("Failed assertion", assertion.span)
+                            .code(codes::FAILED_ASSERTION)
+                            .primary_label(label, Level::Warning),
+                    );
+                } else {
+                    failures.push(
+                        Diagnostic::error("Failed assertion", assertion.span)
+                            .code(codes::FAILED_ASSERTION)
+                            .primary_label(label, Level::Error),
+                    );
                 }
-                Ok(Value::Array(values))
             }
-            ExprKind::NameRef(name) => Ok(self.names[name].clone()),
         }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ExecutionError::AssertionsFailed(failures))
+        }
+    }
+
+    /// Evaluates the argument of `read()` to a path relative to the source
+    /// file, or `None` for `-`, which stands for standard input.
+    fn eval_path(&self, expr: &Expr) -> Result<Option<PathBuf>, ExecutionError> {
+        let path = self.eval_expr(expr)?;
+        Ok(match (path.string(), &self.options.base_dir) {
+            ("-", _) => None,
+            (path, Some(dir)) => Some(dir.join(path)),
+            (path, None) => Some(PathBuf::from(path)),
+        })
+    }
+
+    /// Combines the `[Options]` of an entry with the defaults of the run.
+    fn eval_request_options(&self, expr: Option<&Expr>) -> Result<RequestOptions, ExecutionError> {
+        let mut options = RequestOptions {
+            http_version: self.options.http_version,
+            delay: self.options.delay.unwrap_or_default(),
+            ..RequestOptions::default()
+        };
+        let Some(expr) = expr else {
+            return Ok(options);
+        };
+
+        let value = self.eval_expr(expr)?;
+        for (name, value) in value.dictionary() {
+            // Durations are given in milliseconds.
+            let count = || match **value {
+                Value::Integer(i) if i >= 0 => Ok(i as u64),
+                _ => Err(ExecutionError::Diagnostic(
+                    Diagnostic::error("Invalid option", expr.span)
+                        .code(codes::INVALID_OPTION)
+                        .primary_label(
+                            format!(
+                                "I was expecting `{name}` not to be negative, but found `{value}`"
+                            ),
+                            Level::Error,
+                        ),
+                )),
+            };
+            match name.as_str() {
+                "timeout" => options.timeout = Some(Duration::from_millis(count()?)),
+                "connect_timeout" => {
+                    options.connect_timeout = Some(Duration::from_millis(count()?))
+                }
+                "retry_backoff" => options.retry_backoff = Duration::from_millis(count()?),
+                "delay" => options.delay = Duration::from_millis(count()?),
+                "max_redirects" => {
+                    options.max_redirects = usize::try_from(count()?).unwrap_or(usize::MAX)
+                }
+                "retries" => options.retries = u32::try_from(count()?).unwrap_or(u32::MAX),
+                "max_events" => {
+                    options.max_events = Some(usize::try_from(count()?).unwrap_or(usize::MAX))
+                }
+                "max_duration" => options.max_duration = Some(Duration::from_millis(count()?)),
+                "follow_redirects" => {
+                    options.follow_redirects = matches!(**value, Value::Bool(true))
+                }
+                "gzip" => options.gzip = matches!(**value, Value::Bool(true)),
+                "decompress" => options.decompress = matches!(**value, Value::Bool(true)),
+                "poll" => {
+                    let mut poll = Poll::default();
+                    for (name, value) in value.dictionary() {
+                        let Value::Integer(i) = &**value else {
+                            unreachable!("the validator only accepts integers in `poll`");
+                        };
+                        let min = if name == "attempts" { 1 } else { 0 };
+                        if *i < min {
+                            return Err(ExecutionError::Diagnostic(
+                                Diagnostic::error("Invalid option", expr.span).code(codes::INVALID_OPTION).primary_label(
+                                    format!(
+                                        "I was expecting `{name}` of `poll` to be at least {min}, but found `{value}`"
+                                    ),
+                                    Level::Error,
+                                ),
+                            ));
+                        }
+                        match name.as_str() {
+                            "interval" => poll.interval = Duration::from_millis(*i as u64),
+                            _ => poll.attempts = u32::try_from(*i).unwrap_or(u32::MAX),
+                        }
+                    }
+                    options.poll = Some(poll);
+                }
+                "http_version" => {
+                    let version = HttpVersion::parse(value.string()).ok_or_else(|| {
+                        ExecutionError::Diagnostic(
+                            Diagnostic::error("Invalid option", expr.span).code(codes::INVALID_OPTION).primary_label(
+                                format!(
+                                    "I only know the HTTP versions `1.1` and `2`, but found `{value}`"
+                                ),
+                                Level::Error,
+                            ),
+                        )
+                    })?;
+                    options.http_version = Some(version);
+                }
+                _ => unreachable!("the validator only accepts known options"),
+            }
+        }
+        Ok(options)
+    }
+
+    fn record_metrics(&self, result: &Result<Response, HttpError>) {
+        let Some(path) = &self.options.metrics_file else {
+            return;
+        };
+
+        let mut metrics = self.metrics.lock().unwrap();
+        match result {
+            Ok(response) => metrics.record_response(response.status, response.elapsed),
+            Err(_) => metrics.record_error(),
+        }
+        if let Err(e) = metrics.write_to(path) {
+            tracing::warn!("I couldn't write metrics to `{}`: {e}", path.display());
+        }
+    }
+
+    fn eval_expr(&self, expr: &Expr) -> Result<Arc<Value>, ExecutionError> {
+        match &expr.kind {
+            ExprKind::StringLiteral(parts) => {
+                let mut out = String::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Literal(s) => {
+                            out.push_str(s);
+                        }
+                        TemplatePart::Expr(expr) => {
+                            let value = self.eval_expr(expr)?;
+                            out.push_str(&value.to_string());
+                        }
+                    }
+                }
+
+                Ok(Arc::new(Value::String(out)))
+            }
+            ExprKind::IntegerLiteral(i) => Ok(Arc::new(Value::Integer(*i))),
+            ExprKind::FloatLiteral(f) => Ok(Arc::new(Value::Float(*f))),
+            ExprKind::NullLiteral => Ok(Arc::new(Value::Null)),
+            ExprKind::BoolLiteral(b) => Ok(Arc::new(Value::Bool(*b))),
+            ExprKind::Dictionary(fields) => {
+                let mut map = IndexMap::with_capacity(fields.len());
+                for field in fields {
+                    match field {
+                        DictionaryField::Pair { key, value } => {
+                            let key = self.eval_expr(key)?.string().to_owned();
+                            let value = self.eval_expr(value)?;
+                            map.shift_remove(&key);
+                            map.insert(key, value);
+                        }
+                        DictionaryField::Spread(expr) => {
+                            let Value::Dictionary(spread) = &*self.eval_expr(expr)? else {
+                                unreachable!("the validator only accepts spreads of dictionaries");
+                            };
+                            for (key, value) in spread {
+                                map.shift_remove(key);
+                                map.insert(key.clone(), Arc::clone(value));
+                            }
+                        }
+                    }
+                }
+                Ok(Arc::new(Value::Dictionary(map)))
+            }
+            ExprKind::Array(elems) => {
+                let mut values = Vec::with_capacity(elems.len());
+                for elem in elems {
+                    let value = self.eval_expr(elem)?;
+                    values.push(value);
+                }
+                Ok(Arc::new(Value::Array(values)))
+            }
+            ExprKind::NameRef(name) => self.lookup(name),
+            ExprKind::Capture(name) => match self.captures.borrow().get(name) {
+                Some(value) => Ok(Arc::clone(value)),
+                // Nothing is captured without responses, so the placeholder
+                // is shown instead.
+                None if self.options.dry_run => {
+                    Ok(Arc::new(Value::String(format!("{{{{{name}}}}}"))))
+                }
+                None => Err(ExecutionError::Diagnostic(
+                    Diagnostic::error("Missing capture", expr.span).code(codes::INVALID_CAPTURE).primary_label(
+                        format!(
+                            "I don't have a value for `{name}` yet, the entry that captures it has to run first"
+                        ),
+                        Level::Error,
+                    ),
+                )),
+            },
+            ExprKind::Local(name) => {
+                let locals = self.locals.borrow();
+                let (_, value) = locals
+                    .iter()
+                    .rev()
+                    .find(|(local, _)| local == name)
+                    .expect("the validator only accepts locals that are in scope");
+                Ok(Arc::clone(value))
+            }
+            ExprKind::Call(Builtin::Raw, args) => {
+                let value = self.eval_expr(&args[0])?;
+                Ok(Arc::new(Value::String(value.to_string())))
+            }
+            ExprKind::Call(Builtin::Uuid, _) => {
+                Ok(Arc::new(Value::String(Uuid::new_v4().to_string())))
+            }
+            ExprKind::Call(Builtin::Now, args) => {
+                let now = Utc::now();
+                let Some(format) = args.first() else {
+                    let now = now.to_rfc3339_opts(SecondsFormat::Secs, true);
+                    return Ok(Arc::new(Value::String(now)));
+                };
+
+                let mut out = String::new();
+                // An invalid format only shows up while formatting.
+                if write!(out, "{}", now.format(self.eval_expr(format)?.string())).is_err() {
+                    return Err(ExecutionError::Diagnostic(
+                        Diagnostic::error("Invalid time format", format.span).code(codes::INVALID_TIME_FORMAT).primary_label(
+                            "I don't understand this `strftime` format",
+                            Level::Error,
+                        ),
+                    ));
+                }
+                Ok(Arc::new(Value::String(out)))
+            }
+            ExprKind::Call(Builtin::Timestamp, _) => {
+                Ok(Arc::new(Value::Integer(Utc::now().timestamp())))
+            }
+            ExprKind::Call(Builtin::Env, args) => {
+                let name = self.eval_expr(&args[0])?;
+                if let Ok(value) = std::env::var(name.string()) {
+                    return Ok(Arc::new(Value::String(value)));
+                }
+                match args.get(1) {
+                    Some(default) => self.eval_expr(default),
+                    None => Err(ExecutionError::Diagnostic(
+                        Diagnostic::error("Unset environment variable", expr.span).code(codes::UNSET_ENV_VAR).primary_label(
+                            format!(
+                                "I couldn't find the environment variable `{}` and there is no default",
+                                name.string()
+                            ),
+                            Level::Error,
+                        ),
+                    )),
+                }
+            }
+            ExprKind::Call(Builtin::Read, args) => {
+                let Some(path) = self.eval_path(&args[0])? else {
+                    let mut contents = String::new();
+                    return match std::io::stdin().read_to_string(&mut contents) {
+                        Ok(_) => Ok(Arc::new(Value::String(contents))),
+                        Err(e) => Err(unreadable_file(
+                            Path::new("standard input"),
+                            &e.to_string(),
+                            expr.span,
+                        )),
+                    };
+                };
+                match std::fs::read_to_string(&path) {
+                    Ok(contents) => Ok(Arc::new(Value::String(contents))),
+                    Err(e) => Err(unreadable_file(&path, &e.to_string(), expr.span)),
+                }
+            }
+            ExprKind::Call(
+                builtin @ (Builtin::Base64 | Builtin::UrlEncode | Builtin::Sha256),
+                args,
+            ) => {
+                let value = self.eval_expr(&args[0])?;
+                Ok(Arc::new(Value::String(encode(*builtin, value.string()))))
+            }
+            ExprKind::Binary(BinOp::Concat, lhs, rhs) => {
+                let lhs = self.eval_expr(lhs)?;
+                let rhs = self.eval_expr(rhs)?;
+                Ok(Arc::new(Value::String(format!(
+                    "{}{}",
+                    lhs.string(),
+                    rhs.string()
+                ))))
+            }
+            ExprKind::If(cond, then, else_) => match *self.eval_expr(cond)? {
+                Value::Bool(true) => self.eval_expr(then),
+                Value::Bool(false) => self.eval_expr(else_),
+                _ => unreachable!("the validator only accepts bools as conditions"),
+            },
+            ExprKind::Field(base, name) => {
+                let base = self.eval_expr(base)?;
+                Ok(Arc::clone(&base.dictionary()[name.as_str()]))
+            }
+            ExprKind::ToFloat(expr) => match *self.eval_expr(expr)? {
+                Value::Integer(i) => Ok(Arc::new(Value::Float(i as f64))),
+                _ => unreachable!("the validator only converts integers to floats"),
+            },
+            ExprKind::Binary(BinOp::Coalesce, lhs, rhs) => {
+                let lhs = self.eval_expr(lhs)?;
+                match *lhs {
+                    Value::Null => self.eval_expr(rhs),
+                    _ => Ok(lhs),
+                }
+            }
+            ExprKind::Binary(op, lhs, rhs) => {
+                let lhs = self.eval_expr(lhs)?;
+                let rhs = self.eval_expr(rhs)?;
+                arithmetic(*op, &lhs, &rhs)
+                    .map(Arc::new)
+                    .map_err(ExecutionError::Runtime)
+            }
+        }
+    }
+
+    /// Evaluates the URL of a request.
+    ///
+    /// Values interpolated after the authority are percent-encoded, unless
+    /// they are wrapped in `raw(...)`. Values in front of it are kept as they
+    /// are, so base URLs and hosts can still be interpolated.
+    fn eval_url(&self, expr: &Expr) -> Result<String, ExecutionError> {
+        let ExprKind::StringLiteral(parts) = &expr.kind else {
+            return Ok(self.eval_expr(expr)?.to_string());
+        };
+
+        let mut url = String::new();
+        for part in parts {
+            match part {
+                TemplatePart::Literal(s) => url.push_str(s),
+                TemplatePart::Expr(expr) => {
+                    let value = self.eval_expr(expr)?.to_string();
+                    if is_raw(expr) || in_authority(&url) {
+                        url.push_str(&value);
+                    } else {
+                        url.extend(utf8_percent_encode(&value, URL_COMPONENT));
+                    }
+                }
+            }
+        }
+
+        Ok(url)
+    }
+
+    fn append_query(&self, url: &mut String, expr: &Expr) -> Result<(), ExecutionError> {
+        let ExprKind::Dictionary(fields) = &expr.kind else {
+            unreachable!("the validator only accepts dictionaries as `[Query]`");
+        };
+
+        // Values and whether they are `raw(...)`, fields replace earlier
+        // fields with the same key like in other dictionaries.
+        let mut params = IndexMap::new();
+        for field in fields {
+            match field {
+                DictionaryField::Pair { key, value } => {
+                    let key = self.eval_expr(key)?.to_string();
+                    let raw = is_raw(value);
+                    params.shift_remove(&key);
+                    params.insert(key, (self.eval_expr(value)?.to_string(), raw));
+                }
+                DictionaryField::Spread(expr) => {
+                    let Value::Dictionary(spread) = &*self.eval_expr(expr)? else {
+                        unreachable!("the validator only accepts spreads of dictionaries");
+                    };
+                    for (key, value) in spread {
+                        params.shift_remove(key);
+                        params.insert(key.clone(), (value.to_string(), false));
+                    }
+                }
+            }
+        }
+
+        for (key, (value, raw)) in params {
+            url.push(if url.contains('?') { '&' } else { '?' });
+            url.extend(utf8_percent_encode(&key, URL_COMPONENT));
+            url.push('=');
+            if raw {
+                url.push_str(&value);
+            } else {
+                url.extend(utf8_percent_encode(&value, URL_COMPONENT));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<Value>, ExecutionError> {
+        if let Some(value) = self.names.borrow().get(name) {
+            return Ok(Arc::clone(value));
+        }
+
+        let value = if let Some(konst) = self.globals.get(name) {
+            let value = self.eval_expr(&konst.expr)?;
+            if konst.secret {
+                self.options.secrets.add_value(&value);
+            }
+            value
+        } else {
+            let value = self.external_vars.resolve(name).ok_or_else(|| {
+                ExecutionError::Runtime(RuntimeError::UnresolvedVariable(name.to_string()))
+            })?;
+            Arc::new(value)
+        };
+
+        self.names
+            .borrow_mut()
+            .insert(name.to_string(), Arc::clone(&value));
+        Ok(value)
+    }
+}
+
+/// Prints the text with the secrets redacted, or adds it to `buffer` to be
+/// printed when the entry is done.
+fn print_redacted(secrets: &Secrets, buffer: Option<&Mutex<Vec<Output>>>, output: Output) {
+    let output = match output {
+        Output::Stdout(text) => Output::Stdout(secrets.redact(&text).into_owned()),
+        Output::Stderr(text) => Output::Stderr(secrets.redact(&text).into_owned()),
+    };
+    match buffer {
+        Some(buffer) => buffer.lock().unwrap().push(output),
+        None => print_outputs(vec![output]),
+    }
+}
+
+/// Prints the texts in order, without the output of other threads in
+/// between.
+fn print_outputs(outputs: Vec<Output>) {
+    let mut stdout = std::io::stdout().lock();
+    let mut stderr = std::io::stderr().lock();
+    for output in outputs {
+        _ = match output {
+            Output::Stdout(text) => stdout.write_all(text.as_bytes()),
+            Output::Stderr(text) => stderr.write_all(text.as_bytes()),
+        };
+    }
+    _ = stdout.flush();
+}
+
+/// Returns the entries of `order` that can run at the same time as any other
+/// entry. They don't need or call other entries and aren't needed by them, and they
+/// neither capture values nor read captured ones.
+fn independent_entries<'a, 'input>(order: &[&'a Entry<'input>]) -> Vec<&'a Entry<'input>> {
+    let needed = order
+        .iter()
+        .flat_map(|entry| entry.needs.iter().map(|it| it.text))
+        .collect::<HashSet<_>>();
+    order
+        .iter()
+        .filter(|entry| {
+            entry.needs.is_empty()
+                && entry.calls.is_empty()
+                && !needed.contains(entry.name.text)
+                && entry.captures.is_empty()
+                && !entry_exprs(entry).iter().any(|expr| reads_captures(expr))
+        })
+        .copied()
+        .collect()
+}
+
+/// Returns `true` if evaluating `expr` reads a captured value. Globals can't
+/// refer to captures, so they don't need to be followed.
+fn reads_captures(expr: &Expr) -> bool {
+    let mut found = false;
+    visit_exprs(expr, &mut |expr| {
+        found |= matches!(expr.kind, ExprKind::Capture(_));
+    });
+    found
+}
+
+/// Returns the expressions of the entry's sections, not the ones nested in
+/// them.
+fn entry_exprs<'a>(entry: &'a Entry<'_>) -> Vec<&'a Expr> {
+    let mut exprs = vec![];
+    exprs.extend(entry.calls.iter().flat_map(|it| &it.args));
+    exprs.extend(entry.for_each.as_ref().map(|it| &it.items));
+    exprs.extend(entry.request.as_ref().map(|it| &it.url));
+    exprs.extend(
+        [
+            &entry.base_url,
+            &entry.query,
+            &entry.headers,
+            &entry.cookies,
+            &entry.body,
+            &entry.options,
+        ]
+        .into_iter()
+        .flatten(),
+    );
+    match &entry.auth {
+        Some(Auth::Bearer(token)) => exprs.push(token),
+        Some(Auth::AwsSigV4(aws)) => {
+            exprs.extend([&aws.region, &aws.service, &aws.access_key, &aws.secret_key]);
+            exprs.extend(&aws.session_token);
+        }
+        None => {}
+    }
+    for assertion in &entry.assertions {
+        if let Subject::Header(name) = &assertion.subject {
+            exprs.push(name);
+        }
+        exprs.push(&assertion.expected);
+    }
+    exprs
+}
+
+/// Calls `f` with `expr` and every expression nested in it.
+fn visit_exprs(expr: &Expr, f: &mut dyn FnMut(&Expr)) {
+    f(expr);
+    match &expr.kind {
+        ExprKind::StringLiteral(parts) => {
+            for part in parts {
+                if let TemplatePart::Expr(expr) = part {
+                    visit_exprs(expr, f);
+                }
+            }
+        }
+        ExprKind::Dictionary(fields) => {
+            for field in fields {
+                match field {
+                    DictionaryField::Pair { key, value } => {
+                        visit_exprs(key, f);
+                        visit_exprs(value, f);
+                    }
+                    DictionaryField::Spread(expr) => visit_exprs(expr, f),
+                }
+            }
+        }
+        ExprKind::Array(items) | ExprKind::Call(_, items) => {
+            for item in items {
+                visit_exprs(item, f);
+            }
+        }
+        ExprKind::Binary(_, lhs, rhs) => {
+            visit_exprs(lhs, f);
+            visit_exprs(rhs, f);
+        }
+        ExprKind::Field(expr, _) | ExprKind::ToFloat(expr) => visit_exprs(expr, f),
+        ExprKind::If(condition, then, otherwise) => {
+            visit_exprs(condition, f);
+            visit_exprs(then, f);
+            visit_exprs(otherwise, f);
+        }
+        ExprKind::NameRef(_)
+        | ExprKind::Local(_)
+        | ExprKind::Capture(_)
+        | ExprKind::IntegerLiteral(_)
+        | ExprKind::FloatLiteral(_)
+        | ExprKind::NullLiteral
+        | ExprKind::BoolLiteral(_) => {}
+    }
+}
+
+/// Applies an arithmetic operator. The validator only lets numbers through.
+fn arithmetic(op: BinOp, lhs: &Value, rhs: &Value) -> Result<Value, RuntimeError> {
+    if let (Value::Integer(a), Value::Integer(b)) = (lhs, rhs) {
+        let result = match op {
+            BinOp::Add => a.checked_add(*b),
+            BinOp::Sub => a.checked_sub(*b),
+            BinOp::Mul => a.checked_mul(*b),
+            BinOp::Div if *b == 0 => return Err(RuntimeError::DivisionByZero),
+            BinOp::Div => a.checked_div(*b),
+            BinOp::Concat | BinOp::Coalesce => unreachable!("`{op:?}` is not arithmetic"),
+        };
+        return result
+            .map(Value::Integer)
+            .ok_or(RuntimeError::IntegerOverflow);
+    }
+
+    let as_float = |value: &Value| match value {
+        Value::Integer(i) => *i as f64,
+        Value::Float(f) => *f,
+        _ => unreachable!("the validator only accepts numbers in arithmetic"),
+    };
+    let (a, b) = (as_float(lhs), as_float(rhs));
+    let result = match op {
+        BinOp::Add => a + b,
+        BinOp::Sub => a - b,
+        BinOp::Mul => a * b,
+        BinOp::Div => a / b,
+        BinOp::Concat | BinOp::Coalesce => unreachable!("`{op:?}` is not arithmetic"),
+    };
+    // JSON has no infinity or NaN, so they can't be sent or compared.
+    if !result.is_finite() {
+        return Err(RuntimeError::NonFiniteFloat(NonFiniteFloat(result)));
+    }
+    Ok(Value::Float(result))
+}
+
+/// Everything except the unreserved characters of RFC 3986.
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Headers whose values are masked in verbose output
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-amz-security-token",
+];
+
+/// Returns the value of a header as it is shown in verbose output. Values
+/// of sensitive headers are masked, except for the scheme of an
+/// `Authorization` header.
+pub fn masked_header_value<'a>(name: &str, value: &'a str) -> Cow<'a, str> {
+    let name = name.to_ascii_lowercase();
+    if !SENSITIVE_HEADERS.contains(&name.as_str()) {
+        return Cow::Borrowed(value);
+    }
+    match value.split_once(' ') {
+        Some((scheme, _)) if name.ends_with("authorization") => Cow::Owned(format!("{scheme} ***")),
+        _ => Cow::Borrowed("***"),
+    }
+}
+
+/// Formats the request line and the headers like `curl -v` does.
+fn verbose_request(request: &Request) -> String {
+    let mut out = format!("> {} {}\n", request.method.as_str(), request.url);
+    for (name, value) in &request.headers {
+        _ = writeln!(out, "> {name}: {}", masked_header_value(name, value));
+    }
+    out.push_str(">\n");
+    out
+}
+
+/// Formats the status line and the headers like `curl -v` does, followed by
+/// the timings of the request.
+fn verbose_response(response: &Response) -> String {
+    let status = response.status;
+    let mut out = format!(
+        "< {} {}\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    for (name, value) in &response.headers {
+        _ = writeln!(out, "< {name}: {}", masked_header_value(name, value));
+    }
+    out.push_str("<\n");
+
+    let millis = |it: Duration| format!("{}ms", it.as_millis());
+    let mut timings = vec![];
+    if let Some(dns) = response.timings.dns {
+        timings.push(format!("dns {}", millis(dns)));
+    }
+    if let Some(connect) = response.timings.connect {
+        timings.push(format!("connect {}", millis(connect)));
+    }
+    timings.push(format!("ttfb {}", millis(response.timings.ttfb)));
+    timings.push(format!("total {}", millis(response.elapsed)));
+    timings.push(format!("{} bytes", response.body.len()));
+    _ = writeln!(out, "* {}", timings.join(", "));
+    out
+}
+
+/// Returns the entries whose names match one of `patterns`, in file order.
+/// A pattern may contain `*` for any number of characters and `?` for a
+/// single one. Without patterns, all entries are returned.
+pub fn select_entries<'a, 'input>(
+    entries: &'a IndexMap<&'input str, Entry<'input>>,
+    patterns: &[String],
+) -> Result<Vec<&'a Entry<'input>>, RuntimeError> {
+    if let Some(pattern) = patterns
+        .iter()
+        .find(|pattern| !entries.keys().any(|name| matches_pattern(pattern, name)))
+    {
+        return Err(RuntimeError::EntryNotFound(pattern.clone()));
+    }
+    Ok(entries
+        .values()
+        .filter(|entry| {
+            patterns.is_empty()
+                || patterns
+                    .iter()
+                    .any(|pattern| matches_pattern(pattern, entry.name.text))
+        })
+        .collect())
+}
+
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was and the position in `name` it has matched up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the `*` match one more character.
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|it| *it == '*')
+}
+
+/// Returns `targets` in order, each preceded by the entries it needs that
+/// haven't been added before. Every entry is returned at most once.
+pub fn run_order<'a, 'input>(
+    entries: &'a IndexMap<&'input str, Entry<'input>>,
+    targets: Vec<&'a Entry<'input>>,
+) -> Vec<&'a Entry<'input>> {
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    for entry in targets {
+        visit_needs(entries, entry, &mut seen, &mut order);
+    }
+    order
+}
+
+/// Like [`run_order`], with the hooks of the file around `targets`.
+/// `@before_each` and `@after_each` entries are added for every target, so
+/// they may be returned more than once. Hooks that are targets themselves
+/// run like other entries.
+pub fn run_order_with_hooks<'a, 'input>(
+    entries: &'a IndexMap<&'input str, Entry<'input>>,
+    targets: Vec<&'a Entry<'input>>,
+) -> Vec<&'a Entry<'input>> {
+    if targets.is_empty() {
+        return vec![];
+    }
+    let hooks = |hook| {
+        entries
+            .values()
+            .filter(move |it| it.hook() == Some(hook))
+            .filter(|it| {
+                !targets
+                    .iter()
+                    .any(|target| target.name.text == it.name.text)
+            })
+            .collect::<Vec<_>>()
+    };
+    let (before_each, after_each) = (hooks(Hook::BeforeEach), hooks(Hook::AfterEach));
+
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    // Hooks that run more than once are added again, with the entries they
+    // need only the first time.
+    let add_again = |hook: &'a Entry<'input>,
+                     seen: &mut HashSet<&'input str>,
+                     order: &mut Vec<&'a Entry<'input>>| {
+        visit_prerequisites(entries, hook, seen, order);
+        seen.insert(hook.name.text);
+        order.push(hook);
+    };
+    for hook in hooks(Hook::BeforeAll) {
+        visit_needs(entries, hook, &mut seen, &mut order);
+    }
+    for target in &targets {
+        // Entries that another target needed have already run.
+        if seen.contains(target.name.text) {
+            continue;
+        }
+        for hook in &before_each {
+            add_again(hook, &mut seen, &mut order);
+        }
+        visit_needs(entries, target, &mut seen, &mut order);
+        for hook in &after_each {
+            add_again(hook, &mut seen, &mut order);
+        }
+    }
+    for hook in hooks(Hook::AfterAll) {
+        visit_needs(entries, hook, &mut seen, &mut order);
+    }
+    order
+}
+
+/// Adds `entry` to `order`, after the entries it needs. Entries in `seen`
+/// are left out.
+fn visit_needs<'a, 'input>(
+    entries: &'a IndexMap<&'input str, Entry<'input>>,
+    entry: &'a Entry<'input>,
+    seen: &mut HashSet<&'input str>,
+    order: &mut Vec<&'a Entry<'input>>,
+) {
+    if !seen.insert(entry.name.text) {
+        return;
+    }
+    visit_prerequisites(entries, entry, seen, order);
+    order.push(entry);
+}
+
+/// Adds the entries that `entry` needs to `order`, and the ones needed by
+/// the entries it calls. Called entries run as part of `entry`, so they
+/// aren't added themselves.
+fn visit_prerequisites<'a, 'input>(
+    entries: &'a IndexMap<&'input str, Entry<'input>>,
+    entry: &'a Entry<'input>,
+    seen: &mut HashSet<&'input str>,
+    order: &mut Vec<&'a Entry<'input>>,
+) {
+    // The validator rejects unknown and cyclic needs and calls.
+    for need in &entry.needs {
+        visit_needs(entries, &entries[need.text], seen, order);
+    }
+    for call in &entry.calls {
+        visit_prerequisites(entries, &entries[call.entry.text], seen, order);
+    }
+}
+
+/// Returns whether `actual op expected` holds. Numbers compare by value,
+/// regardless of whether they are integers or floats, strings compare
+/// lexicographically. Values of other types are only ever equal.
+fn compare(op: CompareOp, actual: &serde_json::Value, expected: &serde_json::Value) -> bool {
+    use serde_json::Value as Json;
+
+    let ordering = match (actual, expected) {
+        (Json::Number(a), Json::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Json::String(a), Json::String(b)) => Some(a.cmp(b)),
+        (a, b) if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+    match op {
+        CompareOp::Eq => ordering == Some(Ordering::Equal),
+        CompareOp::Ne => ordering != Some(Ordering::Equal),
+        CompareOp::Lt => ordering == Some(Ordering::Less),
+        CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        CompareOp::Gt => ordering == Some(Ordering::Greater),
+        CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        CompareOp::Contains => match (actual, expected) {
+            (Json::String(a), Json::String(b)) => a.contains(b.as_str()),
+            (Json::Array(items), expected) => {
+                items.iter().any(|it| compare(CompareOp::Eq, it, expected))
+            }
+            (Json::Object(map), Json::String(key)) => map.contains_key(key),
+            _ => false,
+        },
+        CompareOp::Matches => unreachable!("patterns are matched by the caller"),
+    }
+}
+
+/// Shortens `text` to at most `max` characters for a message.
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Returns `true` for values that go into a URL as they are, either because
+/// they were explicitly marked as raw or because they are already encoded.
+fn is_raw(expr: &Expr) -> bool {
+    matches!(
+        expr.kind,
+        ExprKind::Call(Builtin::Raw | Builtin::UrlEncode, _)
+    )
+}
+
+fn unreadable_file(path: &Path, reason: &str, span: Span) -> ExecutionError {
+    ExecutionError::Diagnostic(
+        Diagnostic::error("Unreadable file", span)
+            .code(codes::UNREADABLE_FILE)
+            .primary_label(
+                format!("I couldn't read `{}`: {reason}", path.display()),
+                Level::Error,
+            ),
+    )
+}
+
+fn encode(builtin: Builtin, input: &str) -> String {
+    match builtin {
+        Builtin::Base64 => BASE64_STANDARD.encode(input),
+        Builtin::UrlEncode => utf8_percent_encode(input, URL_COMPONENT).to_string(),
+        Builtin::Sha256 => format!("{:x}", Sha256::digest(input)),
+        _ => unreachable!("`{builtin:?}` is not an encoding"),
+    }
+}
+
+/// Returns `url` prefixed with `base`, unless it has a scheme of its own.
+fn with_base_url(base: &str, url: &str) -> String {
+    if url.contains("://") {
+        return url.to_string();
+    }
+    if url.is_empty() || url.starts_with('?') {
+        return format!("{base}{url}");
+    }
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        url.trim_start_matches('/')
+    )
+}
+
+/// Returns `true` if text appended to `prefix` is still part of the scheme
+/// or authority of the URL.
+fn in_authority(prefix: &str) -> bool {
+    let rest = match prefix.find("://") {
+        Some(i) => &prefix[i + 3..],
+        None => prefix,
+    };
+    !rest.contains(['/', '?', '#'])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{
+        ExecutionError, IdempotencyKey, RunOptions, RuntimeError, arithmetic, compare, encode,
+        execute_with_client, in_authority, independent_entries, masked_header_value,
+        matches_pattern, run_order, with_base_url,
+    };
+    use crate::{
+        cancel::CancellationToken,
+        client::{HttpError, Request, Response},
+        middleware::Middleware,
+        mock::{MockHttpClient, MockResponse},
+        retry::RetryingHttpClient,
+        validated::{BinOp, Builtin, CompareOp, HttpMethod},
+        validator,
+        value::Value,
+    };
+
+    #[test]
+    fn encoding_builtins() {
+        assert_eq!(encode(Builtin::Base64, "ada:secret"), "YWRhOnNlY3JldA==");
+        assert_eq!(encode(Builtin::UrlEncode, "a b&c/d"), "a%20b%26c%2Fd");
+        assert_eq!(
+            encode(Builtin::Sha256, "abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let result = arithmetic(BinOp::Div, &Value::Integer(7), &Value::Integer(2)).unwrap();
+        assert!(matches!(result, Value::Integer(3)));
+        assert!(matches!(
+            arithmetic(BinOp::Div, &Value::Integer(1), &Value::Integer(0)),
+            Err(RuntimeError::DivisionByZero)
+        ));
+        assert!(matches!(
+            arithmetic(BinOp::Add, &Value::Integer(i64::MAX), &Value::Integer(1)),
+            Err(RuntimeError::IntegerOverflow)
+        ));
+    }
+
+    #[test]
+    fn mixed_arithmetic_is_float() {
+        let result = arithmetic(BinOp::Mul, &Value::Integer(3), &Value::Float(0.5)).unwrap();
+        assert!(matches!(result, Value::Float(1.5)));
+    }
+
+    #[test]
+    fn float_division_by_zero() {
+        assert!(matches!(
+            arithmetic(BinOp::Div, &Value::Float(1.0), &Value::Integer(0)),
+            Err(RuntimeError::NonFiniteFloat(_))
+        ));
+        assert!(matches!(
+            arithmetic(BinOp::Div, &Value::Float(0.0), &Value::Float(0.0)),
+            Err(RuntimeError::NonFiniteFloat(_))
+        ));
+        assert!(matches!(
+            arithmetic(BinOp::Mul, &Value::Float(f64::MAX), &Value::Integer(2)),
+            Err(RuntimeError::NonFiniteFloat(_))
+        ));
+    }
+
+    #[test]
+    fn in_authority_until_path_starts() {
+        assert!(in_authority(""));
+        assert!(in_authority("https://"));
+        assert!(in_authority("https://example.com:"));
+        assert!(!in_authority("https://example.com/"));
+        assert!(!in_authority("https://example.com?"));
+        assert!(!in_authority("https://example.com/users/"));
+    }
+
+    #[test]
+    fn base_url_prefixes_relative_urls() {
+        let base = "https://example.com/api/";
+        assert_eq!(
+            with_base_url(base, "/users"),
+            "https://example.com/api/users"
+        );
+        assert_eq!(
+            with_base_url(base, "users?page=2"),
+            "https://example.com/api/users?page=2"
+        );
+        assert_eq!(with_base_url(base, ""), base);
+        assert_eq!(
+            with_base_url(base, "http://localhost/a"),
+            "http://localhost/a"
+        );
+    }
+
+    #[test]
+    fn compare_json_values() {
+        use serde_json::json;
+
+        assert!(compare(CompareOp::Eq, &json!(200), &json!(200.0)));
+        assert!(compare(CompareOp::Ne, &json!("1"), &json!(1)));
+        assert!(compare(CompareOp::Lt, &json!(120), &json!(500)));
+        assert!(!compare(CompareOp::Lt, &json!("120"), &json!(500)));
+        assert!(compare(CompareOp::Ge, &json!("b"), &json!("a")));
+        assert!(compare(
+            CompareOp::Eq,
+            &json!({"a": [1]}),
+            &json!({"a": [1]})
+        ));
+        assert!(compare(
+            CompareOp::Contains,
+            &json!("application/json"),
+            &json!("json")
+        ));
+        assert!(compare(CompareOp::Contains, &json!([1, 2.5]), &json!(2.5)));
+        assert!(compare(
+            CompareOp::Contains,
+            &json!({"id": 1}),
+            &json!("id")
+        ));
+        assert!(!compare(CompareOp::Contains, &json!(12), &json!(1)));
+    }
+
+    #[test]
+    fn entry_patterns() {
+        assert!(matches_pattern("user", "user"));
+        assert!(!matches_pattern("user", "users"));
+        assert!(matches_pattern("user_*", "user_create"));
+        assert!(matches_pattern("user_*", "user_"));
+        assert!(!matches_pattern("user_*", "admin_user_create"));
+        assert!(matches_pattern("*_user_*", "admin_user_create"));
+        assert!(matches_pattern("get?", "getA"));
+        assert!(!matches_pattern("get?", "get"));
+        assert!(matches_pattern("a*b*c", "aXbYbZc"));
+        assert!(!matches_pattern("a*b*c", "aXbYbZ"));
+    }
+
+    #[test]
+    fn independent_entries_run_in_parallel() {
+        let input = r#"
+const host = "https://example.com"
+entry login {
+    POST "https://example.com/login"
+    [Capture] {"token": "$.token"}
+}
+entry me {
+    GET "https://example.com/me"
+    [Headers] {"Authorization": "Bearer {{token}}"}
+}
+entry seed {
+    POST "https://example.com/seed"
+}
+entry users needs seed {
+    GET "https://example.com/users"
+}
+entry health {
+    GET "{{host}}/health"
+}
+entry status {
+    GET "https://example.com/status"
+}
+"#;
+        let file = validator::validate(input, &HashMap::<String, String>::new()).unwrap();
+        let order = run_order(&file.entries, file.entries.values().collect());
+        let names = independent_entries(&order)
+            .iter()
+            .map(|it| it.name.text)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["health", "status"]);
+    }
+
+    #[test]
+    fn mask_sensitive_headers() {
+        assert_eq!(masked_header_value("Accept", "*/*"), "*/*");
+        assert_eq!(
+            masked_header_value("Authorization", "Bearer abc.def"),
+            "Bearer ***"
+        );
+        assert_eq!(masked_header_value("authorization", "abc"), "***");
+        assert_eq!(masked_header_value("Cookie", "session=abc"), "***");
+        assert_eq!(masked_header_value("X-Api-Key", "abc"), "***");
+    }
+
+    #[test]
+    fn execute_with_mock_client() {
+        let input = r#"
+entry login {
+    POST "https://example.com/login"
+    [Capture] {"token": "$.access_token"}
+}
+
+entry me needs login {
+    GET "https://example.com/me"
+    [Headers] {"Authorization": "Bearer {{token}}"}
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Post,
+            "https://example.com/login",
+            MockResponse::new(200).json(r#"{"access_token": "abc"}"#),
+        )
+        .on(
+            HttpMethod::Get,
+            "https://example.com/me",
+            MockResponse::new(200).json(r#"{"name": "Ada"}"#),
+        );
+        let responses = execute_with_client(
+            input,
+            &["me".to_string()],
+            &HashMap::<String, Value>::new(),
+            &RunOptions::default(),
+            {
+                let mock = mock.clone();
+                move || mock.clone()
+            },
+        )
+        .unwrap();
+        // Only the responses of the selected entries are returned.
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].body, br#"{"name": "Ada"}"#);
+
+        let calls = mock.calls();
+        assert_eq!(calls[0].url, "https://example.com/login");
+        assert_eq!(
+            calls[1].headers,
+            [("Authorization".to_string(), "Bearer abc".to_string())]
+        );
+    }
+
+    #[test]
+    fn entries_call_entries_with_arguments() {
+        let input = r#"
+entry login {
+    POST "https://example.com/login"
+}
+
+entry getUser(id) needs login {
+    GET "https://example.com/users/{{id}}"
+}
+
+entry profile(id) needs getUser("7"), getUser(id) {
+    GET "https://example.com/profiles/{{id}}"
+}
+"#;
+        let mock = MockHttpClient::new();
+        for path in ["users/7", "users/1", "profiles/1"] {
+            mock.on(
+                HttpMethod::Get,
+                &format!("https://example.com/{path}"),
+                MockResponse::new(200),
+            );
+        }
+        mock.on(
+            HttpMethod::Post,
+            "https://example.com/login",
+            MockResponse::new(200),
+        );
+        let options = RunOptions {
+            args: vec![("id".to_string(), "1".to_string())],
+            ..RunOptions::default()
+        };
+        let responses = execute_with_client(
+            input,
+            &["profile".to_string()],
+            &HashMap::<String, Value>::new(),
+            &options,
+            {
+                let mock = mock.clone();
+                move || mock.clone()
+            },
+        )
+        .unwrap();
+        // The responses of called entries belong to the caller.
+        assert_eq!(responses.len(), 1);
+
+        // The callee's needs run first, and the caller's parameter is bound
+        // again after the calls.
+        let calls = mock
+            .calls()
+            .into_iter()
+            .map(|it| it.url)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "https://example.com/login",
+                "https://example.com/users/7",
+                "https://example.com/users/1",
+                "https://example.com/profiles/1",
+            ]
+        );
+
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Post,
+            "https://example.com/login",
+            MockResponse::new(200),
+        )
+        .on(
+            HttpMethod::Get,
+            "https://example.com/users/7",
+            MockResponse::new(404),
+        );
+        let err = execute_with_client(
+            input,
+            &["profile".to_string()],
+            &HashMap::<String, Value>::new(),
+            &options,
+            move || mock.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "I couldn't run the entry `profile`, because the entry `getUser` it calls failed"
+        );
+    }
+
+    #[test]
+    fn spread_fields() {
+        let input = r#"
+const defaults = {"Accept": "application/json", "X-Trace": "none"}
+
+entry a {
+    GET "https://example.com/items"
+    [Query] {"page": "1", ...{"page": "2", "q": "a b"}}
+    [Headers] {"X-Trace": "abc", ...defaults, "Accept": "text/plain"}
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Get,
+            "https://example.com/items?page=2&q=a%20b",
+            MockResponse::new(200),
+        );
+        execute_with_client(
+            input,
+            &["a".to_string()],
+            &HashMap::<String, Value>::new(),
+            &RunOptions::default(),
+            {
+                let mock = mock.clone();
+                move || mock.clone()
+            },
+        )
+        .unwrap();
+
+        // Later fields replace the fields of the spread and move behind them.
+        assert_eq!(
+            mock.calls()[0].headers,
+            [
+                ("X-Trace".to_string(), "none".to_string()),
+                ("Accept".to_string(), "text/plain".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let input = r#"
+const rate = 1.5
+const defaults = {"Accept": "application/json"}
+
+entry a {
+    GET "https://example.com/items"
+    [Headers] {...defaults, "X-Half": "{{rate / 2}}"}
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Get,
+            "https://example.com/items",
+            MockResponse::new(200),
+        );
+        let options = RunOptions {
+            overrides: vec![
+                ("rate".to_string(), "1 + 2".to_string()),
+                (
+                    "defaults".to_string(),
+                    r#"{"Accept": "text/plain", "X-Extra": "1"}"#.to_string(),
+                ),
+            ],
+            ..RunOptions::default()
+        };
+        execute_with_client(
+            input,
+            &["a".to_string()],
+            &HashMap::<String, Value>::new(),
+            &options,
+            {
+                let mock = mock.clone();
+                move || mock.clone()
+            },
+        )
+        .unwrap();
+
+        // The float stays a float, and the spread has the extra field.
+        assert_eq!(
+            mock.calls()[0].headers,
+            [
+                ("Accept".to_string(), "text/plain".to_string()),
+                ("X-Extra".to_string(), "1".to_string()),
+                ("X-Half".to_string(), "1.5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn body_digests() {
+        let input = r#"
+entry download {
+    GET "https://example.com/file.txt"
+    [Assert] {
+        sha256 == "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        md5 == "5d41402abc4b2a76b9719d911017c592"
+    }
+}
+
+entry tampered {
+    GET "https://example.com/file.txt"
+    [Assert] {
+        md5 != "5d41402abc4b2a76b9719d911017c592"
+    }
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Get,
+            "https://example.com/file.txt",
+            MockResponse::new(200).body("hello"),
+        );
+        let run = |entry: &str| {
+            execute_with_client(
+                input,
+                &[entry.to_string()],
+                &HashMap::<String, Value>::new(),
+                &RunOptions::default(),
+                {
+                    let mock = mock.clone();
+                    move || mock.clone()
+                },
+            )
+        };
+        run("download").unwrap();
+        let Err(ExecutionError::AssertionsFailed(failures)) = run("tampered") else {
+            panic!("the digest should match");
+        };
+        assert_eq!(
+            failures[0].labels[0].message,
+            "I was expecting this to hold, but `md5` is \"5d41402abc4b2a76b9719d911017c592\""
+        );
+    }
+
+    #[test]
+    fn soft_and_negated_assertions() {
+        let input = r#"
+entry users {
+    GET "https://example.com/users"
+    [Assert] {
+        not status == 500
+        soft not body contains "deprecated"
+        soft status == 201
+    }
+}
+
+entry failing {
+    GET "https://example.com/users"
+    [Assert] {
+        not body contains "deprecated"
+    }
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Get,
+            "https://example.com/users",
+            MockResponse::new(200).json(r#"{"deprecated": true}"#),
+        );
+        let options = RunOptions::default();
+        let run = |entry: &str| {
+            execute_with_client(
+                input,
+                &[entry.to_string()],
+                &HashMap::<String, Value>::new(),
+                &options,
+                {
+                    let mock = mock.clone();
+                    move || mock.clone()
+                },
+            )
+        };
+
+        // Failed soft assertions are only warnings.
+        run("users").unwrap();
+        let warnings = options.warnings.take();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|it| it.labels[0].message.as_str())
+                .collect::<Vec<_>>(),
+            [
+                r#"I was expecting this not to hold, but the body is "{\"deprecated\": true}""#,
+                "I was expecting this to hold, but `status` is 200",
+            ]
+        );
+
+        let Err(ExecutionError::AssertionsFailed(failures)) = run("failing") else {
+            panic!("the body contains `deprecated`");
+        };
+        assert_eq!(failures.len(), 1);
+        assert!(options.warnings.take().is_empty());
+    }
+
+    #[test]
+    fn hooks_run_around_entries() {
+        let input = r#"
+@before_all
+entry login {
+    POST "https://example.com/login"
+}
+
+@before_each
+entry create {
+    POST "https://example.com/items"
+}
+
+@after_each
+entry cleanup {
+    DELETE "https://example.com/items"
+}
+
+@after_all
+entry logout {
+    POST "https://example.com/logout"
+}
+
+entry read {
+    GET "https://example.com/items"
+    [Assert] {
+        status == 200
+    }
+}
+
+entry count {
+    GET "https://example.com/items/count"
+    [Assert] {
+        status == 200
+    }
+}
+"#;
+        let mock = MockHttpClient::new();
+        for (method, path) in [
+            (HttpMethod::Post, "login"),
+            (HttpMethod::Post, "items"),
+            (HttpMethod::Delete, "items"),
+            (HttpMethod::Post, "logout"),
+            (HttpMethod::Get, "items"),
+            (HttpMethod::Get, "items/count"),
+        ] {
+            mock.on(
+                method,
+                &format!("https://example.com/{path}"),
+                MockResponse::new(200),
+            );
+        }
+        let options = RunOptions {
+            tests_only: true,
+            jobs: 4,
+            ..RunOptions::default()
+        };
+        let responses =
+            execute_with_client(input, &[], &HashMap::<String, Value>::new(), &options, {
+                let mock = mock.clone();
+                move || mock.clone()
+            })
+            .unwrap();
+        // Only the responses of the tests are returned.
+        assert_eq!(responses.len(), 2);
+
+        let calls = mock
+            .calls()
+            .into_iter()
+            .map(|it| format!("{} {}", it.method.as_str(), it.url))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "POST https://example.com/login",
+                "POST https://example.com/items",
+                "GET https://example.com/items",
+                "DELETE https://example.com/items",
+                "POST https://example.com/items",
+                "GET https://example.com/items/count",
+                "DELETE https://example.com/items",
+                "POST https://example.com/logout",
+            ]
+        );
+    }
+
+    #[test]
+    fn idempotency_keys() {
+        let input = r#"
+entry create {
+    POST "https://example.com/items"
+    [Options] {"retries": 1, "retry_backoff": 0}
+}
+
+entry update {
+    PUT "https://example.com/items/1"
+    [Headers] {"idempotency-key": "mine"}
+}
+
+entry read {
+    GET "https://example.com/items"
+}
+"#;
+        // The keys of every request, the first attempt of `create` fails.
+        let run = |mode| {
+            let mock = MockHttpClient::new();
+            mock.on_once(
+                HttpMethod::Post,
+                "https://example.com/items",
+                MockResponse::new(503),
+            )
+            .on(
+                HttpMethod::Post,
+                "https://example.com/*",
+                MockResponse::new(201),
+            )
+            .on(
+                HttpMethod::Put,
+                "https://example.com/*",
+                MockResponse::new(200),
+            )
+            .on(
+                HttpMethod::Get,
+                "https://example.com/*",
+                MockResponse::new(200),
+            );
+            let options = RunOptions {
+                idempotency_key: mode,
+                jobs: 1,
+                ..Default::default()
+            };
+            execute_with_client(input, &[], &HashMap::<String, Value>::new(), &options, {
+                let mock = mock.clone();
+                move || RetryingHttpClient::new(mock.clone(), CancellationToken::new())
+            })
+            .unwrap();
+            mock.calls()
+                .into_iter()
+                .map(|call| {
+                    let keys = call
+                        .headers
+                        .into_iter()
+                        .filter(|(name, _)| name.eq_ignore_ascii_case("Idempotency-Key"))
+                        .map(|(_, value)| value)
+                        .collect::<Vec<_>>();
+                    (call.method, keys)
+                })
+                .collect::<Vec<_>>()
+        };
+        let keys = |calls: &[(HttpMethod, Vec<String>)], method| {
+            calls
+                .iter()
+                .filter(|(it, _)| *it == method)
+                .map(|(_, keys)| keys.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let fresh = run(Some(IdempotencyKey::Fresh));
+        let posts = keys(&fresh, HttpMethod::Post);
+        // The retry carries the key of the first attempt.
+        assert_eq!(posts.len(), 2);
+        assert_eq!(posts[0].len(), 1);
+        assert_eq!(posts[0], posts[1]);
+        assert_eq!(keys(&fresh, HttpMethod::Put), [["mine"]]);
+        assert_eq!(keys(&fresh, HttpMethod::Get), [Vec::<String>::new()]);
+        let fresh_again = run(Some(IdempotencyKey::Fresh));
+        assert_ne!(keys(&fresh_again, HttpMethod::Post)[0], posts[0]);
+
+        let stable = keys(&run(Some(IdempotencyKey::Stable)), HttpMethod::Post);
+        assert_eq!(
+            stable,
+            keys(&run(Some(IdempotencyKey::Stable)), HttpMethod::Post)
+        );
+        assert_eq!(
+            stable[0],
+            [uuid::Uuid::new_v5(
+                &uuid::Uuid::NAMESPACE_URL,
+                b"create https://example.com/items"
+            )
+            .to_string()]
+        );
+
+        let without = keys(&run(None), HttpMethod::Post);
+        assert!(without.iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn middleware_around_requests() {
+        /// Adds a token, and fails requests to `/admin`.
+        struct Auth;
+
+        impl Middleware for Auth {
+            fn before_request(&self, _entry: &str, request: &mut Request) -> Result<(), String> {
+                if request.url.ends_with("/admin") {
+                    return Err("no token for the admin API".to_string());
+                }
+                request
+                    .headers
+                    .push(("Authorization".to_string(), "Bearer abc".to_string()));
+                Ok(())
+            }
+        }
+
+        /// Records the order the hooks run in.
+        struct Log(&'static str, Arc<Mutex<Vec<String>>>);
+
+        impl Middleware for Log {
+            fn before_request(&self, entry: &str, _request: &mut Request) -> Result<(), String> {
+                self.1
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} before {entry}", self.0));
+                Ok(())
+            }
+
+            fn after_response(
+                &self,
+                entry: &str,
+                _request: &Request,
+                result: &mut Result<Response, HttpError>,
+            ) {
+                let status = result.as_ref().map_or(0, |it| it.status.as_u16());
+                self.1
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} after {entry}: {status}", self.0));
+            }
+        }
+
+        let input = r#"
+entry users {
+    GET "https://example.com/users"
+}
+
+entry admin {
+    GET "https://example.com/admin"
+}
+"#;
+        let mock = MockHttpClient::new();
+        mock.on(
+            HttpMethod::Get,
+            "https://example.com/*",
+            MockResponse::new(200),
+        );
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut options = RunOptions::default();
+        options.middleware.push(Log("outer", Arc::clone(&log)));
+        options.middleware.push(Auth);
+        options.middleware.push(Log("inner", Arc::clone(&log)));
+        let run = |entry: &str| {
+            execute_with_client(
+                input,
+                &[entry.to_string()],
+                &HashMap::<String, Value>::new(),
+                &options,
+                {
+                    let mock = mock.clone();
+                    move || mock.clone()
+                },
+            )
+        };
+
+        run("users").unwrap();
+        assert_eq!(
+            mock.calls()[0].headers,
+            [("Authorization".to_string(), "Bearer abc".to_string())]
+        );
+        assert!(matches!(
+            run("admin"),
+            Err(ExecutionError::Runtime(RuntimeError::Middleware { .. }))
+        ));
+        assert_eq!(mock.calls().len(), 1);
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "outer before users",
+                "inner before users",
+                "inner after users: 200",
+                "outer after users: 200",
+                "outer before admin",
+            ]
+        );
     }
 }
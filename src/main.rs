@@ -1,43 +1,439 @@
 use std::{
-    collections::{HashMap, hash_map},
+    collections::{HashMap, HashSet, hash_map},
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
 };
 
 use anyhow::Context;
-use clap::{Parser, Subcommand};
-
-mod ast;
-mod client;
-mod diagnostic;
-mod lexer;
-mod machine;
-mod parser;
-mod span;
-mod token;
-mod validated;
-mod validator;
-mod value;
+use clap::{Parser, Subcommand, ValueEnum};
+
+use aurora::{
+    Value, bench, cancel, client, codes, diagnostic, diff, format, history, line_index, list,
+    machine, openapi, output_dir, plan, report, secrets, sources, validator, vars,
+};
 
 #[derive(Parser)]
 struct Args {
+    /// Most verbose level of log messages to print
+    #[arg(long, global = true, value_name = "LEVEL", default_value_t = tracing::Level::INFO)]
+    log_level: tracing::Level,
+    /// Format of log messages
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text")]
+    log_format: LogFormat,
+    /// What to do with the warnings about a `.au` file
+    #[arg(
+        short = 'W',
+        long,
+        global = true,
+        value_name = "LEVEL",
+        default_value = "warn"
+    )]
+    warnings: WarningsArg,
+    /// Don't run a `.au` file that has warnings, like `-W deny`, e.g. in CI
+    #[arg(long, global = true)]
+    deny_warnings: bool,
+    /// How to print the errors and warnings about a `.au` file
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "human")]
+    diagnostics_format: DiagnosticsFormat,
     #[command(subcommand)]
     cmd: Command,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// One JSON document with every entry's requests, responses, timings,
+    /// captures and assertion results
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WarningsArg {
+    /// Don't print warnings
+    Allow,
+    /// Print warnings to stderr and run the file anyway
+    Warn,
+    /// Print warnings to stderr and fail without running the file
+    Deny,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DiagnosticsFormat {
+    /// The lines of the file with the problems marked
+    Human,
+    /// One JSON object per line and diagnostic, with byte offsets and
+    /// zero-based lines and columns
+    Json,
+    /// One SARIF 2.1.0 log per line and file, for code scanning
+    Sarif,
+}
+
+/// How the errors and warnings about `.au` files are printed.
+#[derive(Clone, Copy)]
+struct DiagnosticOptions {
+    format: DiagnosticsFormat,
+    warnings: WarningsArg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Log messages go to stderr, so they never mix with response bodies.
+/// Secrets are redacted from them.
+fn init_logging(level: tracing::Level, format: LogFormat, secrets: &secrets::Secrets) {
+    let secrets = secrets.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(move || secrets.writer(std::io::stderr()));
+    match format {
+        LogFormat::Text => subscriber.without_time().with_target(false).init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
 #[derive(Subcommand)]
 enum Command {
-    Run {
-        /// Path to the `.au` file to execute
+    Run(Box<RunArgs>),
+    /// Run every entry with an `[Assert]` section as a test, after the
+    /// entries it needs, and summarize the results
+    Test(Box<TestArgs>),
+    /// Send the request of an entry many times and print how long the
+    /// requests took
+    Bench(Box<BenchArgs>),
+    /// Print what `run` would do, without sending any requests
+    ExplainPlan {
+        /// Path to the `.au` file to explain
+        path: PathBuf,
+        /// Name of an entry to explain, may contain `*` and `?` wildcards.
+        /// Can be given multiple times.
+        #[arg(long("entry"))]
+        entries: Vec<String>,
+        /// Define a variable
+        #[arg(long("var"), value_parser=parse_var_value)]
+        vars: Vec<(String, String)>,
+        /// Define the variables of a `.env` file, `--var` takes precedence
+        #[arg(long("env-file"), value_name = "PATH")]
+        env_files: Vec<PathBuf>,
+        /// Define typed variables from a JSON, TOML or YAML file, `--var` and
+        /// `--env-file` take precedence
+        #[arg(long("vars-file"), value_name = "PATH")]
+        vars_files: Vec<PathBuf>,
+        /// Use the consts of this `env` block
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Print a table of the entries of a `.au` file
+    List {
+        /// Path to the `.au` file to list
         path: PathBuf,
-        /// Name of an entry to execute
+    },
+    /// Rewrite `.au` files in their canonical layout
+    Fmt {
+        /// Paths of the `.au` files to format
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Don't write the files, but fail if any of them isn't formatted
         #[arg(long)]
+        check: bool,
+    },
+    /// Generate a `.au` file from another format and print it
+    #[command(subcommand)]
+    Import(ImportFormat),
+    /// Compare two JSON responses structurally, either of an entry run
+    /// with two sets of variables or saved in two files
+    Diff {
+        /// The `.au` file to run with `--against` and `--and`, or two files
+        /// with response bodies
+        #[arg(required = true, num_args = 1..=2)]
+        paths: Vec<PathBuf>,
+        /// Name of the entry to run
+        #[arg(long, requires = "against")]
         entry: Option<String>,
-        /// Define a variable
+        /// Run the entry with the variables of this file first...
+        #[arg(long, value_name = "VARS_FILE", requires_all = ["entry", "and"])]
+        against: Option<PathBuf>,
+        /// ...and with the variables of this file second
+        #[arg(long, value_name = "VARS_FILE", requires = "against")]
+        and: Option<PathBuf>,
+        /// Define a variable for both runs, it takes precedence over the
+        /// variables files
         #[arg(long("var"), value_parser=parse_var_value)]
         vars: Vec<(String, String)>,
+        /// Use the consts of this `env` block
+        #[arg(long)]
+        env: Option<String>,
+    },
+    /// Print the most recent requests that `run` and `test` sent
+    History {
+        /// Only requests of entries with this name, may contain `*` and `?`
+        /// wildcards
+        #[arg(long)]
+        entry: Option<String>,
+        /// Only responses with this status, `x` stands for any digit, e.g.
+        /// `404` or `5xx`
+        #[arg(long)]
+        status: Option<String>,
+        /// Only requests sent within this long, e.g. `30m` or `1d`
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        since: Option<Duration>,
+        /// How many requests to print
+        #[arg(short = 'n', long, value_name = "N", default_value_t = 20)]
+        limit: usize,
+        /// Print one JSON object per line instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Read this history file instead of the default one
+        #[arg(long, value_name = "PATH", env = "AURORA_HISTORY_FILE")]
+        history_file: Option<PathBuf>,
+    },
+    /// Print a detailed explanation of an error code, e.g. `AU0004`
+    Explain {
+        /// The code shown in brackets after `error` or `warning`
+        code: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportFormat {
+    /// Generate one entry per operation of an OpenAPI document
+    Openapi {
+        /// Path to the OpenAPI document, in JSON or YAML
+        path: PathBuf,
     },
 }
 
+#[derive(clap::Args)]
+struct BenchArgs {
+    /// Path to the `.au` file with the entry
+    path: PathBuf,
+    /// Name of the entry to benchmark, the entries it needs run once before
+    #[arg(long)]
+    entry: String,
+    /// How many requests to send
+    #[arg(short = 'n', long, value_name = "N", default_value_t = 100)]
+    requests: usize,
+    /// How many requests to send at the same time
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    concurrency: usize,
+    /// Define a variable
+    #[arg(long("var"), value_parser=parse_var_value)]
+    vars: Vec<(String, String)>,
+    /// Define a variable whose value is redacted in all output, e.g. a token
+    #[arg(long("secret-var"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    secret_vars: Vec<(String, String)>,
+    /// Define the variables of a `.env` file, `--var` takes precedence
+    #[arg(long("env-file"), value_name = "PATH")]
+    env_files: Vec<PathBuf>,
+    /// Define typed variables from a JSON, TOML or YAML file, `--var` and
+    /// `--env-file` take precedence
+    #[arg(long("vars-file"), value_name = "PATH")]
+    vars_files: Vec<PathBuf>,
+    /// Use the consts of this `env` block
+    #[arg(long)]
+    env: Option<String>,
+    /// Override the value of a `const` for this run
+    #[arg(long("set"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    overrides: Vec<(String, String)>,
+    /// Give a value to a parameter of the entry
+    #[arg(long("arg"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    args: Vec<(String, String)>,
+    /// Keep a file with Prometheus metrics up to date during the benchmark
+    #[arg(long, value_name = "PATH")]
+    metrics_file: Option<PathBuf>,
+    /// Send every request with this HTTP version, unless the entry's
+    /// `[Options]` choose another one
+    #[arg(long, value_name = "VERSION")]
+    http_version: Option<HttpVersionArg>,
+    /// Don't verify TLS certificates
+    #[arg(long, short = 'k')]
+    insecure: bool,
+    /// Trust the root certificates in this PEM file in addition to the
+    /// system's
+    #[arg(long, value_name = "FILE")]
+    cacert: Option<PathBuf>,
+    /// Connect to ADDR instead of looking up HOST, e.g.
+    /// `api.example.com:443:10.0.0.5`. Can be given multiple times.
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = parse_resolve)]
+    resolve: Vec<(String, SocketAddr)>,
+}
+
+#[derive(clap::Args)]
+struct TestArgs {
+    #[command(flatten)]
+    run: RunArgs,
+    /// Stop at the first test that fails
+    #[arg(long)]
+    fail_fast: bool,
+}
+
+/// Whether entries are executed for their responses, or checked as tests.
+#[derive(Clone, Copy)]
+enum Mode {
+    Run,
+    Test { fail_fast: bool },
+}
+
+#[derive(clap::Args)]
+struct RunArgs {
+    /// Path to the `.au` file to execute, a directory whose `.au` files are
+    /// executed, or a glob like `"api/**/*.au"`
+    path: PathBuf,
+    /// Name of an entry to execute, may contain `*` and `?` wildcards. Can
+    /// be given multiple times, the matching entries run in file order.
+    #[arg(long("entry"))]
+    entries: Vec<String>,
+    /// Define a variable
+    #[arg(long("var"), value_parser=parse_var_value)]
+    vars: Vec<(String, String)>,
+    /// Define a variable whose value is redacted in all output, e.g. a token
+    #[arg(long("secret-var"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    secret_vars: Vec<(String, String)>,
+    /// Define the variables of a `.env` file with `KEY=VALUE` lines. Can be
+    /// given multiple times, later files and `--var` take precedence.
+    #[arg(long("env-file"), value_name = "PATH")]
+    env_files: Vec<PathBuf>,
+    /// Define variables from a `.json`, `.toml` or `.yaml` file, keeping
+    /// their types. Can be given multiple times, later files, `--env-file`
+    /// and `--var` take precedence.
+    #[arg(long("vars-file"), value_name = "PATH")]
+    vars_files: Vec<PathBuf>,
+    /// Use the consts of this `env` block
+    #[arg(long)]
+    env: Option<String>,
+    /// Only run entries with this tag, can be given multiple times
+    #[arg(long("tag"), value_name = "TAG")]
+    tags: Vec<String>,
+    /// Override the value of a `const` for this run
+    #[arg(long("set"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    overrides: Vec<(String, String)>,
+    /// Give a value to a parameter of the executed entry
+    #[arg(long("arg"), value_name = "NAME=VALUE", value_parser=parse_var_value)]
+    args: Vec<(String, String)>,
+    /// Pretty-print JSON bodies regardless of their size
+    #[arg(long)]
+    pretty: bool,
+    /// Print the status line and the headers of every response before its
+    /// body
+    #[arg(short, long)]
+    include: bool,
+    /// Exit successfully and print nothing for responses with a non-2xx
+    /// status. By default they are printed to stderr and fail the run.
+    #[arg(long)]
+    allow_failure: bool,
+    /// Write the body of every response to a file in this directory instead
+    /// of printing it
+    #[arg(long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+    /// Name of the files in `--output-dir`, with the placeholders `{entry}`,
+    /// `{status}` and `{ext}`, the extension for the `Content-Type`
+    #[arg(long, value_name = "PATTERN", default_value = output_dir::OutputDir::DEFAULT_PATTERN, requires = "output_dir")]
+    output_name: String,
+    /// Attach an `Idempotency-Key` header to mutating requests
+    #[arg(long, value_name = "MODE")]
+    idempotency_key: Option<IdempotencyKeyArg>,
+    /// Export a trace of the run to this OTLP/HTTP collector
+    #[arg(long, value_name = "URL", env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+    /// Keep a file with Prometheus metrics up to date during the run
+    #[arg(long, value_name = "PATH")]
+    metrics_file: Option<PathBuf>,
+    /// Append every sent request to this history file instead of the
+    /// default one, see `aurora history`
+    #[arg(long, value_name = "PATH", env = "AURORA_HISTORY_FILE")]
+    history_file: Option<PathBuf>,
+    /// Don't record the requests of this run in the history
+    #[arg(long, conflicts_with = "history_file")]
+    no_history: bool,
+    /// Cache GET responses in this directory, honoring `Cache-Control`
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Load cookies from a Netscape cookie file, as written by curl, or a
+    /// `.json` file
+    #[arg(long, value_name = "FILE")]
+    cookies: Option<PathBuf>,
+    /// Keep cookies in this file across runs: they are loaded from it unless
+    /// `--cookies` is given, and all cookies are saved to it after the run
+    #[arg(long, value_name = "FILE")]
+    cookie_jar: Option<PathBuf>,
+    /// Send every request with this HTTP version, unless an entry's
+    /// `[Options]` choose another one
+    #[arg(long, value_name = "VERSION")]
+    http_version: Option<HttpVersionArg>,
+    /// Don't verify TLS certificates, e.g. self-signed ones of a staging server
+    #[arg(long, short = 'k')]
+    insecure: bool,
+    /// Trust the root certificates in this PEM file in addition to the
+    /// system's
+    #[arg(long, value_name = "FILE")]
+    cacert: Option<PathBuf>,
+    /// Wait this long between requests, e.g. `500ms` or `2s`
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    delay: Option<Duration>,
+    /// Connect to ADDR instead of looking up HOST, e.g.
+    /// `api.example.com:443:10.0.0.5`. Can be given multiple times.
+    #[arg(long, value_name = "HOST:PORT:ADDR", value_parser = parse_resolve)]
+    resolve: Vec<(String, SocketAddr)>,
+    /// Print the headers of every request and response, and how long the
+    /// request took, to stderr. Secrets in headers are masked.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Print a report of the run to stdout instead of the response bodies.
+    /// Diagnostics go to stderr then.
+    #[arg(long, value_name = "FORMAT")]
+    report: Option<ReportFormat>,
+    /// Print every request with its final URL, headers and body instead of
+    /// sending it
+    #[arg(long)]
+    dry_run: bool,
+    /// Run up to N entries at the same time. Entries that capture or read
+    /// captured values, or that need or are needed by others, still run one
+    /// after another once the rest are done.
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    jobs: usize,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HttpVersionArg {
+    /// HTTP/1.1 only
+    #[value(name = "1.1")]
+    Http1,
+    /// HTTP/2 without upgrading from HTTP/1.1, the server must support it
+    #[value(name = "2")]
+    Http2,
+}
+
+impl From<HttpVersionArg> for client::HttpVersion {
+    fn from(value: HttpVersionArg) -> Self {
+        match value {
+            HttpVersionArg::Http1 => client::HttpVersion::Http1,
+            HttpVersionArg::Http2 => client::HttpVersion::Http2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IdempotencyKeyArg {
+    /// A new random key for every request
+    Fresh,
+    /// A key derived from the entry name and URL, identical across runs
+    Stable,
+}
+
+impl From<IdempotencyKeyArg> for machine::IdempotencyKey {
+    fn from(value: IdempotencyKeyArg) -> Self {
+        match value {
+            IdempotencyKeyArg::Fresh => machine::IdempotencyKey::Fresh,
+            IdempotencyKeyArg::Stable => machine::IdempotencyKey::Stable,
+        }
+    }
+}
+
 fn parse_var_value(raw: &str) -> anyhow::Result<(String, String)> {
     if let Some((name, value)) = raw.split_once('=') {
         Ok((name.to_string(), value.to_string()))
@@ -46,51 +442,865 @@ fn parse_var_value(raw: &str) -> anyhow::Result<(String, String)> {
     }
 }
 
-fn validate_vars(vars: Vec<(String, String)>) -> anyhow::Result<HashMap<String, String>> {
+fn parse_duration(raw: &str) -> anyhow::Result<Duration> {
+    let digits = raw.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let amount = digits
+        .parse::<u64>()
+        .map_err(|_| anyhow::anyhow!("expected a duration like `500ms` or `2s`"))?;
+    match &raw[digits.len()..] {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        "d" => Ok(Duration::from_secs(amount * 24 * 60 * 60)),
+        _ => Err(anyhow::anyhow!(
+            "expected a duration like `500ms` or `2s`, with the unit `ms`, `s`, `m`, `h` or `d`"
+        )),
+    }
+}
+
+fn parse_resolve(raw: &str) -> anyhow::Result<(String, SocketAddr)> {
+    let mut parts = raw.splitn(3, ':');
+    let (Some(host), Some(port), Some(addr)) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!("expected `HOST:PORT:ADDR`");
+    };
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| anyhow::anyhow!("expected a port, but found `{port}`"))?;
+    // IPv6 addresses may be given in brackets, like in a URL.
+    let addr = addr.trim_start_matches('[').trim_end_matches(']');
+    let ip = addr
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow::anyhow!("expected an IP address, but found `{addr}`"))?;
+    Ok((host.to_string(), SocketAddr::new(ip, port)))
+}
+
+/// Adds the secret variables to the others, they are validated together.
+fn with_secret_vars(
+    mut vars: Vec<(String, String)>,
+    secret_vars: Vec<(String, String)>,
+    secrets: &secrets::Secrets,
+) -> Vec<(String, String)> {
+    for (_, value) in &secret_vars {
+        secrets.add(value);
+    }
+    vars.extend(secret_vars);
+    vars
+}
+
+fn validate_vars(
+    vars: Vec<(String, String)>,
+    env_files: &[PathBuf],
+    vars_files: &[PathBuf],
+) -> anyhow::Result<HashMap<String, Value>> {
     // TODO: proper validation
     let mut validated_vars = HashMap::with_capacity(vars.len());
 
     for (name, value) in vars {
         match validated_vars.entry(name.clone()) {
             hash_map::Entry::Occupied(_) => anyhow::bail!("Duplicate variable found `{name}`"),
-            hash_map::Entry::Vacant(vacant) => _ = vacant.insert(value),
+            hash_map::Entry::Vacant(vacant) => _ = vacant.insert(Value::String(value)),
         }
     }
 
+    // `--var` wins over the env files, which win over the vars files. Later
+    // files win over earlier ones.
+    let mut env_vars = HashMap::new();
+    for path in env_files {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+        let vars = vars::parse_env_file(&contents)
+            .map_err(|e| anyhow::anyhow!("`{}` is not a valid env file, {e}", path.display()))?;
+        env_vars.extend(vars);
+    }
+    let mut typed_vars = HashMap::new();
+    for path in vars_files {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+        let vars = vars::parse_vars_file(path, &contents)
+            .map_err(|e| anyhow::anyhow!("`{}` is not a valid vars file, {e}", path.display()))?;
+        typed_vars.extend(vars);
+    }
+    for (name, value) in env_vars {
+        validated_vars.entry(name).or_insert(Value::String(value));
+    }
+    for (name, value) in typed_vars {
+        validated_vars.entry(name).or_insert(value);
+    }
+
     Ok(validated_vars)
 }
 
-fn run(path: &Path, entry: Option<String>, vars: Vec<(String, String)>) -> anyhow::Result<()> {
-    let validated_vars = validate_vars(vars)?;
-    let input = std::fs::read_to_string(path)
+/// Maps the file into memory instead of reading it, so large generated files
+/// are lexed in place without being copied first.
+fn map_source(path: &Path) -> anyhow::Result<memmap2::Mmap> {
+    let file = std::fs::File::open(path)
         .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+    // SAFETY: the mapping is read-only and only lives for the duration of the
+    // run. Modifying the file while it is being executed is not supported.
+    unsafe { memmap2::Mmap::map(&file) }
+        .with_context(|| format!("could not read `{}`", path.to_string_lossy()))
+}
 
-    match machine::execute(&input, entry, &validated_vars) {
+fn run(
+    args: RunArgs,
+    mode: Mode,
+    diagnostics: DiagnosticOptions,
+    secrets: secrets::Secrets,
+) -> anyhow::Result<ExitCode> {
+    let start = std::time::Instant::now();
+    let given_vars = var_names(&args.vars, &args.secret_vars);
+    let vars = with_secret_vars(args.vars, args.secret_vars, &secrets);
+    let validated_vars = validate_vars(vars, &args.env_files, &args.vars_files)?;
+    let paths = sources::source_files(&args.path)
+        .with_context(|| format!("could not read `{}`", args.path.to_string_lossy()))?;
+    if paths.is_empty() {
+        anyhow::bail!("no `.au` files found at `{}`", args.path.display());
+    }
+
+    let mut options = machine::RunOptions {
+        cancellation: cancel::CancellationToken::new(),
+        idempotency_key: args.idempotency_key.map(Into::into),
+        otlp_endpoint: args.otlp_endpoint,
+        metrics_file: args.metrics_file,
+        history: None,
+        middleware: Default::default(),
+        cache_dir: args.cache_dir,
+        cookies: args.cookies,
+        cookie_jar: args.cookie_jar,
+        overrides: args.overrides,
+        args: args.args,
+        env: args.env,
+        tags: args.tags,
+        http_version: args.http_version.map(Into::into),
+        insecure: args.insecure,
+        ca_file: args.cacert,
+        delay: args.delay,
+        resolve: args.resolve,
+        output_dir: args
+            .output_dir
+            .map(|dir| output_dir::OutputDir::new(dir, args.output_name)),
+        dry_run: args.dry_run,
+        verbose: args.verbose,
+        report: match (args.report, mode) {
+            (None, Mode::Run) => None,
+            // Tests are summarized from the report.
+            _ => Some(report::ReportRecorder::new()),
+        },
+        tests_only: matches!(mode, Mode::Test { .. }),
+        keep_going: matches!(mode, Mode::Test { fail_fast: false }),
+        jobs: args.jobs,
+        warnings: machine::Warnings::new(),
+        secrets: secrets.clone(),
+        base_dir: None,
+    };
+    let handler_token = options.cancellation.clone();
+    ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        tracing::warn!("Cancelling after the current request, press Ctrl-C again to abort");
+        handler_token.cancel();
+    })
+    .context("could not install the Ctrl-C handler")?;
+
+    // Every file is run on its own, with the same variables and options. The
+    // report combines the reports of all of them.
+    let output = OutputOptions {
+        report: args.report,
+        pretty: args.pretty,
+        include: args.include,
+        allow_failure: args.allow_failure,
+        saved: options.output_dir.is_some(),
+        diagnostics,
+    };
+    let report = options.report.clone();
+    // Dry runs don't send anything to record.
+    let history = match (args.history_file, args.no_history || args.dry_run) {
+        (_, true) => None,
+        (Some(path), false) => Some(history::History::new(path, secrets.clone())),
+        (None, false) => history::History::default_path()
+            .map(|path| history::History::new(path, secrets.clone())),
+    };
+    let mut success = true;
+    // Unknown once a file isn't validated, e.g. because the run stopped.
+    let mut referenced = Some(HashSet::new());
+    for path in &paths {
+        if options.cancellation.is_cancelled() {
+            referenced = None;
+            break;
+        }
+        if paths.len() > 1 {
+            tracing::info!("Running `{}`", path.display());
+        }
+        options.base_dir = path.parent().map(Path::to_path_buf);
+        options.history = history.as_ref().map(|it| it.for_file(path));
+        options.report = report.as_ref().map(|_| report::ReportRecorder::new());
+        let file_success = run_file(
+            path,
+            &args.entries,
+            mode,
+            &validated_vars,
+            &options,
+            &output,
+            &mut referenced,
+        )?;
+        if let (Some(report), Some(file_report)) = (&report, &options.report) {
+            if paths.len() > 1 {
+                report.append_file_entries(&path.to_string_lossy(), file_report.take_entries());
+            } else {
+                report.append_entries(file_report.take_entries());
+            }
+        }
+        success &= file_success;
+        if !file_success && !options.keep_going {
+            referenced = None;
+            break;
+        }
+    }
+    if let Some(referenced) = referenced {
+        success &= warn_unused_vars(&given_vars, &referenced, diagnostics.warnings);
+    }
+
+    if let (Mode::Test { .. }, Some(report)) = (mode, &report) {
+        success &= report.all_passed();
+        let summary = report.test_summary(start.elapsed());
+        let summary = secrets.redact(&summary);
+        match args.report {
+            Some(_) => eprint!("{summary}"),
+            None => print!("{summary}"),
+        }
+    }
+    if let (Some(ReportFormat::Json), Some(report)) = (args.report, &report) {
+        let json = report.to_json(success);
+        println!("{}", secrets.redact(&serde_json::to_string_pretty(&json)?));
+    }
+    Ok(if success {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// How the responses and errors of a run are printed.
+struct OutputOptions {
+    report: Option<ReportFormat>,
+    pretty: bool,
+    include: bool,
+    allow_failure: bool,
+    /// The bodies were written to `--output-dir`, so they aren't printed
+    saved: bool,
+    diagnostics: DiagnosticOptions,
+}
+
+/// Executes one file and prints its responses or errors. Returns whether
+/// the run succeeded.
+fn run_file(
+    path: &Path,
+    entries: &[String],
+    mode: Mode,
+    vars: &HashMap<String, Value>,
+    options: &machine::RunOptions,
+    output: &OutputOptions,
+    referenced: &mut Option<HashSet<String>>,
+) -> anyhow::Result<bool> {
+    let source = map_source(path)?;
+    let input = std::str::from_utf8(&source)
+        .with_context(|| format!("`{}` is not valid UTF-8", path.to_string_lossy()))?;
+
+    let warnings = file_warnings(input, vars, options.env.as_deref(), referenced);
+    if !print_warnings(input, path, &warnings, output.diagnostics, &options.secrets)? {
+        return Ok(false);
+    }
+    let result = machine::execute(input, entries, vars, options);
+    // The report takes stdout, so diagnostics move to stderr.
+    let print = |d: &[diagnostic::Diagnostic]| {
+        let rendered = render_diagnostics(input, path, d, output.diagnostics.format)?;
+        let rendered = options.secrets.redact(&rendered);
+        match output.report {
+            Some(_) => eprintln!("{rendered}"),
+            None => println!("{rendered}"),
+        }
+        Ok(())
+    };
+    // Failed soft assertions only fail the run if warnings are denied.
+    let run_warnings = options.warnings.take();
+    if !run_warnings.is_empty() && output.diagnostics.warnings != WarningsArg::Allow {
+        let rendered = render_diagnostics(input, path, &run_warnings, output.diagnostics.format)?;
+        eprintln!("{}", options.secrets.redact(&rendered));
+    }
+    let mut success = result.is_ok()
+        && (run_warnings.is_empty() || output.diagnostics.warnings != WarningsArg::Deny);
+    match result {
         Ok(responses) => {
-            for response in responses {
-                if response.status.is_success() && !response.body.is_empty() {
-                    println!("{}", response.pretty_body());
+            if matches!(mode, Mode::Run) && !output.allow_failure {
+                success &= responses.iter().all(|it| it.status.is_success());
+            }
+            if output.report.is_none() && matches!(mode, Mode::Run) {
+                for response in responses {
+                    if !response.status.is_success() {
+                        if !output.allow_failure {
+                            eprint_failed_response(&response, output.pretty);
+                        }
+                        continue;
+                    }
+                    // Streamed bodies were printed while they arrived.
+                    if response.streamed {
+                        continue;
+                    }
+                    if output.include {
+                        println!("{}", response.head());
+                    } else if response.body.is_empty() && !output.saved {
+                        // Without a body there would be no sign of the response.
+                        println!("{}", response.status_line());
+                    }
+                    if !response.body.is_empty() && !output.saved {
+                        println!("{}", response.pretty_body(output.pretty));
+                    }
                 }
             }
         }
-        Err(err) => match err {
-            machine::ExecutionError::Diagnostic(d) => {
-                let mut buf = String::new();
-                diagnostic::dump(&input, path, &d, diagnostic::RenderStyle::Styled, &mut buf)?;
-                println!("{}", buf);
-            }
-            machine::ExecutionError::Runtime(e) => eprintln!("error: {e}"),
-            machine::ExecutionError::Transport(e) => eprintln!("HTTP error: {e}"),
-        },
+        Err(err) => print_error(err, &print)?,
+    }
+    Ok(success)
+}
+
+/// Prints a response with a non-2xx status to stderr, with its headers and
+/// body, so that the failure doesn't go unnoticed.
+fn eprint_failed_response(response: &client::Response, pretty: bool) {
+    eprintln!("{}", response.head());
+    if !response.body.is_empty() && !response.streamed {
+        eprintln!("{}", response.pretty_body(pretty));
     }
+}
 
+fn print_error(
+    err: machine::ExecutionError,
+    print: &dyn Fn(&[diagnostic::Diagnostic]) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    match err {
+        machine::ExecutionError::Diagnostic(d) => print(&[d])?,
+        machine::ExecutionError::InvalidSource(diagnostics) => print(&diagnostics)?,
+        machine::ExecutionError::Runtime(e) => tracing::error!("{e}"),
+        machine::ExecutionError::Transport(e) => tracing::error!("HTTP error: {e}"),
+        machine::ExecutionError::AssertionsFailed(failures) => {
+            for d in failures {
+                print(&[d])?;
+            }
+        }
+        machine::ExecutionError::EntriesFailed(errors) => {
+            for err in errors {
+                print_error(err, print)?;
+            }
+        }
+        machine::ExecutionError::Cancelled => tracing::warn!("Run cancelled"),
+    }
     Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    match Args::parse().cmd {
-        Command::Run { path, entry, vars } => run(&path, entry, vars)?,
+/// Benchmarks an entry and prints the latencies. Fails if a request failed
+/// or got a non-2xx response.
+fn bench(
+    args: BenchArgs,
+    diagnostics: DiagnosticOptions,
+    secrets: secrets::Secrets,
+) -> anyhow::Result<ExitCode> {
+    let given_vars = var_names(&args.vars, &args.secret_vars);
+    let vars = with_secret_vars(args.vars, args.secret_vars, &secrets);
+    let validated_vars = validate_vars(vars, &args.env_files, &args.vars_files)?;
+    let source = map_source(&args.path)?;
+    let input = std::str::from_utf8(&source)
+        .with_context(|| format!("`{}` is not valid UTF-8", args.path.to_string_lossy()))?;
+
+    let options = machine::RunOptions {
+        cancellation: cancel::CancellationToken::new(),
+        metrics_file: args.metrics_file,
+        overrides: args.overrides,
+        args: args.args,
+        env: args.env,
+        base_dir: args.path.parent().map(Path::to_path_buf),
+        http_version: args.http_version.map(Into::into),
+        insecure: args.insecure,
+        ca_file: args.cacert,
+        resolve: args.resolve,
+        secrets: secrets.clone(),
+        jobs: 1,
+        ..Default::default()
+    };
+    let handler_token = options.cancellation.clone();
+    ctrlc::set_handler(move || {
+        if handler_token.is_cancelled() {
+            std::process::exit(130);
+        }
+        tracing::warn!("Stopping the benchmark, press Ctrl-C again to abort");
+        handler_token.cancel();
+    })
+    .context("could not install the Ctrl-C handler")?;
+
+    let mut referenced = Some(HashSet::new());
+    let file_warnings = file_warnings(
+        input,
+        &validated_vars,
+        options.env.as_deref(),
+        &mut referenced,
+    );
+    if !print_warnings(input, &args.path, &file_warnings, diagnostics, &secrets)? {
+        return Ok(ExitCode::FAILURE);
+    }
+    if let Some(referenced) = referenced
+        && !warn_unused_vars(&given_vars, &referenced, diagnostics.warnings)
+    {
+        return Ok(ExitCode::FAILURE);
+    }
+    let settings = bench::BenchSettings {
+        requests: args.requests,
+        concurrency: args.concurrency,
+    };
+    match machine::bench(input, &args.entry, &validated_vars, &options, settings) {
+        Ok(report) => {
+            print!("{report}");
+            Ok(if report.failures() == 0 {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            })
+        }
+        Err(err) => {
+            print_error(err, &|d| {
+                println!(
+                    "{}",
+                    secrets.redact(&render_diagnostics(
+                        input,
+                        &args.path,
+                        d,
+                        diagnostics.format
+                    )?)
+                );
+                Ok(())
+            })?;
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn explain_plan(
+    path: &Path,
+    entries: Vec<String>,
+    vars: Vec<(String, String)>,
+    env_files: &[PathBuf],
+    vars_files: &[PathBuf],
+    env: Option<String>,
+    diagnostics: DiagnosticOptions,
+) -> anyhow::Result<ExitCode> {
+    let validated_vars = validate_vars(vars, env_files, vars_files)?;
+    let source = map_source(path)?;
+    let input = std::str::from_utf8(&source)
+        .with_context(|| format!("`{}` is not valid UTF-8", path.to_string_lossy()))?;
+
+    let file = match validator::validate_in_env(input, &validated_vars, env.as_deref()) {
+        Ok(file) => file,
+        Err(d) => {
+            print_diagnostics(input, path, &d, diagnostics.format)?;
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if !print_warnings(
+        input,
+        path,
+        &file.warnings,
+        diagnostics,
+        &secrets::Secrets::new(),
+    )? {
+        return Ok(ExitCode::FAILURE);
+    }
+    if let Some(env) = env
+        && !file.envs.iter().any(|it| it.text == env)
+    {
+        tracing::error!("{}", machine::RuntimeError::EnvNotFound(env));
+        return Ok(ExitCode::FAILURE);
+    }
+    match plan::explain(&file, &entries) {
+        Ok(plan) => {
+            print!("{plan}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            tracing::error!("{e}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+fn list(path: &Path, format: DiagnosticsFormat) -> anyhow::Result<ExitCode> {
+    let source = map_source(path)?;
+    let input = std::str::from_utf8(&source)
+        .with_context(|| format!("`{}` is not valid UTF-8", path.to_string_lossy()))?;
+
+    match list::list(input) {
+        Ok(listing) => {
+            print!("{listing}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(d) => {
+            print_diagnostics(input, path, &d, format)?;
+            Ok(ExitCode::FAILURE)
+        }
     }
+}
 
+/// A status, if the response was received in this run, and the body, parsed
+/// as JSON if it is JSON.
+type DiffSide = (Option<u16>, serde_json::Value);
+
+/// Runs `entry` with the variables of `vars_file` and returns its last
+/// response.
+fn run_for_diff(
+    path: &Path,
+    entry: &str,
+    vars: Vec<(String, String)>,
+    vars_file: &Path,
+    env: &Option<String>,
+    format: DiagnosticsFormat,
+) -> anyhow::Result<DiffSide> {
+    let validated_vars = validate_vars(vars, &[], &[vars_file.to_path_buf()])?;
+    let source = map_source(path)?;
+    let input = std::str::from_utf8(&source)
+        .with_context(|| format!("`{}` is not valid UTF-8", path.to_string_lossy()))?;
+
+    let options = machine::RunOptions {
+        env: env.clone(),
+        base_dir: path.parent().map(Path::to_path_buf),
+        jobs: 1,
+        ..Default::default()
+    };
+    let responses = match machine::execute(input, &[entry.to_string()], &validated_vars, &options) {
+        Ok(responses) => responses,
+        Err(err) => {
+            print_error(err, &|d| eprint_diagnostics(input, path, d, format))?;
+            anyhow::bail!("could not run `{entry}` with `{}`", vars_file.display());
+        }
+    };
+    let Some(response) = responses.last() else {
+        anyhow::bail!("`{entry}` sent no request with `{}`", vars_file.display());
+    };
+    Ok((Some(response.status.as_u16()), parse_body(&response.body)))
+}
+
+fn read_body(path: &Path) -> anyhow::Result<DiffSide> {
+    let body = std::fs::read(path)
+        .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+    Ok((None, parse_body(&body)))
+}
+
+/// Parses a body as JSON. Other bodies are compared as one string.
+fn parse_body(body: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(body)
+        .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(body).into_owned()))
+}
+
+/// Prints the differences between two responses. Fails if there are any,
+/// like `diff` does.
+fn diff((a_status, a): DiffSide, (b_status, b): DiffSide) -> anyhow::Result<ExitCode> {
+    let mut same = true;
+    if a_status != b_status
+        && let (Some(a_status), Some(b_status)) = (a_status, b_status)
+    {
+        println!("~ status: {a_status} -> {b_status}");
+        same = false;
+    }
+    for change in diff::diff(&a, &b) {
+        println!("{change}");
+        same = false;
+    }
+    if same {
+        tracing::info!("The responses are the same");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Formats the files in place, or only reports the ones that aren't formatted
+/// with `check`. Fails if a file couldn't be formatted or, with `check`, isn't
+/// formatted.
+fn fmt(paths: &[PathBuf], check: bool, format: DiagnosticsFormat) -> anyhow::Result<ExitCode> {
+    let mut code = ExitCode::SUCCESS;
+    for path in paths {
+        let input = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+        let formatted = match format::format_source(&input) {
+            Ok(formatted) => formatted,
+            Err(d) => {
+                print_diagnostics(&input, path, &d, format)?;
+                code = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        if formatted == input {
+            continue;
+        }
+
+        if check {
+            println!("`{}` is not formatted", path.to_string_lossy());
+            code = ExitCode::FAILURE;
+        } else {
+            std::fs::write(path, formatted)
+                .with_context(|| format!("could not write `{}`", path.to_string_lossy()))?;
+        }
+    }
+    Ok(code)
+}
+
+/// The warnings about `input`. A file with errors has none, the run reports
+/// its errors.
+///
+/// Adds the names the file refers to to `referenced`, or clears it if the
+/// file has errors, because they aren't known then.
+fn file_warnings(
+    input: &str,
+    vars: &HashMap<String, Value>,
+    env: Option<&str>,
+    referenced: &mut Option<HashSet<String>>,
+) -> Vec<diagnostic::Diagnostic> {
+    match validator::validate_in_env(input, vars, env) {
+        Ok(file) => {
+            if let Some(referenced) = referenced {
+                referenced.extend(file.referenced_names());
+            }
+            file.warnings
+        }
+        Err(_) => {
+            *referenced = None;
+            vec![]
+        }
+    }
+}
+
+/// The names of the variables given with `--var` and `--secret-var`.
+/// Variables of files may be meant for other `.au` files, so they are left
+/// out.
+fn var_names(vars: &[(String, String)], secret_vars: &[(String, String)]) -> Vec<String> {
+    vars.iter()
+        .chain(secret_vars)
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Warns about the variables of `given` that no file refers to, unless
+/// warnings are allowed. Returns `false` if they are denied.
+fn warn_unused_vars(given: &[String], referenced: &HashSet<String>, warnings: WarningsArg) -> bool {
+    if warnings == WarningsArg::Allow {
+        return true;
+    }
+    let unused = given
+        .iter()
+        .filter(|it| !referenced.contains(*it))
+        .collect::<Vec<_>>();
+    for name in &unused {
+        tracing::warn!("The variable `{name}` is never used");
+    }
+    unused.is_empty() || warnings != WarningsArg::Deny
+}
+
+/// Prints `warnings` to stderr unless they are allowed. Returns `false` if
+/// they are denied, so the file must not run.
+fn print_warnings(
+    input: &str,
+    path: &Path,
+    warnings: &[diagnostic::Diagnostic],
+    options: DiagnosticOptions,
+    secrets: &secrets::Secrets,
+) -> anyhow::Result<bool> {
+    if warnings.is_empty() || options.warnings == WarningsArg::Allow {
+        return Ok(true);
+    }
+    eprintln!(
+        "{}",
+        secrets.redact(&render_diagnostics(input, path, warnings, options.format)?)
+    );
+    if options.warnings == WarningsArg::Deny {
+        tracing::error!(
+            "I didn't run `{}`, because warnings are denied",
+            path.display()
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+fn print_diagnostics(
+    input: &str,
+    path: &Path,
+    diagnostics: &[diagnostic::Diagnostic],
+    format: DiagnosticsFormat,
+) -> anyhow::Result<()> {
+    println!("{}", render_diagnostics(input, path, diagnostics, format)?);
+    Ok(())
+}
+
+fn eprint_diagnostics(
+    input: &str,
+    path: &Path,
+    diagnostics: &[diagnostic::Diagnostic],
+    format: DiagnosticsFormat,
+) -> anyhow::Result<()> {
+    eprintln!("{}", render_diagnostics(input, path, diagnostics, format)?);
     Ok(())
 }
+
+fn render_diagnostics(
+    input: &str,
+    path: &Path,
+    diagnostics: &[diagnostic::Diagnostic],
+    format: DiagnosticsFormat,
+) -> anyhow::Result<String> {
+    let line_index = line_index::LineIndex::new(input);
+    match format {
+        DiagnosticsFormat::Human => {
+            let mut buf = String::new();
+            diagnostic::dump(
+                input,
+                &line_index,
+                path,
+                diagnostics,
+                diagnostic::RenderStyle::Styled,
+                &mut buf,
+            )?;
+            Ok(buf)
+        }
+        DiagnosticsFormat::Json => Ok(diagnostics
+            .iter()
+            .map(|d| {
+                let mut json = d.to_json(&line_index);
+                json["file"] = serde_json::json!(path.to_string_lossy());
+                json.to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")),
+        DiagnosticsFormat::Sarif => {
+            Ok(diagnostic::to_sarif(input, &line_index, path, diagnostics).to_string())
+        }
+    }
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args = Args::parse();
+    let secrets = secrets::Secrets::new();
+    init_logging(args.log_level, args.log_format, &secrets);
+    let diagnostics = DiagnosticOptions {
+        format: args.diagnostics_format,
+        warnings: if args.deny_warnings {
+            WarningsArg::Deny
+        } else {
+            args.warnings
+        },
+    };
+
+    match args.cmd {
+        Command::Run(args) => run(*args, Mode::Run, diagnostics, secrets),
+        Command::Bench(args) => bench(*args, diagnostics, secrets),
+        Command::Test(args) => run(
+            args.run,
+            Mode::Test {
+                fail_fast: args.fail_fast,
+            },
+            diagnostics,
+            secrets,
+        ),
+        Command::ExplainPlan {
+            path,
+            entries,
+            vars,
+            env_files,
+            vars_files,
+            env,
+        } => explain_plan(
+            &path,
+            entries,
+            vars,
+            &env_files,
+            &vars_files,
+            env,
+            diagnostics,
+        ),
+        Command::List { path } => list(&path, diagnostics.format),
+        Command::Diff {
+            paths,
+            entry,
+            against,
+            and,
+            vars,
+            env,
+        } => {
+            let (a, b) = match (paths.as_slice(), entry, against, and) {
+                ([path], Some(entry), Some(against), Some(and)) => {
+                    let run = |vars_file| {
+                        run_for_diff(
+                            path,
+                            &entry,
+                            vars.clone(),
+                            vars_file,
+                            &env,
+                            diagnostics.format,
+                        )
+                    };
+                    (run(&against)?, run(&and)?)
+                }
+                ([a, b], None, None, None) => (read_body(a)?, read_body(b)?),
+                _ => anyhow::bail!(
+                    "expected a `.au` file with `--entry`, `--against` and `--and`, or two response files"
+                ),
+            };
+            diff(a, b)
+        }
+        Command::Fmt { paths, check } => fmt(&paths, check, diagnostics.format),
+        Command::Import(ImportFormat::Openapi { path }) => {
+            let spec = std::fs::read_to_string(&path)
+                .with_context(|| format!("could not read `{}`", path.to_string_lossy()))?;
+            let source = openapi::import(&spec).map_err(|e| {
+                anyhow::anyhow!("`{}` is not a valid OpenAPI document, {e}", path.display())
+            })?;
+            print!("{source}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::History {
+            entry,
+            status,
+            since,
+            limit,
+            json,
+            history_file,
+        } => {
+            let Some(path) = history_file.or_else(history::History::default_path) else {
+                anyhow::bail!("I couldn't find the history, pass `--history-file`");
+            };
+            let records = history::read(&path)
+                .with_context(|| format!("could not read `{}`", path.display()))?;
+            let query = history::Query {
+                entry,
+                status,
+                since: since.map(|it| {
+                    chrono::Utc::now() - chrono::Duration::from_std(it).unwrap_or_default()
+                }),
+                limit: Some(limit),
+            };
+            let records = query.apply(&records);
+            if json {
+                for record in records {
+                    println!("{}", record.to_json());
+                }
+            } else {
+                print!("{}", history::Table(&records));
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Explain { code } => {
+            let Some(code) = codes::find(&code) else {
+                anyhow::bail!("`{code}` is not an error code, they look like `AU0004`");
+            };
+            println!("{}: {}\n", code.id, code.title);
+            print!("{}", code.explanation);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
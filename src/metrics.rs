@@ -0,0 +1,153 @@
+use std::{collections::BTreeMap, fmt::Write, io, path::Path, time::Duration};
+
+use crate::client::StatusCode;
+
+/// Upper bounds of the latency histogram buckets in seconds. These are the
+/// Prometheus client defaults, so existing dashboards work without changes.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Request counters and a latency histogram, rendered in the Prometheus text
+/// exposition format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    responses: BTreeMap<u16, u64>,
+    errors: u64,
+    /// Number of observations per bucket in `LATENCY_BUCKETS`, not cumulative
+    latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+    latency_count: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a request that received a response.
+    pub fn record_response(&mut self, status: StatusCode, elapsed: Duration) {
+        *self.responses.entry(status.as_u16()).or_default() += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.latency_buckets[bucket] += 1;
+        }
+        self.latency_sum += seconds;
+        self.latency_count += 1;
+    }
+
+    /// Records a request that failed without a response.
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        _ = writeln!(
+            out,
+            "# HELP aurora_responses_total Number of requests that received a response."
+        );
+        _ = writeln!(out, "# TYPE aurora_responses_total counter");
+        for (status, count) in &self.responses {
+            _ = writeln!(out, "aurora_responses_total{{status=\"{status}\"}} {count}");
+        }
+
+        _ = writeln!(
+            out,
+            "# HELP aurora_errors_total Number of requests that failed without a response."
+        );
+        _ = writeln!(out, "# TYPE aurora_errors_total counter");
+        _ = writeln!(out, "aurora_errors_total {}", self.errors);
+
+        _ = writeln!(
+            out,
+            "# HELP aurora_request_duration_seconds Time until the whole response was received."
+        );
+        _ = writeln!(out, "# TYPE aurora_request_duration_seconds histogram");
+        let mut cumulative = 0;
+        for (le, count) in LATENCY_BUCKETS.iter().zip(self.latency_buckets) {
+            cumulative += count;
+            _ = writeln!(
+                out,
+                "aurora_request_duration_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            );
+        }
+        _ = writeln!(
+            out,
+            "aurora_request_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.latency_count
+        );
+        _ = writeln!(
+            out,
+            "aurora_request_duration_seconds_sum {}",
+            self.latency_sum
+        );
+        _ = writeln!(
+            out,
+            "aurora_request_duration_seconds_count {}",
+            self.latency_count
+        );
+
+        out
+    }
+
+    /// Writes the metrics to `path`.
+    ///
+    /// The file is replaced atomically, so collectors such as the node
+    /// exporter's textfile collector never read a partially written file
+    /// while a long run keeps updating it.
+    pub fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, self.render())?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use expect_test::expect;
+
+    use super::Metrics;
+    use crate::client::StatusCode;
+
+    #[test]
+    fn render() {
+        let mut metrics = Metrics::new();
+        metrics.record_response(StatusCode::from(200), Duration::from_millis(20));
+        metrics.record_response(StatusCode::from(200), Duration::from_millis(300));
+        metrics.record_response(StatusCode::from(404), Duration::from_secs(30));
+        metrics.record_error();
+
+        expect![[r##"
+            # HELP aurora_responses_total Number of requests that received a response.
+            # TYPE aurora_responses_total counter
+            aurora_responses_total{status="200"} 2
+            aurora_responses_total{status="404"} 1
+            # HELP aurora_errors_total Number of requests that failed without a response.
+            # TYPE aurora_errors_total counter
+            aurora_errors_total 1
+            # HELP aurora_request_duration_seconds Time until the whole response was received.
+            # TYPE aurora_request_duration_seconds histogram
+            aurora_request_duration_seconds_bucket{le="0.005"} 0
+            aurora_request_duration_seconds_bucket{le="0.01"} 0
+            aurora_request_duration_seconds_bucket{le="0.025"} 1
+            aurora_request_duration_seconds_bucket{le="0.05"} 1
+            aurora_request_duration_seconds_bucket{le="0.1"} 1
+            aurora_request_duration_seconds_bucket{le="0.25"} 1
+            aurora_request_duration_seconds_bucket{le="0.5"} 2
+            aurora_request_duration_seconds_bucket{le="1"} 2
+            aurora_request_duration_seconds_bucket{le="2.5"} 2
+            aurora_request_duration_seconds_bucket{le="5"} 2
+            aurora_request_duration_seconds_bucket{le="10"} 2
+            aurora_request_duration_seconds_bucket{le="+Inf"} 3
+            aurora_request_duration_seconds_sum 30.32
+            aurora_request_duration_seconds_count 3
+        "##]]
+        .assert_eq(&metrics.render());
+    }
+}
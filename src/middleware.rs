@@ -0,0 +1,70 @@
+use std::{fmt, sync::Arc};
+
+use crate::client::{HttpError, Request, Response};
+
+/// Hooks around every request the machine sends, for library users that
+/// want to add authentication, logging or metrics of their own.
+///
+/// Both hooks run on the thread that sends the request, so entries that run
+/// at the same time call them at the same time.
+pub trait Middleware: Send + Sync {
+    /// Called before `request` of the entry `entry` is sent, and before it is
+    /// signed, so a signature covers headers added here. An error stops the
+    /// entry without sending the request.
+    fn before_request(&self, _entry: &str, _request: &mut Request) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called with what sending `request` resulted in, before captures,
+    /// assertions and reports see it.
+    fn after_response(
+        &self,
+        _entry: &str,
+        _request: &Request,
+        _result: &mut Result<Response, HttpError>,
+    ) {
+    }
+}
+
+/// The middleware of a run. `before_request` hooks run in the order the
+/// middleware was added, `after_response` hooks in the reverse order, so the
+/// first middleware wraps all others.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain(Vec<Arc<dyn Middleware>>);
+
+impl MiddlewareChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, middleware: impl Middleware + 'static) {
+        self.0.push(Arc::new(middleware));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn before_request(&self, entry: &str, request: &mut Request) -> Result<(), String> {
+        self.0
+            .iter()
+            .try_for_each(|it| it.before_request(entry, request))
+    }
+
+    pub fn after_response(
+        &self,
+        entry: &str,
+        request: &Request,
+        result: &mut Result<Response, HttpError>,
+    ) {
+        for middleware in self.0.iter().rev() {
+            middleware.after_response(entry, request, result);
+        }
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MiddlewareChain({} middleware)", self.0.len())
+    }
+}
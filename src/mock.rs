@@ -0,0 +1,214 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    client::{CacheStatus, HttpClient, HttpError, Request, Response, StatusCode},
+    machine::matches_pattern,
+    validated::HttpMethod,
+};
+
+/// Answers requests with scripted responses instead of sending them, so
+/// `.au` files can be tested without a network. Every request is recorded.
+///
+/// A request gets the response of the first route that matches its method
+/// and URL. URL patterns may contain `*` and `?` wildcards, and a route
+/// added with [`on_once`](Self::on_once) only answers one request. Requests
+/// that no route matches fail with a transport error.
+///
+/// Clones share their routes and calls, so a clone can be handed to
+/// [`execute_with_client`](crate::machine::execute_with_client) for every
+/// thread while the original is asserted on.
+#[derive(Debug, Clone, Default)]
+pub struct MockHttpClient {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    calls: Vec<Request>,
+}
+
+#[derive(Debug)]
+struct Route {
+    method: HttpMethod,
+    url: String,
+    response: MockResponse,
+    once: bool,
+}
+
+impl MockHttpClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every `method` request to a URL matching `url` with
+    /// `response`.
+    pub fn on(&self, method: HttpMethod, url: &str, response: MockResponse) -> &Self {
+        self.add_route(method, url, response, false)
+    }
+
+    /// Like [`on`](Self::on), but only answers the next matching request.
+    /// Scripts a sequence of responses when added before a route with `on`.
+    pub fn on_once(&self, method: HttpMethod, url: &str, response: MockResponse) -> &Self {
+        self.add_route(method, url, response, true)
+    }
+
+    fn add_route(
+        &self,
+        method: HttpMethod,
+        url: &str,
+        response: MockResponse,
+        once: bool,
+    ) -> &Self {
+        self.state.lock().unwrap().routes.push(Route {
+            method,
+            url: url.to_string(),
+            response,
+            once,
+        });
+        self
+    }
+
+    /// The requests received so far, in the order they arrived.
+    pub fn calls(&self) -> Vec<Request> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+impl HttpClient for MockHttpClient {
+    fn send(&self, request: Request) -> Result<Response, HttpError> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(request.clone());
+        let i = state
+            .routes
+            .iter()
+            .position(|it| it.method == request.method && matches_pattern(&it.url, &request.url))
+            .ok_or_else(|| {
+                HttpError::Transport(format!(
+                    "no mock response for {} {}",
+                    request.method.as_str(),
+                    request.url
+                ))
+            })?;
+        let response = if state.routes[i].once {
+            state.routes.remove(i).response
+        } else {
+            state.routes[i].response.clone()
+        };
+        Ok(response.into())
+    }
+}
+
+/// A scripted response of a [`MockHttpClient`].
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a JSON body and its `Content-Type`.
+    pub fn json(self, body: &str) -> Self {
+        self.header("Content-Type", "application/json").body(body)
+    }
+}
+
+impl From<MockResponse> for Response {
+    fn from(response: MockResponse) -> Self {
+        Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(response.status),
+            headers: response.headers,
+            body: response.body,
+            elapsed: Duration::ZERO,
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RequestOptions;
+
+    fn request(method: HttpMethod, url: &str) -> Request {
+        Request {
+            method,
+            url: url.to_string(),
+            headers: vec![],
+            body: None,
+            options: RequestOptions::default(),
+            events: None,
+        }
+    }
+
+    #[test]
+    fn scripted_responses() {
+        let mock = MockHttpClient::new();
+        mock.on_once(
+            HttpMethod::Get,
+            "https://example.com/users/*",
+            MockResponse::new(503),
+        )
+        .on(
+            HttpMethod::Get,
+            "https://example.com/users/*",
+            MockResponse::new(200).json(r#"{"id": 1}"#),
+        );
+
+        let status = |method, url| {
+            HttpClient::send(&mock, request(method, url)).map(|it| it.status.as_u16())
+        };
+        let url = "https://example.com/users/1";
+        assert_eq!(status(HttpMethod::Get, url).ok(), Some(503));
+        assert_eq!(status(HttpMethod::Get, url).ok(), Some(200));
+        assert_eq!(
+            status(HttpMethod::Get, "https://example.com/users/2").ok(),
+            Some(200)
+        );
+        assert!(matches!(
+            status(HttpMethod::Delete, url),
+            Err(HttpError::Transport(_))
+        ));
+
+        let calls = mock
+            .calls()
+            .iter()
+            .map(|it| format!("{} {}", it.method.as_str(), it.url))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "GET https://example.com/users/1",
+                "GET https://example.com/users/1",
+                "GET https://example.com/users/2",
+                "DELETE https://example.com/users/1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,519 @@
+use std::{collections::HashSet, fmt::Write};
+
+use serde_json::{Map, Value};
+
+use crate::{format, lexer::escape_identifier};
+
+/// The methods of a path item, in the order their entries are written.
+const METHODS: [&str; 7] = ["get", "put", "post", "delete", "options", "head", "patch"];
+
+/// How many references in a row are followed before giving up, since they
+/// may form a cycle.
+const MAX_REFS: usize = 16;
+
+/// Generates an `.au` skeleton from an OpenAPI document, in JSON or YAML.
+///
+/// Every operation becomes an entry named after its `operationId`. Path
+/// parameters and required query and header parameters become parameters of
+/// the entry, and JSON request bodies are filled with an example derived
+/// from their schema. The first server becomes the `[BaseUrl]` of the
+/// `defaults` block.
+pub fn import(spec: &str) -> Result<String, String> {
+    // YAML is a superset of JSON, so both are read by the YAML parser.
+    let spec = serde_yaml::from_str::<Value>(spec).map_err(|e| e.to_string())?;
+    let Some(paths) = spec.get("paths").and_then(Value::as_object) else {
+        return Err("expected a `paths` object at the top level".to_string());
+    };
+
+    let mut out = String::new();
+    if let Some(info) = spec.get("info") {
+        let title = info.get("title").and_then(Value::as_str).unwrap_or("API");
+        match info.get("version").and_then(Value::as_str) {
+            Some(version) => _ = writeln!(out, "# {title} {version}\n"),
+            None => _ = writeln!(out, "# {title}\n"),
+        }
+    }
+    if let Some(base_url) = base_url(&spec) {
+        _ = writeln!(out, "defaults {{\n[BaseUrl] {}\n}}\n", string(&base_url));
+    }
+
+    let mut names = HashSet::new();
+    for (path, item) in paths {
+        let item = resolve(&spec, item);
+        for method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            let operation = resolve(&spec, operation);
+            let name = unique_name(&mut names, &entry_name(method, path, operation));
+            write_entry(&mut out, &spec, &name, method, path, item, operation);
+        }
+    }
+
+    format::format_source(&out).map_err(|d| format!("generated invalid source, {}", d[0].message))
+}
+
+fn write_entry(
+    out: &mut String,
+    spec: &Value,
+    name: &str,
+    method: &str,
+    path: &str,
+    item: &Value,
+    operation: &Value,
+) {
+    let mut params = vec![];
+    let mut query = vec![];
+    let mut headers = vec![];
+    let mut optional = vec![];
+    // The path parameters are replaced by templates after escaping the path.
+    let mut url = escape(path);
+    let mut body = None;
+
+    // Parameters of the operation override those of the path item with the
+    // same name and location.
+    let mut parameters = Vec::<&Value>::new();
+    for list in [item.get("parameters"), operation.get("parameters")] {
+        for parameter in list.and_then(Value::as_array).into_iter().flatten() {
+            let parameter = resolve(spec, parameter);
+            parameters.retain(|it| {
+                it.get("name") != parameter.get("name") || it.get("in") != parameter.get("in")
+            });
+            parameters.push(parameter);
+        }
+    }
+    for parameter in parameters {
+        let Some(raw) = parameter.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let required = parameter.get("required").and_then(Value::as_bool) == Some(true);
+        let param = escape_identifier(&identifier(raw)).into_owned();
+        match parameter.get("in").and_then(Value::as_str) {
+            Some("path") => {
+                url = url.replace(&format!("{{{raw}}}"), &format!("{{{{{param}}}}}"));
+                params.push(param);
+            }
+            Some("query") if required => {
+                query.push(format!("{}: {param}", string(raw)));
+                params.push(param);
+            }
+            Some("header") if required => {
+                headers.push(format!("{}: {param}", string(raw)));
+                params.push(param);
+            }
+            Some("query") => optional.push(format!("`{raw}`")),
+            // Swagger 2.0 describes the request body as a parameter.
+            Some("body") => {
+                body = parameter
+                    .get("schema")
+                    .map(|it| example(spec, it, &mut vec![]))
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(content) = operation
+        .get("requestBody")
+        .map(|it| resolve(spec, it))
+        .and_then(|it| it.get("content"))
+        .and_then(Value::as_object)
+        && let Some((_, media)) = content.iter().find(|(ty, _)| ty.contains("json"))
+    {
+        body = media_example(spec, media);
+    }
+
+    for key in ["summary", "description"] {
+        if let Some(doc) = operation.get(key).and_then(Value::as_str) {
+            for line in doc.lines().map(str::trim).filter(|it| !it.is_empty()) {
+                _ = writeln!(out, "# {line}");
+            }
+            break;
+        }
+    }
+    if !optional.is_empty() {
+        _ = writeln!(out, "# Optional query parameters: {}", optional.join(", "));
+    }
+    let tags = operation
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|tag| format!("@{} ", escape_identifier(&identifier(tag))))
+        .collect::<String>();
+    _ = write!(out, "{tags}entry {name}");
+    if !params.is_empty() {
+        _ = write!(out, "({})", params.join(", "));
+    }
+    _ = writeln!(out, " {{\n{} \"{url}\"", method.to_uppercase());
+    if !headers.is_empty() {
+        _ = writeln!(out, "[Headers] {{{}}}", headers.join(", "));
+    }
+    if !query.is_empty() {
+        _ = writeln!(out, "[Query] {{{}}}", query.join(", "));
+    }
+    match body {
+        // A body has to be a dictionary, anything else is left to the user.
+        Some(body @ Value::Object(_)) => _ = writeln!(out, "[Body] {}", expr(&body)),
+        Some(_) => _ = writeln!(out, "# [Body] expects JSON that isn't an object"),
+        None => {}
+    }
+    _ = writeln!(out, "}}\n");
+}
+
+/// Returns the URL of the first server, with its variables replaced by
+/// their defaults. Swagger 2.0 documents give the host and base path
+/// instead.
+fn base_url(spec: &Value) -> Option<String> {
+    if let Some(server) = spec
+        .get("servers")
+        .and_then(Value::as_array)
+        .and_then(|it| it.first())
+    {
+        let mut url = server.get("url")?.as_str()?.to_string();
+        for (name, variable) in server
+            .get("variables")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(default) = variable.get("default").and_then(Value::as_str) {
+                url = url.replace(&format!("{{{name}}}"), default);
+            }
+        }
+        return Some(url);
+    }
+
+    let host = spec.get("host")?.as_str()?;
+    let scheme = spec
+        .get("schemes")
+        .and_then(|it| it.get(0))
+        .and_then(Value::as_str)
+        .unwrap_or("https");
+    let base_path = spec.get("basePath").and_then(Value::as_str).unwrap_or("");
+    Some(format!("{scheme}://{host}{base_path}"))
+}
+
+/// Follows a `$ref` to a local definition, e.g. `#/components/schemas/Pet`.
+/// Returns `value` itself if it isn't a reference or the reference can't be
+/// followed.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> &'a Value {
+    let mut value = value;
+    // References may point to references, but not forever.
+    for _ in 0..MAX_REFS {
+        match value
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|it| it.strip_prefix('#'))
+            .and_then(|pointer| spec.pointer(pointer))
+        {
+            Some(target) => value = target,
+            None => break,
+        }
+    }
+    value
+}
+
+/// Returns the example of a media type, or one derived from its schema.
+fn media_example(spec: &Value, media: &Value) -> Option<Value> {
+    if let Some(example) = media.get("example") {
+        return Some(example.clone());
+    }
+    if let Some((_, example)) = media
+        .get("examples")
+        .and_then(Value::as_object)
+        .and_then(|it| it.iter().next())
+    {
+        return resolve(spec, example).get("value").cloned();
+    }
+    media.get("schema").map(|it| example(spec, it, &mut vec![]))
+}
+
+/// Derives an example value from a schema, preferring the examples,
+/// defaults and enums the schema gives. `refs` are the references that are
+/// being followed, a schema that refers to one of them is left `null`.
+fn example<'a>(spec: &'a Value, schema: &'a Value, refs: &mut Vec<&'a str>) -> Value {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        if refs.contains(&reference) {
+            return Value::Null;
+        }
+        refs.push(reference);
+        let example = example(spec, resolve(spec, schema), refs);
+        refs.pop();
+        return example;
+    }
+
+    for key in ["example", "default"] {
+        if let Some(value) = schema.get(key) {
+            return value.clone();
+        }
+    }
+    if let Some(value) = schema
+        .get("enum")
+        .and_then(Value::as_array)
+        .and_then(|it| it.first())
+    {
+        return value.clone();
+    }
+    if let Some(schemas) = schema.get("allOf").and_then(Value::as_array) {
+        let mut merged = Map::new();
+        for schema in schemas {
+            match example(spec, schema, refs) {
+                Value::Object(fields) => merged.extend(fields),
+                other => return other,
+            }
+        }
+        return Value::Object(merged);
+    }
+    for key in ["oneOf", "anyOf"] {
+        if let Some(schema) = schema
+            .get(key)
+            .and_then(Value::as_array)
+            .and_then(|it| it.first())
+        {
+            return example(spec, schema, refs);
+        }
+    }
+
+    // OpenAPI 3.1 allows a list of types, e.g. `["string", "null"]`.
+    let ty = match schema.get("type") {
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|it| *it != "null"),
+        Some(ty) => ty.as_str(),
+        None if schema.get("properties").is_some() => Some("object"),
+        None => None,
+    };
+    match ty {
+        Some("object") => Value::Object(
+            schema
+                .get("properties")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .map(|(name, schema)| (name.clone(), example(spec, schema, refs)))
+                .collect(),
+        ),
+        Some("array") => Value::Array(
+            schema
+                .get("items")
+                .map(|it| example(spec, it, refs))
+                .filter(|it| !it.is_null())
+                .into_iter()
+                .collect(),
+        ),
+        Some("string") => Value::from(match schema.get("format").and_then(Value::as_str) {
+            Some("date") => "2024-01-01",
+            Some("date-time") => "2024-01-01T00:00:00Z",
+            Some("email") => "user@example.com",
+            Some("uuid") => "00000000-0000-0000-0000-000000000000",
+            Some("uri" | "url") => "https://example.com",
+            _ => "string",
+        }),
+        Some("integer") => Value::from(0),
+        Some("number") => Value::from(0.0),
+        Some("boolean") => Value::from(false),
+        _ => Value::Null,
+    }
+}
+
+/// Names an entry after the operation ID, or after the method and the path
+/// if the operation has none.
+fn entry_name(method: &str, path: &str, operation: &Value) -> String {
+    if let Some(id) = operation.get("operationId").and_then(Value::as_str) {
+        return identifier(id);
+    }
+    let words = path
+        .split('/')
+        .map(|it| it.trim_matches(['{', '}']))
+        .filter(|it| !it.is_empty())
+        .collect::<Vec<_>>();
+    identifier(&format!("{method} {}", words.join(" ")))
+}
+
+/// Appends a number to `name` if an entry with the name already exists.
+fn unique_name(names: &mut HashSet<String>, name: &str) -> String {
+    let mut unique = name.to_string();
+    let mut n = 2;
+    while !names.insert(unique.clone()) {
+        unique = format!("{name}{n}");
+        n += 1;
+    }
+    escape_identifier(&unique).into_owned()
+}
+
+/// Turns a name like `get-user by id` into the identifier `getUserById`.
+fn identifier(name: &str) -> String {
+    let mut out = String::new();
+    for (i, word) in name
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|it| !it.is_empty())
+        .enumerate()
+    {
+        let mut chars = word.chars();
+        if i > 0
+            && let Some(first) = chars.next()
+        {
+            out.extend(first.to_uppercase());
+        }
+        out.push_str(chars.as_str());
+    }
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+fn string(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+/// Escapes text for a string literal. `{{` would start a template, so the
+/// literal is split into a concatenation there.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace("{{", "{\" + \"{")
+}
+
+/// Writes JSON as an expression.
+fn expr(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        // There are no negative literals.
+        Value::Number(n) if n.to_string().starts_with('-') => {
+            format!("0 - {}", &n.to_string()[1..])
+        }
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => string(s),
+        Value::Array(items) => {
+            format!(
+                "[{}]",
+                items.iter().map(expr).collect::<Vec<_>>().join(", ")
+            )
+        }
+        Value::Object(fields) => format!(
+            "{{{}}}",
+            fields
+                .iter()
+                .map(|(name, value)| format!("{}: {}", string(name), expr(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::import;
+
+    #[test]
+    fn import_openapi() {
+        let spec = r##"
+openapi: 3.0.0
+info:
+  title: Petstore
+  version: 1.0.0
+servers:
+  - url: https://{region}.example.com/v1
+    variables:
+      region:
+        default: eu
+paths:
+  /pets:
+    get:
+      operationId: list-pets
+      summary: List all pets
+      tags: [pets]
+      parameters:
+        - {name: limit, in: query, schema: {type: integer}}
+        - {name: X-Api-Key, in: header, required: true, schema: {type: string}}
+    post:
+      tags: [pets]
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: "#/components/schemas/Pet"
+  /pets/{pet_id}:
+    parameters:
+      - {name: pet_id, in: path, required: true, schema: {type: string}}
+    get:
+      summary: |
+        Info for a specific pet.
+        Returns 404 if there is none.
+    delete:
+      operationId: delete
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        id: {type: integer, example: -1}
+        name: {type: string}
+        status: {type: string, enum: [available, sold]}
+        born: {type: string, format: date}
+        weight: {type: number}
+        tags: {type: array, items: {type: string}}
+        owner: {$ref: "#/components/schemas/Owner"}
+    Owner:
+      allOf:
+        - properties:
+            email: {type: string, format: email}
+        - properties:
+            pets: {type: array, items: {$ref: "#/components/schemas/Pet"}}
+"##;
+
+        expect![[r##"
+            # Petstore 1.0.0
+
+            defaults {
+                [BaseUrl] "https://eu.example.com/v1"
+            }
+
+            # List all pets
+            # Optional query parameters: `limit`
+            @pets
+            entry listPets(XApiKey) {
+                GET "/pets"
+                [Headers] {"X-Api-Key": XApiKey}
+            }
+
+            @pets
+            entry postPets {
+                POST "/pets"
+                [Body] {
+                    "id": 0 - 1,
+                    "name": "string",
+                    "status": "available",
+                    "born": "2024-01-01",
+                    "weight": 0.0,
+                    "tags": ["string"],
+                    "owner": {"email": "user@example.com", "pets": []},
+                }
+            }
+
+            # Info for a specific pet.
+            # Returns 404 if there is none.
+            entry getPetsPet_id(pet_id) {
+                GET "/pets/{{pet_id}}"
+            }
+
+            entry delete(pet_id) {
+                DELETE "/pets/{{pet_id}}"
+            }
+        "##]]
+        .assert_eq(&import(spec).unwrap());
+
+        assert_eq!(
+            import("openapi: 3.0.0").unwrap_err(),
+            "expected a `paths` object at the top level"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    collections::HashSet,
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::client::Response;
+
+/// Writes response bodies to files in a directory, for `--output-dir`.
+///
+/// The file name comes from a pattern with the placeholders `{entry}`,
+/// `{status}` and `{ext}`, the extension that matches the `Content-Type` of
+/// the response. A file that would overwrite one written earlier in the same
+/// run gets a `-2`, `-3`, ... suffix instead, e.g. for the responses of a
+/// `for` loop.
+#[derive(Debug, Clone)]
+pub struct OutputDir {
+    dir: PathBuf,
+    pattern: String,
+    written: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl OutputDir {
+    pub const DEFAULT_PATTERN: &str = "{entry}.{ext}";
+
+    pub fn new(dir: PathBuf, pattern: String) -> Self {
+        Self {
+            dir,
+            pattern,
+            written: Arc::default(),
+        }
+    }
+
+    /// Writes the body of `response` of the entry `entry` and returns the
+    /// path of the file.
+    pub fn write(&self, entry: &str, response: &Response) -> io::Result<PathBuf> {
+        let name = self
+            .pattern
+            .replace("{entry}", entry)
+            .replace("{status}", &response.status.as_u16().to_string())
+            .replace("{ext}", extension(response));
+        let path = {
+            let mut written = self.written.lock().unwrap();
+            let path = unused_path(&self.dir.join(name), &written);
+            written.insert(path.clone());
+            path
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, &response.body)?;
+        Ok(path)
+    }
+}
+
+/// Returns `path`, or `path` with a numbered suffix before the extension if
+/// it was already written.
+fn unused_path(path: &Path, written: &HashSet<PathBuf>) -> PathBuf {
+    if !written.contains(path) {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    (2..)
+        .map(|n| {
+            let name = match path.extension() {
+                Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+                None => format!("{stem}-{n}"),
+            };
+            path.with_file_name(name)
+        })
+        .find(|it| !written.contains(it))
+        .unwrap()
+}
+
+/// Guesses the file extension of a body from the `Content-Type` of its
+/// response. Unknown types get `bin`.
+fn extension(response: &Response) -> &'static str {
+    let content_type = response
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+        .map(|(_, value)| value.split(';').next().unwrap_or_default().trim())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match content_type.as_str() {
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "text/csv" => "csv",
+        "text/css" => "css",
+        "text/event-stream" => "sse",
+        "text/javascript" | "application/javascript" => "js",
+        "application/yaml" | "application/x-yaml" | "text/yaml" => "yaml",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" | "application/x-gzip" => "gz",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        ty if ty.ends_with("+json") => "json",
+        ty if ty.ends_with("+xml") => "xml",
+        ty if ty.starts_with("text/") => "txt",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::client::{CacheStatus, StatusCode};
+
+    fn response(status: u16, content_type: Option<&str>, body: &str) -> Response {
+        Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(status),
+            headers: content_type
+                .map(|it| ("Content-Type".to_string(), it.to_string()))
+                .into_iter()
+                .collect(),
+            body: body.as_bytes().to_vec(),
+            elapsed: Duration::ZERO,
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        }
+    }
+
+    #[test]
+    fn extensions_from_content_type() {
+        let ext = |ty| extension(&response(200, ty, ""));
+        assert_eq!(ext(Some("application/json; charset=utf-8")), "json");
+        assert_eq!(ext(Some("application/problem+json")), "json");
+        assert_eq!(ext(Some("Image/PNG")), "png");
+        assert_eq!(ext(Some("text/markdown")), "txt");
+        assert_eq!(ext(Some("application/octet-stream")), "bin");
+        assert_eq!(ext(None), "bin");
+    }
+
+    #[test]
+    fn write_bodies() {
+        let dir = std::env::temp_dir().join(format!("aurora-output-{}", std::process::id()));
+        let output = OutputDir::new(dir.clone(), "{entry}-{status}.{ext}".to_string());
+
+        let json = response(200, Some("application/json"), "{}");
+        let html = response(404, Some("text/html"), "<p>Not found</p>");
+        let written = [
+            output.write("getUser", &json).unwrap(),
+            output.write("getUser", &json).unwrap(),
+            output.write("getUser", &json).unwrap(),
+            output.write("missing", &html).unwrap(),
+        ];
+        let names = written
+            .iter()
+            .map(|it| {
+                it.strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "getUser-200.json",
+                "getUser-200-2.json",
+                "getUser-200-3.json",
+                "missing-404.html"
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("missing-404.html")).unwrap(),
+            "<p>Not found</p>"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,35 +1,103 @@
+use std::{iter, vec};
+
 use crate::{
     ast::{
-        DictionaryField, Entry, EntryItem, EntryItemKind, Expr, ExprKind, HttpMethod, Item,
-        ItemKind, Name, Request, SourceFile, TemplatePart,
+        Assertion, BinOp, CompareOp, DictionaryField, Entry, EntryItem, EntryItemKind, Expr,
+        ExprKind, HttpMethod, Item, ItemKind, Name, Need, Request, SourceFile, TemplatePart,
+        TypeExpr, TypeExprKind,
     },
+    codes,
     diagnostic::{Diagnostic, Level},
     lexer,
     span::Span,
     token::{self, Delim, Keyword, Token, TokenKind},
 };
 
-pub fn parse<'input>(input: &'input str) -> Result<SourceFile<'input>, Diagnostic> {
-    let tokens = lexer::lex(input)?;
-    let mut parser = Parser::new(tokens);
-    parser.parse()
+/// Parses `input`, or returns all syntax errors in the order they appear.
+pub fn parse<'input>(input: &'input str) -> Result<SourceFile<'input>, Vec<Diagnostic>> {
+    Parser::new(lexer::Lexer::new(input)).parse_file()
+}
+
+pub fn parse_tokens(tokens: Vec<Token<'_>>) -> Result<SourceFile<'_>, Vec<Diagnostic>> {
+    Parser::new(token_stream(tokens)).parse_file()
+}
+
+/// Parses `input` as a single expression, e.g. a value given on the command
+/// line.
+pub fn parse_expr(input: &str) -> Result<Expr<'_>, Diagnostic> {
+    let mut parser = Parser::new(lexer::Lexer::new(input));
+    let result = parser.parse_expr();
+    if let Some(diag) = parser.lex_error {
+        return Err(diag);
+    }
+
+    let expr = result?;
+    if parser.peek().is_some() {
+        return Err(Diagnostic::error("Unexpected token", parser.peek_span())
+            .code(codes::SYNTAX)
+            .primary_label(
+                "I was expecting the end of the expression here",
+                Level::Error,
+            ));
+    }
+    Ok(expr)
+}
+
+type TokenStream<'input> =
+    iter::Map<vec::IntoIter<Token<'input>>, fn(Token<'input>) -> Result<Token<'input>, Diagnostic>>;
+
+fn token_stream(tokens: Vec<Token<'_>>) -> TokenStream<'_> {
+    tokens.into_iter().map(Ok as fn(_) -> _)
 }
 
-struct Parser<'input> {
-    tokens: Vec<Token<'input>>,
-    pos: usize,
+struct Parser<'input, I> {
+    tokens: I,
+    current: Option<Token<'input>>,
+    prev_span: Span,
+    lex_error: Option<Diagnostic>,
+    /// Number of delimiters opened and not yet closed
+    depth: usize,
 }
 
-impl<'input> Parser<'input> {
-    fn new(tokens: Vec<Token<'input>>) -> Self {
-        Self { tokens, pos: 0 }
+impl<'input, I> Parser<'input, I>
+where
+    I: Iterator<Item = Result<Token<'input>, Diagnostic>>,
+{
+    fn new(tokens: I) -> Self {
+        let mut parser = Self {
+            tokens,
+            current: None,
+            prev_span: Span::new(0, 0),
+            lex_error: None,
+            depth: 0,
+        };
+        parser.current = parser.next_token();
+        parser
     }
 
-    fn parse(&mut self) -> Result<SourceFile<'input>, Diagnostic> {
+    /// Parses the whole token stream.
+    ///
+    /// An item with a syntax error is skipped up to the start of the next
+    /// item, so the errors of all items are reported at once. A lexer error
+    /// ends the stream, so it is the last error.
+    fn parse_file(mut self) -> Result<SourceFile<'input>, Vec<Diagnostic>> {
         let mut items = vec![];
+        let mut errors = vec![];
         while self.peek().is_some() {
-            let item = self.parse_item()?;
-            items.push(item);
+            let start = self.peek_span().start;
+            match self.parse_item() {
+                Ok(item) => items.push(item),
+                // The item only ended early because the lexer stopped.
+                Err(_) if self.lex_error.is_some() => {}
+                Err(diag) => {
+                    errors.push(diag);
+                    self.synchronize(start);
+                }
+            }
+        }
+        errors.extend(self.lex_error);
+        if !errors.is_empty() {
+            return Err(errors);
         }
 
         let span = if let (Some(first), Some(last)) = (items.first(), items.last()) {
@@ -41,25 +109,142 @@ impl<'input> Parser<'input> {
         Ok(SourceFile { items, span })
     }
 
+    /// Skips the tokens of the broken item that started at `start`, up to
+    /// the next `entry` or tag, or the next `const`, `env` or `defaults`
+    /// that starts a line outside of any delimiters.
+    fn synchronize(&mut self, start: usize) {
+        while let Some(token) = self.peek() {
+            if token.span.start > start {
+                match token.kind {
+                    TokenKind::Keyword(Keyword::Entry) | TokenKind::At => {
+                        // Whatever is still open belongs to the broken item.
+                        self.depth = 0;
+                        return;
+                    }
+                    TokenKind::Keyword(Keyword::Const)
+                    | TokenKind::Identifier("secret" | "env" | "defaults")
+                        if self.depth == 0 && token.skipped_newline =>
+                    {
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+            self.bump();
+        }
+    }
+
     fn parse_item(&mut self) -> Result<Item<'input>, Diagnostic> {
+        let mut tags = vec![];
+        let mut tags_span = None;
+        while let Some(at_span) = self.eat(TokenKind::At) {
+            let name = self.parse_name().ok_or(
+                Diagnostic::error("Expected identifier", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label("I was expecting a tag name here", Level::Error),
+            )?;
+            tags_span.get_or_insert(at_span);
+            tags.push(name);
+        }
+
         if let Some(span) = self.eat_keyword(Keyword::Entry) {
-            return self.parse_entry(span);
+            return self.parse_entry(tags_span.unwrap_or(span), tags);
+        }
+
+        if !tags.is_empty() {
+            return Err(Diagnostic::error("Expected entry", self.peek_span())
+                .code(codes::SYNTAX)
+                .primary_label("I was expecting an entry after its tags here", Level::Error));
+        }
+
+        if let Some((span, secret)) = self.eat_const()? {
+            return self.parse_const(span, secret);
         }
 
-        if let Some(span) = self.eat_keyword(Keyword::Const) {
-            return self.parse_const(span);
+        // `env` and `defaults` are not keywords, so `env(...)` can still be
+        // called and `defaults` used as a name.
+        if let Some(&Token {
+            kind: TokenKind::Identifier("env"),
+            span,
+            ..
+        }) = self.peek()
+        {
+            self.bump();
+            return self.parse_env(span);
+        }
+        if let Some(&Token {
+            kind: TokenKind::Identifier("defaults"),
+            span,
+            ..
+        }) = self.peek()
+        {
+            self.bump();
+            return self.parse_defaults(span);
         }
 
         Err(Diagnostic::error("Expected item", self.peek_span())
+            .code(codes::SYNTAX)
             .primary_label("I was expecting an item here", Level::Error))
     }
 
-    fn parse_entry(&mut self, entry_span: Span) -> Result<Item<'input>, Diagnostic> {
+    fn parse_entry(
+        &mut self,
+        entry_span: Span,
+        tags: Vec<Name<'input>>,
+    ) -> Result<Item<'input>, Diagnostic> {
         let name = self.parse_name().ok_or(
             Diagnostic::error("Expected identifier", self.peek_span())
+                .code(codes::SYNTAX)
                 .primary_label("I was expecting a name here", Level::Error),
         )?;
 
+        let params = if self.eat(TokenKind::Delim(Delim::OpenParen)).is_some() {
+            self.parse_entry_params()?
+        } else {
+            vec![]
+        };
+
+        let extends = match self.eat_keyword(Keyword::Extends) {
+            Some(_) => Some(
+                self.parse_name().ok_or(
+                    Diagnostic::error("Expected identifier", self.peek_span())
+                        .code(codes::SYNTAX)
+                        .primary_label("I was expecting the name of an entry here", Level::Error),
+                )?,
+            ),
+            None => None,
+        };
+
+        // Like `env`, `needs` is not a keyword, so it stays usable as a name.
+        let mut needs = vec![];
+        if let Some(Token {
+            kind: TokenKind::Identifier("needs"),
+            ..
+        }) = self.peek()
+        {
+            self.bump();
+            loop {
+                let name = self.parse_name().ok_or(
+                    Diagnostic::error("Expected identifier", self.peek_span())
+                        .code(codes::SYNTAX)
+                        .primary_label("I was expecting the name of an entry here", Level::Error),
+                )?;
+                // Like a function call, the arguments start on the same line.
+                let args = match self.peek() {
+                    Some(Token {
+                        kind: TokenKind::Delim(Delim::OpenParen),
+                        skipped_newline: false,
+                        ..
+                    }) => Some(self.parse_args()?.0),
+                    _ => None,
+                };
+                needs.push(Need { name, args });
+                if self.eat(TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+        }
+
         _ = self.expect_delim(Delim::OpenBrace)?;
         let mut entry_items = vec![];
         while let Some(item) = self.opt_parse_entry_item()? {
@@ -70,31 +255,147 @@ impl<'input> Parser<'input> {
         Ok(Item {
             kind: ItemKind::Entry(Entry {
                 name,
+                tags,
+                params,
+                extends,
+                needs,
                 body: entry_items,
             }),
             span,
         })
     }
 
-    fn parse_const(&mut self, const_span: Span) -> Result<Item<'input>, Diagnostic> {
+    /// Parses the parameter names of an entry after its opening parenthesis.
+    fn parse_entry_params(&mut self) -> Result<Vec<Name<'input>>, Diagnostic> {
+        let mut params = vec![];
+        while self.eat(TokenKind::Delim(Delim::CloseParen)).is_none() {
+            let name = self.parse_name().ok_or(
+                Diagnostic::error("Expected identifier", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label("I was expecting a parameter name here", Level::Error),
+            )?;
+            params.push(name);
+
+            if self.eat(TokenKind::Comma).is_none() {
+                _ = self.expect_delim(Delim::CloseParen)?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    fn parse_env(&mut self, env_span: Span) -> Result<Item<'input>, Diagnostic> {
+        let name = self.parse_name().ok_or(
+            Diagnostic::error("Expected identifier", self.peek_span())
+                .code(codes::SYNTAX)
+                .primary_label(
+                    "I was expecting the name of an environment here",
+                    Level::Error,
+                ),
+        )?;
+
+        _ = self.expect_delim(Delim::OpenBrace)?;
+        let mut items = vec![];
+        while let Some((span, secret)) = self.eat_const()? {
+            items.push(self.parse_const(span, secret)?);
+        }
+        let close_span = self.expect_delim(Delim::CloseBrace)?;
+        Ok(Item {
+            kind: ItemKind::Env(name, items),
+            span: env_span.to(close_span),
+        })
+    }
+
+    fn parse_defaults(&mut self, defaults_span: Span) -> Result<Item<'input>, Diagnostic> {
+        _ = self.expect_delim(Delim::OpenBrace)?;
+        let mut items = vec![];
+        while let Some(item) = self.opt_parse_entry_item()? {
+            items.push(item);
+        }
+        let close_span = self.expect_delim(Delim::CloseBrace)?;
+        Ok(Item {
+            kind: ItemKind::Defaults(items),
+            span: defaults_span.to(close_span),
+        })
+    }
+
+    /// Eats `const` or `secret const` and returns the span of the first word
+    /// and whether the const is secret.
+    fn eat_const(&mut self) -> Result<Option<(Span, bool)>, Diagnostic> {
+        // `secret` is not a keyword, so it stays usable as a name.
+        if let Some(&Token {
+            kind: TokenKind::Identifier("secret"),
+            span,
+            ..
+        }) = self.peek()
+        {
+            self.bump();
+            if self.eat_keyword(Keyword::Const).is_none() {
+                return Err(Diagnostic::error("Expected `const`", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label("I was expecting `const` after `secret` here", Level::Error));
+            }
+            return Ok(Some((span, true)));
+        }
+        Ok(self.eat_keyword(Keyword::Const).map(|span| (span, false)))
+    }
+
+    fn parse_const(&mut self, const_span: Span, secret: bool) -> Result<Item<'input>, Diagnostic> {
         let name = self.parse_name().ok_or(
             Diagnostic::error("Expected identifier", self.peek_span())
+                .code(codes::SYNTAX)
                 .primary_label("I was expecting a variable name here", Level::Error),
         )?;
 
+        let ty = match self.eat(TokenKind::Colon) {
+            Some(_) => Some(self.parse_type()?),
+            None => None,
+        };
+
         if self.eat(TokenKind::Eq).is_none() {
-            return Err(Diagnostic::error("Expected `=`", self.peek_span()));
+            return Err(Diagnostic::error("Expected `=`", self.peek_span()).code(codes::SYNTAX));
         }
 
         let expr = self.parse_expr()?;
         self.expect_newline()?;
         let span = const_span.to(expr.span);
         Ok(Item {
-            kind: ItemKind::Const(name, expr),
+            kind: ItemKind::Const(name, ty, expr, secret),
             span,
         })
     }
 
+    fn parse_type(&mut self) -> Result<TypeExpr<'input>, Diagnostic> {
+        if let Some(name) = self.parse_name() {
+            return Ok(TypeExpr {
+                kind: TypeExprKind::Named(name),
+                span: name.span,
+            });
+        }
+
+        if let Some(open_span) = self.eat(TokenKind::Delim(Delim::OpenBrack)) {
+            let elem = self.parse_type()?;
+            let close_span = self.expect_delim(Delim::CloseBrack)?;
+            return Ok(TypeExpr {
+                kind: TypeExprKind::Array(Box::new(elem)),
+                span: open_span.to(close_span),
+            });
+        }
+
+        if let Some(open_span) = self.eat(TokenKind::Delim(Delim::OpenBrace)) {
+            let value = self.parse_type()?;
+            let close_span = self.expect_delim(Delim::CloseBrace)?;
+            return Ok(TypeExpr {
+                kind: TypeExprKind::Dictionary(Box::new(value)),
+                span: open_span.to(close_span),
+            });
+        }
+
+        Err(Diagnostic::error("Expected type", self.peek_span())
+            .code(codes::SYNTAX)
+            .primary_label("I was expecting a type here", Level::Error))
+    }
+
     fn opt_parse_entry_item(&mut self) -> Result<Option<EntryItem<'input>>, Diagnostic> {
         match self.peek() {
             Some(&Token {
@@ -178,6 +479,62 @@ impl<'input> Parser<'input> {
                     span: method_span.to(url_span),
                 }))
             }
+            Some(&Token {
+                kind: TokenKind::HttpMethod(token::HttpMethod::Head),
+                span: method_span,
+                ..
+            }) => {
+                self.bump();
+                let url = self.parse_expr()?;
+                let url_span = url.span;
+                Ok(Some(EntryItem {
+                    kind: EntryItemKind::Request(Request {
+                        method: HttpMethod::Head,
+                        url,
+                    }),
+                    span: method_span.to(url_span),
+                }))
+            }
+            Some(&Token {
+                kind: TokenKind::HttpMethod(token::HttpMethod::Options),
+                span: method_span,
+                ..
+            }) => {
+                self.bump();
+                let url = self.parse_expr()?;
+                let url_span = url.span;
+                Ok(Some(EntryItem {
+                    kind: EntryItemKind::Request(Request {
+                        method: HttpMethod::Options,
+                        url,
+                    }),
+                    span: method_span.to(url_span),
+                }))
+            }
+            Some(&Token {
+                kind: TokenKind::Keyword(Keyword::For),
+                span: for_span,
+                ..
+            }) => {
+                self.bump();
+                let name = self.parse_name().ok_or(
+                    Diagnostic::error("Expected identifier", self.peek_span())
+                        .code(codes::SYNTAX)
+                        .primary_label("I was expecting a variable name here", Level::Error),
+                )?;
+                self.expect_keyword(Keyword::In, "in")?;
+                let items = self.parse_expr()?;
+                _ = self.expect_delim(Delim::OpenBrace)?;
+                let mut body = vec![];
+                while let Some(item) = self.opt_parse_entry_item()? {
+                    body.push(item);
+                }
+                let close_span = self.expect_delim(Delim::CloseBrace)?;
+                Ok(Some(EntryItem {
+                    kind: EntryItemKind::For(name, items, body),
+                    span: for_span.to(close_span),
+                }))
+            }
             Some(&Token {
                 kind: TokenKind::Delim(Delim::OpenBrack),
                 span: open_span,
@@ -186,9 +543,17 @@ impl<'input> Parser<'input> {
                 self.bump();
                 let name = self.parse_name().ok_or(
                     Diagnostic::error("Expected identifier", self.peek_span())
+                        .code(codes::SYNTAX)
                         .primary_label("I was expecting a section name here", Level::Error),
                 )?;
                 _ = self.expect_delim(Delim::CloseBrack)?;
+                if name.text == "Assert" {
+                    let (assertions, close_span) = self.parse_assertions()?;
+                    return Ok(Some(EntryItem {
+                        kind: EntryItemKind::Assert(assertions),
+                        span: open_span.to(close_span),
+                    }));
+                }
                 let body = self.parse_expr()?;
                 let span = open_span.to(body.span);
                 Ok(Some(EntryItem {
@@ -200,15 +565,182 @@ impl<'input> Parser<'input> {
         }
     }
 
+    /// Parses the block of an `[Assert]` section, one assertion per line.
+    fn parse_assertions(&mut self) -> Result<(Vec<Assertion<'input>>, Span), Diagnostic> {
+        _ = self.expect_delim(Delim::OpenBrace)?;
+        let mut assertions = vec![];
+        while let Some(mut subject) = self.parse_name() {
+            let start = subject.span;
+            // `soft` and `not` are only modifiers in front of a subject.
+            let (mut soft, mut negated) = (false, false);
+            loop {
+                let modifier = match subject.text {
+                    "soft" if !soft => &mut soft,
+                    "not" if !negated => &mut negated,
+                    _ => break,
+                };
+                if !matches!(
+                    self.peek(),
+                    Some(Token {
+                        kind: TokenKind::Identifier(_),
+                        skipped_newline: false,
+                        ..
+                    })
+                ) {
+                    break;
+                }
+                *modifier = true;
+                subject = self.parse_name().expect("an identifier should follow");
+            }
+            let argument = match self.peek_compare_op() {
+                Some(_) => None,
+                None => Some(self.parse_expr()?),
+            };
+            let Some(op) = self.peek_compare_op() else {
+                return Err(Diagnostic::error("Expected comparison", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label(
+                        "I was expecting a comparison like `==` or `contains` here",
+                        Level::Error,
+                    ));
+            };
+            self.bump();
+            let expected = self.parse_expr()?;
+            let unit = match self.peek() {
+                Some(&Token {
+                    kind: TokenKind::Identifier(text),
+                    span,
+                    ..
+                }) if span.start == expected.span.end => {
+                    self.bump();
+                    Some(Name { text, span })
+                }
+                _ => None,
+            };
+            let span = start.to(unit.map_or(expected.span, |it| it.span));
+            assertions.push(Assertion {
+                soft,
+                negated,
+                subject,
+                argument,
+                op,
+                expected,
+                unit,
+                span,
+            });
+
+            if !matches!(
+                self.peek(),
+                Some(Token {
+                    kind: TokenKind::Delim(Delim::CloseBrace),
+                    ..
+                })
+            ) {
+                self.expect_newline()?;
+            }
+        }
+        let close_span = self.expect_delim(Delim::CloseBrace)?;
+        Ok((assertions, close_span))
+    }
+
+    /// `contains` and `matches` are only operators inside of assertions, so
+    /// they stay usable as names everywhere else.
+    fn peek_compare_op(&self) -> Option<CompareOp> {
+        let token = self.peek().filter(|it| !it.skipped_newline)?;
+        match token.kind {
+            TokenKind::EqEq => Some(CompareOp::Eq),
+            TokenKind::BangEq => Some(CompareOp::Ne),
+            TokenKind::Lt => Some(CompareOp::Lt),
+            TokenKind::LtEq => Some(CompareOp::Le),
+            TokenKind::Gt => Some(CompareOp::Gt),
+            TokenKind::GtEq => Some(CompareOp::Ge),
+            TokenKind::Identifier("contains") => Some(CompareOp::Contains),
+            TokenKind::Identifier("matches") => Some(CompareOp::Matches),
+            _ => None,
+        }
+    }
+
     fn parse_expr(&mut self) -> Result<Expr<'input>, Diagnostic> {
         match self.opt_parse_expr()? {
             Some(expr) => Ok(expr),
             None => Err(Diagnostic::error("Expected expression", self.peek_span())
+                .code(codes::SYNTAX)
                 .primary_label("I was expecting an expression here", Level::Error)),
         }
     }
 
     fn opt_parse_expr(&mut self) -> Result<Option<Expr<'input>>, Diagnostic> {
+        self.opt_parse_binary(0)
+    }
+
+    /// Parses operands joined by operators that bind tighter than
+    /// `min_precedence`. Operators of equal precedence associate to the left.
+    fn opt_parse_binary(&mut self, min_precedence: u8) -> Result<Option<Expr<'input>>, Diagnostic> {
+        let Some(mut lhs) = self.opt_parse_operand()? else {
+            return Ok(None);
+        };
+
+        while let Some(op) = self.peek_bin_op()
+            && op.precedence() > min_precedence
+        {
+            self.bump();
+            let Some(rhs) = self.opt_parse_binary(op.precedence())? else {
+                return Err(Diagnostic::error("Expected expression", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label("I was expecting an expression here", Level::Error));
+            };
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr {
+                kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+                span,
+            };
+        }
+
+        Ok(Some(lhs))
+    }
+
+    /// Like the opening parenthesis of a call, an operator has to be on the
+    /// same line as its left operand.
+    fn peek_bin_op(&self) -> Option<BinOp> {
+        let token = self.peek().filter(|it| !it.skipped_newline)?;
+        match token.kind {
+            TokenKind::Plus => Some(BinOp::Add),
+            TokenKind::Minus => Some(BinOp::Sub),
+            TokenKind::Star => Some(BinOp::Mul),
+            TokenKind::Slash => Some(BinOp::Div),
+            TokenKind::QuestionQuestion => Some(BinOp::Coalesce),
+            _ => None,
+        }
+    }
+
+    fn opt_parse_operand(&mut self) -> Result<Option<Expr<'input>>, Diagnostic> {
+        let Some(mut expr) = self.opt_parse_primary()? else {
+            return Ok(None);
+        };
+
+        while let Some(Token {
+            kind: TokenKind::Dot,
+            skipped_newline: false,
+            ..
+        }) = self.peek()
+        {
+            self.bump();
+            let name = self.parse_name().ok_or(
+                Diagnostic::error("Expected identifier", self.peek_span())
+                    .code(codes::SYNTAX)
+                    .primary_label("I was expecting a field name here", Level::Error),
+            )?;
+            let span = expr.span.to(name.span);
+            expr = Expr {
+                kind: ExprKind::Field(Box::new(expr), name),
+                span,
+            };
+        }
+
+        Ok(Some(expr))
+    }
+
+    fn opt_parse_primary(&mut self) -> Result<Option<Expr<'input>>, Diagnostic> {
         match self.peek() {
             Some(&Token {
                 kind: TokenKind::Identifier(s),
@@ -216,17 +748,28 @@ impl<'input> Parser<'input> {
                 ..
             }) => {
                 self.bump();
+                // A call needs its opening parenthesis on the same line, so a
+                // name at the end of a line never swallows what follows.
+                if let Some(Token {
+                    kind: TokenKind::Delim(Delim::OpenParen),
+                    skipped_newline: false,
+                    ..
+                }) = self.peek()
+                {
+                    return self.parse_call(Name { text: s, span }).map(Some);
+                }
+
                 Ok(Some(Expr {
                     kind: ExprKind::NameRef(s),
                     span,
                 }))
             }
             Some(&Token {
-                kind: TokenKind::String(ref parts),
+                kind: TokenKind::String(_),
                 span,
                 ..
             }) => {
-                let parts = parts.clone();
+                let parts = self.take_template_parts();
                 self.bump();
                 let mut ast_parts = vec![];
                 for part in parts {
@@ -235,8 +778,16 @@ impl<'input> Parser<'input> {
                             ast_parts.push(TemplatePart::Literal(s, span));
                         }
                         token::TemplatePart::Code(tokens) => {
-                            let mut parser = Parser::new(tokens);
+                            let mut parser = Parser::new(token_stream(tokens));
                             let expr = parser.parse_expr()?;
+                            if let Some(token) = parser.peek() {
+                                return Err(Diagnostic::error("Unexpected token", token.span)
+                                    .code(codes::SYNTAX)
+                                    .primary_label(
+                                        "I was expecting the end of the template expression here",
+                                        Level::Error,
+                                    ));
+                            }
                             ast_parts.push(TemplatePart::Expr(expr));
                         }
                     }
@@ -280,6 +831,47 @@ impl<'input> Parser<'input> {
                     span,
                 }))
             }
+            Some(&Token {
+                kind: TokenKind::Keyword(kw @ (Keyword::True | Keyword::False)),
+                span,
+                ..
+            }) => {
+                self.bump();
+                Ok(Some(Expr {
+                    kind: ExprKind::BoolLiteral(kw == Keyword::True),
+                    span,
+                }))
+            }
+            Some(&Token {
+                kind: TokenKind::Keyword(Keyword::If),
+                span: if_span,
+                ..
+            }) => {
+                self.bump();
+                let cond = self.parse_expr()?;
+                self.expect_keyword(Keyword::Then, "then")?;
+                let then = self.parse_expr()?;
+                self.expect_keyword(Keyword::Else, "else")?;
+                let else_ = self.parse_expr()?;
+                let span = if_span.to(else_.span);
+                Ok(Some(Expr {
+                    kind: ExprKind::If(Box::new(cond), Box::new(then), Box::new(else_)),
+                    span,
+                }))
+            }
+            Some(&Token {
+                kind: TokenKind::Delim(Delim::OpenParen),
+                span: open_span,
+                ..
+            }) => {
+                self.bump();
+                let inner = self.parse_expr()?;
+                let close_span = self.expect_delim(Delim::CloseParen)?;
+                Ok(Some(Expr {
+                    kind: ExprKind::Paren(Box::new(inner)),
+                    span: open_span.to(close_span),
+                }))
+            }
             Some(&Token {
                 kind: TokenKind::Delim(Delim::OpenBrace),
                 span: open_span,
@@ -328,6 +920,7 @@ impl<'input> Parser<'input> {
                                     "Unexpected token",
                                     self.peek_span(),
                                 )
+                                .code(codes::SYNTAX)
                                 .primary_label("I was expecting a comma here", Level::Error));
                             }
                             None => break,
@@ -346,6 +939,56 @@ impl<'input> Parser<'input> {
         }
     }
 
+    fn parse_call(&mut self, name: Name<'input>) -> Result<Expr<'input>, Diagnostic> {
+        let (args, close_span) = self.parse_args()?;
+        let span = name.span.to(close_span);
+        Ok(Expr {
+            kind: ExprKind::Call(name, args),
+            span,
+        })
+    }
+
+    /// Parses arguments separated by commas in parentheses, and returns them
+    /// with the span of the closing parenthesis.
+    fn parse_args(&mut self) -> Result<(Vec<Expr<'input>>, Span), Diagnostic> {
+        _ = self.expect_delim(Delim::OpenParen)?;
+        let mut args = vec![];
+
+        loop {
+            match self.peek() {
+                Some(Token {
+                    kind: TokenKind::Delim(Delim::CloseParen),
+                    ..
+                })
+                | None => break,
+                _ => {}
+            }
+
+            let arg = self.parse_expr()?;
+            args.push(arg);
+
+            if self.eat(TokenKind::Comma).is_none() {
+                match self.peek() {
+                    Some(Token {
+                        kind: TokenKind::Delim(Delim::CloseParen),
+                        ..
+                    }) => {
+                        break;
+                    }
+                    Some(_) => {
+                        return Err(Diagnostic::error("Unexpected token", self.peek_span())
+                            .code(codes::SYNTAX)
+                            .primary_label("I was expecting a comma here", Level::Error));
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        let close_span = self.expect_delim(Delim::CloseParen)?;
+        Ok((args, close_span))
+    }
+
     fn parse_dictionary_fields(&mut self) -> Result<Vec<DictionaryField<'input>>, Diagnostic> {
         let mut fields = vec![];
 
@@ -372,6 +1015,7 @@ impl<'input> Parser<'input> {
                     }
                     Some(_) => {
                         return Err(Diagnostic::error("Unexpected token", self.peek_span())
+                            .code(codes::SYNTAX)
                             .primary_label("I was expecting a comma here", Level::Error));
                     }
                     None => break,
@@ -383,14 +1027,21 @@ impl<'input> Parser<'input> {
     }
 
     fn parse_dictionary_field(&mut self) -> Result<DictionaryField<'input>, Diagnostic> {
+        if let Some(spread_span) = self.eat(TokenKind::DotDotDot) {
+            let expr = self.parse_expr()?;
+            let span = spread_span.to(expr.span);
+            return Ok(DictionaryField::Spread(expr, span));
+        }
+
         let key = self.parse_expr()?;
         if self.eat(TokenKind::Colon).is_none() {
             return Err(Diagnostic::error("Unexpected token", self.peek_span())
+                .code(codes::SYNTAX)
                 .primary_label("I was expecting a colon here", Level::Error));
         }
 
         let value = self.parse_expr()?;
-        Ok(DictionaryField { key, value })
+        Ok(DictionaryField::Pair { key, value })
     }
 
     fn parse_name(&mut self) -> Option<Name<'input>> {
@@ -434,6 +1085,14 @@ impl<'input> Parser<'input> {
         }
     }
 
+    fn expect_keyword(&mut self, kw: Keyword, text: &str) -> Result<Span, Diagnostic> {
+        self.eat_keyword(kw).ok_or_else(|| {
+            Diagnostic::error(format!("Expected `{text}`"), self.peek_span())
+                .code(codes::SYNTAX)
+                .primary_label(format!("I was expecting `{text}` here"), Level::Error)
+        })
+    }
+
     fn expect_delim(&mut self, delim: Delim) -> Result<Span, Diagnostic> {
         if let Some(&Token {
             kind: TokenKind::Delim(delim2),
@@ -452,6 +1111,7 @@ impl<'input> Parser<'input> {
             };
 
             Err(Diagnostic::error("Expected delimiter", self.peek_span())
+                .code(codes::SYNTAX)
                 .primary_label(label_message, Level::Error))
         }
     }
@@ -461,27 +1121,57 @@ impl<'input> Parser<'input> {
             None => Ok(()),
             Some(token) if token.skipped_newline => Ok(()),
             Some(token) => Err(Diagnostic::error("Missing newline", token.span)
+                .code(codes::SYNTAX)
                 .primary_label("I was expecting a newline here", Level::Error)),
         }
     }
 
+    /// Moves the template parts out of the current string token.
+    ///
+    /// The parser never backtracks, so the token is not looked at again.
+    fn take_template_parts(&mut self) -> Vec<token::TemplatePart<'input>> {
+        match &mut self.current {
+            Some(Token {
+                kind: TokenKind::String(parts),
+                ..
+            }) => std::mem::take(parts),
+            _ => vec![],
+        }
+    }
+
     fn peek(&self) -> Option<&Token<'input>> {
-        self.tokens.get(self.pos)
+        self.current.as_ref()
     }
 
     fn peek_span(&self) -> Span {
         if let Some(token) = self.peek() {
             token.span
-        } else if let Some(last) = self.tokens.last() {
-            last.span
         } else {
-            Span::new(0, 0)
+            self.prev_span
         }
     }
 
     fn bump(&mut self) {
-        if self.pos < self.tokens.len() {
-            self.pos += 1;
+        if let Some(token) = self.current.take() {
+            if let TokenKind::Delim(delim) = token.kind {
+                self.depth = if delim.is_open() {
+                    self.depth + 1
+                } else {
+                    self.depth.saturating_sub(1)
+                };
+            }
+            self.prev_span = token.span;
+            self.current = self.next_token();
+        }
+    }
+
+    fn next_token(&mut self) -> Option<Token<'input>> {
+        match self.tokens.next()? {
+            Ok(token) => Some(token),
+            Err(diag) => {
+                self.lex_error = Some(diag);
+                None
+            }
         }
     }
 }
@@ -495,7 +1185,7 @@ mod tests {
 
     use expect_test::expect_file;
 
-    use crate::{diagnostic, parser};
+    use crate::{diagnostic, line_index::LineIndex, parser};
 
     #[test]
     fn parse_ok() {
@@ -520,7 +1210,7 @@ mod tests {
             let input = fs::read_to_string(&case.au_path)
                 .unwrap_or_else(|_| panic!("could not read file `{}`", case.au_path.display()));
 
-            let diag = parser::parse(&input).expect_err("parse error");
+            let diags = parser::parse(&input).expect_err("parse error");
             let filename = case.au_path.file_name().unwrap();
             let display_path = case
                 .au_path
@@ -532,8 +1222,9 @@ mod tests {
             let mut pretty_diag = String::new();
             diagnostic::dump(
                 &input,
+                &LineIndex::new(&input),
                 &display_path,
-                &diag,
+                &diags,
                 diagnostic::RenderStyle::Plain,
                 &mut pretty_diag,
             )
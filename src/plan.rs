@@ -0,0 +1,351 @@
+use std::fmt;
+
+use crate::{
+    machine::{self, RuntimeError},
+    validated::{
+        Entry, Expr, ExprKind, HttpMethod, SourceFile,
+        visit::{self, Visitor},
+    },
+};
+
+/// What a run of a source file would do, without sending any requests.
+#[derive(Debug)]
+pub struct Plan<'input> {
+    pub steps: Vec<Step<'input>>,
+}
+
+#[derive(Debug)]
+pub struct Step<'input> {
+    pub entry: &'input str,
+    pub action: Action,
+    /// Name bound to each element when the request is sent once per element
+    /// of an array
+    pub for_each: Option<&'input str>,
+    /// Entries that have to succeed first
+    pub needs: Vec<&'input str>,
+    /// Entries that run with arguments as part of this one
+    pub calls: Vec<&'input str>,
+    /// Names the entry reads, in order of first use
+    pub reads: Vec<NameUse>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Action {
+    Send(HttpMethod),
+    /// The entry has no request and is skipped
+    Skip,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NameUse {
+    pub name: String,
+    pub origin: NameOrigin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameOrigin {
+    Const,
+    /// Defined outside the source file
+    Variable,
+    /// Captured from the response of an earlier entry
+    Capture,
+}
+
+/// Resolves the steps a run would execute, in the order the machine executes
+/// them. Entries run one after another, after the entries they need and
+/// between the hooks of the file.
+pub fn explain<'input>(
+    file: &SourceFile<'input>,
+    entry_patterns: &[String],
+) -> Result<Plan<'input>, RuntimeError> {
+    let mut entries = machine::select_entries(&file.entries, entry_patterns)?;
+    if entry_patterns.is_empty() {
+        entries.retain(|entry| entry.hook().is_none());
+    }
+
+    let steps = machine::run_order_with_hooks(&file.entries, entries)
+        .into_iter()
+        .map(|entry| Step {
+            entry: entry.name.text,
+            action: match &entry.request {
+                Some(request) => Action::Send(request.method),
+                None => Action::Skip,
+            },
+            for_each: entry.for_each.as_ref().map(|it| it.name.text),
+            needs: entry.needs.iter().map(|it| it.text).collect(),
+            calls: entry.calls.iter().map(|it| it.entry.text).collect(),
+            reads: reads(file, entry),
+        })
+        .collect();
+
+    Ok(Plan { steps })
+}
+
+fn reads(file: &SourceFile<'_>, entry: &Entry<'_>) -> Vec<NameUse> {
+    struct NameRefCollector<'a> {
+        names: &'a mut Vec<NameUse>,
+        file: &'a SourceFile<'a>,
+    }
+
+    impl<'input> Visitor<'input> for NameRefCollector<'_> {
+        fn visit_expr(&mut self, expr: &Expr) {
+            let origin = match &expr.kind {
+                ExprKind::NameRef(name) if self.file.globals.contains_key(name.as_str()) => {
+                    Some((name, NameOrigin::Const))
+                }
+                ExprKind::NameRef(name) => Some((name, NameOrigin::Variable)),
+                ExprKind::Capture(name) => Some((name, NameOrigin::Capture)),
+                _ => None,
+            };
+            if let Some((name, origin)) = origin
+                && !self.names.iter().any(|it| it.name == *name)
+            {
+                self.names.push(NameUse {
+                    name: name.clone(),
+                    origin,
+                });
+            }
+            visit::walk_expr(self, expr);
+        }
+    }
+
+    let mut names = vec![];
+    NameRefCollector {
+        names: &mut names,
+        file,
+    }
+    .visit_entry(entry);
+    names
+}
+
+impl fmt::Display for Plan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .steps
+            .iter()
+            .map(|step| step.entry.len())
+            .max()
+            .unwrap_or_default();
+
+        let mut position = 0;
+        for step in &self.steps {
+            match step.action {
+                Action::Send(method) => {
+                    position += 1;
+                    let label = format!("{position}.");
+                    write!(f, "{label:<4}{:width$}  {}", step.entry, method.as_str())?;
+                    if let Some(name) = step.for_each {
+                        write!(f, " for each {name}")?;
+                    }
+                }
+                Action::Skip => write!(f, "-   {:width$}  skipped, no request", step.entry)?,
+            }
+
+            if !step.needs.is_empty() {
+                write!(f, "\n    needs {}", step.needs.join(", "))?;
+            }
+            if !step.calls.is_empty() {
+                write!(f, "\n    calls {}", step.calls.join(", "))?;
+            }
+            if !step.reads.is_empty() {
+                let reads = step
+                    .reads
+                    .iter()
+                    .map(|it| match it.origin {
+                        NameOrigin::Const => it.name.clone(),
+                        NameOrigin::Variable => format!("{} (variable)", it.name),
+                        NameOrigin::Capture => format!("{} (captured)", it.name),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "\n    reads {reads}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use expect_test::expect;
+
+    use super::explain;
+    use crate::validator;
+
+    const INPUT: &str = r#"const baseUrl = "https://example.com"
+
+entry getUser {
+    GET "{{baseUrl}}/users/{{id}}"
+    [Headers] {"Authorization": token}
+}
+
+entry notes {
+}
+
+entry createUser {
+    POST "{{baseUrl}}/users"
+    [Body] {"name": "Ada"}
+}
+"#;
+
+    #[test]
+    fn explain_all_entries() {
+        let vars = HashMap::from([
+            ("id".to_string(), String::new()),
+            ("token".to_string(), String::new()),
+        ]);
+        let file = validator::validate(INPUT, &vars).unwrap();
+        let plan = explain(&file, &[]).unwrap();
+
+        expect![[r#"
+            1.  getUser     GET
+                reads baseUrl, id (variable), token (variable)
+            -   notes       skipped, no request
+            2.  createUser  POST
+                reads baseUrl
+        "#]]
+        .assert_eq(&plan.to_string());
+    }
+
+    #[test]
+    fn explain_for_each() {
+        let input = r#"const users = [{"name": "Ada"}, {"name": "Grace"}]
+
+entry seed {
+    for user in users {
+        POST "https://example.com/users"
+        [Body] user
+    }
+}
+"#;
+        let file = validator::validate(input, &HashMap::<String, String>::new()).unwrap();
+        let plan = explain(&file, &[]).unwrap();
+
+        expect![[r#"
+            1.  seed  POST for each user
+                reads users
+        "#]]
+        .assert_eq(&plan.to_string());
+    }
+
+    #[test]
+    fn explain_hooks() {
+        let input = r#"@before_all
+entry seed {
+    POST "https://example.com/fixtures"
+}
+
+@after_each
+entry cleanup {
+    DELETE "https://example.com/items"
+}
+
+entry create {
+    POST "https://example.com/items"
+}
+
+entry list {
+    GET "https://example.com/items"
+}
+"#;
+        let file = validator::validate(input, &HashMap::<String, String>::new()).unwrap();
+
+        expect![[r#"
+            1.  seed     POST
+            2.  create   POST
+            3.  cleanup  DELETE
+            4.  list     GET
+            5.  cleanup  DELETE
+        "#]]
+        .assert_eq(&explain(&file, &[]).unwrap().to_string());
+        // A hook chosen by name runs like other entries.
+        expect![[r#"
+            1.  seed     POST
+            2.  cleanup  DELETE
+        "#]]
+        .assert_eq(
+            &explain(&file, &["cleanup".to_string()])
+                .unwrap()
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn explain_captures() {
+        let input = r#"entry login {
+    POST "https://example.com/login"
+    [Capture] {"token": "$.token"}
+}
+
+entry me {
+    GET "https://example.com/me"
+    [Headers] {"Authorization": "Bearer {{token}}"}
+}
+"#;
+        let file = validator::validate(input, &HashMap::<String, String>::new()).unwrap();
+        let plan = explain(&file, &[]).unwrap();
+
+        expect![[r#"
+            1.  login  POST
+            2.  me     GET
+                reads token (captured)
+        "#]]
+        .assert_eq(&plan.to_string());
+    }
+
+    #[test]
+    fn explain_unknown_entry() {
+        let vars = HashMap::from([
+            ("id".to_string(), String::new()),
+            ("token".to_string(), String::new()),
+        ]);
+        let file = validator::validate(INPUT, &vars).unwrap();
+        assert!(explain(&file, &["deleteUser".to_string()]).is_err());
+    }
+
+    #[test]
+    fn explain_needs() {
+        let input = r#"entry me needs login {
+    GET "https://example.com/me"
+}
+
+entry health {
+    GET "https://example.com/health"
+}
+
+entry login {
+    POST "https://example.com/login"
+}
+"#;
+        let file = validator::validate(input, &HashMap::<String, String>::new()).unwrap();
+
+        expect![[r#"
+            1.  login  POST
+            2.  me     GET
+                needs login
+        "#]]
+        .assert_eq(&explain(&file, &["me".to_string()]).unwrap().to_string());
+        expect![[r#"
+            1.  login   POST
+            2.  me      GET
+                needs login
+            3.  health  GET
+        "#]]
+        .assert_eq(&explain(&file, &[]).unwrap().to_string());
+        expect![[r#"
+            1.  login   POST
+            2.  me      GET
+                needs login
+            3.  health  GET
+        "#]]
+        .assert_eq(
+            &explain(&file, &["h*".to_string(), "me".to_string()])
+                .unwrap()
+                .to_string(),
+        );
+    }
+}
@@ -0,0 +1,525 @@
+use std::{
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::{Map, Value as Json, json};
+
+use crate::{
+    client::{HttpError, Request, Response, StatusCode},
+    machine::masked_header_value,
+    timing::Timings,
+};
+
+/// What happened during a run, for `--report json`.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub entries: Vec<EntryReport>,
+}
+
+#[derive(Debug)]
+pub struct EntryReport {
+    pub name: String,
+    /// The file the entry is in, when several files were run
+    pub file: Option<String>,
+    /// Whether the entry was chosen to run, rather than only needed by one
+    /// that was
+    pub selected: bool,
+    /// The entry it needs that failed, if it was skipped because of it
+    pub skipped_because: Option<String>,
+    /// One per request that was sent, including retried polls
+    pub exchanges: Vec<Exchange>,
+    /// Why the entry stopped, if it failed for another reason than the
+    /// status or an assertion
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Exchange {
+    pub method: &'static str,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// The response, or why none was received
+    pub result: Result<ResponseSummary, String>,
+    pub assertions: Vec<AssertionResult>,
+    pub captures: Vec<(String, String)>,
+}
+
+/// A response without its body, which the report doesn't include.
+#[derive(Debug)]
+pub struct ResponseSummary {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub size: usize,
+    pub elapsed: Duration,
+    pub timings: Timings,
+}
+
+#[derive(Debug)]
+pub struct AssertionResult {
+    /// E.g. `status == 200`, with the expected value evaluated
+    pub assertion: String,
+    /// Why the assertion doesn't hold, `None` if it does
+    pub failure: Option<String>,
+}
+
+impl EntryReport {
+    /// Returns the name, prefixed with the file if there is one, e.g.
+    /// `api/users.au::getUser`.
+    pub fn qualified_name(&self) -> String {
+        match &self.file {
+            Some(file) => format!("{file}::{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.skipped_because.is_none()
+            && self.error.is_none()
+            && self.exchanges.last().is_none_or(|exchange| {
+                matches!(&exchange.result, Ok(response) if response.status.is_success())
+                    && exchange.assertions.iter().all(|it| it.failure.is_none())
+            })
+    }
+}
+
+/// A cheaply clonable handle the machine writes the report into while it
+/// runs, so the report is complete up to the point where a run failed.
+#[derive(Debug, Clone, Default)]
+pub struct ReportRecorder(Arc<Mutex<Report>>);
+
+impl ReportRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_entry(&self, name: &str, selected: bool) {
+        self.0.lock().unwrap().entries.push(EntryReport {
+            name: name.to_string(),
+            file: None,
+            selected,
+            skipped_because: None,
+            exchanges: vec![],
+            error: None,
+        });
+    }
+
+    pub fn skip_entry(&self, name: &str, selected: bool, need: &str) {
+        self.start_entry(name, selected);
+        self.with_entry(|entry| entry.skipped_because = Some(need.to_string()));
+    }
+
+    /// Records why the current entry failed.
+    pub fn fail_entry(&self, error: String) {
+        self.with_entry(|entry| entry.error = Some(error));
+    }
+
+    /// Records a request of the current entry that is about to be sent.
+    pub fn request(&self, request: &Request) {
+        self.with_entry(|entry| {
+            entry.exchanges.push(Exchange {
+                method: request.method.as_str(),
+                url: request.url.clone(),
+                headers: masked_headers(&request.headers),
+                result: Err("no response".to_string()),
+                assertions: vec![],
+                captures: vec![],
+            })
+        });
+    }
+
+    pub fn response(&self, result: &Result<Response, HttpError>) {
+        self.with_exchange(|exchange| {
+            exchange.result = match result {
+                Ok(response) => Ok(ResponseSummary {
+                    status: response.status,
+                    headers: masked_headers(&response.headers),
+                    size: response.body.len(),
+                    elapsed: response.elapsed,
+                    timings: response.timings,
+                }),
+                Err(e) => Err(e.to_string()),
+            }
+        });
+    }
+
+    pub fn assertion(&self, assertion: String, failure: Option<String>) {
+        self.with_exchange(|exchange| {
+            exchange
+                .assertions
+                .push(AssertionResult { assertion, failure })
+        });
+    }
+
+    pub fn capture(&self, name: &str, value: &str) {
+        self.with_exchange(|exchange| {
+            exchange
+                .captures
+                .push((name.to_string(), value.to_string()))
+        });
+    }
+
+    /// Removes the entries recorded so far, e.g. to append them to another
+    /// report with [`append_entries`](Self::append_entries).
+    pub fn take_entries(&self) -> Vec<EntryReport> {
+        std::mem::take(&mut self.0.lock().unwrap().entries)
+    }
+
+    pub fn append_entries(&self, entries: Vec<EntryReport>) {
+        self.0.lock().unwrap().entries.extend(entries);
+    }
+
+    /// Appends the entries of a run of another file, e.g. when a directory
+    /// is run.
+    pub fn append_file_entries(&self, file: &str, mut entries: Vec<EntryReport>) {
+        for entry in &mut entries {
+            entry.file = Some(file.to_string());
+        }
+        self.append_entries(entries);
+    }
+
+    /// Returns the report as JSON. `success` tells whether the run as a whole
+    /// succeeded, which also covers errors outside of any entry.
+    pub fn to_json(&self, success: bool) -> Json {
+        let report = self.0.lock().unwrap();
+        json!({
+            "success": success,
+            "entries": report.entries.iter().map(entry_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Whether every selected entry passed, i.e. none failed or was skipped.
+    pub fn all_passed(&self) -> bool {
+        let report = self.0.lock().unwrap();
+        report
+            .entries
+            .iter()
+            .filter(|it| it.selected)
+            .all(|it| it.passed())
+    }
+
+    /// Returns a line with the result of every selected entry, followed by
+    /// the totals, as `aurora test` prints them.
+    pub fn test_summary(&self, elapsed: Duration) -> String {
+        let report = self.0.lock().unwrap();
+        let entries = report
+            .entries
+            .iter()
+            .filter(|it| it.selected)
+            .collect::<Vec<_>>();
+        let width = entries
+            .iter()
+            .map(|it| it.qualified_name().len())
+            .max()
+            .unwrap_or_default();
+
+        let mut out = String::new();
+        let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+        for entry in entries {
+            let time = entry
+                .exchanges
+                .iter()
+                .filter_map(|it| it.result.as_ref().ok())
+                .map(|it| it.elapsed)
+                .sum::<Duration>();
+            let (label, detail) = if let Some(need) = &entry.skipped_because {
+                skipped += 1;
+                ("SKIP", format!("`{need}` failed"))
+            } else if entry.passed() {
+                passed += 1;
+                ("PASS", format!("{}ms", time.as_millis()))
+            } else {
+                failed += 1;
+                ("FAIL", format!("{}ms", time.as_millis()))
+            };
+            _ = writeln!(out, "{label}  {:width$}  {detail}", entry.qualified_name());
+        }
+        _ = writeln!(
+            out,
+            "\n{passed} passed, {failed} failed, {skipped} skipped in {:.2}s",
+            elapsed.as_secs_f64()
+        );
+        out
+    }
+
+    fn with_entry(&self, f: impl FnOnce(&mut EntryReport)) {
+        if let Some(entry) = self.0.lock().unwrap().entries.last_mut() {
+            f(entry);
+        }
+    }
+
+    fn with_exchange(&self, f: impl FnOnce(&mut Exchange)) {
+        self.with_entry(|entry| {
+            if let Some(exchange) = entry.exchanges.last_mut() {
+                f(exchange);
+            }
+        });
+    }
+}
+
+/// Returns the headers with the values of sensitive ones masked, as in
+/// verbose output.
+fn masked_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.clone(), masked_header_value(name, value).into_owned()))
+        .collect()
+}
+
+fn entry_json(entry: &EntryReport) -> Json {
+    let status = if entry.skipped_because.is_some() {
+        "skipped"
+    } else if entry.passed() {
+        "passed"
+    } else {
+        "failed"
+    };
+    let mut json = Map::new();
+    json.insert("name".to_string(), json!(entry.name));
+    if let Some(file) = &entry.file {
+        json.insert("file".to_string(), json!(file));
+    }
+    json.insert("status".to_string(), json!(status));
+    if let Some(need) = &entry.skipped_because {
+        json.insert("skipped_because".to_string(), json!(need));
+    }
+    if let Some(error) = &entry.error {
+        json.insert("error".to_string(), json!(error));
+    }
+    json.insert(
+        "requests".to_string(),
+        entry.exchanges.iter().map(exchange_json).collect(),
+    );
+    Json::Object(json)
+}
+
+fn exchange_json(exchange: &Exchange) -> Json {
+    let headers = |headers: &[(String, String)]| {
+        headers
+            .iter()
+            .map(|(name, value)| json!([name, value]))
+            .collect::<Vec<_>>()
+    };
+
+    let mut json = Map::new();
+    json.insert(
+        "request".to_string(),
+        json!({
+            "method": exchange.method,
+            "url": exchange.url,
+            "headers": headers(&exchange.headers),
+        }),
+    );
+    match &exchange.result {
+        Ok(response) => {
+            let millis = |it: Duration| it.as_millis() as u64;
+            json.insert(
+                "response".to_string(),
+                json!({
+                    "status": response.status.as_u16(),
+                    "headers": headers(&response.headers),
+                    "size": response.size,
+                }),
+            );
+            json.insert(
+                "timings".to_string(),
+                json!({
+                    "dns_ms": response.timings.dns.map(millis),
+                    "connect_ms": response.timings.connect.map(millis),
+                    "ttfb_ms": millis(response.timings.ttfb),
+                    "total_ms": millis(response.elapsed),
+                }),
+            );
+        }
+        Err(e) => {
+            json.insert("error".to_string(), json!(e));
+        }
+    }
+    json.insert(
+        "assertions".to_string(),
+        exchange
+            .assertions
+            .iter()
+            .map(|it| {
+                json!({
+                    "assertion": it.assertion,
+                    "passed": it.failure.is_none(),
+                    "failure": it.failure,
+                })
+            })
+            .collect(),
+    );
+    json.insert(
+        "captures".to_string(),
+        exchange
+            .captures
+            .iter()
+            .map(|(name, value)| (name.clone(), json!(value)))
+            .collect::<Map<_, _>>()
+            .into(),
+    );
+    Json::Object(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::*;
+    use crate::{
+        client::{CacheStatus, RequestOptions},
+        validated::HttpMethod,
+    };
+
+    fn request(url: &str) -> Request {
+        Request {
+            method: HttpMethod::Get,
+            url: url.to_string(),
+            headers: vec![(
+                "Authorization".to_string(),
+                "Basic YWRhOnNlY3JldA==".to_string(),
+            )],
+            body: None,
+            options: RequestOptions::default(),
+            events: None,
+        }
+    }
+
+    fn response(status: u16) -> Response {
+        Response {
+            version: "HTTP/1.1",
+            status: StatusCode::from(status),
+            headers: vec![],
+            body: b"{}".to_vec(),
+            elapsed: Duration::from_millis(12),
+            timings: Default::default(),
+            cache: CacheStatus::Network,
+            redirects: vec![],
+            streamed: false,
+        }
+    }
+
+    #[test]
+    fn report_entries() {
+        let report = ReportRecorder::new();
+        report.start_entry("login", true);
+        report.request(&request("https://example.com/login"));
+        report.response(&Ok(response(200)));
+        report.assertion("status == 200".to_string(), None);
+        report.capture("token", "abc");
+        report.start_entry("me", false);
+        report.request(&request("https://example.com/me"));
+        report.response(&Ok(response(404)));
+        report.skip_entry("orders", true, "me");
+        report.start_entry("health", true);
+        report.request(&request("https://example.com/health"));
+        report.response(&Err(HttpError::Timeout));
+
+        let failed = report
+            .to_json(false)
+            .get("entries")
+            .and_then(|it| it.as_array())
+            .unwrap()
+            .iter()
+            .map(|it| format!("{} {}", it["name"], it["status"]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failed,
+            [
+                r#""login" "passed""#,
+                r#""me" "failed""#,
+                r#""orders" "skipped""#,
+                r#""health" "failed""#
+            ]
+        );
+
+        expect![[r#"
+            {
+              "name": "login",
+              "status": "passed",
+              "requests": [
+                {
+                  "request": {
+                    "method": "GET",
+                    "url": "https://example.com/login",
+                    "headers": [
+                      [
+                        "Authorization",
+                        "Basic ***"
+                      ]
+                    ]
+                  },
+                  "response": {
+                    "status": 200,
+                    "headers": [],
+                    "size": 2
+                  },
+                  "timings": {
+                    "dns_ms": null,
+                    "connect_ms": null,
+                    "ttfb_ms": 0,
+                    "total_ms": 12
+                  },
+                  "assertions": [
+                    {
+                      "assertion": "status == 200",
+                      "passed": true,
+                      "failure": null
+                    }
+                  ],
+                  "captures": {
+                    "token": "abc"
+                  }
+                }
+              ]
+            }"#]]
+        .assert_eq(&serde_json::to_string_pretty(&report.to_json(false)["entries"][0]).unwrap());
+    }
+
+    #[test]
+    fn summarize_tests() {
+        let report = ReportRecorder::new();
+        report.start_entry("login", false);
+        report.request(&request("https://example.com/login"));
+        report.response(&Ok(response(200)));
+        report.start_entry("getUser", true);
+        report.request(&request("https://example.com/users/1"));
+        report.response(&Ok(response(200)));
+        report.assertion("status == 200".to_string(), None);
+        report.start_entry("createUser", true);
+        report.request(&request("https://example.com/users"));
+        report.response(&Ok(response(200)));
+        report.assertion(
+            "status == 201".to_string(),
+            Some("`status` is 200".to_string()),
+        );
+        report.skip_entry("deleteUser", true, "createUser");
+
+        expect![[r#"
+            PASS  getUser     12ms
+            FAIL  createUser  12ms
+            SKIP  deleteUser  `createUser` failed
+
+            1 passed, 1 failed, 1 skipped in 1.25s
+        "#]]
+        .assert_eq(&report.test_summary(Duration::from_millis(1250)));
+
+        let combined = ReportRecorder::new();
+        combined.append_file_entries("users.au", report.take_entries());
+        let other = ReportRecorder::new();
+        other.start_entry("health", true);
+        other.request(&request("https://example.com/health"));
+        other.response(&Ok(response(200)));
+        combined.append_file_entries("health.au", other.take_entries());
+        expect![[r#"
+            PASS  users.au::getUser     12ms
+            FAIL  users.au::createUser  12ms
+            SKIP  users.au::deleteUser  `createUser` failed
+            PASS  health.au::health     12ms
+
+            2 passed, 1 failed, 1 skipped in 1.25s
+        "#]]
+        .assert_eq(&combined.test_summary(Duration::from_millis(1250)));
+    }
+}
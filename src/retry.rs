@@ -0,0 +1,200 @@
+use crate::{
+    cancel::CancellationToken,
+    client::{HttpClient, HttpError, Request, Response},
+};
+
+/// An [`HttpClient`] that sends a request again, as often as its
+/// [`retries`](crate::client::RequestOptions::retries) allow, when the
+/// server couldn't be reached or failed to answer it.
+///
+/// A cancelled run stops waiting for the next attempt, and the last
+/// response or error is returned.
+pub struct RetryingHttpClient<C> {
+    inner: C,
+    cancellation: CancellationToken,
+}
+
+impl<C: HttpClient> RetryingHttpClient<C> {
+    pub fn new(inner: C, cancellation: CancellationToken) -> Self {
+        Self {
+            inner,
+            cancellation,
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for RetryingHttpClient<C> {
+    fn send(&self, request: Request) -> Result<Response, HttpError> {
+        let retries = request.options.retries;
+        let mut backoff = request.options.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let replayable = request
+                .body
+                .as_ref()
+                .is_none_or(|body| body.is_replayable());
+            if attempt == retries || !replayable {
+                return self.inner.send(request);
+            }
+
+            let result = self.inner.send(request.clone());
+            let reason = match &result {
+                Ok(response) if response.status.as_u16() >= 500 => {
+                    format!("status {}", response.status.as_u16())
+                }
+                Err(
+                    e @ (HttpError::Connection(_) | HttpError::Timeout | HttpError::Transport(_)),
+                ) => e.to_string(),
+                _ => return result,
+            };
+
+            attempt += 1;
+            tracing::info!(
+                "Retrying `{} {}` in {}ms after {reason} ({attempt}/{retries})",
+                request.method.as_str(),
+                request.url,
+                backoff.as_millis()
+            );
+            if !self.cancellation.sleep(backoff) {
+                return result;
+            }
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::{
+        client::{Body, CacheStatus, RequestOptions, StatusCode},
+        validated::HttpMethod,
+    };
+
+    /// Answers with the given statuses in order, `None` for a timeout.
+    struct FlakyClient {
+        statuses: RefCell<Vec<Option<u16>>>,
+        sent: Cell<usize>,
+    }
+
+    impl HttpClient for FlakyClient {
+        fn send(&self, _: Request) -> Result<Response, HttpError> {
+            self.sent.set(self.sent.get() + 1);
+            match self.statuses.borrow_mut().remove(0) {
+                Some(status) => Ok(Response {
+                    version: "HTTP/1.1",
+                    status: StatusCode::from(status),
+                    headers: vec![],
+                    body: vec![],
+                    elapsed: Duration::ZERO,
+                    timings: Default::default(),
+                    cache: CacheStatus::Network,
+                    redirects: vec![],
+                    streamed: false,
+                }),
+                None => Err(HttpError::Timeout),
+            }
+        }
+    }
+
+    fn send(statuses: &[Option<u16>], retries: u32) -> (Result<Response, HttpError>, usize) {
+        send_body(statuses, retries, None)
+    }
+
+    fn send_body(
+        statuses: &[Option<u16>],
+        retries: u32,
+        body: Option<Body>,
+    ) -> (Result<Response, HttpError>, usize) {
+        send_with(
+            statuses,
+            retries,
+            Duration::ZERO,
+            body,
+            CancellationToken::new(),
+        )
+    }
+
+    fn send_with(
+        statuses: &[Option<u16>],
+        retries: u32,
+        retry_backoff: Duration,
+        body: Option<Body>,
+        cancellation: CancellationToken,
+    ) -> (Result<Response, HttpError>, usize) {
+        let client = RetryingHttpClient::new(
+            FlakyClient {
+                statuses: RefCell::new(statuses.to_vec()),
+                sent: Cell::new(0),
+            },
+            cancellation,
+        );
+        let result = client.send(Request {
+            method: HttpMethod::Get,
+            url: "https://example.com/".to_string(),
+            headers: vec![],
+            body,
+            options: RequestOptions {
+                retries,
+                retry_backoff,
+                ..RequestOptions::default()
+            },
+            events: None,
+        });
+        (result, client.inner.sent.get())
+    }
+
+    #[test]
+    fn retries_until_success() {
+        let (result, sent) = send(&[None, Some(503), Some(200)], 3);
+        assert_eq!(result.unwrap().status.as_u16(), 200);
+        assert_eq!(sent, 3);
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let (result, sent) = send(&[Some(500), None, Some(502)], 2);
+        assert_eq!(result.unwrap().status.as_u16(), 502);
+        assert_eq!(sent, 3);
+
+        let (result, sent) = send(&[None, Some(200)], 0);
+        assert!(matches!(result, Err(HttpError::Timeout)));
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (result, sent) = send(&[Some(404), Some(200)], 3);
+        assert_eq!(result.unwrap().status.as_u16(), 404);
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    fn stdin_bodies_are_not_retried() {
+        let (result, sent) = send_body(&[Some(503), Some(200)], 3, Some(Body::Stdin));
+        assert_eq!(result.unwrap().status.as_u16(), 503);
+        assert_eq!(sent, 1);
+    }
+
+    #[test]
+    fn cancelling_stops_the_backoff() {
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let start = Instant::now();
+        let (result, sent) = send_with(
+            &[Some(503), Some(200)],
+            3,
+            Duration::from_secs(60),
+            None,
+            cancellation,
+        );
+        assert_eq!(result.unwrap().status.as_u16(), 503);
+        assert_eq!(sent, 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
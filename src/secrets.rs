@@ -0,0 +1,149 @@
+use std::{
+    borrow::Cow,
+    io,
+    sync::{Arc, RwLock},
+};
+
+use crate::value::Value;
+
+const REDACTED: &str = "***";
+
+/// Values that must not show up in output, like tokens and passwords: the
+/// values of `--secret-var` and of `secret const` declarations. Verbose and
+/// dry-run output, reports, the history, logs and diagnostics are redacted
+/// with it.
+///
+/// A cheaply clonable handle, the machine adds secrets as it evaluates them.
+#[derive(Debug, Clone, Default)]
+pub struct Secrets(Arc<RwLock<Vec<String>>>);
+
+impl Secrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a secret, however short it is. Only an empty one is ignored, it
+    /// would be found between any two characters.
+    pub fn add(&self, secret: &str) {
+        if secret.is_empty() {
+            return;
+        }
+        // Within JSON, e.g. of a report, the secret is escaped.
+        let escaped = serde_json::to_string(secret).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+
+        let mut secrets = self.0.write().unwrap();
+        for secret in [secret, escaped] {
+            if !secrets.iter().any(|it| it == secret) {
+                secrets.push(secret.to_string());
+            }
+        }
+        // Longer secrets go first, so a secret that contains another one is
+        // redacted as a whole.
+        secrets.sort_by_key(|it| std::cmp::Reverse(it.len()));
+    }
+
+    /// Adds the strings and numbers in `value`, numbers in the form they are
+    /// printed in. `null` and bools are left out, they say nothing about the
+    /// secret.
+    pub fn add_value(&self, value: &Value) {
+        match value {
+            Value::String(s) => self.add(s),
+            Value::Integer(_) | Value::Float(_) => self.add(&value.to_string()),
+            Value::Null | Value::Bool(_) => {}
+            Value::Dictionary(entries) => entries.values().for_each(|it| self.add_value(it)),
+            Value::Array(items) => items.iter().for_each(|it| self.add_value(it)),
+        }
+    }
+
+    /// Replaces every secret in `text` with `***`.
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let secrets = self.0.read().unwrap();
+        let mut text = Cow::Borrowed(text);
+        for secret in secrets.iter() {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+        text
+    }
+
+    /// Wraps `inner`, so everything written to it is redacted, e.g. the
+    /// logs.
+    pub fn writer<W: io::Write>(&self, inner: W) -> RedactingWriter<W> {
+        RedactingWriter {
+            secrets: self.clone(),
+            inner,
+        }
+    }
+}
+
+/// Redacts what is written to it, see [`Secrets::writer`]. Every write is
+/// redacted on its own, so a secret that is split across two writes isn't
+/// found. The log formatter writes each line at once.
+pub struct RedactingWriter<W> {
+    secrets: Secrets,
+    inner: W,
+}
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self.inner.write_all(self.secrets.redact(text).as_bytes())?,
+            Err(_) => self.inner.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use indexmap::IndexMap;
+
+    use super::*;
+
+    #[test]
+    fn redact_secrets() {
+        let secrets = Secrets::new();
+        secrets.add("");
+        assert_eq!(secrets.redact("nothing to hide"), "nothing to hide");
+
+        secrets.add("abc123");
+        secrets.add(r#"pa"ss"#);
+        secrets.add_value(&Value::Dictionary(IndexMap::from([
+            (
+                "key".to_string(),
+                Arc::new(Value::String("k3y!".to_string())),
+            ),
+            ("pin".to_string(), Arc::new(Value::Integer(4711))),
+            (
+                "code".to_string(),
+                Arc::new(Value::String("x9".to_string())),
+            ),
+            ("admin".to_string(), Arc::new(Value::Bool(true))),
+        ])));
+        // Declared secrets are redacted however short they are.
+        assert_eq!(
+            secrets.redact("Bearer abc123, key k3y!, pin 4711, code x9"),
+            "Bearer ***, key ***, pin ***, code ***"
+        );
+        assert_eq!(
+            secrets.redact(r#"{"admin": true, "name": null}"#),
+            r#"{"admin": true, "name": null}"#
+        );
+        assert_eq!(
+            secrets.redact(r#"{"password": "pa\"ss"}"#),
+            r#"{"password": "***"}"#
+        );
+
+        let mut out = vec![];
+        writeln!(secrets.writer(&mut out), "token=abc123").unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "token=***\n");
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::machine::matches_pattern;
+
+/// Returns the `.au` files `path` stands for, in a stable order: the file
+/// itself, the `.au` files in a directory and its subdirectories, or the
+/// files matching a glob like `api/**/*.au`.
+///
+/// In a glob, `*` and `?` match within a path component and `**` matches
+/// any number of directories.
+pub fn source_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let raw = path.to_string_lossy();
+    if !raw.contains(['*', '?']) {
+        if !path.is_dir() {
+            return Ok(vec![path.to_path_buf()]);
+        }
+        let mut files = vec![];
+        walk(path, &mut files)?;
+        files.retain(|it| it.extension().is_some_and(|ext| ext == "au"));
+        return Ok(files);
+    }
+
+    // Only the directory before the first wildcard has to be searched.
+    let components = path
+        .components()
+        .map(|it| it.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    let wildcard = components
+        .iter()
+        .position(|it| it.contains(['*', '?']))
+        .unwrap_or(components.len());
+    let base = path.components().take(wildcard).collect::<PathBuf>();
+    let pattern = components[wildcard..]
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    let root = if base.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        &base
+    };
+    let mut files = vec![];
+    if root.is_dir() {
+        walk(root, &mut files)?;
+    }
+    files.retain(|file| {
+        let relative = file
+            .strip_prefix(root)
+            .unwrap_or(file)
+            .components()
+            .filter_map(|it| match it {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let relative = relative.iter().map(|it| it.as_ref()).collect::<Vec<_>>();
+        matches_glob(&pattern, &relative)
+    });
+    // Without a directory before the wildcard, `./` would be prepended.
+    if base.as_os_str().is_empty() {
+        for file in &mut files {
+            if let Ok(relative) = file.strip_prefix(".") {
+                *file = relative.to_path_buf();
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Collects the files in `dir` and its subdirectories, sorted by path.
+/// Hidden files and directories are left out.
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|it| it.map(|it| it.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let hidden = path
+            .file_name()
+            .is_some_and(|it| it.to_string_lossy().starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Matches the components of a path against the components of a glob.
+fn matches_glob(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_glob(rest, &path[skip..])),
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| matches_pattern(first, name) && matches_glob(rest, tail)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_components() {
+        assert!(matches_glob(&["*.au"], &["users.au"]));
+        assert!(!matches_glob(&["*.au"], &["v1", "users.au"]));
+        assert!(matches_glob(&["**", "*.au"], &["users.au"]));
+        assert!(matches_glob(&["**", "*.au"], &["v1", "admin", "users.au"]));
+        assert!(matches_glob(&["v?", "**", "*.au"], &["v1", "users.au"]));
+        assert!(!matches_glob(&["v?", "**", "*.au"], &["v10", "users.au"]));
+        assert!(!matches_glob(&["**", "*.au"], &["notes.md"]));
+    }
+
+    #[test]
+    fn find_source_files() {
+        let dir = std::env::temp_dir().join(format!("aurora-sources-{}", std::process::id()));
+        for file in [
+            "a.au",
+            "v1/b.au",
+            "v1/admin/c.au",
+            "v1/notes.md",
+            ".hidden/d.au",
+        ] {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let relative = |files: Vec<PathBuf>| {
+            files
+                .iter()
+                .map(|it| {
+                    it.strip_prefix(&dir)
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            relative(source_files(&dir).unwrap()),
+            ["a.au", "v1/admin/c.au", "v1/b.au"]
+        );
+        assert_eq!(
+            relative(source_files(&dir.join("v1/*.au")).unwrap()),
+            ["v1/b.au"]
+        );
+        assert_eq!(
+            relative(source_files(&dir.join("**/*.au")).unwrap()),
+            ["a.au", "v1/admin/c.au", "v1/b.au"]
+        );
+        assert!(source_files(&dir.join("missing/*.au")).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+
+/// An event of a `text/event-stream` response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerEvent {
+    /// Type given with `event:`, `None` for the default type `message`
+    pub event: Option<String>,
+    /// Lines given with `data:`, joined with newlines
+    pub data: String,
+    pub id: Option<String>,
+}
+
+impl fmt::Display for ServerEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.event {
+            Some(event) => write!(f, "{event}: {}", self.data),
+            None => write!(f, "{}", self.data),
+        }
+    }
+}
+
+/// Collects the lines of an event stream into events, following the
+/// parsing rules of the HTML standard.
+#[derive(Debug, Default)]
+pub struct EventStreamParser {
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+}
+
+impl EventStreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one line without its line ending. Returns the event that an
+    /// empty line completes.
+    pub fn feed_line(&mut self, line: &str) -> Option<ServerEvent> {
+        if line.is_empty() {
+            let event = self.event.take();
+            let id = self.id.take();
+            // Events without data are dropped.
+            return self.data.take().map(|data| ServerEvent { event, data, id });
+        }
+        if line.starts_with(':') {
+            // A comment, often sent to keep the connection alive
+            return None;
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => match &mut self.data {
+                Some(data) => {
+                    data.push('\n');
+                    data.push_str(value);
+                }
+                None => self.data = Some(value.to_string()),
+            },
+            "id" => self.id = Some(value.to_string()),
+            // `retry` only matters for reconnecting, which we don't do.
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Vec<ServerEvent> {
+        let mut parser = EventStreamParser::new();
+        input
+            .lines()
+            .filter_map(|line| parser.feed_line(line))
+            .collect()
+    }
+
+    #[test]
+    fn parse_events() {
+        let events = parse(
+            ": keep-alive\n\
+             data: {\"n\": 1}\n\
+             \n\
+             event: update\n\
+             id: 7\n\
+             data: first\n\
+             data:second\n\
+             \n\
+             event: ignored\n\
+             \n\
+             data: unterminated\n",
+        );
+
+        assert_eq!(
+            events,
+            [
+                ServerEvent {
+                    event: None,
+                    data: "{\"n\": 1}".to_string(),
+                    id: None,
+                },
+                ServerEvent {
+                    event: Some("update".to_string()),
+                    data: "first\nsecond".to_string(),
+                    id: Some("7".to_string()),
+                },
+            ]
+        );
+        assert_eq!(events[1].to_string(), "update: first\nsecond");
+    }
+}
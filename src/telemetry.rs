@@ -0,0 +1,279 @@
+use std::{
+    fmt::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::json;
+use uuid::Uuid;
+
+/// Records a trace of a run and exports it to an OTLP/HTTP collector.
+///
+/// A run produces a single trace. Every entry gets a span, and the request
+/// of an entry gets a child span whose id is propagated to the server in the
+/// `traceparent` header. Without an endpoint, nothing is recorded.
+pub struct Tracer {
+    endpoint: Option<String>,
+    trace_id: [u8; 16],
+    spans: Mutex<Vec<Span>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanId([u8; 8]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    Internal,
+    Client,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        AttributeValue::String(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        AttributeValue::Int(value)
+    }
+}
+
+struct Span {
+    id: SpanId,
+    parent: Option<SpanId>,
+    name: String,
+    kind: SpanKind,
+    start: u128,
+    end: u128,
+    attributes: Vec<(&'static str, AttributeValue)>,
+    failed: bool,
+}
+
+#[derive(Debug)]
+pub struct ExportError(String);
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "I couldn't export the trace: {}", self.0)
+    }
+}
+
+impl Tracer {
+    pub fn new(endpoint: Option<String>) -> Self {
+        Self {
+            endpoint,
+            trace_id: *Uuid::new_v4().as_bytes(),
+            spans: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.endpoint.is_some()
+    }
+
+    pub fn start_span(
+        &self,
+        name: impl Into<String>,
+        kind: SpanKind,
+        parent: Option<SpanId>,
+    ) -> SpanId {
+        let mut id = [0; 8];
+        id.copy_from_slice(&Uuid::new_v4().as_bytes()[..8]);
+        let id = SpanId(id);
+        if self.is_enabled() {
+            self.spans.lock().unwrap().push(Span {
+                id,
+                parent,
+                name: name.into(),
+                kind,
+                start: now_nanos(),
+                end: 0,
+                attributes: vec![],
+                failed: false,
+            });
+        }
+        id
+    }
+
+    pub fn set_attribute(&self, id: SpanId, key: &'static str, value: impl Into<AttributeValue>) {
+        if let Some(span) = self.spans.lock().unwrap().iter_mut().find(|it| it.id == id) {
+            span.attributes.push((key, value.into()));
+        }
+    }
+
+    pub fn end_span(&self, id: SpanId, failed: bool) {
+        if let Some(span) = self.spans.lock().unwrap().iter_mut().find(|it| it.id == id) {
+            span.end = now_nanos();
+            span.failed = failed;
+        }
+    }
+
+    /// Returns the W3C `traceparent` header value for a request made in `span`.
+    pub fn traceparent(&self, span: SpanId) -> String {
+        format!("00-{}-{}-01", hex(&self.trace_id), hex(&span.0))
+    }
+
+    /// Encodes all finished spans as an OTLP `ExportTraceServiceRequest`.
+    pub fn to_otlp_json(&self) -> serde_json::Value {
+        let trace_id = hex(&self.trace_id);
+        let spans = self
+            .spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.end != 0)
+            .map(|span| {
+                let attributes = span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            AttributeValue::String(s) => json!({ "stringValue": s }),
+                            // OTLP/JSON encodes 64 bit integers as strings
+                            AttributeValue::Int(i) => json!({ "intValue": i.to_string() }),
+                        };
+                        json!({ "key": key, "value": value })
+                    })
+                    .collect::<Vec<_>>();
+
+                json!({
+                    "traceId": trace_id,
+                    "spanId": hex(&span.id.0),
+                    "parentSpanId": span.parent.map(|it| hex(&it.0)).unwrap_or_default(),
+                    "name": span.name,
+                    "kind": match span.kind {
+                        SpanKind::Internal => 1,
+                        SpanKind::Client => 3,
+                    },
+                    "startTimeUnixNano": span.start.to_string(),
+                    "endTimeUnixNano": span.end.to_string(),
+                    "attributes": attributes,
+                    "status": { "code": if span.failed { 2 } else { 1 } },
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [{
+                        "key": "service.name",
+                        "value": { "stringValue": "aurora" },
+                    }],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "aurora", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+
+    /// Sends the recorded spans to `<endpoint>/v1/traces`.
+    pub fn export(&self) -> Result<(), ExportError> {
+        let Some(endpoint) = &self.endpoint else {
+            return Ok(());
+        };
+
+        let url = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let response = reqwest::blocking::Client::new()
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(self.to_otlp_json().to_string())
+            .send()
+            .map_err(|e| ExportError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ExportError(format!(
+                "`{url}` responded with {}",
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn now_nanos() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|it| it.as_nanos())
+        .unwrap_or_default()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpanKind, Tracer};
+
+    #[test]
+    fn traceparent_uses_the_span_id() {
+        let tracer = Tracer::new(Some("http://localhost:4318".to_string()));
+        let span = tracer.start_span("request", SpanKind::Client, None);
+        let traceparent = tracer.traceparent(span);
+
+        let parts = traceparent.split('-').collect::<Vec<_>>();
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "00");
+        assert_eq!(parts[1].len(), 32);
+        assert_eq!(parts[2].len(), 16);
+        assert_eq!(parts[3], "01");
+    }
+
+    #[test]
+    fn otlp_json_contains_finished_spans() {
+        let tracer = Tracer::new(Some("http://localhost:4318".to_string()));
+        let entry = tracer.start_span("entry getUser", SpanKind::Internal, None);
+        let request = tracer.start_span("GET", SpanKind::Client, Some(entry));
+        tracer.set_attribute(request, "http.response.status_code", 404);
+        tracer.end_span(request, true);
+        tracer.end_span(entry, true);
+        tracer.start_span("unfinished", SpanKind::Internal, None);
+
+        let json = tracer.to_otlp_json();
+        let spans = json["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0]["name"], "entry getUser");
+        assert_eq!(spans[0]["parentSpanId"], "");
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_eq!(spans[1]["kind"], 3);
+        assert_eq!(spans[1]["attributes"][0]["value"]["intValue"], "404");
+        assert_eq!(spans[1]["status"]["code"], 2);
+    }
+
+    #[test]
+    fn disabled_tracer_records_nothing() {
+        let tracer = Tracer::new(None);
+        let span = tracer.start_span("entry", SpanKind::Internal, None);
+        tracer.end_span(span, false);
+        assert!(!tracer.is_enabled());
+        assert!(
+            tracer.to_otlp_json()["resourceSpans"][0]["scopeSpans"][0]["spans"]
+                .as_array()
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
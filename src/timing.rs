@@ -0,0 +1,120 @@
+use std::{
+    future::Future,
+    net::ToSocketAddrs,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// How long the phases of a request took, besides the total time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Resolving the host name. `None` if no connection was opened, the host
+    /// is an IP address or it is pinned with `--resolve`.
+    pub dns: Option<Duration>,
+    /// Opening the connection, including resolving the host name and the TLS
+    /// handshake, which reqwest does in one step. `None` if an open
+    /// connection was reused.
+    pub connect: Option<Duration>,
+    /// Time until the status line and the headers were received
+    pub ttfb: Duration,
+}
+
+/// Collects the timings of the request that is being sent. reqwest doesn't
+/// report them, so its resolver and connector write them here.
+///
+/// A client sends one request at a time, so the timings always belong to
+/// the current request.
+#[derive(Debug, Clone, Default)]
+pub struct TimingRecorder(Arc<Mutex<Timings>>);
+
+impl TimingRecorder {
+    /// Forgets the timings of the previous request.
+    pub fn reset(&self) {
+        *self.0.lock().unwrap() = Timings::default();
+    }
+
+    pub fn get(&self) -> Timings {
+        *self.0.lock().unwrap()
+    }
+
+    /// A resolver that uses the system's resolver and records how long it
+    /// took.
+    pub fn resolver(&self) -> Arc<TimedResolver> {
+        Arc::new(TimedResolver(self.clone()))
+    }
+
+    /// A connector layer that records how long opening a connection took.
+    pub fn connector_layer(&self) -> TimedConnectLayer {
+        TimedConnectLayer(self.clone())
+    }
+}
+
+pub struct TimedResolver(TimingRecorder);
+
+impl Resolve for TimedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let recorder = self.0.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let start = Instant::now();
+            // The lookup blocks, so it mustn't run on the client's runtime.
+            let addrs = tokio::task::spawn_blocking(move || {
+                (host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(|it| it.collect::<Vec<_>>())
+            })
+            .await??;
+            recorder.0.lock().unwrap().dns = Some(start.elapsed());
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct TimedConnectLayer(TimingRecorder);
+
+impl<S> tower::Layer<S> for TimedConnectLayer {
+    type Service = TimedConnect<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimedConnect {
+            inner,
+            recorder: self.0.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TimedConnect<S> {
+    inner: S,
+    recorder: TimingRecorder,
+}
+
+impl<S, R> tower::Service<R> for TimedConnect<S>
+where
+    S: tower::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let recorder = self.recorder.clone();
+        let start = Instant::now();
+        let connecting = self.inner.call(request);
+        Box::pin(async move {
+            let result = connecting.await;
+            recorder.0.lock().unwrap().connect = Some(start.elapsed());
+            result
+        })
+    }
+}
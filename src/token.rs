@@ -18,8 +18,36 @@ pub enum TokenKind<'input> {
     Colon,
     /// `,`
     Comma,
+    /// `.`
+    Dot,
+    /// `...`
+    DotDotDot,
     /// `=`
     Eq,
+    /// `==`
+    EqEq,
+    /// `!=`
+    BangEq,
+    /// `<`
+    Lt,
+    /// `<=`
+    LtEq,
+    /// `>`
+    Gt,
+    /// `>=`
+    GtEq,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `??`
+    QuestionQuestion,
+    /// `@`
+    At,
     /// E.g., `{`
     Delim(Delim),
 }
@@ -36,6 +64,10 @@ pub enum HttpMethod {
     Patch,
     /// `DELETE`
     Delete,
+    /// `HEAD`
+    Head,
+    /// `OPTIONS`
+    Options,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -44,8 +76,24 @@ pub enum Keyword {
     Entry,
     /// `const`
     Const,
+    /// `extends`
+    Extends,
     /// `null`
     Null,
+    /// `true`
+    True,
+    /// `false`
+    False,
+    /// `if`
+    If,
+    /// `then`
+    Then,
+    /// `else`
+    Else,
+    /// `for`
+    For,
+    /// `in`
+    In,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -54,10 +102,14 @@ pub enum Delim {
     OpenBrace,
     /// `[`
     OpenBrack,
+    /// `(`
+    OpenParen,
     /// `}`
     CloseBrace,
     /// `]`
     CloseBrack,
+    /// `)`
+    CloseParen,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -69,8 +121,8 @@ pub enum TemplatePart<'input> {
 impl Delim {
     pub fn is_open(&self) -> bool {
         match self {
-            Delim::OpenBrace | Delim::OpenBrack => true,
-            Delim::CloseBrace | Delim::CloseBrack => false,
+            Delim::OpenBrace | Delim::OpenBrack | Delim::OpenParen => true,
+            Delim::CloseBrace | Delim::CloseBrack | Delim::CloseParen => false,
         }
     }
 }
@@ -80,12 +132,21 @@ impl std::fmt::Display for Delim {
         match self {
             Delim::OpenBrace => write!(f, "{{"),
             Delim::OpenBrack => write!(f, "["),
+            Delim::OpenParen => write!(f, "("),
             Delim::CloseBrace => write!(f, "}}"),
             Delim::CloseBrack => write!(f, "]"),
+            Delim::CloseParen => write!(f, ")"),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comment<'input> {
+    /// The comment text including the leading `#`
+    pub text: &'input str,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Token<'input> {
     pub kind: TokenKind<'input>,
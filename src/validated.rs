@@ -1,24 +1,216 @@
+pub mod visit;
+
+use std::collections::HashSet;
+
 use indexmap::IndexMap;
 
-use crate::span::Span;
+use crate::{diagnostic::Diagnostic, jsonpath::JsonPath, span::Span};
 
+#[derive(Debug)]
 pub struct SourceFile<'input> {
     pub globals: IndexMap<&'input str, Const<'input>>,
     pub entries: IndexMap<&'input str, Entry<'input>>,
+    /// Names of all `env` blocks
+    pub envs: Vec<Name<'input>>,
+    /// Problems that don't stop the file from running
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl SourceFile<'_> {
+    /// Names of the consts and external variables that the consts and
+    /// entries refer to.
+    pub fn referenced_names(&self) -> HashSet<String> {
+        struct Collector(HashSet<String>);
+
+        impl visit::Visitor<'_> for Collector {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if let ExprKind::NameRef(name) = &expr.kind {
+                    self.0.insert(name.clone());
+                }
+                visit::walk_expr(self, expr);
+            }
+        }
+
+        let mut collector = Collector(HashSet::new());
+        visit::Visitor::visit_source_file(&mut collector, self);
+        collector.0
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Const<'input> {
     pub name: Name<'input>,
     pub expr: Expr,
+    /// Whether the value is redacted in all output
+    pub secret: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Entry<'input> {
     pub name: Name<'input>,
+    /// Tags that select the entry with `--tag`
+    pub tags: Vec<Name<'input>>,
+    /// Names whose values are given as arguments when the entry is run
+    pub params: Vec<Name<'input>>,
+    /// Sends the request once per element of an array
+    pub for_each: Option<ForEach<'input>>,
+    /// Sections that are not given are inherited from this entry
+    pub extends: Option<Name<'input>>,
+    /// Entries that run first, this one is skipped if one of them fails
+    pub needs: Vec<Name<'input>>,
+    /// Entries run with arguments before the request, as part of this one
+    pub calls: Vec<Call<'input>>,
     pub request: Option<Request>,
+    /// Prefix of the request URL unless that has a scheme
+    pub base_url: Option<Expr>,
+    pub query: Option<Expr>,
     pub headers: Option<Expr>,
+    /// Sent in the `Cookie` header, before the cookies of the jar
+    pub cookies: Option<Expr>,
     pub body: Option<Expr>,
+    pub auth: Option<Auth>,
+    /// E.g. `{"timeout": 5000, "retries": 3}`
+    pub options: Option<Expr>,
+    /// Values picked out of the response for later entries
+    pub captures: Vec<Capture>,
+    /// Checks of the response, the run fails if one doesn't hold
+    pub assertions: Vec<Assertion>,
+}
+
+/// E.g. `getUser("42")` in `entry me needs getUser("42")`
+#[derive(Debug, Clone)]
+pub struct Call<'input> {
+    pub entry: Name<'input>,
+    /// One string per parameter of the entry
+    pub args: Vec<Expr>,
+}
+
+impl Entry<'_> {
+    /// Returns where the entry runs around the other entries of a run, if it
+    /// has one of the tags of a hook.
+    pub fn hook(&self) -> Option<Hook> {
+        self.tags.iter().find_map(|tag| match tag.text {
+            "before_all" => Some(Hook::BeforeAll),
+            "before_each" => Some(Hook::BeforeEach),
+            "after_each" => Some(Hook::AfterEach),
+            "after_all" => Some(Hook::AfterAll),
+            _ => None,
+        })
+    }
+}
+
+/// Entries tagged e.g. with `@before_all` run around the entries of a run,
+/// to set up fixtures and clean up after tests. They don't run on their own
+/// unless they are chosen by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// `@before_all`, once before the first entry
+    BeforeAll,
+    /// `@before_each`, before every entry
+    BeforeEach,
+    /// `@after_each`, after every entry
+    AfterEach,
+    /// `@after_all`, once after the last entry
+    AfterAll,
+}
+
+/// E.g. `"token": "$.access_token"` in a `[Capture]` section.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    pub name: String,
+    pub path: JsonPath,
+    pub span: Span,
+}
+
+/// E.g. `status == 200` in an `[Assert]` section.
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    /// A failure is reported as a warning and doesn't fail the entry
+    pub soft: bool,
+    /// The comparison must not hold
+    pub negated: bool,
+    pub subject: Subject,
+    pub op: CompareOp,
+    pub expected: Expr,
+    pub span: Span,
+}
+
+/// The part of a response that an assertion checks.
+#[derive(Debug, Clone)]
+pub enum Subject {
+    Status,
+    /// The values of the header with this name, joined with commas
+    Header(Expr),
+    /// The raw body, or the first value the path selects in a JSON body
+    Body(Option<JsonPath>),
+    /// Time until the whole body was received, in milliseconds
+    Duration,
+    /// Time until the headers were received, in milliseconds
+    Ttfb,
+    /// Length of the body in bytes, after decoding it
+    Size,
+    /// Lowercase hex SHA-256 digest of the body, e.g. of a downloaded file
+    Sha256,
+    /// Lowercase hex MD5 digest of the body
+    Md5,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// A substring, an element of an array or a key of an object
+    Contains,
+    /// A regular expression that matches somewhere in a string
+    Matches,
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+            CompareOp::Contains => "contains",
+            CompareOp::Matches => "matches",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// How the `Authorization` header of a request is generated.
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// `Bearer <token>`
+    Bearer(Expr),
+    /// AWS Signature Version 4, computed when the request is sent
+    AwsSigV4(Box<AwsSigV4>),
+}
+
+/// E.g. `{"aws-sigv4": {"region": "us-east-1", "service": "s3", ...}}`
+#[derive(Debug, Clone)]
+pub struct AwsSigV4 {
+    pub region: Expr,
+    pub service: Expr,
+    pub access_key: Expr,
+    pub secret_key: Expr,
+    /// Needed for temporary credentials
+    pub session_token: Option<Expr>,
+}
+
+/// E.g. `for user in users { ... }`
+#[derive(Debug, Clone)]
+pub struct ForEach<'input> {
+    /// Bound to the current element of `items`
+    pub name: Name<'input>,
+    pub items: Expr,
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +232,30 @@ pub enum HttpMethod {
     Put,
     Patch,
     Delete,
+    Head,
+    Options,
+}
+
+impl HttpMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Options => "OPTIONS",
+        }
+    }
+
+    /// Returns `true` for methods that change state on the server.
+    pub fn is_mutating(self) -> bool {
+        match self {
+            HttpMethod::Get | HttpMethod::Head | HttpMethod::Options => false,
+            HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch | HttpMethod::Delete => true,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -52,12 +268,60 @@ pub struct Expr {
 #[derive(Debug, Clone)]
 pub enum ExprKind {
     NameRef(String),
+    /// A name that is only visible inside the entry, such as a parameter
+    Local(String),
+    /// A value captured from the response of an earlier entry
+    Capture(String),
     StringLiteral(Vec<TemplatePart>),
     IntegerLiteral(i64),
     FloatLiteral(f64),
     NullLiteral,
+    BoolLiteral(bool),
     Dictionary(Vec<DictionaryField>),
     Array(Vec<Expr>),
+    Call(Builtin, Vec<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Field(Box<Expr>, String),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// An integer that is used as a float, e.g. an integer that overrides
+    /// a float const
+    ToFloat(Box<Expr>),
+}
+
+/// Binary operators, resolved by the types of their operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    /// `+` on two strings
+    Concat,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// `??`, the right operand is only evaluated if the left one is `null`
+    Coalesce,
+}
+
+/// Functions that are built into the language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// Inserts a value into a URL without percent-encoding it
+    Raw,
+    /// A random UUID
+    Uuid,
+    /// The current UTC time, in RFC 3339 or a `strftime` format
+    Now,
+    /// The current Unix time in seconds
+    Timestamp,
+    /// Standard base64 with padding
+    Base64,
+    /// Percent-encodes everything except unreserved characters
+    UrlEncode,
+    /// Lowercase hex SHA-256 digest
+    Sha256,
+    /// An environment variable, with an optional default
+    Env,
+    /// The contents of a file, relative to the directory of the source file
+    Read,
 }
 
 #[derive(Debug, Clone)]
@@ -67,9 +331,14 @@ pub enum TemplatePart {
 }
 
 #[derive(Debug, Clone)]
-pub struct DictionaryField {
-    pub key: Expr,
-    pub value: Expr,
+pub enum DictionaryField {
+    Pair {
+        key: Expr,
+        value: Expr,
+    },
+    /// E.g., `...defaultHeaders`, evaluated once when the dictionary is built.
+    /// Its fields replace earlier fields with the same key.
+    Spread(Expr),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -78,7 +347,10 @@ pub enum Ty {
     Integer,
     Float,
     Null,
-    Dictionary(Vec<Ty>),
+    Bool,
+    /// Field names and types, the name is `None` for keys that are
+    /// templates
+    Dictionary(Vec<(Option<String>, Ty)>),
     Array(Box<Ty>),
     Union(Vec<Ty>),
     Unknown,
@@ -91,6 +363,7 @@ impl std::fmt::Display for Ty {
             Ty::Integer => write!(f, "int"),
             Ty::Float => write!(f, "float"),
             Ty::Null => write!(f, "null"),
+            Ty::Bool => write!(f, "bool"),
             Ty::Dictionary(_) => write!(f, "dict"),
             Ty::Array(ty) => write!(f, "{ty}[]"),
             Ty::Union(tys) => {
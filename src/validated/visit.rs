@@ -0,0 +1,168 @@
+//! Read-only traversal over the validated tree.
+//!
+//! Works like [`crate::ast::visit`], but globals are visited before entries,
+//! in definition order.
+
+use crate::validated::{
+    Auth, Const, DictionaryField, Entry, Expr, ExprKind, Request, SourceFile, Subject, TemplatePart,
+};
+
+pub trait Visitor<'input> {
+    fn visit_source_file(&mut self, file: &SourceFile<'input>) {
+        walk_source_file(self, file);
+    }
+
+    fn visit_const(&mut self, konst: &Const<'input>) {
+        walk_const(self, konst);
+    }
+
+    fn visit_entry(&mut self, entry: &Entry<'input>) {
+        walk_entry(self, entry);
+    }
+
+    fn visit_request(&mut self, request: &Request) {
+        walk_request(self, request);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_template_part(&mut self, part: &TemplatePart) {
+        walk_template_part(self, part);
+    }
+
+    fn visit_dictionary_field(&mut self, field: &DictionaryField) {
+        walk_dictionary_field(self, field);
+    }
+}
+
+pub fn walk_source_file<'input, V: Visitor<'input> + ?Sized>(v: &mut V, file: &SourceFile<'input>) {
+    for konst in file.globals.values() {
+        v.visit_const(konst);
+    }
+    for entry in file.entries.values() {
+        v.visit_entry(entry);
+    }
+}
+
+pub fn walk_const<'input, V: Visitor<'input> + ?Sized>(v: &mut V, konst: &Const<'input>) {
+    v.visit_expr(&konst.expr);
+}
+
+pub fn walk_entry<'input, V: Visitor<'input> + ?Sized>(v: &mut V, entry: &Entry<'input>) {
+    for call in &entry.calls {
+        for arg in &call.args {
+            v.visit_expr(arg);
+        }
+    }
+    if let Some(for_each) = &entry.for_each {
+        v.visit_expr(&for_each.items);
+    }
+    if let Some(request) = &entry.request {
+        v.visit_request(request);
+    }
+    if let Some(base_url) = &entry.base_url {
+        v.visit_expr(base_url);
+    }
+    if let Some(query) = &entry.query {
+        v.visit_expr(query);
+    }
+    if let Some(headers) = &entry.headers {
+        v.visit_expr(headers);
+    }
+    if let Some(cookies) = &entry.cookies {
+        v.visit_expr(cookies);
+    }
+    if let Some(body) = &entry.body {
+        v.visit_expr(body);
+    }
+    if let Some(options) = &entry.options {
+        v.visit_expr(options);
+    }
+    match &entry.auth {
+        Some(Auth::Bearer(token)) => v.visit_expr(token),
+        Some(Auth::AwsSigV4(aws)) => {
+            v.visit_expr(&aws.region);
+            v.visit_expr(&aws.service);
+            v.visit_expr(&aws.access_key);
+            v.visit_expr(&aws.secret_key);
+            if let Some(token) = &aws.session_token {
+                v.visit_expr(token);
+            }
+        }
+        None => {}
+    }
+    for assertion in &entry.assertions {
+        if let Subject::Header(name) = &assertion.subject {
+            v.visit_expr(name);
+        }
+        v.visit_expr(&assertion.expected);
+    }
+}
+
+pub fn walk_request<'input, V: Visitor<'input> + ?Sized>(v: &mut V, request: &Request) {
+    v.visit_expr(&request.url);
+}
+
+pub fn walk_expr<'input, V: Visitor<'input> + ?Sized>(v: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::NameRef(_)
+        | ExprKind::Local(_)
+        | ExprKind::Capture(_)
+        | ExprKind::IntegerLiteral(_)
+        | ExprKind::FloatLiteral(_)
+        | ExprKind::NullLiteral
+        | ExprKind::BoolLiteral(_) => {}
+        ExprKind::StringLiteral(parts) => {
+            for part in parts {
+                v.visit_template_part(part);
+            }
+        }
+        ExprKind::Dictionary(fields) => {
+            for field in fields {
+                v.visit_dictionary_field(field);
+            }
+        }
+        ExprKind::Array(elems) => {
+            for elem in elems {
+                v.visit_expr(elem);
+            }
+        }
+        ExprKind::Call(_, args) => {
+            for arg in args {
+                v.visit_expr(arg);
+            }
+        }
+        ExprKind::Binary(_, lhs, rhs) => {
+            v.visit_expr(lhs);
+            v.visit_expr(rhs);
+        }
+        ExprKind::Field(base, _) | ExprKind::ToFloat(base) => v.visit_expr(base),
+        ExprKind::If(cond, then, else_) => {
+            v.visit_expr(cond);
+            v.visit_expr(then);
+            v.visit_expr(else_);
+        }
+    }
+}
+
+pub fn walk_template_part<'input, V: Visitor<'input> + ?Sized>(v: &mut V, part: &TemplatePart) {
+    match part {
+        TemplatePart::Literal(_) => {}
+        TemplatePart::Expr(expr) => v.visit_expr(expr),
+    }
+}
+
+pub fn walk_dictionary_field<'input, V: Visitor<'input> + ?Sized>(
+    v: &mut V,
+    field: &DictionaryField,
+) {
+    match field {
+        DictionaryField::Pair { key, value } => {
+            v.visit_expr(key);
+            v.visit_expr(value);
+        }
+        DictionaryField::Spread(expr) => v.visit_expr(expr),
+    }
+}
@@ -1,133 +1,816 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use indexmap::{IndexMap, map};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::{
-    ast,
+    ast, codes,
     diagnostic::{Diagnostic, Level},
+    jsonpath::JsonPath,
     parser,
     span::Span,
     validated::{self},
+    vars::VariableProvider,
 };
 
-pub fn validate<'vars, 'input>(
+pub fn validate<'input>(
     input: &'input str,
-    external_vars: &'vars HashMap<String, String>,
-) -> Result<validated::SourceFile<'input>, Diagnostic> {
+    external_vars: &dyn VariableProvider,
+) -> Result<validated::SourceFile<'input>, Vec<Diagnostic>> {
+    validate_in_env(input, external_vars, None)
+}
+
+/// Returns the problems of `input`, without any external variables, e.g.
+/// for the browser playground. Empty if the file is valid.
+pub fn check_source(input: &str) -> Vec<Diagnostic> {
+    validate(input, &HashMap::<String, String>::new())
+        .err()
+        .unwrap_or_default()
+}
+
+/// Returns the warnings about `input`, without any external variables. A
+/// file with problems has none, [`check_source`] returns its problems.
+pub fn check_warnings(input: &str) -> Vec<Diagnostic> {
+    validate(input, &HashMap::<String, String>::new())
+        .map(|file| file.warnings)
+        .unwrap_or_default()
+}
+
+/// Validates `input` with the consts of the `env` block named `env` taking
+/// precedence over top-level consts of the same name.
+///
+/// All syntax errors are returned at once. Otherwise the problems of all
+/// globals are, or else the problems of all entries, since an entry can't be
+/// checked against a global that is broken.
+pub fn validate_in_env<'input>(
+    input: &'input str,
+    external_vars: &dyn VariableProvider,
+    env: Option<&str>,
+) -> Result<validated::SourceFile<'input>, Vec<Diagnostic>> {
     let file = parser::parse(input)?;
-    let validator = Validator::new(external_vars);
+    let mut validator = Validator::new(external_vars);
+    validator.env = env;
     validator.validate(file)
 }
 
+#[derive(Debug)]
+pub enum OverrideError {
+    UnknownConst(String),
+    InvalidValue {
+        name: String,
+        value: String,
+        expected: validated::Ty,
+    },
+}
+
+impl std::fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OverrideError::UnknownConst(name) => {
+                write!(f, "I couldn't find a `const` named `{name}` to override")
+            }
+            OverrideError::InvalidValue {
+                name,
+                value,
+                expected,
+            } => write!(
+                f,
+                "I couldn't use `{value}` as the value of `{name}`, I was expecting a value of type `{expected}`"
+            ),
+        }
+    }
+}
+
+/// Replaces the value of the global `name` with `value`, e.g. given with
+/// `--set` on the command line.
+///
+/// String constants take `value` as it is. For all other types, `value` is
+/// parsed as an expression and needs to match the type of the original value.
+/// Integers given for floats are converted, so arithmetic stays the same.
+pub fn override_const(
+    file: &mut validated::SourceFile<'_>,
+    name: &str,
+    value: &str,
+) -> Result<(), OverrideError> {
+    let konst = file
+        .globals
+        .get_mut(name)
+        .ok_or_else(|| OverrideError::UnknownConst(name.to_string()))?;
+
+    let invalid = || OverrideError::InvalidValue {
+        name: name.to_string(),
+        value: value.to_string(),
+        expected: konst.expr.ty.clone(),
+    };
+
+    let expr = if konst.expr.ty == validated::Ty::String {
+        validated::Expr {
+            kind: validated::ExprKind::StringLiteral(vec![validated::TemplatePart::Literal(
+                value.to_string(),
+            )]),
+            span: konst.expr.span,
+            ty: validated::Ty::String,
+        }
+    } else {
+        let no_vars = HashMap::<String, String>::new();
+        let expr = parser::parse_expr(value)
+            .and_then(|expr| Validator::new(&no_vars).validate_expr(expr, &Scope::default()))
+            .map_err(|_| invalid())?;
+        if !accepts(&konst.expr.ty, &expr.ty) {
+            return Err(invalid());
+        }
+        validated::Expr {
+            span: konst.expr.span,
+            ..coerce(expr, &konst.expr.ty)
+        }
+    };
+
+    konst.expr = expr;
+    Ok(())
+}
+
+/// Converts the integers in `expr` that are used where `expected` has
+/// floats into floats.
+fn coerce(expr: validated::Expr, expected: &validated::Ty) -> validated::Expr {
+    use validated::{DictionaryField, ExprKind, Ty};
+
+    match (expected, expr.kind) {
+        (Ty::Float, ExprKind::IntegerLiteral(i)) => validated::Expr {
+            kind: ExprKind::FloatLiteral(i as f64),
+            ty: Ty::Float,
+            ..expr
+        },
+        (Ty::Float, kind) if expr.ty == Ty::Integer => validated::Expr {
+            span: expr.span,
+            kind: ExprKind::ToFloat(Box::new(validated::Expr { kind, ..expr })),
+            ty: Ty::Float,
+        },
+        (Ty::Array(element), ExprKind::Array(items)) => {
+            let items = items
+                .into_iter()
+                .map(|it| coerce(it, element))
+                .collect::<Vec<_>>();
+            validated::Expr {
+                ty: Ty::Array(Box::new(merge_types(
+                    items.iter().map(|it| it.ty.clone()).collect(),
+                ))),
+                kind: ExprKind::Array(items),
+                ..expr
+            }
+        }
+        (Ty::Dictionary(expected), ExprKind::Dictionary(fields)) => {
+            let fields = fields
+                .into_iter()
+                .map(|field| match field {
+                    DictionaryField::Pair { key, value } => {
+                        let name = static_string(&key);
+                        let value = match expected.iter().find(|(it, _)| *it == name) {
+                            Some((_, ty)) if name.is_some() => coerce(value, ty),
+                            _ => value,
+                        };
+                        DictionaryField::Pair { key, value }
+                    }
+                    spread => spread,
+                })
+                .collect::<Vec<_>>();
+            validated::Expr {
+                ty: dictionary_type(&fields),
+                kind: ExprKind::Dictionary(fields),
+                ..expr
+            }
+        }
+        (_, kind) => validated::Expr { kind, ..expr },
+    }
+}
+
+/// Returns `true` if a value of type `actual` can be used where `expected`
+/// was inferred.
+fn accepts(expected: &validated::Ty, actual: &validated::Ty) -> bool {
+    use validated::Ty;
+
+    match (expected, actual) {
+        (expected, actual) if expected == actual => true,
+        (Ty::Float, Ty::Integer) => true,
+        // Fields that are accessed by name have to stay around.
+        (Ty::Dictionary(expected), Ty::Dictionary(actual)) => {
+            expected.iter().all(|(name, expected)| {
+                name.is_none()
+                    || actual
+                        .iter()
+                        .any(|(n, actual)| n == name && accepts(expected, actual))
+            })
+        }
+        (Ty::Array(_), Ty::Array(actual)) if **actual == Ty::Unknown => true,
+        (Ty::Array(expected), Ty::Array(actual)) => accepts(expected, actual),
+        (expected, Ty::Union(actual)) => actual.iter().all(|it| accepts(expected, it)),
+        (Ty::Union(expected), actual) => expected.iter().any(|it| accepts(it, actual)),
+        _ => false,
+    }
+}
+
+/// Names that are only visible inside an entry, such as its parameters.
+#[derive(Debug, Default)]
+struct Scope<'input> {
+    locals: Vec<(&'input str, validated::Ty)>,
+}
+
+impl<'input> Scope<'input> {
+    /// Returns the type of the innermost local named `name`.
+    fn get(&self, name: &str) -> Option<&validated::Ty> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(local, _)| *local == name)
+            .map(|(_, ty)| ty)
+    }
+}
+
 struct Validator<'vars, 'input> {
     globals: IndexMap<&'input str, validated::Const<'input>>,
     entries: IndexMap<&'input str, validated::Entry<'input>>,
-    external_vars: &'vars HashMap<String, String>,
+    envs: Vec<validated::Name<'input>>,
+    /// Name of the active `env` block
+    env: Option<&'vars str>,
+    /// Globals defined by the active `env` block
+    env_globals: HashSet<&'input str>,
+    /// Sections of the `defaults` block, validated like an entry
+    defaults: Option<validated::Entry<'input>>,
+    /// Names bound by the `[Capture]` sections of all entries
+    captures: HashSet<String>,
+    external_vars: &'vars dyn VariableProvider,
+    warnings: Vec<Diagnostic>,
 }
 
 impl<'vars, 'input> Validator<'vars, 'input> {
-    fn new(external_vars: &'vars HashMap<String, String>) -> Self {
+    fn new(external_vars: &'vars dyn VariableProvider) -> Self {
         Self {
             globals: IndexMap::new(),
             entries: IndexMap::new(),
+            envs: vec![],
+            env: None,
+            env_globals: HashSet::new(),
+            defaults: None,
+            captures: HashSet::new(),
             external_vars,
+            warnings: vec![],
         }
     }
 
     fn validate(
         mut self,
         file: ast::SourceFile<'input>,
-    ) -> Result<validated::SourceFile<'input>, Diagnostic> {
+    ) -> Result<validated::SourceFile<'input>, Vec<Diagnostic>> {
+        // Entries can only refer to globals, so they are validated once all
+        // globals are known.
+        let mut entries = vec![];
+        let mut defaults: Option<(Span, Vec<ast::EntryItem<'input>>)> = None;
+        let mut errors = vec![];
         for item in file.items {
             match item.kind {
-                ast::ItemKind::Entry(entry) => {
-                    let entry_name = entry.name;
-                    let validated_entry = self.validate_entry(entry)?;
-                    match self.entries.entry(entry_name.text) {
-                        map::Entry::Occupied(occupied) => {
-                            return Err(Diagnostic::error(
-                                format!(
-                                    "The entry `{}` is defined multiple times",
-                                    entry_name.text
-                                ),
-                                entry_name.span,
-                            )
+                ast::ItemKind::Entry(entry) => entries.push(entry),
+                ast::ItemKind::Defaults(items) => {
+                    if let Some((first, _)) = &defaults {
+                        errors.push(
+                            Diagnostic::error("The defaults are defined multiple times", item.span)
+                                .code(codes::DEFINED_MULTIPLE_TIMES)
+                                .primary_label(
+                                    "I was expecting at most one `defaults` block",
+                                    Level::Error,
+                                )
+                                .label("They were first defined here", *first, Level::Error),
+                        );
+                        continue;
+                    }
+                    if let Some(item) = items.iter().find(|it| {
+                        matches!(
+                            it.kind,
+                            ast::EntryItemKind::Request(_) | ast::EntryItemKind::For(..)
+                        )
+                    }) {
+                        errors.push(Diagnostic::error("Unexpected request", item.span).code(codes::MISPLACED_ITEM)
                             .primary_label(
-                                "I have already seen an entry with this name",
-                                Level::Error,
-                            )
-                            .label(
-                                "It was first defined here",
-                                occupied.get().name.span,
+                                "I was expecting only sections in `defaults`, requests belong into entries",
                                 Level::Error,
                             ));
-                        }
-                        map::Entry::Vacant(vacant) => _ = vacant.insert(validated_entry),
+                        continue;
                     }
+                    defaults = Some((item.span, items));
                 }
-                ast::ItemKind::Const(name, expr) => {
-                    if self.external_vars.contains_key(name.text) {
-                        return Err(Diagnostic::error(
-                            format!("The variable `{}` is defined multiple times", name.text),
-                            name.span,
-                        )
-                        .primary_label(
-                            "I have already seen a variable with this name as a command line argument",
-                            Level::Error,
-                        ));
+                ast::ItemKind::Const(name, ty, expr, secret) => {
+                    if let Err(d) = self.define_const(name, ty, expr, secret, false) {
+                        errors.push(d);
                     }
-
-                    let validated_expr = self.validate_expr(expr)?;
-                    match self.globals.entry(name.text) {
-                        map::Entry::Occupied(occupied) => {
-                            return Err(Diagnostic::error(
-                                format!("The variable `{}` is defined multiple times", name.text),
+                }
+                ast::ItemKind::Env(name, items) => {
+                    if let Some(first) = self.envs.iter().find(|it| it.text == name.text) {
+                        errors.push(
+                            Diagnostic::error(
+                                format!(
+                                    "The environment `{}` is defined multiple times",
+                                    name.text
+                                ),
                                 name.span,
                             )
+                            .code(codes::DEFINED_MULTIPLE_TIMES)
                             .primary_label(
-                                "I have already seen a variable with this name",
+                                "I have already seen an environment with this name",
                                 Level::Error,
                             )
                             .label(
                                 "It was first defined here",
-                                occupied.get().name.span,
+                                first.span,
                                 Level::Error,
-                            ));
-                        }
-                        map::Entry::Vacant(vacant) => {
-                            _ = vacant.insert(validated::Const {
-                                name: validated::Name {
-                                    text: name.text,
-                                    span: name.span,
-                                },
-                                expr: validated_expr,
-                            })
+                            ),
+                        );
+                        continue;
+                    }
+                    self.envs.push(validated::Name {
+                        text: name.text,
+                        span: name.span,
+                    });
+
+                    if self.env == Some(name.text) {
+                        for item in items {
+                            if let ast::ItemKind::Const(name, ty, expr, secret) = item.kind
+                                && let Err(d) = self.define_const(name, ty, expr, secret, true)
+                            {
+                                errors.push(d);
+                            }
                         }
                     }
                 }
             }
         }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Any entry may refer to what another one captures, so the names are
+        // known before the entries are validated.
+        for entry in &entries {
+            self.captures.extend(capture_names(&entry.body));
+        }
+
+        if let Some((span, body)) = defaults {
+            self.defaults = Some(
+                self.validate_entry(ast::Entry {
+                    name: ast::Name {
+                        text: "defaults",
+                        span,
+                    },
+                    tags: vec![],
+                    params: vec![],
+                    extends: None,
+                    needs: vec![],
+                    body,
+                })
+                .map_err(|d| vec![d])?,
+            );
+        }
+
+        let validated_entries = entries
+            .into_par_iter()
+            .map(|entry| self.validate_entry(entry))
+            .collect::<Vec<_>>();
+
+        for validated_entry in validated_entries {
+            let validated_entry = match validated_entry {
+                Ok(entry) => entry,
+                Err(d) => {
+                    errors.push(d);
+                    continue;
+                }
+            };
+            let entry_name = validated_entry.name.clone();
+            match self.entries.entry(entry_name.text) {
+                map::Entry::Occupied(occupied) => {
+                    errors.push(
+                        Diagnostic::error(
+                            format!("The entry `{}` is defined multiple times", entry_name.text),
+                            entry_name.span,
+                        )
+                        .code(codes::DEFINED_MULTIPLE_TIMES)
+                        .primary_label("I have already seen an entry with this name", Level::Error)
+                        .label(
+                            "It was first defined here",
+                            occupied.get().name.span,
+                            Level::Error,
+                        ),
+                    );
+                }
+                map::Entry::Vacant(vacant) => _ = vacant.insert(validated_entry),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // Before resolving, entries only have their own sections, so an
+        // inherited body is reported once.
+        for entry in self.entries.values() {
+            if let (Some(request), Some(body)) = (&entry.request, &entry.body)
+                && matches!(
+                    request.method,
+                    validated::HttpMethod::Get | validated::HttpMethod::Head
+                )
+            {
+                let method = request.method.as_str();
+                self.warnings.push(
+                    Diagnostic::warning(
+                        format!("The `{method}` request of `{}` has a body", entry.name.text),
+                        body.span,
+                    )
+                    .code(codes::GET_WITH_BODY)
+                    .primary_label("I will send this body anyway", Level::Warning)
+                    .note(format!(
+                        "Servers usually ignore the body of a `{method}` request, or reject it"
+                    )),
+                );
+            }
+
+            // Parameters and the loop variable hide globals and external
+            // variables of the same name inside the entry.
+            let locals = entry
+                .params
+                .iter()
+                .map(|it| ("parameter", it))
+                .chain(entry.for_each.iter().map(|it| ("loop variable", &it.name)));
+            for (kind, local) in locals {
+                let warning = |shadowed: &str| {
+                    Diagnostic::warning(
+                        format!("The {kind} `{}` shadows the {shadowed}", local.text),
+                        local.span,
+                    )
+                    .code(codes::SHADOWED_NAME)
+                    .primary_label(
+                        format!(
+                            "Inside `{}`, `{}` refers to this {kind}",
+                            entry.name.text, local.text
+                        ),
+                        Level::Warning,
+                    )
+                };
+                if let Some(konst) = self.globals.get(local.text) {
+                    self.warnings
+                        .push(warning(&format!("const `{}`", local.text)).label(
+                            "The const is defined here",
+                            konst.name.span,
+                            Level::Warning,
+                        ));
+                } else if self.external_vars.contains(local.text) {
+                    self.warnings.push(
+                        warning(&format!("variable `{}`", local.text))
+                            .note("The variable is given on the command line"),
+                    );
+                }
+            }
+        }
+
+        let names = self.entries.keys().copied().collect::<Vec<_>>();
+        let mut resolved = HashSet::new();
+        for name in &names {
+            self.resolve_extends(name, &mut resolved, &mut vec![])
+                .map_err(|d| vec![d])?;
+        }
+        // Needs are inherited, so cycles are only known after resolving.
+        let mut checked = HashSet::new();
+        for name in names {
+            self.check_needs(name, &mut checked, &mut vec![])
+                .map_err(|d| vec![d])?;
+        }
 
-        Ok(validated::SourceFile {
+        let mut file = validated::SourceFile {
             entries: self.entries,
             globals: self.globals,
-        })
+            envs: self.envs,
+            warnings: self.warnings,
+        };
+        // Entries have inherited the defaults and their parents by now, so
+        // every use of a global is found in the entries or other globals.
+        let referenced = file.referenced_names();
+        for konst in file.globals.values() {
+            let name = konst.name.text;
+            if !name.starts_with('_') && !referenced.contains(name) {
+                file.warnings.push(
+                    Diagnostic::warning(
+                        format!("The const `{name}` is never used"),
+                        konst.name.span,
+                    )
+                    .code(codes::UNUSED_CONST)
+                    .primary_label("No entry or const refers to it", Level::Warning)
+                    .help(format!("Remove it, or rename it to `_{name}` to keep it")),
+                );
+            }
+        }
+        Ok(file)
+    }
+
+    /// Adds a global. A const of the active `env` block replaces a top-level
+    /// const of the same name, whose type it has to match.
+    fn define_const(
+        &mut self,
+        name: ast::Name<'input>,
+        ty: Option<ast::TypeExpr<'input>>,
+        expr: ast::Expr<'input>,
+        secret: bool,
+        from_env: bool,
+    ) -> Result<(), Diagnostic> {
+        if self.external_vars.contains(name.text) {
+            return Err(Diagnostic::error(
+                format!("The variable `{}` is defined multiple times", name.text),
+                name.span,
+            )
+            .code(codes::DEFINED_MULTIPLE_TIMES)
+            .primary_label(
+                "I have already seen a variable with this name as a command line argument",
+                Level::Error,
+            ));
+        }
+
+        let validated_expr = self.validate_expr(expr, &Scope::default())?;
+        if let Some(ty) = ty
+            && !Annotation::resolve(&ty)?.matches(&validated_expr.ty)
+        {
+            return Err(mismatched_type(
+                validated_expr.span,
+                &format!("a value of type `{ty}`"),
+                &validated_expr.ty,
+            )
+            .label("The type was declared here", ty.span, Level::Error));
+        }
+        let konst = validated::Const {
+            name: validated::Name {
+                text: name.text,
+                span: name.span,
+            },
+            expr: validated_expr,
+            secret,
+        };
+        match self.globals.entry(name.text) {
+            map::Entry::Occupied(mut occupied)
+                if from_env != self.env_globals.contains(name.text) =>
+            {
+                let (default, value) = if from_env {
+                    (occupied.get(), &konst)
+                } else {
+                    (&konst, occupied.get())
+                };
+                if !accepts(&default.expr.ty, &value.expr.ty) {
+                    return Err(mismatched_type(
+                        value.expr.span,
+                        &format!("a value of type `{}`", default.expr.ty),
+                        &value.expr.ty,
+                    )
+                    .label(
+                        "The type comes from this definition",
+                        default.name.span,
+                        Level::Error,
+                    ));
+                }
+                if from_env {
+                    // Overriding a secret in an environment keeps it secret.
+                    let secret = konst.secret || occupied.get().secret;
+                    occupied.insert(validated::Const { secret, ..konst });
+                    self.env_globals.insert(name.text);
+                }
+            }
+            map::Entry::Occupied(occupied) => {
+                return Err(Diagnostic::error(
+                    format!("The variable `{}` is defined multiple times", name.text),
+                    name.span,
+                )
+                .code(codes::DEFINED_MULTIPLE_TIMES)
+                .primary_label(
+                    "I have already seen a variable with this name",
+                    Level::Error,
+                )
+                .label(
+                    "It was first defined here",
+                    occupied.get().name.span,
+                    Level::Error,
+                ));
+            }
+            map::Entry::Vacant(vacant) => {
+                vacant.insert(konst);
+                if from_env {
+                    self.env_globals.insert(name.text);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the sections of entry `name` that it inherits from the entry
+    /// it extends, after resolving that entry the same way.
+    fn resolve_extends(
+        &mut self,
+        name: &'input str,
+        resolved: &mut HashSet<&'input str>,
+        extending: &mut Vec<&'input str>,
+    ) -> Result<(), Diagnostic> {
+        if resolved.contains(name) {
+            return Ok(());
+        }
+        let Some(base) = self.entries[name].extends.clone() else {
+            // Only entries at the root of a chain inherit the defaults, the
+            // others get them through their base entry.
+            if let Some(defaults) = self.defaults.clone() {
+                inherit_entry(&mut self.entries[name], defaults);
+            }
+            resolved.insert(name);
+            return Ok(());
+        };
+
+        extending.push(name);
+        if extending.contains(&base.text) {
+            return Err(Diagnostic::error(
+                format!("Entry `{}` extends itself", base.text),
+                base.span,
+            )
+            .code(codes::CYCLIC_ENTRIES)
+            .primary_label(
+                format!("I found a cycle of entries extending `{}` here", base.text),
+                Level::Error,
+            ));
+        }
+        if !self.entries.contains_key(base.text) {
+            return Err(
+                Diagnostic::error(format!("Unknown entry `{}`", base.text), base.span)
+                    .code(codes::UNKNOWN_ENTRY)
+                    .primary_label("I don't know any entry with this name", Level::Error),
+            );
+        }
+        self.resolve_extends(base.text, resolved, extending)?;
+        extending.pop();
+
+        let base = self.entries[base.text].clone();
+        inherit_entry(&mut self.entries[name], base);
+        resolved.insert(name);
+        Ok(())
+    }
+
+    /// Checks that the entries `name` needs or calls exist and don't need
+    /// `name` in turn, and that calls pass an argument for every parameter.
+    fn check_needs(
+        &self,
+        name: &'input str,
+        checked: &mut HashSet<&'input str>,
+        needing: &mut Vec<&'input str>,
+    ) -> Result<(), Diagnostic> {
+        if checked.contains(name) {
+            return Ok(());
+        }
+        needing.push(name);
+        let entry = &self.entries[name];
+        let calls = entry.calls.iter().map(|it| &it.entry);
+        for need in entry.needs.iter().chain(calls) {
+            if needing.contains(&need.text) {
+                return Err(Diagnostic::error(
+                    format!("Entry `{}` needs itself", need.text),
+                    need.span,
+                )
+                .code(codes::CYCLIC_ENTRIES)
+                .primary_label(
+                    format!("I found a cycle of entries needing `{}` here", need.text),
+                    Level::Error,
+                ));
+            }
+            if !self.entries.contains_key(need.text) {
+                return Err(
+                    Diagnostic::error(format!("Unknown entry `{}`", need.text), need.span)
+                        .code(codes::UNKNOWN_ENTRY)
+                        .primary_label("I don't know any entry with this name", Level::Error),
+                );
+            }
+            self.check_needs(need.text, checked, needing)?;
+        }
+        for call in &entry.calls {
+            let params = self.entries[call.entry.text].params.len();
+            if call.args.len() != params {
+                let expected = match params {
+                    1 => "1 argument".to_string(),
+                    n => format!("{n} arguments"),
+                };
+                return Err(
+                    Diagnostic::error("Wrong number of arguments", call.entry.span)
+                        .code(codes::INVALID_CALL)
+                        .primary_label(
+                            format!("I was expecting {expected} to `{}` here", call.entry.text),
+                            Level::Error,
+                        ),
+                );
+            }
+        }
+        needing.pop();
+        checked.insert(name);
+        Ok(())
     }
 
     fn validate_entry(
         &self,
         entry: ast::Entry<'input>,
     ) -> Result<validated::Entry<'input>, Diagnostic> {
+        let mut scope = Scope::default();
+        let mut params: Vec<validated::Name<'input>> = vec![];
+        for param in entry.params {
+            if let Some(first) = params.iter().find(|it| it.text == param.text) {
+                return Err(Diagnostic::error(
+                    format!("The parameter `{}` is defined multiple times", param.text),
+                    param.span,
+                )
+                .code(codes::DEFINED_MULTIPLE_TIMES)
+                .primary_label(
+                    "I have already seen a parameter with this name",
+                    Level::Error,
+                )
+                .label("It was first defined here", first.span, Level::Error));
+            }
+            // Arguments are given on the command line and are therefore strings.
+            scope.locals.push((param.text, validated::Ty::String));
+            params.push(validated::Name {
+                text: param.text,
+                span: param.span,
+            });
+        }
+
+        // Called entries run once before the loop, with the parameters as
+        // their only locals.
+        let mut needs = vec![];
+        let mut calls = vec![];
+        for need in entry.needs {
+            let name = validated::Name {
+                text: need.name.text,
+                span: need.name.span,
+            };
+            let Some(args) = need.args else {
+                needs.push(name);
+                continue;
+            };
+            let mut validated_args = Vec::with_capacity(args.len());
+            for arg in args {
+                let arg_span = arg.span;
+                let arg = self.validate_expr(arg, &scope)?;
+                // Parameters are strings, like the arguments on the command line.
+                if arg.ty != validated::Ty::String {
+                    return Err(mismatched_type(arg_span, "a string", &arg.ty));
+                }
+                validated_args.push(arg);
+            }
+            calls.push(validated::Call {
+                entry: name,
+                args: validated_args,
+            });
+        }
+
+        // A loop repeats the whole entry, so it has to be its only item.
+        let mut body = entry.body;
+        let mut for_each = None;
+        if body.len() == 1
+            && matches!(body[0].kind, ast::EntryItemKind::For(..))
+            && let Some(ast::EntryItemKind::For(name, items, items_body)) =
+                body.pop().map(|it| it.kind)
+        {
+            let items_span = items.span;
+            let items = self.validate_expr(items, &scope)?;
+            let validated::Ty::Array(element_ty) = &items.ty else {
+                return Err(mismatched_type(items_span, "an array", &items.ty));
+            };
+            scope.locals.push((name.text, (**element_ty).clone()));
+            for_each = Some(validated::ForEach {
+                name: validated::Name {
+                    text: name.text,
+                    span: name.span,
+                },
+                items,
+            });
+            body = items_body;
+        }
+        let scope = &scope;
+
         let mut validated_request = None;
+        let mut validated_base_url = None;
+        let mut validated_query = None;
         let mut validated_headers = None;
+        let mut validated_cookies = None;
+        let mut validated_options = None;
         let mut validated_body = None;
-        for item in entry.body {
+        let mut validated_auth = None;
+        let mut validated_captures = None;
+        let mut validated_assertions = None;
+        for item in body {
             match item.kind {
+                ast::EntryItemKind::For(..) => {
+                    return Err(Diagnostic::error("Unexpected loop", item.span).code(codes::MISPLACED_ITEM).primary_label(
+                        "I can only repeat a whole entry, so a `for` has to be the only item of an entry",
+                        Level::Error,
+                    ));
+                }
                 ast::EntryItemKind::Request(request) => {
                     let url_span = request.url.span;
-                    let validated_url = self.validate_expr(request.url)?;
+                    let validated_url = self.validate_expr(request.url, scope)?;
                     if validated_url.ty != validated::Ty::String {
                         return Err(Diagnostic::error("Mismatched types", url_span)
+                            .code(codes::MISMATCHED_TYPES)
                             .primary_label("I was expecting a string here", Level::Error));
                     }
 
@@ -137,6 +820,7 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                 format!("Entry `{}` contains multiple requests", entry.name.text),
                                 item.span,
                             )
+                            .code(codes::REPEATED_SECTION)
                             .primary_label(
                                 format!(
                                     "I was expecting to find one request in entry `{}`",
@@ -153,20 +837,91 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                     ast::HttpMethod::Put => validated::HttpMethod::Put,
                                     ast::HttpMethod::Patch => validated::HttpMethod::Patch,
                                     ast::HttpMethod::Delete => validated::HttpMethod::Delete,
+                                    ast::HttpMethod::Head => validated::HttpMethod::Head,
+                                    ast::HttpMethod::Options => validated::HttpMethod::Options,
                                 },
                                 url: validated_url,
                             })
                         }
                     }
                 }
+                ast::EntryItemKind::Assert(assertions) => {
+                    if validated_assertions.is_some() {
+                        return Err(Diagnostic::error(
+                            format!(
+                                "Entry `{}` contains multiple `[Assert]` sections",
+                                entry.name.text
+                            ),
+                            item.span,
+                        ).code(codes::REPEATED_SECTION)
+                        .primary_label(
+                            format!(
+                                "I was expecting to find at most one `[Assert]` section in entry `{}`",
+                                entry.name.text
+                            ),
+                            Level::Error,
+                        ));
+                    }
+                    let mut validated = Vec::with_capacity(assertions.len());
+                    for assertion in assertions {
+                        validated.push(self.validate_assertion(assertion, scope)?);
+                    }
+                    validated_assertions = Some(validated);
+                }
                 ast::EntryItemKind::Section(name, body) => {
                     let body_span = body.span;
-                    let validated_expr = self.validate_expr(body)?;
+                    let validated_expr = self.validate_expr(body, scope)?;
                     match name.text {
+                        "BaseUrl" => {
+                            if validated_expr.ty != validated::Ty::String {
+                                return Err(mismatched_type(
+                                    body_span,
+                                    "a string",
+                                    &validated_expr.ty,
+                                ));
+                            }
+                            if validated_base_url.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[BaseUrl]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[BaseUrl]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_base_url = Some(validated_expr);
+                        }
+                        "Capture" => {
+                            if validated_captures.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[Capture]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[Capture]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_captures = Some(self.validate_captures(validated_expr)?);
+                        }
                         "Headers" => {
-                            if let validated::Ty::Dictionary(value_types) = &validated_expr.ty {
-                                if !value_types.iter().all(|it| *it == validated::Ty::String) {
+                            if let validated::Ty::Dictionary(fields) = &validated_expr.ty {
+                                if !fields.iter().all(|(_, ty)| *ty == validated::Ty::String) {
                                     return Err(Diagnostic::error("Unexpected types", body_span)
+                                        .code(codes::MISMATCHED_TYPES)
                                         .primary_label(
                                             "I was expecting all the values to be strings here",
                                             Level::Error,
@@ -174,6 +929,7 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                 }
                             } else {
                                 return Err(Diagnostic::error("Unexpected type", body_span)
+                                    .code(codes::MISMATCHED_TYPES)
                                     .primary_label(
                                         "I was expecting a dictionary here",
                                         Level::Error,
@@ -188,7 +944,7 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                                     entry.name.text
                                                 ),
                                                 item.span,
-                                            )
+                                            ).code(codes::REPEATED_SECTION)
                                             .primary_label(
                                                 format!(
                                                     "I was expecting to find at most one `[Headers]` section in entry `{}`",
@@ -202,11 +958,91 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                 }
                             }
                         }
+                        "Cookies" => {
+                            let valid = match &validated_expr.ty {
+                                validated::Ty::Dictionary(fields) => {
+                                    fields.iter().all(|(_, ty)| *ty == validated::Ty::String)
+                                }
+                                _ => false,
+                            };
+                            if !valid {
+                                return Err(Diagnostic::error("Unexpected type", body_span)
+                                    .code(codes::MISMATCHED_TYPES)
+                                    .primary_label(
+                                        "I was expecting a dictionary of strings here",
+                                        Level::Error,
+                                    ));
+                            }
+
+                            if validated_cookies.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[Cookies]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[Cookies]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_cookies = Some(validated_expr);
+                        }
+                        "Query" => {
+                            let valid = match &validated_expr.ty {
+                                validated::Ty::Dictionary(fields) => {
+                                    fields.iter().all(|(_, ty)| {
+                                        matches!(
+                                            ty,
+                                            validated::Ty::String
+                                                | validated::Ty::Integer
+                                                | validated::Ty::Float
+                                                | validated::Ty::Bool
+                                        )
+                                    })
+                                }
+                                _ => false,
+                            };
+                            if !valid {
+                                return Err(Diagnostic::error("Unexpected type", body_span).code(codes::MISMATCHED_TYPES)
+                                    .primary_label(
+                                        "I was expecting a dictionary of strings, numbers and booleans here",
+                                        Level::Error,
+                                    ));
+                            }
+
+                            if validated_query.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[Query]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[Query]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_query = Some(validated_expr);
+                        }
                         "Body" => {
-                            if !matches!(validated_expr.ty, validated::Ty::Dictionary(_)) {
+                            // Strings are sent as they are, e.g. the contents of a file.
+                            if !matches!(
+                                validated_expr.ty,
+                                validated::Ty::Dictionary(_) | validated::Ty::String
+                            ) {
                                 return Err(Diagnostic::error("Unexpected type", body_span)
+                                    .code(codes::MISMATCHED_TYPES)
                                     .primary_label(
-                                        "I was expecting a dictionary here",
+                                        "I was expecting a dictionary or a string here",
                                         Level::Error,
                                     ));
                             }
@@ -219,7 +1055,7 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                                     entry.name.text
                                                 ),
                                                 item.span,
-                                            )
+                                            ).code(codes::REPEATED_SECTION)
                                             .primary_label(
                                                 format!(
                                                     "I was expecting to find at most one `[Body]` section in entry `{}`",
@@ -233,15 +1069,121 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                                 }
                             }
                         }
+                        "Auth" => {
+                            if validated_auth.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[Auth]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[Auth]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_auth = Some(validate_auth(validated_expr)?);
+                        }
+                        "Options" => {
+                            let validated::Ty::Dictionary(fields) = &validated_expr.ty else {
+                                return Err(Diagnostic::error("Unexpected type", body_span)
+                                    .code(codes::MISMATCHED_TYPES)
+                                    .primary_label(
+                                        "I was expecting a dictionary here",
+                                        Level::Error,
+                                    ));
+                            };
+                            for (option, ty) in fields {
+                                let option = option.as_deref().unwrap_or_default();
+                                let expected = match option {
+                                    "timeout" | "connect_timeout" | "max_redirects" | "retries"
+                                    | "retry_backoff" | "delay" | "max_events" | "max_duration" => {
+                                        validated::Ty::Integer
+                                    }
+                                    "follow_redirects" | "gzip" | "decompress" => {
+                                        validated::Ty::Bool
+                                    }
+                                    "http_version" => validated::Ty::String,
+                                    "poll" => {
+                                        let valid = match ty {
+                                            validated::Ty::Dictionary(fields) => {
+                                                fields.iter().all(|(name, ty)| {
+                                                    matches!(
+                                                        name.as_deref(),
+                                                        Some("interval" | "attempts")
+                                                    ) && *ty == validated::Ty::Integer
+                                                })
+                                            }
+                                            _ => false,
+                                        };
+                                        if !valid {
+                                            return Err(Diagnostic::error(
+                                                "Unexpected type",
+                                                body_span,
+                                            ).code(codes::MISMATCHED_TYPES)
+                                            .primary_label(
+                                                "I was expecting `poll` to be a dictionary with the integers `interval` and `attempts`",
+                                                Level::Error,
+                                            ));
+                                        }
+                                        continue;
+                                    }
+                                    _ => {
+                                        return Err(Diagnostic::error(
+                                            format!("Unknown option `{option}`"),
+                                            body_span,
+                                        ).code(codes::INVALID_OPTION)
+                                        .primary_label(
+                                            "I only know the options `timeout`, `connect_timeout`, `follow_redirects`, `max_redirects`, `retries`, `retry_backoff`, `http_version`, `delay`, `max_events`, `max_duration`, `gzip`, `decompress` and `poll`",
+                                            Level::Error,
+                                        ));
+                                    }
+                                };
+                                if *ty != expected {
+                                    return Err(mismatched_type(
+                                        body_span,
+                                        &format!("`{expected}` as `{option}`"),
+                                        ty,
+                                    ));
+                                }
+                            }
+
+                            if validated_options.is_some() {
+                                return Err(Diagnostic::error(
+                                    format!(
+                                        "Entry `{}` contains multiple `[Options]` sections",
+                                        entry.name.text
+                                    ),
+                                    item.span,
+                                ).code(codes::REPEATED_SECTION)
+                                .primary_label(
+                                    format!(
+                                        "I was expecting to find at most one `[Options]` section in entry `{}`",
+                                        entry.name.text
+                                    ),
+                                    Level::Error,
+                                ));
+                            }
+                            validated_options = Some(validated_expr);
+                        }
                         _ => {
-                            return Err(Diagnostic::error(
+                            let diagnostic = Diagnostic::error(
                                 format!("Unknown section name `{}`", name.text),
                                 name.span,
                             )
+                            .code(codes::UNKNOWN_SECTION)
                             .primary_label(
                                 "I don't know what to do with this section here",
                                 Level::Error,
-                            ));
+                            );
+                            return Err(match suggest(name.text, SECTIONS.into_iter()) {
+                                Some(known) => diagnostic.help(format!("Did you mean `{known}`?")),
+                                None => diagnostic,
+                            });
                         }
                     }
                 }
@@ -253,13 +1195,298 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                 text: entry.name.text,
                 span: entry.name.span,
             },
+            tags: entry
+                .tags
+                .into_iter()
+                .map(|it| validated::Name {
+                    text: it.text,
+                    span: it.span,
+                })
+                .collect(),
+            params,
+            for_each,
+            extends: entry.extends.map(|it| validated::Name {
+                text: it.text,
+                span: it.span,
+            }),
+            needs,
+            calls,
             request: validated_request,
+            base_url: validated_base_url,
+            query: validated_query,
             headers: validated_headers,
+            cookies: validated_cookies,
             body: validated_body,
+            auth: validated_auth,
+            options: validated_options,
+            captures: validated_captures.unwrap_or_default(),
+            assertions: validated_assertions.unwrap_or_default(),
+        })
+    }
+
+    fn validate_assertion(
+        &self,
+        assertion: ast::Assertion<'input>,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Assertion, Diagnostic> {
+        use validated::{CompareOp, Subject, Ty};
+
+        let subject_name = assertion.subject.text;
+        let argument = match assertion.argument {
+            Some(argument) => {
+                let span = argument.span;
+                Some((self.validate_expr(argument, scope)?, span))
+            }
+            None => None,
+        };
+        let subject = match (subject_name, argument) {
+            ("status", None) => Subject::Status,
+            ("duration", None) => Subject::Duration,
+            ("ttfb", None) => Subject::Ttfb,
+            ("size", None) => Subject::Size,
+            ("sha256", None) => Subject::Sha256,
+            ("md5", None) => Subject::Md5,
+            ("body", None) => Subject::Body(None),
+            ("body", Some((path, span))) => {
+                let Some(raw) = static_string(&path) else {
+                    return Err(mismatched_type(span, "a JSONPath string", &path.ty));
+                };
+                let path = JsonPath::parse(&raw).map_err(|e| {
+                    Diagnostic::error("Invalid JSONPath", span)
+                        .code(codes::INVALID_JSONPATH)
+                        .primary_label(format!("I couldn't read this path: {e}"), Level::Error)
+                })?;
+                Subject::Body(Some(path))
+            }
+            ("header", Some((name, span))) => {
+                if !accepts(&Ty::String, &name.ty) && name.ty != Ty::Unknown {
+                    return Err(mismatched_type(span, "a header name", &name.ty));
+                }
+                Subject::Header(name)
+            }
+            ("header", None) => {
+                return Err(
+                    Diagnostic::error("Missing header name", assertion.subject.span)
+                        .code(codes::INVALID_ASSERTION)
+                        .primary_label(
+                            "I was expecting the name of a header after `header`",
+                            Level::Error,
+                        ),
+                );
+            }
+            ("status" | "duration" | "ttfb" | "size" | "sha256" | "md5", Some((_, span))) => {
+                return Err(Diagnostic::error("Unexpected argument", span)
+                    .code(codes::INVALID_ASSERTION)
+                    .primary_label(
+                        format!("I was expecting a comparison after `{subject_name}`"),
+                        Level::Error,
+                    ));
+            }
+            _ => {
+                return Err(Diagnostic::error(
+                    format!("Unknown assertion `{subject_name}`"),
+                    assertion.subject.span,
+                ).code(codes::INVALID_ASSERTION)
+                .primary_label(
+                    "I only know how to check `status`, `header`, `body`, `size`, `sha256`, `md5`, `duration` and `ttfb`",
+                    Level::Error,
+                ));
+            }
+        };
+
+        let op = match assertion.op {
+            ast::CompareOp::Eq => CompareOp::Eq,
+            ast::CompareOp::Ne => CompareOp::Ne,
+            ast::CompareOp::Lt => CompareOp::Lt,
+            ast::CompareOp::Le => CompareOp::Le,
+            ast::CompareOp::Gt => CompareOp::Gt,
+            ast::CompareOp::Ge => CompareOp::Ge,
+            ast::CompareOp::Contains => CompareOp::Contains,
+            ast::CompareOp::Matches => CompareOp::Matches,
+        };
+        // Values picked out of a JSON body can have any type, so only numbers
+        // and plain strings are checked here.
+        let (subject_ty, ops): (Option<Ty>, &[CompareOp]) = match &subject {
+            Subject::Status | Subject::Duration | Subject::Ttfb | Subject::Size => (
+                Some(Ty::Integer),
+                &[
+                    CompareOp::Eq,
+                    CompareOp::Ne,
+                    CompareOp::Lt,
+                    CompareOp::Le,
+                    CompareOp::Gt,
+                    CompareOp::Ge,
+                ],
+            ),
+            Subject::Header(_) | Subject::Body(None) => (
+                Some(Ty::String),
+                &[
+                    CompareOp::Eq,
+                    CompareOp::Ne,
+                    CompareOp::Contains,
+                    CompareOp::Matches,
+                ],
+            ),
+            Subject::Sha256 | Subject::Md5 => (Some(Ty::String), &[CompareOp::Eq, CompareOp::Ne]),
+            Subject::Body(Some(_)) => (None, &[]),
+        };
+        if subject_ty.is_some() && !ops.contains(&op) {
+            let ops = ops
+                .iter()
+                .map(|it| format!("`{it}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(Diagnostic::error("Unexpected comparison", assertion.span)
+                .code(codes::INVALID_ASSERTION)
+                .primary_label(
+                    format!("I can only check `{subject_name}` with {ops}"),
+                    Level::Error,
+                ));
+        }
+
+        let expected_span = assertion.expected.span;
+        let mut expected = self.validate_expr(assertion.expected, scope)?;
+        let expected_ty = match op {
+            CompareOp::Matches => Some(Ty::String),
+            _ => subject_ty,
+        };
+        if let Some(ty) = &expected_ty
+            && !accepts(ty, &expected.ty)
+            && expected.ty != Ty::Unknown
+        {
+            let description = match ty {
+                Ty::Integer => "an integer",
+                _ => "a string",
+            };
+            return Err(mismatched_type(expected_span, description, &expected.ty));
+        }
+        if op == CompareOp::Matches
+            && let Some(pattern) = static_string(&expected)
+        {
+            compile_regex(&pattern, expected_span)?;
+        }
+
+        // Durations are compared in milliseconds.
+        if let Some(unit) = assertion.unit {
+            let factor = match (&subject, unit.text) {
+                (Subject::Duration | Subject::Ttfb, "ms") => 1,
+                (Subject::Duration | Subject::Ttfb, "s") => 1000,
+                _ => {
+                    return Err(Diagnostic::error(
+                        format!("Unexpected unit `{}`", unit.text),
+                        unit.span,
+                    )
+                    .code(codes::INVALID_ASSERTION)
+                    .primary_label(
+                        "I only know the units `ms` and `s`, for `duration` and `ttfb`",
+                        Level::Error,
+                    ));
+                }
+            };
+            if factor != 1 {
+                let span = expected.span.to(unit.span);
+                expected = validated::Expr {
+                    kind: validated::ExprKind::Binary(
+                        validated::BinOp::Mul,
+                        Box::new(expected),
+                        Box::new(validated::Expr {
+                            kind: validated::ExprKind::IntegerLiteral(factor),
+                            span: unit.span,
+                            ty: Ty::Integer,
+                        }),
+                    ),
+                    span,
+                    ty: Ty::Integer,
+                };
+            }
+        }
+
+        Ok(validated::Assertion {
+            soft: assertion.soft,
+            negated: assertion.negated,
+            subject,
+            op,
+            expected,
+            span: assertion.span,
         })
     }
 
-    fn validate_expr(&self, expr: ast::Expr<'input>) -> Result<validated::Expr, Diagnostic> {
+    /// Turns the value of a `[Capture]` section, e.g.
+    /// `{"token": "$.access_token"}`, into the captures it names.
+    fn validate_captures(
+        &self,
+        expr: validated::Expr,
+    ) -> Result<Vec<validated::Capture>, Diagnostic> {
+        let validated::ExprKind::Dictionary(fields) = expr.kind else {
+            return Err(Diagnostic::error("Unexpected type", expr.span)
+                .code(codes::MISMATCHED_TYPES)
+                .primary_label(
+                    "I was expecting a dictionary of names and JSONPaths here",
+                    Level::Error,
+                ));
+        };
+
+        let expected_name = |span| {
+            Diagnostic::error("Expected name", span)
+                .code(codes::INVALID_CAPTURE)
+                .primary_label(
+                    "I was expecting a plain name here, not a template or spread",
+                    Level::Error,
+                )
+        };
+
+        let mut captures: Vec<validated::Capture> = vec![];
+        for field in fields {
+            let (key, value) = match field {
+                validated::DictionaryField::Pair { key, value } => (key, value),
+                validated::DictionaryField::Spread(expr) => return Err(expected_name(expr.span)),
+            };
+            // Spread fields aren't known before validation, so only literal
+            // keys are collected as names.
+            let Some(name) = static_string(&key).filter(|it| self.captures.contains(it)) else {
+                return Err(expected_name(key.span));
+            };
+            if self.globals.contains_key(name.as_str()) || self.external_vars.contains(&name) {
+                return Err(Diagnostic::error(
+                    format!("The variable `{name}` is defined multiple times"),
+                    key.span,
+                )
+                .code(codes::DEFINED_MULTIPLE_TIMES)
+                .primary_label(
+                    "I have already seen a variable with this name",
+                    Level::Error,
+                ));
+            }
+            if captures.iter().any(|it| it.name == name) {
+                return Err(Diagnostic::error(
+                    format!("`{name}` is captured multiple times"),
+                    key.span,
+                )
+                .code(codes::DEFINED_MULTIPLE_TIMES)
+                .primary_label("I have already seen a capture with this name", Level::Error));
+            }
+            let Some(path) = static_string(&value) else {
+                return Err(mismatched_type(value.span, "a JSONPath string", &value.ty));
+            };
+            let path = JsonPath::parse(&path).map_err(|e| {
+                Diagnostic::error("Invalid JSONPath", value.span)
+                    .code(codes::INVALID_JSONPATH)
+                    .primary_label(format!("I couldn't read this path: {e}"), Level::Error)
+            })?;
+            captures.push(validated::Capture {
+                name,
+                path,
+                span: key.span.to(value.span),
+            });
+        }
+        Ok(captures)
+    }
+
+    fn validate_expr(
+        &self,
+        expr: ast::Expr<'input>,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Expr, Diagnostic> {
         match expr.kind {
             ast::ExprKind::StringLiteral(parts) => {
                 let mut validated_parts = vec![];
@@ -270,7 +1497,7 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                             validated_parts.push(validated::TemplatePart::Literal(unescaped));
                         }
                         ast::TemplatePart::Expr(expr) => {
-                            let validated_expr = self.validate_expr(expr)?;
+                            let validated_expr = self.validate_expr(expr, scope)?;
                             validated_parts.push(validated::TemplatePart::Expr(validated_expr));
                         }
                     }
@@ -282,9 +1509,18 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                 })
             }
             ast::ExprKind::IntegerLiteral(s) => {
-                let value = s
-                    .parse::<i64>()
-                    .map_err(|_| Diagnostic::error("Invalid integer literal", expr.span))?;
+                let digits = s.replace('_', "");
+                let value = match digits
+                    .strip_prefix("0x")
+                    .or_else(|| digits.strip_prefix("0X"))
+                {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => digits.parse::<i64>(),
+                }
+                .map_err(|_| {
+                    Diagnostic::error("Invalid integer literal", expr.span)
+                        .code(codes::INVALID_LITERAL)
+                })?;
 
                 Ok(validated::Expr {
                     kind: validated::ExprKind::IntegerLiteral(value),
@@ -293,9 +1529,15 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                 })
             }
             ast::ExprKind::FloatLiteral(s) => {
-                let value = s
-                    .parse::<f64>()
-                    .map_err(|_| Diagnostic::error("Invalid float literal", expr.span))?;
+                let value = s.replace('_', "").parse::<f64>().map_err(|_| {
+                    Diagnostic::error("Invalid float literal", expr.span)
+                        .code(codes::INVALID_LITERAL)
+                })?;
+                if !value.is_finite() {
+                    return Err(Diagnostic::error("Invalid float literal", expr.span)
+                        .code(codes::INVALID_LITERAL)
+                        .primary_label("This number is too large for a float", Level::Error));
+                }
 
                 Ok(validated::Expr {
                     kind: validated::ExprKind::FloatLiteral(value),
@@ -308,56 +1550,342 @@ impl<'vars, 'input> Validator<'vars, 'input> {
                 span: expr.span,
                 ty: validated::Ty::Null,
             }),
-            ast::ExprKind::Dictionary(fields) => self.validate_dictionary_fields(fields, expr.span),
-            ast::ExprKind::Array(elements) => self.validate_array_elements(elements, expr.span),
-            ast::ExprKind::NameRef(name) => {
-                if let Some(konst) = self.globals.get(name) {
-                    Ok(validated::Expr {
-                        kind: validated::ExprKind::NameRef(name.to_string()),
-                        span: konst.expr.span,
-                        ty: konst.expr.ty.clone(),
+            ast::ExprKind::BoolLiteral(b) => Ok(validated::Expr {
+                kind: validated::ExprKind::BoolLiteral(b),
+                span: expr.span,
+                ty: validated::Ty::Bool,
+            }),
+            ast::ExprKind::Dictionary(fields) => {
+                self.validate_dictionary_fields(fields, expr.span, scope)
+            }
+            ast::ExprKind::Array(elements) => {
+                self.validate_array_elements(elements, expr.span, scope)
+            }
+            ast::ExprKind::Call(name, args) => self.validate_call(name, args, expr.span, scope),
+            ast::ExprKind::Binary(op, lhs, rhs) => {
+                self.validate_binary(op, *lhs, *rhs, expr.span, scope)
+            }
+            ast::ExprKind::Field(base, name) => self.validate_field(*base, name, expr.span, scope),
+            ast::ExprKind::If(cond, then, else_) => {
+                let cond_span = cond.span;
+                let cond = self.validate_expr(*cond, scope)?;
+                if cond.ty != validated::Ty::Bool {
+                    return Err(mismatched_type(cond_span, "a bool", &cond.ty));
+                }
+                let then = self.validate_expr(*then, scope)?;
+                let else_ = self.validate_expr(*else_, scope)?;
+                let ty = merge_types(vec![then.ty.clone(), else_.ty.clone()]);
+                Ok(validated::Expr {
+                    kind: validated::ExprKind::If(Box::new(cond), Box::new(then), Box::new(else_)),
+                    span: expr.span,
+                    ty,
+                })
+            }
+            ast::ExprKind::Paren(inner) => {
+                let inner = self.validate_expr(*inner, scope)?;
+                Ok(validated::Expr {
+                    span: expr.span,
+                    ..inner
+                })
+            }
+            ast::ExprKind::NameRef(name) => {
+                if let Some(ty) = scope.get(name) {
+                    Ok(validated::Expr {
+                        kind: validated::ExprKind::Local(name.to_string()),
+                        span: expr.span,
+                        ty: ty.clone(),
+                    })
+                } else if let Some(konst) = self.globals.get(name) {
+                    Ok(validated::Expr {
+                        kind: validated::ExprKind::NameRef(name.to_string()),
+                        span: konst.expr.span,
+                        ty: konst.expr.ty.clone(),
                     })
-                } else if self.external_vars.contains_key(name) {
+                } else if self.external_vars.contains(name) {
                     Ok(validated::Expr {
                         kind: validated::ExprKind::NameRef(name.to_string()),
                         span: expr.span,
+                        ty: self.external_vars.ty(name),
+                    })
+                } else if self.captures.contains(name) {
+                    Ok(validated::Expr {
+                        kind: validated::ExprKind::Capture(name.to_string()),
+                        span: expr.span,
                         ty: validated::Ty::String,
                     })
                 } else {
-                    Err(Diagnostic::error("Unknown identifier", expr.span)
-                        .primary_label("I don't know what this name is referring to", Level::Error))
+                    let known = scope
+                        .locals
+                        .iter()
+                        .map(|(local, _)| *local)
+                        .chain(self.globals.keys().copied())
+                        .chain(self.external_vars.names())
+                        .chain(self.captures.iter().map(String::as_str));
+                    let diagnostic = Diagnostic::error("Unknown identifier", expr.span)
+                        .code(codes::UNKNOWN_IDENTIFIER)
+                        .primary_label("I don't know what this name is referring to", Level::Error);
+                    Err(match suggest(name, known) {
+                        Some(known) => diagnostic.help(format!("Did you mean `{known}`?")),
+                        None => diagnostic,
+                    })
                 }
             }
         }
     }
 
+    fn validate_call(
+        &self,
+        name: ast::Name<'input>,
+        args: Vec<ast::Expr<'input>>,
+        call_span: Span,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Expr, Diagnostic> {
+        use validated::{Builtin, Ty};
+
+        // Arguments of functions with an argument type all have that type.
+        let (builtin, arity, arg_ty, ty) = match name.text {
+            "raw" => (Builtin::Raw, 1..=1, None, Ty::String),
+            "uuid" => (Builtin::Uuid, 0..=0, None, Ty::String),
+            "now" => (Builtin::Now, 0..=1, Some(Ty::String), Ty::String),
+            "timestamp" => (Builtin::Timestamp, 0..=0, None, Ty::Integer),
+            "base64" => (Builtin::Base64, 1..=1, Some(Ty::String), Ty::String),
+            "urlencode" => (Builtin::UrlEncode, 1..=1, Some(Ty::String), Ty::String),
+            "sha256" => (Builtin::Sha256, 1..=1, Some(Ty::String), Ty::String),
+            "env" => (Builtin::Env, 1..=2, Some(Ty::String), Ty::String),
+            "read" => (Builtin::Read, 1..=1, Some(Ty::String), Ty::String),
+            _ => {
+                return Err(Diagnostic::error(
+                    format!("Unknown function `{}`", name.text),
+                    name.span,
+                )
+                .code(codes::INVALID_CALL)
+                .primary_label("I don't know any function with this name", Level::Error));
+            }
+        };
+
+        if !arity.contains(&args.len()) {
+            let (min, max) = (*arity.start(), *arity.end());
+            let expected = match (min, max) {
+                (1, 1) => "1 argument".to_string(),
+                (min, max) if min == max => format!("{min} arguments"),
+                (min, max) => format!("{min} to {max} arguments"),
+            };
+            return Err(Diagnostic::error("Wrong number of arguments", call_span)
+                .code(codes::INVALID_CALL)
+                .primary_label(
+                    format!("I was expecting {expected} to `{}` here", name.text),
+                    Level::Error,
+                ));
+        }
+
+        let mut validated_args = Vec::with_capacity(args.len());
+        for arg in args {
+            let arg_span = arg.span;
+            let arg = self.validate_expr(arg, scope)?;
+            if let Some(expected) = &arg_ty
+                && arg.ty != *expected
+            {
+                return Err(mismatched_type(arg_span, &format!("a {expected}"), &arg.ty));
+            }
+            validated_args.push(arg);
+        }
+
+        Ok(validated::Expr {
+            kind: validated::ExprKind::Call(builtin, validated_args),
+            span: call_span,
+            ty,
+        })
+    }
+
+    fn validate_binary(
+        &self,
+        op: ast::BinOp,
+        lhs: ast::Expr<'input>,
+        rhs: ast::Expr<'input>,
+        span: Span,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Expr, Diagnostic> {
+        if op == ast::BinOp::Coalesce {
+            return self.validate_coalesce(lhs, rhs, span, scope);
+        }
+
+        let (lhs_span, rhs_span) = (lhs.span, rhs.span);
+        let lhs = self.validate_expr(lhs, scope)?;
+        let rhs = self.validate_expr(rhs, scope)?;
+
+        let is_number =
+            |ty: &validated::Ty| matches!(ty, validated::Ty::Integer | validated::Ty::Float);
+
+        let (op, ty) = if op == ast::BinOp::Add && lhs.ty == validated::Ty::String {
+            if rhs.ty != validated::Ty::String {
+                return Err(mismatched_type(rhs_span, "a string", &rhs.ty));
+            }
+            (validated::BinOp::Concat, validated::Ty::String)
+        } else {
+            let expected = if op == ast::BinOp::Add {
+                "a string or a number"
+            } else {
+                "a number"
+            };
+            if !is_number(&lhs.ty) {
+                return Err(mismatched_type(lhs_span, expected, &lhs.ty));
+            }
+            if !is_number(&rhs.ty) {
+                return Err(mismatched_type(rhs_span, "a number", &rhs.ty));
+            }
+
+            let op = match op {
+                ast::BinOp::Add => validated::BinOp::Add,
+                ast::BinOp::Sub => validated::BinOp::Sub,
+                ast::BinOp::Mul => validated::BinOp::Mul,
+                ast::BinOp::Div => validated::BinOp::Div,
+                ast::BinOp::Coalesce => unreachable!("`??` is validated separately"),
+            };
+            // Integers stay integers unless a float is involved.
+            let ty = if lhs.ty == validated::Ty::Float || rhs.ty == validated::Ty::Float {
+                validated::Ty::Float
+            } else {
+                validated::Ty::Integer
+            };
+            (op, ty)
+        };
+
+        Ok(validated::Expr {
+            kind: validated::ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)),
+            span,
+            ty,
+        })
+    }
+
+    /// Validates `lhs ?? rhs`. A name that is neither a global nor an external
+    /// variable is not an error on the left, the expression is `rhs` then.
+    fn validate_coalesce(
+        &self,
+        lhs: ast::Expr<'input>,
+        rhs: ast::Expr<'input>,
+        span: Span,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Expr, Diagnostic> {
+        if let ast::ExprKind::NameRef(name) = lhs.kind
+            && scope.get(name).is_none()
+            && !self.globals.contains_key(name)
+            && !self.external_vars.contains(name)
+        {
+            return self.validate_expr(rhs, scope);
+        }
+
+        let lhs = self.validate_expr(lhs, scope)?;
+        let rhs = self.validate_expr(rhs, scope)?;
+        let ty = match &lhs.ty {
+            validated::Ty::Null => rhs.ty.clone(),
+            validated::Ty::Union(tys) if tys.contains(&validated::Ty::Null) => {
+                let mut tys = tys.clone();
+                tys.retain(|it| *it != validated::Ty::Null);
+                tys.push(rhs.ty.clone());
+                merge_types(tys)
+            }
+            ty => ty.clone(),
+        };
+
+        Ok(validated::Expr {
+            kind: validated::ExprKind::Binary(
+                validated::BinOp::Coalesce,
+                Box::new(lhs),
+                Box::new(rhs),
+            ),
+            span,
+            ty,
+        })
+    }
+
+    fn validate_field(
+        &self,
+        base: ast::Expr<'input>,
+        name: ast::Name<'input>,
+        span: Span,
+        scope: &Scope<'input>,
+    ) -> Result<validated::Expr, Diagnostic> {
+        let base_span = base.span;
+        let base = self.validate_expr(base, scope)?;
+        let validated::Ty::Dictionary(fields) = &base.ty else {
+            return Err(Diagnostic::error("Mismatched types", base_span)
+                .code(codes::MISMATCHED_TYPES)
+                .primary_label(
+                    format!("I was expecting a dictionary here, but found `{}`", base.ty),
+                    Level::Error,
+                ));
+        };
+
+        // A later field with the same key wins, like at runtime.
+        let Some((_, ty)) = fields
+            .iter()
+            .rev()
+            .find(|(n, _)| n.as_deref() == Some(name.text))
+        else {
+            return Err(
+                Diagnostic::error(format!("Unknown field `{}`", name.text), name.span)
+                    .code(codes::UNKNOWN_FIELD)
+                    .primary_label(
+                        "I don't know any field with this name in this dictionary",
+                        Level::Error,
+                    ),
+            );
+        };
+
+        Ok(validated::Expr {
+            ty: ty.clone(),
+            kind: validated::ExprKind::Field(Box::new(base), name.text.to_string()),
+            span,
+        })
+    }
+
     fn validate_dictionary_fields(
         &self,
         fields: Vec<ast::DictionaryField<'input>>,
         dictionary_span: Span,
+        scope: &Scope<'input>,
     ) -> Result<validated::Expr, Diagnostic> {
         let mut validated_fields = Vec::with_capacity(fields.len());
 
         for field in fields {
-            let key_span = field.key.span;
-            let key = self.validate_expr(field.key)?;
-            if key.ty != validated::Ty::String {
-                return Err(Diagnostic::error("Mismatched types", key_span)
-                    .primary_label("I was expecting a string as key here", Level::Error));
+            match field {
+                ast::DictionaryField::Pair { key, value } => {
+                    let key_span = key.span;
+                    let key = self.validate_expr(key, scope)?;
+                    if key.ty != validated::Ty::String {
+                        return Err(Diagnostic::error("Mismatched types", key_span)
+                            .code(codes::MISMATCHED_TYPES)
+                            .primary_label("I was expecting a string as key here", Level::Error));
+                    }
+                    let value = self.validate_expr(value, scope)?;
+
+                    // A later field replaces an earlier field with the same key.
+                    if let Some(name) = static_string(&key) {
+                        validated_fields.retain(|it| {
+                            !matches!(
+                                it,
+                                validated::DictionaryField::Pair { key, .. }
+                                    if static_string(key).as_ref() == Some(&name)
+                            )
+                        });
+                    }
+                    validated_fields.push(validated::DictionaryField::Pair { key, value });
+                }
+                ast::DictionaryField::Spread(expr, _) => {
+                    let expr_span = expr.span;
+                    let expr = self.validate_expr(expr, scope)?;
+                    if !matches!(expr.ty, validated::Ty::Dictionary(_)) {
+                        return Err(mismatched_type(expr_span, "a dictionary", &expr.ty));
+                    }
+                    validated_fields.push(validated::DictionaryField::Spread(expr));
+                }
             }
-            let value = self.validate_expr(field.value)?;
-            validated_fields.push(validated::DictionaryField { key, value });
         }
 
-        let value_types = validated_fields
-            .iter()
-            .map(|it| it.value.ty.clone())
-            .collect();
-
         Ok(validated::Expr {
+            ty: dictionary_type(&validated_fields),
             kind: validated::ExprKind::Dictionary(validated_fields),
             span: dictionary_span,
-            ty: validated::Ty::Dictionary(value_types),
         })
     }
 
@@ -365,10 +1893,11 @@ impl<'vars, 'input> Validator<'vars, 'input> {
         &self,
         elements: Vec<ast::Expr<'input>>,
         array_span: Span,
+        scope: &Scope<'input>,
     ) -> Result<validated::Expr, Diagnostic> {
         let mut validated_exprs = Vec::with_capacity(elements.len());
         for elem in elements {
-            let validated_expr = self.validate_expr(elem)?;
+            let validated_expr = self.validate_expr(elem, scope)?;
             validated_exprs.push(validated_expr);
         }
 
@@ -382,37 +1911,429 @@ impl<'vars, 'input> Validator<'vars, 'input> {
 
     fn infer_array_type(&self, elements: &[validated::Expr]) -> validated::Ty {
         let types = elements.iter().map(|it| it.ty.clone()).collect();
-        self.merge_types(types)
+        merge_types(types)
     }
+}
 
-    fn merge_types(&self, types: Vec<validated::Ty>) -> validated::Ty {
-        let mut flat = vec![];
+/// Flattens unions and removes duplicates. A single type is returned as it
+/// is, no types at all are `Unknown`.
+pub fn merge_types(types: Vec<validated::Ty>) -> validated::Ty {
+    let mut flat = vec![];
 
-        for ty in types {
-            match ty {
-                validated::Ty::Union(inner) => {
-                    for inner_ty in inner {
-                        if !flat.contains(&inner_ty) {
-                            flat.push(inner_ty);
+    for ty in types {
+        match ty {
+            validated::Ty::Union(inner) => {
+                for inner_ty in inner {
+                    if !flat.contains(&inner_ty) {
+                        flat.push(inner_ty);
+                    }
+                }
+            }
+            other => {
+                if !flat.contains(&other) {
+                    flat.push(other);
+                }
+            }
+        }
+    }
+
+    match flat.len() {
+        0 => validated::Ty::Unknown,
+        1 => flat.pop().unwrap(),
+        _ => validated::Ty::Union(flat),
+    }
+}
+
+/// Returns the names a `[Capture]` section in `body` binds. Keys that are
+/// not plain strings are reported when the section is validated.
+fn capture_names(body: &[ast::EntryItem<'_>]) -> Vec<String> {
+    let mut names = vec![];
+    for item in body {
+        match &item.kind {
+            ast::EntryItemKind::Section(name, expr) if name.text == "Capture" => {
+                if let ast::ExprKind::Dictionary(fields) = &expr.kind {
+                    for field in fields {
+                        if let ast::DictionaryField::Pair { key, .. } = field
+                            && let ast::ExprKind::StringLiteral(parts) = &key.kind
+                            && let [ast::TemplatePart::Literal(raw, _)] = &parts[..]
+                            && let Ok(name) = unescape_string(raw, key.span)
+                        {
+                            names.push(name);
                         }
                     }
                 }
-                other => {
-                    if !flat.contains(&other) {
-                        flat.push(other);
+            }
+            ast::EntryItemKind::For(_, _, body) => names.extend(capture_names(body)),
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Fills in the request and sections of `entry` that it doesn't define
+/// itself from `base`.
+fn inherit_entry<'input>(entry: &mut validated::Entry<'input>, base: validated::Entry<'input>) {
+    if entry.request.is_none() {
+        entry.request = base.request;
+    }
+    if entry.for_each.is_none() {
+        entry.for_each = base.for_each;
+    }
+    if entry.base_url.is_none() {
+        entry.base_url = base.base_url;
+    }
+    entry.query = inherit(entry.query.take(), base.query);
+    entry.headers = inherit(entry.headers.take(), base.headers);
+    entry.cookies = inherit(entry.cookies.take(), base.cookies);
+    entry.body = inherit(entry.body.take(), base.body);
+    entry.options = inherit(entry.options.take(), base.options);
+    if entry.auth.is_none() {
+        entry.auth = base.auth;
+    }
+    if entry.captures.is_empty() {
+        entry.captures = base.captures;
+    }
+    if entry.assertions.is_empty() {
+        entry.assertions = base.assertions;
+    }
+    if entry.needs.is_empty() && entry.calls.is_empty() {
+        entry.needs = base.needs;
+        entry.calls = base.calls;
+    }
+    // Inherited sections may refer to the parameters of the base entry.
+    for param in base.params {
+        if !entry.params.iter().any(|it| it.text == param.text) {
+            entry.params.push(param);
+        }
+    }
+}
+
+/// Combines a section with the one inherited for it. Dictionary literals
+/// are merged, with the fields of `own` replacing inherited fields with the
+/// same key. Otherwise `own` replaces the inherited section.
+fn inherit(own: Option<validated::Expr>, base: Option<validated::Expr>) -> Option<validated::Expr> {
+    let (own, base) = match (own, base) {
+        (Some(own), Some(base)) => (own, base),
+        (own, base) => return own.or(base),
+    };
+    let (validated::ExprKind::Dictionary(own_fields), validated::ExprKind::Dictionary(base_fields)) =
+        (&own.kind, base.kind)
+    else {
+        return Some(own);
+    };
+
+    let own_key = |key: &Option<String>| {
+        own_fields.iter().any(|it| {
+            matches!(it, validated::DictionaryField::Pair { key: own, .. } if static_string(own) == *key)
+        })
+    };
+    let mut fields = base_fields
+        .into_iter()
+        .filter(|field| match field {
+            validated::DictionaryField::Pair { key, .. } => {
+                let key = static_string(key);
+                key.is_none() || !own_key(&key)
+            }
+            validated::DictionaryField::Spread(_) => true,
+        })
+        .collect::<Vec<_>>();
+    fields.extend(own_fields.iter().cloned());
+
+    Some(validated::Expr {
+        ty: dictionary_type(&fields),
+        kind: validated::ExprKind::Dictionary(fields),
+        span: own.span,
+    })
+}
+
+/// Returns the type of a dictionary with `fields`. The fields of spreads
+/// are in it like pairs, a later field replaces an earlier field with the
+/// same key.
+fn dictionary_type(fields: &[validated::DictionaryField]) -> validated::Ty {
+    let mut field_types: Vec<(Option<String>, validated::Ty)> = vec![];
+    let mut push = |name: Option<String>, ty: validated::Ty| {
+        if name.is_some() {
+            field_types.retain(|(it, _)| *it != name);
+        }
+        field_types.push((name, ty));
+    };
+    for field in fields {
+        match field {
+            validated::DictionaryField::Pair { key, value } => {
+                push(static_string(key), value.ty.clone());
+            }
+            validated::DictionaryField::Spread(expr) => {
+                if let validated::Ty::Dictionary(spread) = &expr.ty {
+                    for (name, ty) in spread {
+                        push(name.clone(), ty.clone());
                     }
                 }
             }
         }
+    }
+    validated::Ty::Dictionary(field_types)
+}
+
+/// Returns the value of a string literal without interpolations.
+fn static_string(expr: &validated::Expr) -> Option<String> {
+    match &expr.kind {
+        validated::ExprKind::StringLiteral(parts) => match &parts[..] {
+            [] => Some(String::new()),
+            [validated::TemplatePart::Literal(s)] => Some(s.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Turns the value of an `[Auth]` section, e.g. `{"bearer": token}`, into
+/// the scheme it names.
+fn validate_auth(expr: validated::Expr) -> Result<validated::Auth, Diagnostic> {
+    let validated::Ty::Dictionary(fields) = &expr.ty else {
+        return Err(mismatched_type(
+            expr.span,
+            "a dictionary like `{\"bearer\": token}`",
+            &expr.ty,
+        ));
+    };
+    let [(scheme, ty)] = fields.as_slice() else {
+        return Err(Diagnostic::error("Invalid authentication", expr.span)
+            .code(codes::INVALID_AUTH)
+            .primary_label(
+                "I was expecting exactly one authentication scheme here",
+                Level::Error,
+            ));
+    };
+    let (scheme, ty) = (scheme.clone(), ty.clone());
+
+    match scheme.as_deref() {
+        Some("bearer") => {
+            if ty != validated::Ty::String {
+                return Err(mismatched_type(expr.span, "a string as the token", &ty));
+            }
+            Ok(validated::Auth::Bearer(field(expr, "bearer", ty)))
+        }
+        Some("aws-sigv4") => {
+            let validated::Ty::Dictionary(settings) = &ty else {
+                return Err(mismatched_type(
+                    expr.span,
+                    "a dictionary of signing settings",
+                    &ty,
+                ));
+            };
+            for (name, ty) in settings {
+                if !AWS_SIGV4_SETTINGS.contains(&name.as_deref().unwrap_or_default()) {
+                    return Err(Diagnostic::error(
+                        format!("Unknown setting `{}`", name.as_deref().unwrap_or_default()),
+                        expr.span,
+                    )
+                    .code(codes::INVALID_AUTH)
+                    .primary_label(
+                        format!(
+                            "I only know the settings {}",
+                            AWS_SIGV4_SETTINGS.map(|it| format!("`{it}`")).join(", ")
+                        ),
+                        Level::Error,
+                    ));
+                }
+                if *ty != validated::Ty::String {
+                    return Err(mismatched_type(
+                        expr.span,
+                        &format!("a string as `{}`", name.as_deref().unwrap_or_default()),
+                        ty,
+                    ));
+                }
+            }
+
+            let settings_expr = field(expr, "aws-sigv4", ty.clone());
+            let setting = |name: &str| {
+                settings
+                    .iter()
+                    .any(|(it, _)| it.as_deref() == Some(name))
+                    .then(|| field(settings_expr.clone(), name, validated::Ty::String))
+            };
+            let required = |name: &str| {
+                setting(name).ok_or_else(|| {
+                    Diagnostic::error(format!("Missing setting `{name}`"), settings_expr.span)
+                        .code(codes::INVALID_AUTH)
+                        .primary_label(
+                            format!("I was expecting `aws-sigv4` to have a `{name}` here"),
+                            Level::Error,
+                        )
+                })
+            };
+
+            Ok(validated::Auth::AwsSigV4(Box::new(validated::AwsSigV4 {
+                region: required("region")?,
+                service: required("service")?,
+                access_key: required("accessKey")?,
+                secret_key: required("secretKey")?,
+                session_token: setting("sessionToken"),
+            })))
+        }
+        scheme => Err(Diagnostic::error(
+            format!(
+                "Unknown authentication scheme `{}`",
+                scheme.unwrap_or_default()
+            ),
+            expr.span,
+        )
+        .code(codes::INVALID_AUTH)
+        .primary_label(
+            "I only know the schemes `bearer` and `aws-sigv4`",
+            Level::Error,
+        )),
+    }
+}
+
+const AWS_SIGV4_SETTINGS: [&str; 5] = [
+    "region",
+    "service",
+    "accessKey",
+    "secretKey",
+    "sessionToken",
+];
+
+/// Returns the expression reading `name` from the dictionary `expr`.
+fn field(expr: validated::Expr, name: &str, ty: validated::Ty) -> validated::Expr {
+    validated::Expr {
+        span: expr.span,
+        ty,
+        kind: validated::ExprKind::Field(Box::new(expr), name.to_string()),
+    }
+}
 
-        match flat.len() {
-            0 => validated::Ty::Unknown,
-            1 => flat.pop().unwrap(),
-            _ => validated::Ty::Union(flat),
+/// A type written in source code, with its names resolved.
+enum Annotation {
+    Ty(validated::Ty),
+    Array(Box<Annotation>),
+    /// A dictionary whose values all have this type
+    Dictionary(Box<Annotation>),
+}
+
+impl Annotation {
+    fn resolve(ty: &ast::TypeExpr) -> Result<Self, Diagnostic> {
+        use validated::Ty;
+
+        Ok(match &ty.kind {
+            ast::TypeExprKind::Named(name) => Annotation::Ty(match name.text {
+                "string" => Ty::String,
+                "int" => Ty::Integer,
+                "float" => Ty::Float,
+                "bool" => Ty::Bool,
+                "null" => Ty::Null,
+                _ => {
+                    return Err(Diagnostic::error(
+                        format!("Unknown type `{}`", name.text),
+                        name.span,
+                    )
+                    .code(codes::UNKNOWN_TYPE)
+                    .primary_label("I don't know any type with this name", Level::Error));
+                }
+            }),
+            ast::TypeExprKind::Array(elem) => Annotation::Array(Box::new(Self::resolve(elem)?)),
+            ast::TypeExprKind::Dictionary(value) => {
+                Annotation::Dictionary(Box::new(Self::resolve(value)?))
+            }
+        })
+    }
+
+    /// Returns `true` if a value of type `actual` has this type.
+    fn matches(&self, actual: &validated::Ty) -> bool {
+        use validated::Ty;
+
+        match (self, actual) {
+            // E.g. the elements of an empty array
+            (_, Ty::Unknown) => true,
+            (_, Ty::Union(types)) => types.iter().all(|it| self.matches(it)),
+            (Annotation::Ty(expected), actual) => accepts(expected, actual),
+            (Annotation::Array(elem), Ty::Array(actual)) => elem.matches(actual),
+            (Annotation::Dictionary(value), Ty::Dictionary(fields)) => {
+                fields.iter().all(|(_, actual)| value.matches(actual))
+            }
+            (Annotation::Array(_) | Annotation::Dictionary(_), _) => false,
         }
     }
 }
 
+/// Compiles the pattern of a `matches` assertion.
+pub fn compile_regex(pattern: &str, span: Span) -> Result<regex::Regex, Diagnostic> {
+    regex::Regex::new(pattern).map_err(|e| {
+        // Syntax errors repeat the pattern with a caret, only the last line
+        // says what is wrong.
+        let e = e.to_string();
+        let reason = e.lines().last().unwrap_or_default();
+        let reason = reason.strip_prefix("error: ").unwrap_or(reason);
+        Diagnostic::error("Invalid regex", span)
+            .code(codes::INVALID_ASSERTION)
+            .primary_label(
+                format!("I couldn't read this pattern: {reason}"),
+                Level::Error,
+            )
+    })
+}
+
+/// Names of the sections of an entry, e.g. `[Headers]`.
+const SECTIONS: [&str; 9] = [
+    "Assert", "Auth", "BaseUrl", "Body", "Capture", "Cookies", "Headers", "Options", "Query",
+];
+
+/// Returns the name of `known` that `name` is most likely a misspelling of,
+/// if any is close enough. Case differences count as half an edit.
+fn suggest<'a>(name: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    // Short names are close to too many others to guess.
+    let max_distance = (name.chars().count() / 3).max(1) * 2;
+    known
+        .filter(|it| *it != name)
+        .map(|it| (edit_distance(name, it), it))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, it)| it)
+}
+
+/// The number of edits that turn `a` into `b`, doubled. An edit inserts,
+/// removes or replaces a character, or swaps two adjacent ones. Replacing a
+/// character with the same one in another case counts as half an edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // The distances of the prefixes of `a` to all prefixes of `b`, for the
+    // current prefix and the two before it.
+    let mut before = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).map(|it| it * 2).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for i in 0..a.len() {
+        current[0] = (i + 1) * 2;
+        for j in 0..b.len() {
+            let replace = if a[i] == b[j] {
+                0
+            } else if a[i].eq_ignore_ascii_case(&b[j]) {
+                1
+            } else {
+                2
+            };
+            current[j + 1] = (previous[j] + replace)
+                .min(previous[j + 1] + 2)
+                .min(current[j] + 2);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                current[j + 1] = current[j + 1].min(before[j - 1] + 2);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn mismatched_type(span: Span, expected: &str, found: &validated::Ty) -> Diagnostic {
+    Diagnostic::error("Mismatched types", span)
+        .code(codes::MISMATCHED_TYPES)
+        .primary_label(
+            format!("I was expecting {expected} here, but found `{found}`"),
+            Level::Error,
+        )
+}
+
 fn unescape_string(raw: &str, span: Span) -> Result<String, Diagnostic> {
     let mut result = String::new();
     let mut escape = false;
@@ -427,6 +2348,7 @@ fn unescape_string(raw: &str, span: Span) -> Result<String, Diagnostic> {
                     let span = Span::new(absolute_index, absolute_index + c.len_utf8());
                     return Err(
                         Diagnostic::error(format!("Unknown character escape `{c}`"), span)
+                            .code(codes::INVALID_LITERAL)
                             .primary_label(
                                 "I don't know how to handle this character escape",
                                 Level::Error,
@@ -448,12 +2370,1232 @@ fn unescape_string(raw: &str, span: Span) -> Result<String, Diagnostic> {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
 
     fn unescape_ok(raw: &str) -> String {
         unescape_string(raw, Span::new(0, raw.len())).expect("String should unescape successfully")
     }
 
+    fn single(mut diagnostics: Vec<Diagnostic>) -> Diagnostic {
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        diagnostics.remove(0)
+    }
+
+    #[test]
+    fn validate_entry_can_refer_to_later_global() {
+        let input = r#"
+entry foo {
+    GET "{{base}}/users"
+}
+const base = "example.com"
+"#;
+        let file =
+            validate(input, &HashMap::<String, String>::new()).expect("input should validate");
+        assert_eq!(file.entries.len(), 1);
+    }
+
+    #[test]
+    fn validate_extends_merges_sections() {
+        let input = r#"
+entry base {
+    GET "example.com/users"
+    [Headers] {"Accept": "application/json", "X-Team": "core"}
+}
+
+entry admins extends base {
+    [Headers] {"X-Team": "admin"}
+}
+
+entry other extends admins {
+    POST "example.com/other"
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let other = &file.entries["other"];
+        assert_eq!(
+            other.request.as_ref().unwrap().method,
+            validated::HttpMethod::Post
+        );
+        let Some(validated::ExprKind::Dictionary(fields)) =
+            other.headers.as_ref().map(|it| &it.kind)
+        else {
+            panic!("headers should be a dictionary");
+        };
+        let headers = fields
+            .iter()
+            .map(|it| {
+                let validated::DictionaryField::Pair { key, value } = it else {
+                    panic!("headers shouldn't have spreads");
+                };
+                (static_string(key).unwrap(), static_string(value).unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                ("Accept".to_string(), "application/json".to_string()),
+                ("X-Team".to_string(), "admin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_env_blocks() {
+        let input = r#"
+const base = "http://localhost:8080"
+const port = 8080
+env staging {
+    const base = "https://staging.example.com"
+    const token = "abc"
+}
+env prod {
+    const base = "https://example.com"
+}
+"#;
+        let file =
+            validate_in_env(input, &HashMap::<String, String>::new(), Some("staging")).unwrap();
+        assert_eq!(file.envs.len(), 2);
+        assert!(matches!(
+            &file.globals["base"].expr.kind,
+            validated::ExprKind::StringLiteral(parts)
+                if matches!(&parts[0], validated::TemplatePart::Literal(s) if s == "https://staging.example.com")
+        ));
+        assert!(file.globals.contains_key("token"));
+
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert!(!file.globals.contains_key("token"));
+
+        let input = "const port = 8080\nenv dev {\n    const port = \"80\"\n}\n";
+        let diagnostic = validate_in_env(input, &HashMap::<String, String>::new(), Some("dev"))
+            .map_err(single)
+            .expect_err("env const of another type");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_const_annotations() {
+        let input = r#"
+const port: int = 8080
+const ratio: float = 1
+const headers: {string} = {"Accept": "application/json"}
+const ids: [int] = []
+const users: [{string}] = [{"name": "Ada"}]
+"#;
+        validate(input, &HashMap::<String, String>::new()).unwrap();
+
+        let diagnostic = validate(
+            "const port: int = \"8080\"",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("wrong type");
+        assert_eq!(diagnostic.message, "Mismatched types");
+
+        let diagnostic = validate(
+            "const h: {string} = {\"a\": 1}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("wrong value type");
+        assert_eq!(diagnostic.message, "Mismatched types");
+
+        let diagnostic = validate(
+            "const ids: [strnig] = []",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("unknown type");
+        assert_eq!(diagnostic.message, "Unknown type `strnig`");
+    }
+
+    #[test]
+    fn validate_entry_tags() {
+        let file = validate(
+            "@smoke @auth\nentry login {}",
+            &HashMap::<String, String>::new(),
+        )
+        .unwrap();
+        let tags = file.entries["login"]
+            .tags
+            .iter()
+            .map(|it| it.text)
+            .collect::<Vec<_>>();
+        assert_eq!(tags, ["smoke", "auth"]);
+    }
+
+    #[test]
+    fn validate_entry_params() {
+        let input = r#"
+const base = "https://example.com"
+const id = 1
+entry getUser(id) {
+    GET "{{base}}/users/{{id}}"
+}
+entry getMe extends getUser {}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let Some(request) = &file.entries["getUser"].request else {
+            panic!("expected a request");
+        };
+        let validated::ExprKind::StringLiteral(parts) = &request.url.kind else {
+            panic!("expected a template");
+        };
+        // The parameter shadows the global of the same name.
+        assert!(matches!(
+            &parts[2],
+            validated::TemplatePart::Expr(validated::Expr {
+                kind: validated::ExprKind::Local(name),
+                ty: validated::Ty::String,
+                ..
+            }) if name == "id"
+        ));
+        assert_eq!(file.entries["getMe"].params[0].text, "id");
+    }
+
+    #[test]
+    fn validate_for_each() {
+        let input = r#"
+const users = [{"name": "Ada"}, {"name": "Grace"}]
+entry seed {
+    for user in users {
+        POST "https://example.com/users/{{user.name}}"
+        [Body] user
+    }
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let entry = &file.entries["seed"];
+        assert_eq!(entry.for_each.as_ref().unwrap().name.text, "user");
+        assert!(matches!(
+            entry.body.as_ref().unwrap().kind,
+            validated::ExprKind::Local(ref name) if name == "user"
+        ));
+
+        let diagnostic = validate(
+            "entry seed { for user in \"x\" {} }",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("loop over a string");
+        assert_eq!(diagnostic.message, "Mismatched types");
+
+        let diagnostic = validate(
+            "entry seed {\n  GET \"https://example.com\"\n  for user in [] {}\n}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("loop next to a request");
+        assert_eq!(diagnostic.message, "Unexpected loop");
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        let messages = |input| {
+            validate(input, &HashMap::<String, String>::new())
+                .expect_err("invalid file")
+                .into_iter()
+                .map(|it| it.message)
+                .collect::<Vec<_>>()
+        };
+
+        let input = r#"
+const a = 1 +
+const b: string = 1
+entry c {
+    GET "https://example.com/{{a}}"
+}
+"#;
+        assert_eq!(messages(input), ["Expected expression"]);
+
+        let input = r#"
+const a = 1
+const b: string = 1
+const c = unknown
+entry d {
+    GET "https://example.com/{{missing}}"
+}
+"#;
+        // The entries are only checked once the globals are valid.
+        assert_eq!(messages(input), ["Mismatched types", "Unknown identifier"]);
+
+        let input = r#"
+entry a {
+    GET "https://example.com/{{missing}}"
+}
+entry b {
+    GET "https://example.com"
+    [Nope] {}
+}
+entry c {}
+entry c {}
+"#;
+        assert_eq!(
+            messages(input),
+            [
+                "Unknown identifier",
+                "Unknown section name `Nope`",
+                "The entry `c` is defined multiple times"
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_warns_about_get_with_body() {
+        let input = r#"
+entry search {
+    GET "https://example.com/search"
+    [Body] {"query": "aurora"}
+}
+entry searchAgain extends search {}
+entry create {
+    POST "https://example.com/items"
+    [Body] {"name": "aurora"}
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let messages = file
+            .warnings
+            .iter()
+            .map(|it| (it.level, it.message.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [(Level::Warning, "The `GET` request of `search` has a body")]
+        );
+        assert!(check_source(input).is_empty());
+        assert_eq!(check_warnings(input).len(), 1);
+    }
+
+    #[test]
+    fn validate_warns_about_unused_and_shadowed_names() {
+        let input = r#"
+const host = "https://example.com"
+const headers = {"Accept": "application/json"}
+const timeout = 5000
+const _spare = 1
+const id = "me"
+const users = [{"name": "a"}]
+defaults {
+    [Headers] headers
+}
+entry getUser(id, token) {
+    GET "{{host}}/users/{{id}}"
+}
+entry seed {
+    for users in users {
+        POST "{{host}}/users"
+    }
+}
+"#;
+        let vars = HashMap::from([("token".to_string(), "secret".to_string())]);
+        let file = validate(input, &vars).unwrap();
+        let messages = file
+            .warnings
+            .iter()
+            .map(|it| (it.code.map(|code| code.id), it.message.as_str(), it.span))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                (
+                    Some("AU0026"),
+                    "The parameter `id` shadows the const `id`",
+                    Span::new(216, 218)
+                ),
+                (
+                    Some("AU0026"),
+                    "The parameter `token` shadows the variable `token`",
+                    Span::new(220, 225)
+                ),
+                (
+                    Some("AU0026"),
+                    "The loop variable `users` shadows the const `users`",
+                    Span::new(284, 289)
+                ),
+                (
+                    Some("AU0025"),
+                    "The const `timeout` is never used",
+                    Span::new(89, 96)
+                ),
+                (
+                    Some("AU0025"),
+                    "The const `id` is never used",
+                    Span::new(127, 129)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_suggests_known_names() {
+        let help = |input: &str, vars: &HashMap<String, String>| {
+            validate(input, vars)
+                .map_err(single)
+                .expect_err("unknown name")
+                .labels
+                .into_iter()
+                .find(|it| it.level == Level::Help)
+                .map(|it| it.message)
+        };
+        let no_vars = HashMap::new();
+
+        let input = r#"
+const baseUrl = "https://example.com"
+entry a {
+    GET "{{baseURL}}/users"
+}
+"#;
+        assert_eq!(
+            help(input, &no_vars).as_deref(),
+            Some("Did you mean `baseUrl`?")
+        );
+
+        let input = r#"
+entry a(userId) {
+    GET "https://example.com/users/{{usrId}}"
+}
+"#;
+        assert_eq!(
+            help(input, &no_vars).as_deref(),
+            Some("Did you mean `userId`?")
+        );
+
+        let vars = HashMap::from([("token".to_string(), "secret".to_string())]);
+        assert_eq!(
+            help("const auth = \"Bearer \" + tokne", &vars).as_deref(),
+            Some("Did you mean `token`?")
+        );
+
+        let input = r#"
+entry a {
+    GET "https://example.com"
+    [headers] {"Accept": "application/json"}
+}
+"#;
+        assert_eq!(
+            help(input, &no_vars).as_deref(),
+            Some("Did you mean `Headers`?")
+        );
+
+        assert_eq!(help("const a = xyz", &no_vars), None);
+    }
+
+    #[test]
+    fn suggest_closest_name() {
+        let known = ["Body", "Headers", "Query"];
+        assert_eq!(suggest("Hedaers", known.into_iter()), Some("Headers"));
+        assert_eq!(suggest("body", known.into_iter()), Some("Body"));
+        assert_eq!(suggest("Params", known.into_iter()), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 6);
+        assert_eq!(edit_distance("tokne", "token"), 2);
+    }
+
+    #[test]
+    fn validate_duplicate_entry_param() {
+        let diagnostic = validate("entry a(id, id) {}", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("duplicate parameter");
+        assert_eq!(
+            diagnostic.message,
+            "The parameter `id` is defined multiple times"
+        );
+    }
+
+    #[test]
+    fn validate_extends_cycle() {
+        let input = r#"
+entry a extends b {}
+entry b extends a {}
+"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("cycle");
+        assert_eq!(diagnostic.message, "Entry `a` extends itself");
+    }
+
+    #[test]
+    fn validate_extends_unknown_entry() {
+        let diagnostic = validate("entry a extends b {}", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("unknown entry");
+        assert_eq!(diagnostic.message, "Unknown entry `b`");
+        assert_eq!(diagnostic.span, Span::new(16, 17));
+    }
+
+    #[test]
+    fn validate_duplicate_entry_reports_second_definition() {
+        let input = r#"
+entry foo {}
+entry bar {}
+entry foo {}
+"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("duplicate entry");
+        assert_eq!(
+            diagnostic.message,
+            "The entry `foo` is defined multiple times"
+        );
+        assert_eq!(diagnostic.span, Span::new(33, 36));
+    }
+
+    #[test]
+    fn validate_unknown_function() {
+        let input = r#"const path = encode("a b")"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("unknown function");
+        assert_eq!(diagnostic.message, "Unknown function `encode`");
+        assert_eq!(diagnostic.span, Span::new(13, 19));
+    }
+
+    #[test]
+    fn check_source_without_variables() {
+        assert!(check_source("const port = 8080").is_empty());
+        assert_eq!(check_warnings("const port = 8080").len(), 1);
+
+        let diagnostics = check_source("const url = host + \"/users\"");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span, Span::new(12, 16));
+    }
+
+    #[test]
+    fn validate_time_functions() {
+        let input = r#"
+const id = uuid()
+const date = now("%Y-%m-%d")
+const ts = timestamp()
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.globals["date"].expr.ty, validated::Ty::String);
+        assert_eq!(file.globals["ts"].expr.ty, validated::Ty::Integer);
+
+        let diagnostic = validate("const date = now(1)", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("int format");
+        assert_eq!(diagnostic.message, "Mismatched types");
+        let diagnostic = validate("const id = uuid(1)", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("arity");
+        assert_eq!(diagnostic.message, "Wrong number of arguments");
+    }
+
+    #[test]
+    fn validate_encoding_functions() {
+        let input = r#"
+const user = "ada"
+const auth = base64(user + ":" + pass)
+const q = urlencode("a b")
+const digest = sha256(auth)
+"#;
+        let vars = HashMap::from([("pass".to_string(), String::new())]);
+        let file = validate(input, &vars).unwrap();
+        assert_eq!(file.globals["digest"].expr.ty, validated::Ty::String);
+
+        let diagnostic = validate("const n = base64(1)", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("int");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_env() {
+        let input = r#"
+const token = env("API_TOKEN")
+const region = env("REGION", "eu-west-1")
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.globals["token"].expr.ty, validated::Ty::String);
+
+        let diagnostic = validate(
+            r#"const t = env("A", "b", "c")"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("too many arguments");
+        assert_eq!(diagnostic.message, "Wrong number of arguments");
+    }
+
+    #[test]
+    fn validate_read() {
+        let input = r#"
+entry createUser {
+    POST "https://example.com/users"
+    [Body] read("fixtures/user.json")
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let body = file.entries["createUser"].body.as_ref().unwrap();
+        assert!(matches!(
+            body.kind,
+            validated::ExprKind::Call(validated::Builtin::Read, _)
+        ));
+
+        let diagnostic = validate("const a = read(1)", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("path is not a string");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_auth() {
+        let input = r#"
+const auth = {"bearer": token}
+entry base {
+    [Auth] auth
+}
+entry me extends base {
+    GET "https://example.com/me"
+}
+"#;
+        let vars = HashMap::from([("token".to_string(), String::new())]);
+        let file = validate(input, &vars).unwrap();
+        assert!(matches!(
+            file.entries["me"].auth,
+            Some(validated::Auth::Bearer(_))
+        ));
+
+        let diagnostic = validate(
+            r#"entry a { [Auth] {"basic": "x"} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("unknown scheme");
+        assert_eq!(diagnostic.message, "Unknown authentication scheme `basic`");
+
+        let diagnostic = validate(
+            r#"entry a { [Auth] {"bearer": 1} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("token is not a string");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_options() {
+        let input = r#"
+entry base {
+    [Options] {"timeout": 5000, "retries": 2, "max_redirects": 3}
+}
+entry slow extends base {
+    GET "https://example.com/report"
+    [Options] {"timeout": 60000, "follow_redirects": false, "http_version": "2"}
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let options = file.entries["slow"].options.as_ref().unwrap();
+        assert_eq!(
+            options.ty,
+            validated::Ty::Dictionary(vec![
+                (Some("retries".to_string()), validated::Ty::Integer),
+                (Some("max_redirects".to_string()), validated::Ty::Integer),
+                (Some("timeout".to_string()), validated::Ty::Integer),
+                (Some("follow_redirects".to_string()), validated::Ty::Bool),
+                (Some("http_version".to_string()), validated::Ty::String),
+            ])
+        );
+
+        let diagnostic = validate(
+            r#"entry a { [Options] {"retry": 1} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("unknown option");
+        assert_eq!(diagnostic.message, "Unknown option `retry`");
+
+        let diagnostic = validate(
+            r#"entry a { [Options] {"timeout": "5s"} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("timeout is not an integer");
+        assert_eq!(diagnostic.message, "Mismatched types");
+
+        validate(
+            r#"entry a { [Options] {"poll": {"interval": 500, "attempts": 20}} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .unwrap();
+        let diagnostic = validate(
+            r#"entry a { [Options] {"poll": {"every": 500}} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("unknown poll setting");
+        assert_eq!(diagnostic.message, "Unexpected type");
+    }
+
+    #[test]
+    fn validate_cookies() {
+        let input = r#"
+entry base {
+    [Cookies] {"theme": "dark", "lang": "en"}
+}
+entry home extends base {
+    GET "https://example.com/"
+    [Cookies] {"lang": "de"}
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let cookies = file.entries["home"].cookies.as_ref().unwrap();
+        assert_eq!(
+            cookies.ty,
+            validated::Ty::Dictionary(vec![
+                (Some("theme".to_string()), validated::Ty::String),
+                (Some("lang".to_string()), validated::Ty::String),
+            ])
+        );
+
+        let diagnostic = validate(
+            r#"entry a { [Cookies] {"id": 1} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("cookie value is not a string");
+        assert_eq!(diagnostic.message, "Unexpected type");
+    }
+
+    #[test]
+    fn validate_needs() {
+        let input = r#"
+entry authed needs login {}
+entry me extends authed {
+    GET "https://example.com/me"
+}
+entry login {
+    POST "https://example.com/login"
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.entries["me"].needs[0].text, "login");
+
+        let diagnostic = validate("entry a needs b {}", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("unknown entry");
+        assert_eq!(diagnostic.message, "Unknown entry `b`");
+
+        let diagnostic = validate(
+            "entry a needs b {}\nentry b needs c {}\nentry c needs a {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("cyclic needs");
+        assert_eq!(diagnostic.message, "Entry `a` needs itself");
+
+        let diagnostic = validate(
+            "entry base needs login {}\nentry login extends base {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("inherited cycle");
+        assert_eq!(diagnostic.message, "Entry `login` needs itself");
+    }
+
+    #[test]
+    fn validate_entry_calls() {
+        let input = r#"
+entry getUser(id) {
+    GET "https://example.com/users/{{id}}"
+}
+entry me(id) needs getUser(id) {}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let me = &file.entries["me"];
+        assert!(me.needs.is_empty());
+        assert_eq!(me.calls[0].entry.text, "getUser");
+
+        let diagnostic = validate(
+            "entry getUser(id) {}\nentry me needs getUser() {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("missing argument");
+        assert_eq!(diagnostic.message, "Wrong number of arguments");
+
+        let diagnostic = validate(
+            "entry getUser(id) {}\nentry me needs getUser(42) {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("integer argument");
+        assert_eq!(diagnostic.message, "Mismatched types");
+
+        let diagnostic = validate(
+            "entry a(x) needs b(x) {}\nentry b(y) needs a(y) {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("cyclic calls");
+        assert_eq!(diagnostic.message, "Entry `a` needs itself");
+    }
+
+    #[test]
+    fn validate_assertions() {
+        let input = r#"
+entry users {
+    GET "https://example.com/users"
+    [Assert] {
+        status < 400
+        header "Content-Type" matches "json$"
+        body "$.items[*].id" contains 1
+        ttfb < 2s
+    }
+}
+entry admins extends users {
+    GET "https://example.com/admins"
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let assertions = &file.entries["admins"].assertions;
+        assert_eq!(assertions.len(), 4);
+        assert!(matches!(
+            &assertions[2].subject,
+            validated::Subject::Body(Some(path)) if path.to_string() == "$.items[*].id"
+        ));
+        assert_eq!(assertions[1].op, validated::CompareOp::Matches);
+        assert!(matches!(
+            &assertions[3].expected.kind,
+            validated::ExprKind::Binary(validated::BinOp::Mul, _, factor)
+                if matches!(factor.kind, validated::ExprKind::IntegerLiteral(1000))
+        ));
+
+        let cases = [
+            ("length == 1", "Unknown assertion `length`"),
+            ("size < 10kb", "Unexpected unit `kb`"),
+            ("duration < 5min", "Unexpected unit `min`"),
+            ("header == \"a\"", "Missing header name"),
+            ("status 1 == 200", "Unexpected argument"),
+            ("sha256 contains \"ab\"", "Unexpected comparison"),
+            ("md5 == 1", "Mismatched types"),
+            ("status contains 2", "Unexpected comparison"),
+            ("header \"Accept\" < \"b\"", "Unexpected comparison"),
+            ("status == \"200\"", "Mismatched types"),
+            ("body \"$.id\" matches 1", "Mismatched types"),
+            ("body matches \"(\"", "Invalid regex"),
+            ("body \"id\" == 1", "Invalid JSONPath"),
+        ];
+        for (assertion, message) in cases {
+            let input = format!("entry a {{\n    [Assert] {{\n        {assertion}\n    }}\n}}");
+            let diagnostic = validate(&input, &HashMap::<String, String>::new())
+                .map_err(single)
+                .expect_err(assertion);
+            assert_eq!(diagnostic.message, message, "{assertion}");
+        }
+    }
+
+    #[test]
+    fn validate_captures() {
+        let input = r#"
+entry login {
+    POST "https://example.com/login"
+    [Capture] {"token": "$.access_token", "userId": "$.user.id"}
+}
+entry me {
+    GET "https://example.com/users/{{userId}}"
+    [Headers] {"Authorization": "Bearer {{token}}"}
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let captures = &file.entries["login"].captures;
+        assert_eq!(
+            captures
+                .iter()
+                .map(|it| it.name.as_str())
+                .collect::<Vec<_>>(),
+            ["token", "userId"]
+        );
+        assert_eq!(captures[1].path.to_string(), "$.user.id");
+
+        let diagnostic = validate(
+            r#"entry a { [Capture] {"id": "id"} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("path without `$`");
+        assert_eq!(diagnostic.message, "Invalid JSONPath");
+
+        let diagnostic = validate(
+            "const id = 1\nentry a { [Capture] {\"id\": \"$.id\"} }",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("capture shadows a const");
+        assert_eq!(
+            diagnostic.message,
+            "The variable `id` is defined multiple times"
+        );
+    }
+
+    #[test]
+    fn validate_defaults() {
+        let input = r#"
+defaults {
+    [BaseUrl] "https://example.com"
+    [Headers] {"Accept": "application/json", "X-Client": "aurora"}
+}
+entry base {
+    [Headers] {"X-Client": "base"}
+}
+entry users extends base {
+    GET "/users"
+}
+entry health {
+    GET "/health"
+    [BaseUrl] "http://localhost:8080"
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let users = &file.entries["users"];
+        assert!(users.base_url.is_some());
+        assert_eq!(
+            users.headers.as_ref().unwrap().ty,
+            validated::Ty::Dictionary(vec![
+                (Some("Accept".to_string()), validated::Ty::String),
+                (Some("X-Client".to_string()), validated::Ty::String),
+            ])
+        );
+        let health = &file.entries["health"];
+        let span = health.base_url.as_ref().unwrap().span;
+        assert_eq!(&input[span.start..span.end], "\"http://localhost:8080\"");
+
+        let diagnostic = validate(
+            "defaults {\n    GET \"https://example.com\"\n}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("request in defaults");
+        assert_eq!(diagnostic.message, "Unexpected request");
+
+        let diagnostic = validate(
+            "defaults {}\ndefaults {}",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("defaults defined twice");
+        assert_eq!(
+            diagnostic.message,
+            "The defaults are defined multiple times"
+        );
+
+        let diagnostic = validate(
+            "defaults { [BaseUrl] 1 }",
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("base URL is not a string");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_aws_sigv4_auth() {
+        let input = r#"
+entry listBuckets {
+    GET "https://s3.amazonaws.com/"
+    [Auth] {"aws-sigv4": {
+        "region": "us-east-1",
+        "service": "s3",
+        "accessKey": env("AWS_ACCESS_KEY_ID"),
+        "secretKey": env("AWS_SECRET_ACCESS_KEY"),
+    }}
+}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        let Some(validated::Auth::AwsSigV4(aws)) = &file.entries["listBuckets"].auth else {
+            panic!("expected AWS signing");
+        };
+        assert!(aws.session_token.is_none());
+
+        let diagnostic = validate(
+            r#"entry a { [Auth] {"aws-sigv4": {"region": "us-east-1"}} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("settings are missing");
+        assert_eq!(diagnostic.message, "Missing setting `service`");
+
+        let diagnostic = validate(
+            r#"entry a { [Auth] {"aws-sigv4": {"regoin": "us-east-1"}} }"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("unknown setting");
+        assert_eq!(diagnostic.message, "Unknown setting `regoin`");
+    }
+
+    #[test]
+    fn validate_if() {
+        let input = r#"
+const useStaging = false
+const url = if useStaging then "staging.example.com" else "example.com"
+const limit = if useStaging then 10 else "none"
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.globals["url"].expr.ty, validated::Ty::String);
+        assert_eq!(
+            file.globals["limit"].expr.ty,
+            validated::Ty::Union(vec![validated::Ty::Integer, validated::Ty::String])
+        );
+
+        let diagnostic = validate(
+            r#"const url = if "yes" then 1 else 2"#,
+            &HashMap::<String, String>::new(),
+        )
+        .map_err(single)
+        .expect_err("string condition");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_coalesce() {
+        let input = r#"
+const host = region ?? "us-east-1"
+const port = null ?? 8080
+const url = host + ":" + (given ?? "x")
+"#;
+        let vars = HashMap::from([("given".to_string(), String::new())]);
+        let file = validate(input, &vars).unwrap();
+        assert!(matches!(
+            file.globals["host"].expr.kind,
+            validated::ExprKind::StringLiteral(_)
+        ));
+        assert_eq!(file.globals["port"].expr.ty, validated::Ty::Integer);
+        assert_eq!(file.globals["url"].expr.ty, validated::Ty::String);
+    }
+
+    #[test]
+    fn validate_spread() {
+        let input = r#"
+const defaults = {"Accept": "application/json", "X-Trace": "none"}
+const headers = {...defaults, "X-Trace": "abc"}
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(
+            file.globals["headers"].expr.ty,
+            validated::Ty::Dictionary(vec![
+                (Some("Accept".to_string()), validated::Ty::String),
+                (Some("X-Trace".to_string()), validated::Ty::String),
+            ])
+        );
+        // The spread is kept, so the machine evaluates it once.
+        assert!(matches!(
+            &file.globals["headers"].expr.kind,
+            validated::ExprKind::Dictionary(fields)
+                if matches!(&fields[..], [validated::DictionaryField::Spread(_), validated::DictionaryField::Pair { .. }])
+        ));
+
+        let diagnostic = validate("const h = {...1}", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("spread of a number");
+        assert_eq!(diagnostic.message, "Mismatched types");
+    }
+
+    #[test]
+    fn validate_raw_arity() {
+        let input = r#"const path = raw("a", "b")"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("wrong arity");
+        assert_eq!(diagnostic.message, "Wrong number of arguments");
+    }
+
+    #[test]
+    fn validate_string_concat() {
+        let input = r#"
+const base = "example.com"
+const url = base + "/users" + path
+"#;
+        let vars = HashMap::from([("path".to_string(), String::new())]);
+        let file = validate(input, &vars).unwrap();
+        assert_eq!(file.globals["url"].expr.ty, validated::Ty::String);
+    }
+
+    #[test]
+    fn validate_string_concat_rejects_numbers() {
+        let input = r#"const url = "example.com:" + 8080"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("mismatched types");
+        assert_eq!(diagnostic.message, "Mismatched types");
+        assert_eq!(diagnostic.span, Span::new(29, 33));
+    }
+
+    #[test]
+    fn validate_arithmetic() {
+        let input = r#"
+const port = 8080
+const next = port + 1
+const half = (port - 80) / 2
+const ratio = port * 0.5
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.globals["next"].expr.ty, validated::Ty::Integer);
+        assert_eq!(file.globals["half"].expr.ty, validated::Ty::Integer);
+        assert_eq!(file.globals["ratio"].expr.ty, validated::Ty::Float);
+    }
+
+    #[test]
+    fn validate_arithmetic_rejects_strings() {
+        let input = r#"const n = 1 - "2""#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("mismatched types");
+        assert_eq!(diagnostic.message, "Mismatched types");
+        assert_eq!(diagnostic.span, Span::new(14, 17));
+    }
+
+    #[test]
+    fn validate_field_access() {
+        let input = r#"
+const user = {"name": "ada", "address": {"zip": 12345}}
+const zip = user.address.zip
+const greeting = "hello {{user.name}}"
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert_eq!(file.globals["zip"].expr.ty, validated::Ty::Integer);
+    }
+
+    #[test]
+    fn validate_unknown_field() {
+        let input = r#"
+const user = {"name": "ada"}
+const age = user.age
+"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("unknown field");
+        assert_eq!(diagnostic.message, "Unknown field `age`");
+        assert_eq!(diagnostic.span, Span::new(47, 50));
+    }
+
+    #[test]
+    fn validate_numeric_literals() {
+        let input = r#"
+const mask = 0xFF
+const big = 1_000_000
+const rate = 1.5e9
+const small = 2_5E-1
+"#;
+        let file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        assert!(matches!(
+            file.globals["mask"].expr.kind,
+            validated::ExprKind::IntegerLiteral(255)
+        ));
+        assert!(matches!(
+            file.globals["big"].expr.kind,
+            validated::ExprKind::IntegerLiteral(1_000_000)
+        ));
+        assert!(matches!(
+            file.globals["rate"].expr.kind,
+            validated::ExprKind::FloatLiteral(1.5e9)
+        ));
+        assert!(matches!(
+            file.globals["small"].expr.kind,
+            validated::ExprKind::FloatLiteral(2.5)
+        ));
+    }
+
+    #[test]
+    fn validate_invalid_hex_literal() {
+        let diagnostic = validate("const mask = 0x", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("no digits");
+        assert_eq!(diagnostic.message, "Invalid integer literal");
+    }
+
+    #[test]
+    fn validate_infinite_float_literal() {
+        let diagnostic = validate("const big = 1e999", &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("infinite float");
+        assert_eq!(diagnostic.message, "Invalid float literal");
+        assert_eq!(diagnostic.code, Some(codes::INVALID_LITERAL));
+    }
+
+    #[test]
+    fn validate_query_rejects_nested_values() {
+        let input = r#"
+entry foo {
+    GET "example.com"
+    [Query] {"ids": [1, 2]}
+}
+"#;
+        let diagnostic = validate(input, &HashMap::<String, String>::new())
+            .map_err(single)
+            .expect_err("nested query value");
+        assert_eq!(diagnostic.message, "Unexpected type");
+    }
+
+    #[test]
+    fn override_const_with_matching_type() {
+        let input = r#"
+const base = "example.com"
+const pageSize = 20
+const ids = [1, 2]
+"#;
+        let mut file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        override_const(&mut file, "base", "localhost:8080").unwrap();
+        override_const(&mut file, "pageSize", "50").unwrap();
+        override_const(&mut file, "ids", "[]").unwrap();
+        assert!(matches!(
+            file.globals["pageSize"].expr.kind,
+            validated::ExprKind::IntegerLiteral(50)
+        ));
+    }
+
+    #[test]
+    fn override_float_with_integer() {
+        let input = r#"
+const rate = 1.5
+const weights = [0.5, 1.5]
+const limits = {"rate": 0.5}
+"#;
+        let mut file = validate(input, &HashMap::<String, String>::new()).unwrap();
+        override_const(&mut file, "rate", "3").unwrap();
+        override_const(&mut file, "weights", "[1, 2]").unwrap();
+        override_const(&mut file, "limits", r#"{"rate": 1 + 1, "burst": 5}"#).unwrap();
+        assert!(matches!(
+            file.globals["rate"].expr.kind,
+            validated::ExprKind::FloatLiteral(3.0)
+        ));
+        let validated::ExprKind::Dictionary(fields) = &file.globals["limits"].expr.kind else {
+            panic!("Expected a dictionary");
+        };
+        assert!(matches!(
+            &fields[0],
+            validated::DictionaryField::Pair { value, .. }
+                if matches!(value.kind, validated::ExprKind::ToFloat(_))
+        ));
+        assert_eq!(
+            file.globals["weights"].expr.ty,
+            validated::Ty::Array(Box::new(validated::Ty::Float))
+        );
+        // Fields that the original value doesn't have keep their types.
+        assert_eq!(
+            file.globals["limits"].expr.ty,
+            validated::Ty::Dictionary(vec![
+                (Some("rate".to_string()), validated::Ty::Float),
+                (Some("burst".to_string()), validated::Ty::Integer),
+            ])
+        );
+    }
+
+    #[test]
+    fn override_const_with_mismatched_type() {
+        let mut file = validate("const pageSize = 20", &HashMap::<String, String>::new()).unwrap();
+        let err = override_const(&mut file, "pageSize", "\"many\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "I couldn't use `\"many\"` as the value of `pageSize`, I was expecting a value of type `int`"
+        );
+        assert!(matches!(
+            override_const(&mut file, "size", "1"),
+            Err(OverrideError::UnknownConst(_))
+        ));
+    }
+
     #[test]
     fn unescape_string_simple() {
         assert_eq!(unescape_ok("foo"), "foo");
@@ -1,13 +1,31 @@
+use std::sync::Arc;
+
 use indexmap::IndexMap;
 
+use crate::{validated::Ty, validator};
+
+/// A value at runtime. The fields of dictionaries and the elements of
+/// arrays are shared, so building a dictionary or an array from other
+/// values doesn't copy them.
 #[derive(Debug, Clone)]
 pub enum Value {
     String(String),
     Integer(i64),
     Float(f64),
     Null,
-    Dictionary(IndexMap<String, Value>),
-    Array(Vec<Value>),
+    Bool(bool),
+    Dictionary(IndexMap<String, Arc<Value>>),
+    Array(Vec<Arc<Value>>),
+}
+
+/// A float that is infinite or NaN. JSON has no numbers for these.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NonFiniteFloat(pub f64);
+
+impl std::fmt::Display for NonFiniteFloat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` isn't a finite number", self.0)
+    }
 }
 
 impl std::fmt::Display for Value {
@@ -17,6 +35,7 @@ impl std::fmt::Display for Value {
             Value::Integer(i) => write!(f, "{i}"),
             Value::Float(fl) => write!(f, "{fl}"),
             Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{b}"),
             Value::Dictionary(d) => {
                 let inner = d
                     .iter()
@@ -45,32 +64,74 @@ impl Value {
         }
     }
 
-    pub fn dictionary(&self) -> &IndexMap<String, Value> {
+    pub fn dictionary(&self) -> &IndexMap<String, Arc<Value>> {
         match self {
             Value::Dictionary(d) => d,
             _ => panic!("Expected a dictionary"),
         }
     }
 
-    pub fn to_json(&self) -> serde_json::Value {
-        match self {
+    /// Converts the value to JSON. Fails if it contains a float that is
+    /// infinite or NaN.
+    pub fn to_json(&self) -> Result<serde_json::Value, NonFiniteFloat> {
+        Ok(match self {
             Value::String(s) => serde_json::Value::String(s.clone()),
             Value::Integer(i) => serde_json::Value::Number((*i).into()),
             Value::Float(f) => serde_json::Value::Number(
-                serde_json::Number::from_f64(*f).expect("Number should be finite"),
+                serde_json::Number::from_f64(*f).ok_or(NonFiniteFloat(*f))?,
             ),
             Value::Null => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(*b),
             Value::Dictionary(d) => {
                 let mut map = serde_json::Map::new();
                 for (k, v) in d {
-                    map.insert(k.clone(), v.to_json());
+                    map.insert(k.clone(), v.to_json()?);
                 }
                 serde_json::Value::Object(map)
             }
             Value::Array(a) => {
-                let elems = a.iter().map(|it| it.to_json()).collect();
+                let elems = a.iter().map(|it| it.to_json()).collect::<Result<_, _>>()?;
                 serde_json::Value::Array(elems)
             }
+        })
+    }
+
+    /// Converts JSON to a value. Numbers become integers if they fit into
+    /// one and floats otherwise.
+    pub fn from_json(json: &serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(a) => {
+                Value::Array(a.iter().map(|it| Arc::new(Value::from_json(it))).collect())
+            }
+            serde_json::Value::Object(o) => Value::Dictionary(
+                o.iter()
+                    .map(|(k, v)| (k.clone(), Arc::new(Value::from_json(v))))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Returns the type the validator would give a literal of this value.
+    pub fn ty(&self) -> Ty {
+        match self {
+            Value::String(_) => Ty::String,
+            Value::Integer(_) => Ty::Integer,
+            Value::Float(_) => Ty::Float,
+            Value::Null => Ty::Null,
+            Value::Bool(_) => Ty::Bool,
+            Value::Dictionary(d) => {
+                Ty::Dictionary(d.iter().map(|(k, v)| (Some(k.clone()), v.ty())).collect())
+            }
+            Value::Array(a) => Ty::Array(Box::new(validator::merge_types(
+                a.iter().map(|it| it.ty()).collect(),
+            ))),
         }
     }
 
@@ -80,6 +141,7 @@ impl Value {
             Value::Integer(i) => i.to_string(),
             Value::Float(f) => f.to_string(),
             Value::Null => "null".to_string(),
+            Value::Bool(b) => b.to_string(),
             Value::Dictionary(d) => {
                 let inner = d
                     .iter()
@@ -161,11 +223,26 @@ mod tests {
         assert_eq!(v.stringify(), "1.23");
     }
 
+    #[test]
+    fn stringify_bool() {
+        assert_eq!(Value::Bool(true).stringify(), "true");
+        assert_eq!(
+            Value::Bool(false).to_json(),
+            Ok(serde_json::Value::Bool(false))
+        );
+    }
+
+    #[test]
+    fn non_finite_floats_have_no_json() {
+        let v = Value::Array(vec![Arc::new(Value::Float(f64::INFINITY))]);
+        assert_eq!(v.to_json(), Err(NonFiniteFloat(f64::INFINITY)));
+    }
+
     #[test]
     fn stringify_dict_simple() {
         let mut map = IndexMap::new();
-        map.insert("a".to_string(), Value::Integer(1));
-        map.insert("b".to_string(), Value::Integer(2));
+        map.insert("a".to_string(), Arc::new(Value::Integer(1)));
+        map.insert("b".to_string(), Arc::new(Value::Integer(2)));
 
         let v = Value::Dictionary(map);
         assert_eq!(v.stringify(), r#"{"a": 1, "b": 2}"#);
@@ -176,7 +253,7 @@ mod tests {
         let mut map = IndexMap::new();
         map.insert(
             r#"ke"y"#.to_string(),
-            Value::String(r#"va"lue"#.to_string()),
+            Arc::new(Value::String(r#"va"lue"#.to_string())),
         );
 
         let v = Value::Dictionary(map);
@@ -186,10 +263,10 @@ mod tests {
     #[test]
     fn stringify_nested_dict() {
         let mut inner = IndexMap::new();
-        inner.insert("x".to_string(), Value::Integer(9));
+        inner.insert("x".to_string(), Arc::new(Value::Integer(9)));
 
         let mut outer = IndexMap::new();
-        outer.insert("inner".to_string(), Value::Dictionary(inner));
+        outer.insert("inner".to_string(), Arc::new(Value::Dictionary(inner)));
 
         let v = Value::Dictionary(outer);
         assert_eq!(v.stringify(), r#"{"inner": {"x": 9}}"#);
@@ -197,13 +274,16 @@ mod tests {
 
     #[test]
     fn stringify_array_single_element() {
-        let a = Value::Array(vec![Value::Integer(1)]);
+        let a = Value::Array(vec![Arc::new(Value::Integer(1))]);
         assert_eq!(a.stringify(), "[1]");
     }
 
     #[test]
     fn stringify_array_multiple_elements() {
-        let a = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+        let a = Value::Array(vec![
+            Arc::new(Value::Integer(1)),
+            Arc::new(Value::Integer(2)),
+        ]);
         assert_eq!(a.stringify(), "[1, 2]");
     }
 
@@ -228,8 +308,8 @@ mod tests {
     #[test]
     fn display_dictionary_flat() {
         let mut d = IndexMap::new();
-        d.insert("b".to_string(), Value::Integer(2));
-        d.insert("a".to_string(), Value::Integer(1));
+        d.insert("b".to_string(), Arc::new(Value::Integer(2)));
+        d.insert("a".to_string(), Arc::new(Value::Integer(1)));
 
         let v = Value::Dictionary(d);
         assert_eq!(format!("{}", v), "{b: 2, a: 1}");
@@ -238,11 +318,11 @@ mod tests {
     #[test]
     fn display_dictionary_nested() {
         let mut inner = IndexMap::new();
-        inner.insert("x".to_string(), Value::Integer(5));
+        inner.insert("x".to_string(), Arc::new(Value::Integer(5)));
         let inner_dict = Value::Dictionary(inner);
 
         let mut outer = IndexMap::new();
-        outer.insert("inner".to_string(), inner_dict);
+        outer.insert("inner".to_string(), Arc::new(inner_dict));
 
         let v = Value::Dictionary(outer);
         assert_eq!(format!("{}", v), "{inner: {x: 5}}");
@@ -250,13 +330,40 @@ mod tests {
 
     #[test]
     fn display_array_single_element() {
-        let a = Value::Array(vec![Value::Integer(1)]);
+        let a = Value::Array(vec![Arc::new(Value::Integer(1))]);
         assert_eq!(format!("{a}"), "[1]");
     }
 
     #[test]
     fn display_array_multiple_element() {
-        let a = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+        let a = Value::Array(vec![
+            Arc::new(Value::Integer(1)),
+            Arc::new(Value::Integer(2)),
+        ]);
         assert_eq!(format!("{a}"), "[1, 2]");
     }
+
+    #[test]
+    fn types_of_json_values() {
+        let value = Value::from_json(&serde_json::json!({
+            "id": 1,
+            "ratio": 0.5,
+            "tags": ["a", "b"],
+            "mixed": [1, "a", 2],
+            "empty": [],
+        }));
+        assert_eq!(
+            value.ty(),
+            Ty::Dictionary(vec![
+                (Some("id".to_string()), Ty::Integer),
+                (Some("ratio".to_string()), Ty::Float),
+                (Some("tags".to_string()), Ty::Array(Box::new(Ty::String))),
+                (
+                    Some("mixed".to_string()),
+                    Ty::Array(Box::new(Ty::Union(vec![Ty::Integer, Ty::String])))
+                ),
+                (Some("empty".to_string()), Ty::Array(Box::new(Ty::Unknown))),
+            ])
+        );
+    }
 }
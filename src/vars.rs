@@ -0,0 +1,212 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{validated::Ty, value::Value};
+
+/// Resolves names that are not defined in the source file itself.
+///
+/// The validator only asks whether a name exists, the machine resolves the
+/// value the first time it is referenced. This allows providers to defer
+/// expensive or secret lookups until they are actually needed.
+///
+/// Providers are shared with the validator's worker threads and therefore
+/// need to be `Sync`.
+pub trait VariableProvider: Sync {
+    /// Returns `true` if this provider knows about `name`.
+    fn contains(&self, name: &str) -> bool;
+
+    /// Returns the type of `name`, which the provider knows about. Unless the
+    /// provider knows better, variables are strings.
+    fn ty(&self, _name: &str) -> Ty {
+        Ty::String
+    }
+
+    /// Returns the value of `name`, or `None` if it cannot be resolved.
+    fn resolve(&self, name: &str) -> Option<Value>;
+
+    /// Returns the names this provider knows about, to suggest one for a
+    /// misspelled name. Providers that can't list their names return none.
+    fn names(&self) -> Vec<&str> {
+        vec![]
+    }
+}
+
+impl VariableProvider for HashMap<String, String> {
+    fn contains(&self, name: &str) -> bool {
+        self.contains_key(name)
+    }
+
+    fn resolve(&self, name: &str) -> Option<Value> {
+        self.get(name).cloned().map(Value::String)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.keys().map(String::as_str).collect()
+    }
+}
+
+impl VariableProvider for HashMap<String, Value> {
+    fn contains(&self, name: &str) -> bool {
+        self.contains_key(name)
+    }
+
+    fn ty(&self, name: &str) -> Ty {
+        self.get(name).map_or(Ty::Unknown, Value::ty)
+    }
+
+    fn resolve(&self, name: &str) -> Option<Value> {
+        self.get(name).cloned()
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.keys().map(String::as_str).collect()
+    }
+}
+
+/// Parses a file of variables whose values keep their types. The format is
+/// chosen by the extension: `.json`, `.toml`, or `.yaml` and `.yml`. The
+/// top level has to be a table of variable names.
+pub fn parse_vars_file(path: &Path, contents: &str) -> Result<Vec<(String, Value)>, String> {
+    let extension = path
+        .extension()
+        .and_then(|it| it.to_str())
+        .unwrap_or_default();
+    let json = match extension {
+        "json" => serde_json::from_str::<serde_json::Value>(contents).map_err(|e| e.to_string()),
+        "toml" => toml::from_str::<serde_json::Value>(contents).map_err(|e| e.to_string()),
+        "yaml" | "yml" => {
+            serde_yaml::from_str::<serde_json::Value>(contents).map_err(|e| e.to_string())
+        }
+        _ => Err("expected a `.json`, `.toml`, `.yaml` or `.yml` file".to_string()),
+    }?;
+    let serde_json::Value::Object(vars) = json else {
+        return Err("expected a table of variables at the top level".to_string());
+    };
+    Ok(vars
+        .iter()
+        .map(|(name, value)| (name.clone(), Value::from_json(value)))
+        .collect())
+}
+
+/// Parses the `KEY=VALUE` lines of a `.env` file, in order.
+///
+/// Blank lines and lines starting with `#` are skipped, and a leading
+/// `export` is ignored. Values may be quoted: single quotes keep the value as
+/// it is, double quotes understand `\n`, `\"` and `\\`. Unquoted values end
+/// at a ` #` comment.
+pub fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut vars = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected `KEY=VALUE`", i + 1));
+        };
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(format!("line {}: invalid name `{key}`", i + 1));
+        }
+
+        let value = value.trim();
+        let value = if let Some(rest) = value.strip_prefix('\'') {
+            rest.strip_suffix('\'')
+                .ok_or_else(|| format!("line {}: missing closing `'`", i + 1))?
+                .to_string()
+        } else if let Some(rest) = value.strip_prefix('"') {
+            let rest = rest
+                .strip_suffix('"')
+                .ok_or_else(|| format!("line {}: missing closing `\"`", i + 1))?;
+            let mut value = String::with_capacity(rest.len());
+            let mut chars = rest.chars();
+            while let Some(c) = chars.next() {
+                match (c, chars.clone().next()) {
+                    ('\\', Some('n')) => value.push('\n'),
+                    ('\\', Some(c @ ('"' | '\\'))) => value.push(c),
+                    _ => {
+                        value.push(c);
+                        continue;
+                    }
+                }
+                chars.next();
+            }
+            value
+        } else {
+            match value.find(" #") {
+                Some(end) => value[..end].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_env_files() {
+        let contents = r#"
+# Secrets of the staging environment
+TOKEN=abc123
+export BASE_URL = https://staging.example.com # the new cluster
+SINGLE='a "quoted" #value'
+DOUBLE="line\nbreak \"quoted\""
+EMPTY=
+"#;
+        let vars = parse_env_file(contents).unwrap();
+        assert_eq!(
+            vars,
+            [
+                ("TOKEN", "abc123"),
+                ("BASE_URL", "https://staging.example.com"),
+                ("SINGLE", "a \"quoted\" #value"),
+                ("DOUBLE", "line\nbreak \"quoted\""),
+                ("EMPTY", ""),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+
+        assert_eq!(
+            parse_env_file("TOKEN").unwrap_err(),
+            "line 1: expected `KEY=VALUE`"
+        );
+        assert_eq!(
+            parse_env_file("\nA B=1").unwrap_err(),
+            "line 2: invalid name `A B`"
+        );
+        assert_eq!(
+            parse_env_file("A=\"abc").unwrap_err(),
+            "line 1: missing closing `\"`"
+        );
+    }
+
+    #[test]
+    fn parse_vars_files() {
+        let json = parse_vars_file(
+            Path::new("vars.json"),
+            r#"{"page": 2, "user": {"admin": true}}"#,
+        )
+        .unwrap();
+        let toml =
+            parse_vars_file(Path::new("vars.toml"), "page = 2\n[user]\nadmin = true\n").unwrap();
+        let yaml =
+            parse_vars_file(Path::new("vars.yml"), "page: 2\nuser:\n  admin: true\n").unwrap();
+        for vars in [json, toml, yaml] {
+            let vars = vars
+                .iter()
+                .map(|(name, value)| format!("{name} = {}", value.stringify()))
+                .collect::<Vec<_>>();
+            assert_eq!(vars, ["page = 2", "user = {\"admin\": true}"]);
+        }
+
+        assert_eq!(
+            parse_vars_file(Path::new("vars.json"), "[1]").unwrap_err(),
+            "expected a table of variables at the top level"
+        );
+        assert!(parse_vars_file(Path::new("vars.ini"), "").is_err());
+    }
+}
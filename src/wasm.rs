@@ -0,0 +1,35 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{diagnostic::Diagnostic, line_index::LineIndex, validator};
+
+/// Checks an `.au` file for the browser playground and returns its
+/// problems, as objects like
+///
+/// ```json
+/// {"level": "error", "code": "AU0004", "message": "...", "start": {"offset": 4, "line": 0, "column": 4}, "end": ..., "labels": [...]}
+/// ```
+///
+/// Offsets and columns count bytes of the UTF-8 input, lines start at 0.
+/// Empty if the file is valid.
+#[wasm_bindgen(js_name = checkSource)]
+pub fn check_source(input: &str) -> Vec<JsValue> {
+    to_js(input, &validator::check_source(input))
+}
+
+/// Returns the warnings about a valid `.au` file, as objects like the
+/// problems of `checkSource`.
+#[wasm_bindgen(js_name = checkWarnings)]
+pub fn check_warnings(input: &str) -> Vec<JsValue> {
+    to_js(input, &validator::check_warnings(input))
+}
+
+fn to_js(input: &str, diagnostics: &[Diagnostic]) -> Vec<JsValue> {
+    let line_index = LineIndex::new(input);
+    diagnostics
+        .iter()
+        .map(|d| {
+            js_sys::JSON::parse(&d.to_json(&line_index).to_string())
+                .expect("the diagnostic is valid JSON")
+        })
+        .collect()
+}